@@ -3,8 +3,7 @@ use async_trait::async_trait;
 use cloud_openapi::{
     apis::{
         apps_api::{
-            api_apps_get, api_apps_id_delete, api_apps_id_get, api_apps_id_logs_get,
-            api_apps_id_logs_raw_get, api_apps_post,
+            api_apps_get, api_apps_id_delete, api_apps_id_get, api_apps_id_logs_get, api_apps_post,
         },
         auth_tokens_api::api_auth_tokens_refresh_post,
         configuration::{ApiKey, Configuration},
@@ -19,7 +18,7 @@ use cloud_openapi::{
         sql_databases_api::{
             api_sql_databases_create_post, api_sql_databases_database_links_delete,
             api_sql_databases_database_links_post, api_sql_databases_database_rename_patch,
-            api_sql_databases_delete, api_sql_databases_execute_post, api_sql_databases_get,
+            api_sql_databases_delete, api_sql_databases_get,
         },
         variable_pairs_api::{
             api_variable_pairs_delete, api_variable_pairs_get, api_variable_pairs_post,
@@ -35,26 +34,114 @@ use cloud_openapi::{
         RefreshTokenCommand, RegisterRevisionCommand, ResourceLabel, RevisionItemPage, TokenInfo,
     },
 };
-use reqwest::header;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use reqwest::{header, Method, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 use uuid::Uuid;
 
-use crate::CloudClientInterface;
+use crate::{
+    models::{
+        AppLabels, ChannelItem, ChannelItemPage, DomainItem, DomainItemPage, ExecuteSqlResult,
+        KeyList, SqlStatementResult,
+    },
+    CloudClientInterface,
+};
 
 const JSON_MIME_TYPE: &str = "application/json";
+const OCTET_STREAM_MIME_TYPE: &str = "application/octet-stream";
 // Requested API version of cloud service
 const CLOUD_API_VERSION: &str = "1.0";
+// The range rule of a channel that serves whichever revision is newest
+const LATEST_REVISION_RANGE_RULE: &str = "*";
+/// The `tracing` target of the request log: a summary of each call at
+/// info level, and of each HTTP request the client makes itself at debug level.
+pub const API_LOG_TARGET: &str = "cloud::api";
 
 pub struct Client {
     configuration: Configuration,
 }
 
+/// The response to a request made with [`CloudClientInterface::send_raw`]
+#[derive(Debug)]
+pub struct RawResponse {
+    pub status: reqwest::StatusCode,
+    pub headers: header::HeaderMap,
+    pub body: Vec<u8>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct ConnectionConfig {
     pub insecure: bool,
     pub token: String,
     pub url: String,
+    #[serde(skip)]
+    pub http: HttpConfig,
+}
+
+/// Network settings for the HTTP clients the plugin creates
+#[derive(Clone, Debug, Default)]
+pub struct HttpConfig {
+    /// Limit on each request as a whole, from connecting until the response has been read
+    pub timeout: Option<Duration>,
+    /// Limit on establishing each connection
+    pub connect_timeout: Option<Duration>,
+    proxies: Vec<reqwest::Proxy>,
+}
+
+impl HttpConfig {
+    /// Settings with the given timeouts, which go through the proxies named
+    /// by the `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment variables.
+    pub fn from_env(timeout: Option<Duration>, connect_timeout: Option<Duration>) -> Result<Self> {
+        Ok(Self {
+            timeout,
+            connect_timeout,
+            proxies: proxies_from(|name| std::env::var(name).ok())?,
+        })
+    }
+
+    /// Applies these settings to `builder`
+    pub fn apply(&self, mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        // Proxies are only ever the ones configured here, so that an invalid
+        // proxy variable is reported rather than silently ignored
+        builder = builder.no_proxy();
+        for proxy in &self.proxies {
+            builder = builder.proxy(proxy.clone());
+        }
+        builder
+    }
+}
+
+// Reads proxy settings as curl does: the lowercase variables take precedence,
+// and HTTPS_PROXY applies only to https URLs and HTTP_PROXY only to http ones.
+fn proxies_from(var: impl Fn(&str) -> Option<String>) -> Result<Vec<reqwest::Proxy>> {
+    let lookup = |lower: &str, upper: &str| {
+        [lower, upper].into_iter().find_map(|name| {
+            var(name)
+                .filter(|v| !v.is_empty())
+                .map(|v| (name.to_owned(), v))
+        })
+    };
+    let no_proxy =
+        lookup("no_proxy", "NO_PROXY").and_then(|(_, value)| reqwest::NoProxy::from_string(&value));
+    let mut proxies = vec![];
+    if let Some((name, url)) = lookup("https_proxy", "HTTPS_PROXY") {
+        let proxy = reqwest::Proxy::https(&url)
+            .with_context(|| format!("Invalid proxy URL '{url}' in {name}"))?;
+        proxies.push(proxy.no_proxy(no_proxy.clone()));
+    }
+    if let Some((name, url)) = lookup("http_proxy", "HTTP_PROXY") {
+        let proxy = reqwest::Proxy::http(&url)
+            .with_context(|| format!("Invalid proxy URL '{url}' in {name}"))?;
+        proxies.push(proxy.no_proxy(no_proxy));
+    }
+    Ok(proxies)
 }
 
 impl Client {
@@ -76,7 +163,9 @@ impl Client {
                 env!("CARGO_PKG_VERSION"),
                 std::env::var("SPIN_VERSION").unwrap_or_else(|_| "0".to_string())
             )),
-            client: reqwest::Client::builder()
+            client: conn_info
+                .http
+                .apply(reqwest::Client::builder())
                 .danger_accept_invalid_certs(conn_info.insecure)
                 .default_headers(headers)
                 .build()
@@ -92,6 +181,65 @@ impl Client {
 
         Self { configuration }
     }
+
+    // Builds a request against an endpoint that is not yet covered by the
+    // OpenAPI specification, carrying the same credentials as generated calls.
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let mut builder = self
+            .configuration
+            .client
+            .request(method, format!("{}{}", self.configuration.base_path, path));
+        if let Some(user_agent) = &self.configuration.user_agent {
+            builder = builder.header(header::USER_AGENT, user_agent);
+        }
+        if let Some(api_key) = &self.configuration.api_key {
+            builder = builder.bearer_auth(&api_key.key);
+        }
+        builder
+    }
+
+    async fn send(builder: RequestBuilder) -> Result<reqwest::Response> {
+        let (client, request) = builder.build_split();
+        let request = request?;
+        let (method, path) = (request.method().clone(), request.url().path().to_owned());
+        let start = std::time::Instant::now();
+        let response = client.execute(request).await?;
+        let status = response.status();
+        tracing::debug!(
+            target: API_LOG_TARGET,
+            "{method} {path}: {status} in {} ms",
+            start.elapsed().as_millis()
+        );
+        if status.is_success() {
+            Ok(response)
+        } else {
+            Err(error_from_response(response).await)
+        }
+    }
+
+    // Logs are polled while following, so they are fetched by the client
+    // itself, whose errors carry any Retry-After the service sends
+    async fn raw_logs(
+        &self,
+        path: &str,
+        max_lines: Option<i32>,
+        since: Option<String>,
+    ) -> Result<GetAppRawLogsVm> {
+        let mut query = vec![];
+        if let Some(max_lines) = max_lines {
+            query.push(("maxLines", max_lines.to_string()));
+        }
+        if let Some(since) = since {
+            query.push(("since", since));
+        }
+        Self::send_json(self.request(Method::GET, path).query(&query)).await
+    }
+
+    async fn send_json<T: DeserializeOwned>(builder: RequestBuilder) -> Result<T> {
+        let response = Self::send(builder).await?;
+        serde_json::from_reader(response.bytes().await?.as_ref())
+            .context("Failed to parse response")
+    }
 }
 
 #[async_trait]
@@ -196,9 +344,111 @@ impl CloudClientInterface for Client {
         max_lines: Option<i32>,
         since: Option<String>,
     ) -> Result<GetAppRawLogsVm> {
-        api_apps_id_logs_raw_get(&self.configuration, &id, max_lines, since.as_deref(), None)
+        self.raw_logs(&api_path(&["apps", &id, "logs", "raw"]), max_lines, since)
             .await
-            .map_err(format_response_error)
+    }
+
+    async fn channel_logs_raw(
+        &self,
+        channel_id: Uuid,
+        max_lines: Option<i32>,
+        since: Option<String>,
+    ) -> Result<GetAppRawLogsVm> {
+        let path = format!("/api/channels/{channel_id}/logs/raw");
+        self.raw_logs(&path, max_lines, since).await
+    }
+
+    async fn list_channels(&self, app_id: Uuid) -> Result<Vec<ChannelItem>> {
+        let page: ChannelItemPage = Self::send_json(
+            self.request(Method::GET, "/api/channels")
+                .query(&[("appId", app_id.to_string())]),
+        )
+        .await?;
+        Ok(page.items)
+    }
+
+    async fn set_channel_revision(&self, channel_id: Uuid, revision_id: Uuid) -> Result<()> {
+        let command = PatchChannelCommand {
+            channel_id: Some(channel_id),
+            revision_selection_strategy: Some(
+                ChannelRevisionSelectionStrategy::UseSpecifiedRevision,
+            ),
+            active_revision_id: Some(revision_id),
+            ..Default::default()
+        };
+        Self::send(
+            self.request(Method::PATCH, &format!("/api/channels/{channel_id}"))
+                .json(&command),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn follow_latest_revision(&self, channel_id: Uuid) -> Result<()> {
+        let command = PatchChannelCommand {
+            channel_id: Some(channel_id),
+            revision_selection_strategy: Some(ChannelRevisionSelectionStrategy::UseRangeRule),
+            range_rule: Some(LATEST_REVISION_RANGE_RULE.to_owned()),
+            ..Default::default()
+        };
+        Self::send(
+            self.request(Method::PATCH, &format!("/api/channels/{channel_id}"))
+                .json(&command),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn add_channel(
+        &self,
+        app_id: Uuid,
+        name: String,
+        revision_id: Uuid,
+        traffic_percentage: Option<u8>,
+    ) -> Result<Uuid> {
+        Self::send_json(
+            self.request(Method::POST, "/api/channels")
+                .json(&CreateChannelCommand {
+                    app_id,
+                    name,
+                    revision_selection_strategy:
+                        ChannelRevisionSelectionStrategy::UseSpecifiedRevision,
+                    active_revision_id: Some(revision_id),
+                    traffic_percentage,
+                }),
+        )
+        .await
+    }
+
+    async fn remove_channel(&self, channel_id: Uuid) -> Result<()> {
+        Self::send(self.request(Method::DELETE, &format!("/api/channels/{channel_id}"))).await?;
+        Ok(())
+    }
+
+    async fn send_raw(
+        &self,
+        method: Method,
+        path: &str,
+        headers: header::HeaderMap,
+        body: Option<Vec<u8>>,
+    ) -> Result<RawResponse> {
+        let mut builder = self.request(method.clone(), path).headers(headers);
+        if let Some(body) = body {
+            builder = builder.body(body);
+        }
+        let start = std::time::Instant::now();
+        let response = builder.send().await?;
+        let status = response.status();
+        tracing::debug!(
+            target: API_LOG_TARGET,
+            "{method} {path}: {status} in {} ms",
+            start.elapsed().as_millis()
+        );
+        Ok(RawResponse {
+            status,
+            headers: response.headers().clone(),
+            body: response.bytes().await?.to_vec(),
+        })
     }
 
     async fn add_revision(
@@ -262,6 +512,50 @@ impl CloudClientInterface for Client {
         .map_err(format_response_error)
     }
 
+    async fn list_keys(&self, store_name: &str) -> anyhow::Result<Vec<String>> {
+        let list: KeyList =
+            Self::send_json(self.request(Method::GET, &key_value_keys_path(store_name))).await?;
+        Ok(list.keys)
+    }
+
+    async fn get_key_value(&self, store_name: &str, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let response = Self::send(
+            self.request(Method::GET, &key_value_path(store_name, key))
+                .header(header::ACCEPT, OCTET_STREAM_MIME_TYPE),
+        )
+        .await;
+        match response {
+            Ok(response) => Ok(Some(response.bytes().await?.to_vec())),
+            Err(e)
+                if e.downcast_ref::<ResponseError>().map(|e| e.status)
+                    == Some(reqwest::StatusCode::NOT_FOUND) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn put_key_value(
+        &self,
+        store_name: &str,
+        key: &str,
+        value: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        Self::send(
+            self.request(Method::PUT, &key_value_path(store_name, key))
+                .header(header::CONTENT_TYPE, OCTET_STREAM_MIME_TYPE)
+                .body(value),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_key_value(&self, store_name: &str, key: &str) -> anyhow::Result<()> {
+        Self::send(self.request(Method::DELETE, &key_value_path(store_name, key))).await?;
+        Ok(())
+    }
+
     async fn create_key_value_store(
         &self,
         store_name: &str,
@@ -386,19 +680,23 @@ impl CloudClientInterface for Client {
         .map_err(format_response_error)
     }
 
-    async fn execute_sql(&self, database: String, statement: String) -> anyhow::Result<()> {
-        api_sql_databases_execute_post(
-            &self.configuration,
-            ExecuteSqlStatementCommand {
-                database,
-                statement,
-                default: false,
-            },
-            None,
+    async fn execute_sql(
+        &self,
+        database: String,
+        statement: String,
+    ) -> anyhow::Result<Vec<SqlStatementResult>> {
+        // The generated client discards the response body, so the request is
+        // crafted manually to get hold of the query results.
+        let result: ExecuteSqlResult = Self::send_json(
+            self.request(Method::POST, "/api/sql-databases/execute")
+                .json(&ExecuteSqlStatementCommand {
+                    database,
+                    statement,
+                    default: false,
+                }),
         )
-        .await
-        .map_err(format_response_error)?;
-        Ok(())
+        .await?;
+        Ok(result.results)
     }
 
     async fn delete_database(&self, name: String) -> anyhow::Result<()> {
@@ -445,6 +743,55 @@ impl CloudClientInterface for Client {
             .await
             .map_err(format_response_error)
     }
+
+    async fn list_domains(&self, app_id: Uuid) -> anyhow::Result<Vec<DomainItem>> {
+        let page: DomainItemPage =
+            Self::send_json(self.request(Method::GET, &format!("/api/apps/{app_id}/domains")))
+                .await?;
+        Ok(page.items)
+    }
+
+    async fn add_domain(&self, app_id: Uuid, name: String) -> anyhow::Result<DomainItem> {
+        Self::send_json(
+            self.request(Method::POST, &format!("/api/apps/{app_id}/domains"))
+                .json(&serde_json::json!({ "name": name })),
+        )
+        .await
+    }
+
+    async fn remove_domain(&self, app_id: Uuid, name: String) -> anyhow::Result<()> {
+        Self::send(self.request(Method::DELETE, &domain_path(app_id, &name))).await?;
+        Ok(())
+    }
+
+    async fn rename_app(&self, app_id: Uuid, name: String) -> anyhow::Result<()> {
+        Self::send(
+            self.request(Method::PATCH, &format!("/api/apps/{app_id}"))
+                .json(&serde_json::json!({ "name": name })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn get_app_labels(&self, app_id: Uuid) -> anyhow::Result<BTreeMap<String, String>> {
+        let labels: AppLabels =
+            Self::send_json(self.request(Method::GET, &format!("/api/apps/{app_id}/labels")))
+                .await?;
+        Ok(labels.labels)
+    }
+
+    async fn set_app_labels(
+        &self,
+        app_id: Uuid,
+        labels: BTreeMap<String, String>,
+    ) -> anyhow::Result<()> {
+        Self::send(
+            self.request(Method::PUT, &format!("/api/apps/{app_id}/labels"))
+                .json(&AppLabels { labels }),
+        )
+        .await?;
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -460,23 +807,127 @@ struct CloudProblemDetails {
 
 fn format_response_error<T>(e: Error<T>) -> anyhow::Error {
     match e {
-        Error::ResponseError(r) => {
-            // Validation failures are distinguished by the presence of `errors` so try that first
-            if let Ok(m) = serde_json::from_str::<ValidationExceptionMessage>(&r.content) {
-                anyhow::anyhow!("{} {:?}", m.title, m.errors)
-            } else if let Ok(d) = serde_json::from_str::<CloudProblemDetails>(&r.content) {
-                anyhow::anyhow!("{}", d.detail)
-            } else {
-                anyhow::anyhow!("response status code: {}", r.status)
-            }
-        }
+        Error::ResponseError(r) => format_error_content(r.status, &r.content),
         Error::Serde(err) => {
             anyhow::anyhow!(format!("could not parse JSON object: {}", err))
         }
+        // Keep the underlying error so that dropped connections can be recognized
+        Error::Reqwest(err) => anyhow::Error::new(err),
         _ => anyhow::anyhow!(e.to_string()),
     }
 }
 
+// Store names and keys are user supplied, so they need to be percent-encoded
+// as path segments rather than interpolated.
+fn key_value_path(store_name: &str, key: &str) -> String {
+    key_value_store_path(&[store_name, "keys", key])
+}
+
+fn key_value_keys_path(store_name: &str) -> String {
+    key_value_store_path(&[store_name, "keys"])
+}
+
+fn key_value_store_path(segments: &[&str]) -> String {
+    api_path(&[&["key-value-stores"], segments].concat())
+}
+
+fn domain_path(app_id: Uuid, name: &str) -> String {
+    api_path(&["apps", &app_id.to_string(), "domains", name])
+}
+
+// A path under /api, with each segment percent-encoded
+fn api_path(segments: &[&str]) -> String {
+    let mut url = reqwest::Url::parse("http://localhost/api").expect("static URL should parse");
+    url.path_segments_mut()
+        .expect("HTTP URL should have path segments")
+        .extend(segments);
+    url.path().to_owned()
+}
+
+fn format_error_content(status: reqwest::StatusCode, content: &str) -> anyhow::Error {
+    anyhow::Error::new(response_error(status, content))
+}
+
+// Only requests the client makes itself see the response headers, so only
+// their errors know how long a rate limited caller should wait
+async fn error_from_response(response: reqwest::Response) -> anyhow::Error {
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_retry_after(value, chrono::Utc::now()));
+    let content = response.text().await.unwrap_or_default();
+    let mut error = response_error(status, &content);
+    error.retry_after = retry_after;
+    anyhow::Error::new(error)
+}
+
+fn response_error(status: reqwest::StatusCode, content: &str) -> ResponseError {
+    // Validation failures are distinguished by the presence of `errors` so try that first
+    let message = if let Ok(m) = serde_json::from_str::<ValidationExceptionMessage>(content) {
+        format!("{} {:?}", m.title, m.errors)
+    } else if let Ok(d) = serde_json::from_str::<CloudProblemDetails>(content) {
+        d.detail
+    } else {
+        format!("response status code: {}", status)
+    };
+    ResponseError::new(status, message)
+}
+
+// `Retry-After` is either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    // A date in the past means the caller may retry straight away
+    Some(
+        (at.with_timezone(&chrono::Utc) - now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// An error response from Fermyon Cloud. It displays as the message the
+/// service gave, and keeps the status so callers can tell failures apart.
+#[derive(Debug)]
+pub struct ResponseError {
+    pub status: reqwest::StatusCode,
+    message: String,
+    retry_after: Option<Duration>,
+}
+
+impl ResponseError {
+    pub fn new(status: reqwest::StatusCode, message: String) -> Self {
+        Self {
+            status,
+            message,
+            retry_after: None,
+        }
+    }
+
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    /// How long the service asked the caller to wait before trying again,
+    /// if it said
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+}
+
+impl std::fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ResponseError {}
+
 #[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
 struct PatchChannelCommand {
     #[serde(rename = "channelId", skip_serializing_if = "Option::is_none")]
@@ -498,3 +949,92 @@ struct PatchChannelCommand {
     #[serde(rename = "activeRevisionId", skip_serializing_if = "Option::is_none")]
     active_revision_id: Option<uuid::Uuid>,
 }
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct CreateChannelCommand {
+    #[serde(rename = "appId")]
+    app_id: uuid::Uuid,
+    #[serde(rename = "name")]
+    name: String,
+    #[serde(rename = "revisionSelectionStrategy")]
+    revision_selection_strategy: ChannelRevisionSelectionStrategy,
+    #[serde(rename = "activeRevisionId", skip_serializing_if = "Option::is_none")]
+    active_revision_id: Option<uuid::Uuid>,
+    // Share of the app's traffic routed to this channel rather than the
+    // default deploy channel
+    #[serde(rename = "trafficPercentage", skip_serializing_if = "Option::is_none")]
+    traffic_percentage: Option<u8>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_value_path_encodes_segments() {
+        assert_eq!(
+            key_value_path("my store", "user/42?"),
+            "/api/key-value-stores/my%20store/keys/user%2F42%3F"
+        );
+        assert_eq!(
+            key_value_keys_path("my store"),
+            "/api/key-value-stores/my%20store/keys"
+        );
+    }
+
+    #[test]
+    fn domain_path_encodes_the_hostname() {
+        let app_id = Uuid::nil();
+        assert_eq!(
+            domain_path(app_id, "www.example.com"),
+            format!("/api/apps/{app_id}/domains/www.example.com")
+        );
+        assert_eq!(
+            domain_path(app_id, "a/b?c"),
+            format!("/api/apps/{app_id}/domains/a%2Fb%3Fc")
+        );
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        let now = chrono::DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn proxies_are_read_from_the_environment() -> Result<()> {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        assert!(proxies_from(env(&[]))?.is_empty());
+        assert_eq!(
+            proxies_from(env(&[
+                ("HTTPS_PROXY", "http://proxy.corp:3128"),
+                ("NO_PROXY", "localhost")
+            ]))?
+            .len(),
+            1
+        );
+        let error = proxies_from(env(&[("https_proxy", "not a url")])).unwrap_err();
+        assert!(format!("{error:#}").contains("https_proxy"));
+        Ok(())
+    }
+}
@@ -5,9 +5,14 @@ use cloud_openapi::models::{
     KeyValueStoreItem, ResourceLabel, RevisionItemPage, TokenInfo,
 };
 
+use reqwest::{header::HeaderMap, Method};
+use std::collections::BTreeMap;
 use std::string::String;
 use uuid::Uuid;
 
+use crate::client::RawResponse;
+use crate::models::{ChannelItem, DomainItem, SqlStatementResult};
+
 #[cfg_attr(feature = "mocks", mockall::automock)]
 #[async_trait]
 pub trait CloudClientInterface: Send + Sync {
@@ -34,6 +39,46 @@ pub trait CloudClientInterface: Send + Sync {
         since: Option<String>,
     ) -> Result<GetAppRawLogsVm>;
 
+    /// The raw logs of the revision a channel serves, for channels other
+    /// than the one `app_logs_raw` reads
+    async fn channel_logs_raw(
+        &self,
+        channel_id: Uuid,
+        max_lines: Option<i32>,
+        since: Option<String>,
+    ) -> Result<GetAppRawLogsVm>;
+
+    async fn list_channels(&self, app_id: Uuid) -> Result<Vec<ChannelItem>>;
+
+    /// Pins a channel to a revision. It keeps serving it, whatever revisions
+    /// are registered later, until `follow_latest_revision` is called.
+    async fn set_channel_revision(&self, channel_id: Uuid, revision_id: Uuid) -> Result<()>;
+
+    /// Makes a pinned channel serve the latest revision again, and each
+    /// revision registered after it
+    async fn follow_latest_revision(&self, channel_id: Uuid) -> Result<()>;
+
+    async fn add_channel(
+        &self,
+        app_id: Uuid,
+        name: String,
+        revision_id: Uuid,
+        traffic_percentage: Option<u8>,
+    ) -> Result<Uuid>;
+
+    async fn remove_channel(&self, channel_id: Uuid) -> Result<()>;
+
+    /// Sends a request to any endpoint, with the client's credentials, and
+    /// returns the response whatever its status. This reaches endpoints that
+    /// the client has no method for yet.
+    async fn send_raw(
+        &self,
+        method: Method,
+        path: &str,
+        headers: HeaderMap,
+        body: Option<Vec<u8>>,
+    ) -> Result<RawResponse>;
+
     async fn add_revision(
         &self,
         app_storage_id: String,
@@ -55,6 +100,19 @@ pub trait CloudClientInterface: Send + Sync {
         value: String,
     ) -> anyhow::Result<()>;
 
+    async fn list_keys(&self, store_name: &str) -> anyhow::Result<Vec<String>>;
+
+    async fn get_key_value(&self, store_name: &str, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+
+    async fn put_key_value(
+        &self,
+        store_name: &str,
+        key: &str,
+        value: Vec<u8>,
+    ) -> anyhow::Result<()>;
+
+    async fn delete_key_value(&self, store_name: &str, key: &str) -> anyhow::Result<()>;
+
     async fn create_key_value_store(
         &self,
         store_name: &str,
@@ -99,7 +157,11 @@ pub trait CloudClientInterface: Send + Sync {
         resource_label: Option<ResourceLabel>,
     ) -> anyhow::Result<()>;
 
-    async fn execute_sql(&self, database: String, statement: String) -> anyhow::Result<()>;
+    async fn execute_sql(
+        &self,
+        database: String,
+        statement: String,
+    ) -> anyhow::Result<Vec<SqlStatementResult>>;
 
     async fn delete_database(&self, name: String) -> anyhow::Result<()>;
 
@@ -118,4 +180,21 @@ pub trait CloudClientInterface: Send + Sync {
     ) -> anyhow::Result<()>;
 
     async fn rename_database(&self, database: String, new_name: String) -> anyhow::Result<()>;
+
+    async fn list_domains(&self, app_id: Uuid) -> anyhow::Result<Vec<DomainItem>>;
+
+    async fn add_domain(&self, app_id: Uuid, name: String) -> anyhow::Result<DomainItem>;
+
+    async fn remove_domain(&self, app_id: Uuid, name: String) -> anyhow::Result<()>;
+
+    async fn rename_app(&self, app_id: Uuid, name: String) -> anyhow::Result<()>;
+
+    async fn get_app_labels(&self, app_id: Uuid) -> anyhow::Result<BTreeMap<String, String>>;
+
+    /// Replaces all of an app's labels with `labels`
+    async fn set_app_labels(
+        &self,
+        app_id: Uuid,
+        labels: BTreeMap<String, String>,
+    ) -> anyhow::Result<()>;
 }
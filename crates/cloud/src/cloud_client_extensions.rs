@@ -1,13 +1,81 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset};
+use cloud_openapi::models::{Entry, RevisionItem};
+use futures::{StreamExt, TryStreamExt};
 use uuid::Uuid;
 
-use crate::CloudClientInterface;
+use crate::{models::ChannelItem, CloudClientInterface};
+
+// How many requests bulk operations such as setting variables keep in flight.
+// The service has no batch endpoints, and this hides most of the round trips
+// without flooding it.
+const BULK_CONCURRENCY: usize = 8;
+
+// How far back the first window of older log lines reaches. Each window
+// after that reaches twice as far as the one before.
+const LOG_TAIL_WINDOW_MINUTES: i64 = 15;
+
+/// Whose logs to read: an app's, which are those of its deploy channel, or
+/// those of one of its other channels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogStream {
+    App(Uuid),
+    Channel(Uuid),
+}
 
 #[async_trait]
 pub trait CloudClientExt {
     async fn get_app_id(&self, app_name: &str) -> Result<Option<Uuid>>;
     async fn get_revision_id(&self, app_id: Uuid, version: &str) -> Result<Uuid>;
+    async fn get_app_revisions(&self, app_id: Uuid) -> Result<Vec<RevisionItem>>;
+    async fn get_channel(&self, app_id: Uuid, channel_name: &str) -> Result<ChannelItem>;
+    async fn get_app_logs_since(
+        &self,
+        app_id: Uuid,
+        since: String,
+        limit: Option<usize>,
+    ) -> Result<Vec<Entry>>;
+    async fn get_logs_raw(
+        &self,
+        stream: LogStream,
+        max_lines: Option<i32>,
+        since: Option<String>,
+    ) -> Result<Vec<Entry>>;
+    async fn get_logs_since(
+        &self,
+        stream: LogStream,
+        since: String,
+        limit: Option<usize>,
+    ) -> Result<Vec<Entry>>;
+    /// The newest `lines` lines logged since `since`, newest first. The
+    /// service caps how many lines one request returns, so if the newest page
+    /// is short, older lines are fetched in ever wider windows going back.
+    async fn get_logs_tail(
+        &self,
+        stream: LogStream,
+        since: String,
+        lines: usize,
+    ) -> Result<Vec<Entry>>;
+    async fn add_variable_pairs(&self, app_id: Uuid, variables: &[(String, String)]) -> Result<()>;
+    async fn delete_variable_pairs(&self, app_id: Uuid, variables: &[String]) -> Result<()>;
+    async fn add_key_value_pairs(
+        &self,
+        app_id: Option<Uuid>,
+        store_name: &str,
+        pairs: &[(String, String)],
+    ) -> Result<()>;
+    /// The value of each of `keys` in the store, in the same order
+    async fn get_key_values(
+        &self,
+        store_name: &str,
+        keys: &[String],
+    ) -> Result<Vec<Option<Vec<u8>>>>;
+    async fn put_key_values(&self, store_name: &str, pairs: &[(String, Vec<u8>)]) -> Result<()>;
+    /// The labels of each of `app_ids`, in the same order
+    async fn get_labels_for_apps(&self, app_ids: &[Uuid]) -> Result<Vec<BTreeMap<String, String>>>;
 }
 
 #[async_trait]
@@ -46,4 +114,506 @@ impl<T: CloudClientInterface> CloudClientExt for T {
             app_id
         ))
     }
+
+    async fn get_app_revisions(&self, app_id: Uuid) -> Result<Vec<RevisionItem>> {
+        let mut revisions = self.list_revisions().await?;
+        let mut app_revisions = vec![];
+
+        loop {
+            app_revisions.extend(
+                revisions
+                    .items
+                    .iter()
+                    .filter(|x| x.app_id == app_id)
+                    .cloned(),
+            );
+
+            if revisions.is_last_page {
+                break;
+            }
+
+            revisions = self.list_revisions_next(&revisions).await?;
+        }
+
+        Ok(app_revisions)
+    }
+
+    async fn get_channel(&self, app_id: Uuid, channel_name: &str) -> Result<ChannelItem> {
+        let channels = self
+            .list_channels(app_id)
+            .await
+            .context("Could not fetch channels")?;
+        let names = channels
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        channels
+            .iter()
+            .find(|c| c.name == channel_name)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "No channel named '{}' found. Available channels: {}",
+                    channel_name,
+                    names
+                )
+            })
+    }
+
+    // The logs of the app's deploy channel, as `get_logs_since` fetches them
+    async fn get_app_logs_since(
+        &self,
+        app_id: Uuid,
+        since: String,
+        limit: Option<usize>,
+    ) -> Result<Vec<Entry>> {
+        self.get_logs_since(LogStream::App(app_id), since, limit)
+            .await
+    }
+
+    async fn get_logs_raw(
+        &self,
+        stream: LogStream,
+        max_lines: Option<i32>,
+        since: Option<String>,
+    ) -> Result<Vec<Entry>> {
+        let logs = match stream {
+            LogStream::App(app_id) => {
+                self.app_logs_raw(app_id.to_string(), max_lines, since)
+                    .await?
+            }
+            LogStream::Channel(channel_id) => {
+                self.channel_logs_raw(channel_id, max_lines, since).await?
+            }
+        };
+        Ok(logs.entries)
+    }
+
+    // Each request sends only `since`, and the service returns a capped page of
+    // lines from then on, so page forward from the newest line of each page
+    // until a page brings nothing newer. Pages overlap at their boundary
+    // timestamp; callers should expect lines at that timestamp more than once.
+    // `limit` is never sent: paging stops once at least that many lines have
+    // been fetched, and all of them are returned, so there may be more.
+    async fn get_logs_since(
+        &self,
+        stream: LogStream,
+        since: String,
+        limit: Option<usize>,
+    ) -> Result<Vec<Entry>> {
+        let mut entries = vec![];
+        let mut since = since;
+        let mut line_count = 0;
+        loop {
+            let page = self.get_logs_raw(stream, None, Some(since.clone())).await?;
+            let newest = page
+                .iter()
+                .filter_map(|e| e.log_lines.as_ref())
+                .flatten()
+                .filter_map(|l| l.time.as_deref())
+                .filter_map(|t| DateTime::parse_from_rfc3339(t).ok().map(|p| (p, t)))
+                .max_by_key(|(parsed, _)| *parsed)
+                .map(|(_, t)| t.to_owned());
+            line_count += page
+                .iter()
+                .filter_map(|e| e.log_lines.as_ref())
+                .map(|l| l.len())
+                .sum::<usize>();
+            entries.extend(page);
+
+            let made_progress = match (&newest, DateTime::parse_from_rfc3339(&since)) {
+                (Some(newest), Ok(since)) => {
+                    DateTime::parse_from_rfc3339(newest).is_ok_and(|n| n > since)
+                }
+                (Some(newest), Err(_)) => newest > &since,
+                (None, _) => false,
+            };
+            let reached_limit = limit.is_some_and(|limit| line_count >= limit);
+            match newest {
+                Some(newest) if made_progress && !reached_limit => since = newest,
+                _ => return Ok(entries),
+            }
+        }
+    }
+
+    async fn get_logs_tail(
+        &self,
+        stream: LogStream,
+        since: String,
+        lines: usize,
+    ) -> Result<Vec<Entry>> {
+        let max_lines = i32::try_from(lines).unwrap_or(i32::MAX);
+        let page = self
+            .get_logs_raw(stream, Some(max_lines), Some(since.clone()))
+            .await?;
+        let mut found = timed_log_lines(page);
+        let (Ok(floor), Some((oldest, _))) = (DateTime::parse_from_rfc3339(&since), found.first())
+        else {
+            return Ok(newest_lines(found, lines));
+        };
+        let mut searched_from = *oldest;
+        let mut window = chrono::Duration::minutes(LOG_TAIL_WINDOW_MINUTES);
+        while found.len() < lines && searched_from > floor {
+            let start = (searched_from - window).max(floor);
+            // The newer lines may not include all of those logged at the
+            // boundary, so those are fetched again along with the older ones
+            found.retain(|(time, _)| *time > searched_from);
+            let mut older = logs_between(self, stream, start, searched_from).await?;
+            older.append(&mut found);
+            found = older;
+            searched_from = start;
+            window = window * 2;
+        }
+        Ok(newest_lines(found, lines))
+    }
+
+    // Stops issuing requests at the first failure; those already in flight
+    // may still have been applied
+    async fn add_variable_pairs(&self, app_id: Uuid, variables: &[(String, String)]) -> Result<()> {
+        futures::stream::iter(last_value_wins(variables).into_iter().map(Ok))
+            .try_for_each_concurrent(BULK_CONCURRENCY, |(name, value)| async move {
+                self.add_variable_pair(app_id, name.clone(), value.clone())
+                    .await
+                    .with_context(|| format!("Problem creating variable {name}"))
+            })
+            .await
+    }
+
+    async fn delete_variable_pairs(&self, app_id: Uuid, variables: &[String]) -> Result<()> {
+        let mut seen = HashSet::new();
+        let variables = variables.iter().filter(|name| seen.insert(name.as_str()));
+        futures::stream::iter(variables.map(Ok))
+            .try_for_each_concurrent(BULK_CONCURRENCY, |name| async move {
+                self.delete_variable_pair(app_id, name.clone())
+                    .await
+                    .with_context(|| format!("Problem deleting variable {name}"))
+            })
+            .await
+    }
+
+    async fn add_key_value_pairs(
+        &self,
+        app_id: Option<Uuid>,
+        store_name: &str,
+        pairs: &[(String, String)],
+    ) -> Result<()> {
+        futures::stream::iter(last_value_wins(pairs).into_iter().map(Ok))
+            .try_for_each_concurrent(BULK_CONCURRENCY, |(key, value)| async move {
+                self.add_key_value_pair(app_id, store_name.to_owned(), key.clone(), value.clone())
+                    .await
+                    .with_context(|| format!("Problem creating key/value {key}"))
+            })
+            .await
+    }
+
+    async fn get_key_values(
+        &self,
+        store_name: &str,
+        keys: &[String],
+    ) -> Result<Vec<Option<Vec<u8>>>> {
+        futures::stream::iter(keys)
+            .map(|key| async move {
+                self.get_key_value(store_name, key)
+                    .await
+                    .with_context(|| format!("Problem reading key {key}"))
+            })
+            .buffered(BULK_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    async fn put_key_values(&self, store_name: &str, pairs: &[(String, Vec<u8>)]) -> Result<()> {
+        futures::stream::iter(last_value_wins(pairs).into_iter().map(Ok))
+            .try_for_each_concurrent(BULK_CONCURRENCY, |(key, value)| async move {
+                self.put_key_value(store_name, key, value.clone())
+                    .await
+                    .with_context(|| format!("Problem writing key {key}"))
+            })
+            .await
+    }
+
+    async fn get_labels_for_apps(&self, app_ids: &[Uuid]) -> Result<Vec<BTreeMap<String, String>>> {
+        futures::stream::iter(app_ids)
+            .map(|app_id| async move {
+                self.get_app_labels(*app_id)
+                    .await
+                    .with_context(|| format!("Problem fetching labels of app {app_id}"))
+            })
+            .buffered(BULK_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+}
+
+// The pairs to write concurrently, leaving out those that a later pair with
+// the same key replaces. Applied one after another, the last value would win,
+// but concurrent requests can finish in any order.
+fn last_value_wins<V>(pairs: &[(String, V)]) -> Vec<&(String, V)> {
+    let last = pairs
+        .iter()
+        .enumerate()
+        .map(|(index, (key, _))| (key.as_str(), index))
+        .collect::<HashMap<_, _>>();
+    pairs
+        .iter()
+        .enumerate()
+        .filter(|(index, (key, _))| last[key.as_str()] == *index)
+        .map(|(_, pair)| pair)
+        .collect()
+}
+
+// Each logged line, as an entry of its own, with the time it was logged,
+// oldest first. Entries come newest first. Lines without a valid timestamp
+// cannot be placed and are left out.
+fn timed_log_lines(entries: Vec<Entry>) -> Vec<(DateTime<FixedOffset>, Entry)> {
+    let mut lines = entries
+        .iter()
+        .rev()
+        .flat_map(|entry| {
+            entry.log_lines.iter().flatten().filter_map(|line| {
+                let time = DateTime::parse_from_rfc3339(line.time.as_deref()?).ok()?;
+                let entry = Entry {
+                    log_lines: Some(vec![line.clone()]),
+                    ..entry.clone()
+                };
+                Some((time, entry))
+            })
+        })
+        .collect::<Vec<_>>();
+    lines.sort_by_key(|(time, _)| *time);
+    lines
+}
+
+// The newest `count` of `lines`, newest first, as entries are returned
+fn newest_lines(lines: Vec<(DateTime<FixedOffset>, Entry)>, count: usize) -> Vec<Entry> {
+    lines
+        .into_iter()
+        .rev()
+        .take(count)
+        .map(|(_, entry)| entry)
+        .collect()
+}
+
+// The lines logged from `start` to `end` inclusive, oldest first. Each page
+// begins where the previous one ended, and the lines at that time which the
+// previous page already had are skipped.
+async fn logs_between<C: CloudClientInterface>(
+    client: &C,
+    stream: LogStream,
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+) -> Result<Vec<(DateTime<FixedOffset>, Entry)>> {
+    let mut lines: Vec<(DateTime<FixedOffset>, Entry)> = vec![];
+    let mut since = start;
+    loop {
+        let page = client
+            .get_logs_raw(stream, None, Some(since.to_rfc3339()))
+            .await?;
+        let page = timed_log_lines(page);
+        let newest = page.last().map(|(time, _)| *time);
+        let mut seen = lines.iter().filter(|(time, _)| *time == since).count();
+        for (time, entry) in page {
+            if time == since && seen > 0 {
+                seen -= 1;
+            } else if time <= end {
+                lines.push((time, entry));
+            }
+        }
+        match newest {
+            Some(newest) if newest > since && newest < end => since = newest,
+            _ => return Ok(lines),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockCloudClientInterface;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn add_variable_pairs_sets_every_variable() -> Result<()> {
+        let variables = (0..20)
+            .map(|i| (format!("var{i}"), format!("value{i}")))
+            .collect::<Vec<_>>();
+        let set = Arc::new(Mutex::new(vec![]));
+        let recorded = set.clone();
+
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_add_variable_pair()
+            .times(20)
+            .returning(move |_, name, value| {
+                recorded.lock().unwrap().push((name, value));
+                Ok(())
+            });
+        mock.add_variable_pairs(Uuid::new_v4(), &variables).await?;
+
+        let mut set = set.lock().unwrap().clone();
+        set.sort_by_key(|(name, _)| name[3..].parse::<u32>().unwrap());
+        assert_eq!(set, variables);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_variable_pairs_sets_the_last_value_of_repeated_variables() -> Result<()> {
+        let variables = [("a", "1"), ("b", "2"), ("a", "3")]
+            .map(|(name, value)| (name.to_owned(), value.to_owned()));
+        let set = Arc::new(Mutex::new(vec![]));
+        let recorded = set.clone();
+
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_add_variable_pair()
+            .times(2)
+            .returning(move |_, name, value| {
+                recorded.lock().unwrap().push((name, value));
+                Ok(())
+            });
+        mock.add_variable_pairs(Uuid::new_v4(), &variables).await?;
+
+        let mut set = set.lock().unwrap().clone();
+        set.sort();
+        assert_eq!(
+            set,
+            [("a", "3"), ("b", "2")].map(|(name, value)| (name.to_owned(), value.to_owned()))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn add_variable_pairs_reports_the_failing_variable() {
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_add_variable_pair()
+            .returning(|_, name, _| match name.as_str() {
+                "bad" => Err(anyhow!("invalid value")),
+                _ => Ok(()),
+            });
+        let error = mock
+            .add_variable_pairs(Uuid::new_v4(), &[("bad".to_owned(), "x".to_owned())])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "Problem creating variable bad: invalid value"
+        );
+    }
+
+    // A client whose pages hold at most two lines, counting forward from
+    // `since` inclusive
+    fn paged_logs_client(
+        logged: &'static [(&'static str, &'static str)],
+    ) -> MockCloudClientInterface {
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_app_logs_raw().returning(move |_, _, since| {
+            let since = DateTime::parse_from_rfc3339(&since.unwrap()).unwrap();
+            logs_page(
+                logged
+                    .iter()
+                    .filter(|(time, _)| DateTime::parse_from_rfc3339(time).unwrap() >= since)
+                    .take(2),
+            )
+        });
+        mock
+    }
+
+    // A response of the logs endpoints, with an entry for each line
+    fn logs_page<'a>(
+        lines: impl Iterator<Item = &'a (&'a str, &'a str)>,
+    ) -> Result<cloud_openapi::models::GetAppRawLogsVm> {
+        let entries = lines
+            .map(|(time, line)| json!({ "logLines": [{ "time": time, "line": line }] }))
+            .collect::<Vec<_>>();
+        Ok(serde_json::from_value(json!({ "entries": entries }))?)
+    }
+
+    fn lines_of(entries: &[Entry]) -> Vec<&str> {
+        entries
+            .iter()
+            .filter_map(|e| e.log_lines.as_ref())
+            .flatten()
+            .filter_map(|l| l.line.as_deref())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn get_logs_since_pages_forward_until_nothing_is_newer() -> Result<()> {
+        let mock = paged_logs_client(&[
+            ("2024-01-01T00:00:01Z", "a"),
+            ("2024-01-01T00:00:02Z", "b"),
+            ("2024-01-01T00:00:03Z", "c"),
+            ("2024-01-01T00:00:04Z", "d"),
+        ]);
+        let entries = mock
+            .get_logs_since(
+                LogStream::App(Uuid::new_v4()),
+                "2024-01-01T00:00:00Z".to_owned(),
+                None,
+            )
+            .await?;
+        // Each page starts at the newest line of the one before
+        assert_eq!(lines_of(&entries), ["a", "b", "b", "c", "c", "d", "d"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_logs_since_stops_once_the_limit_is_reached() -> Result<()> {
+        let mock = paged_logs_client(&[
+            ("2024-01-01T00:00:01Z", "a"),
+            ("2024-01-01T00:00:02Z", "b"),
+            ("2024-01-01T00:00:03Z", "c"),
+            ("2024-01-01T00:00:04Z", "d"),
+        ]);
+        let entries = mock
+            .get_logs_since(
+                LogStream::App(Uuid::new_v4()),
+                "2024-01-01T00:00:00Z".to_owned(),
+                Some(3),
+            )
+            .await?;
+        assert_eq!(lines_of(&entries), ["a", "b", "b", "c"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_logs_tail_pages_back_past_the_page_size() -> Result<()> {
+        let logged = [
+            ("2024-01-01T11:30:00Z", "a"),
+            ("2024-01-01T11:50:00Z", "b"),
+            ("2024-01-01T11:55:00Z", "c"),
+            ("2024-01-01T11:55:00Z", "d"),
+            ("2024-01-01T11:58:00Z", "e"),
+            ("2024-01-01T12:00:00Z", "f"),
+        ];
+        // Like the service, tails are capped at three lines, and other pages
+        // at four, counting forward from `since`
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_app_logs_raw()
+            .returning(move |_, max_lines, since| {
+                let since = DateTime::parse_from_rfc3339(&since.unwrap()).unwrap();
+                let after = logged
+                    .iter()
+                    .filter(|(time, _)| DateTime::parse_from_rfc3339(time).unwrap() >= since);
+                let page: Vec<_> = match max_lines {
+                    Some(max) => {
+                        let after = after.collect::<Vec<_>>();
+                        let count = (max as usize).min(3).min(after.len());
+                        after[after.len() - count..].to_vec()
+                    }
+                    None => after.take(4).collect(),
+                };
+                logs_page(page.into_iter().rev())
+            });
+
+        let entries = mock
+            .get_logs_tail(
+                LogStream::App(Uuid::new_v4()),
+                "2024-01-01T10:00:00Z".to_owned(),
+                4,
+            )
+            .await?;
+        assert_eq!(lines_of(&entries), ["f", "e", "d", "c"]);
+        Ok(())
+    }
 }
@@ -1,10 +1,16 @@
 pub mod client;
 mod client_interface;
 mod cloud_client_extensions;
+pub mod models;
+pub mod recording;
+pub mod retry;
+pub mod testing;
 
 pub use client_interface::CloudClientInterface;
 #[cfg(feature = "mocks")]
 pub use client_interface::MockCloudClientInterface;
-pub use cloud_client_extensions::CloudClientExt;
+pub use cloud_client_extensions::{CloudClientExt, LogStream};
 
 pub const DEFAULT_APPLIST_PAGE_SIZE: i32 = 50;
+// The channel that `spin cloud deploy` creates for every app
+pub const SPIN_DEPLOY_CHANNEL_NAME: &str = "spin-deploy";
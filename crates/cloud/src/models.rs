@@ -0,0 +1,98 @@
+//! Models for Fermyon Cloud endpoints that are not yet described by the
+//! OpenAPI specification. When the specification catches up, these should
+//! be replaced by their `cloud_openapi::models` equivalents.
+use std::collections::BTreeMap;
+
+use cloud_openapi::models::ChannelRevisionSelectionStrategy;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct ChannelItem {
+    #[serde(rename = "id")]
+    pub id: Uuid,
+    #[serde(rename = "appId")]
+    pub app_id: Uuid,
+    #[serde(rename = "name")]
+    pub name: String,
+    #[serde(rename = "domain", default)]
+    pub domain: Option<String>,
+    #[serde(rename = "activeRevisionId", default)]
+    pub active_revision_id: Option<Uuid>,
+    #[serde(rename = "activeRevisionNumber", default)]
+    pub active_revision_number: Option<String>,
+    /// Whether the channel follows the latest revision or is pinned to one
+    #[serde(rename = "revisionSelectionStrategy", default)]
+    pub revision_selection_strategy: Option<ChannelRevisionSelectionStrategy>,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct ChannelItemPage {
+    #[serde(rename = "items")]
+    pub items: Vec<ChannelItem>,
+}
+
+/// The rows produced by a single statement executed against a SQLite database
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct SqlStatementResult {
+    #[serde(rename = "columns", default)]
+    pub columns: Vec<String>,
+    #[serde(rename = "rows", default)]
+    pub rows: Vec<Vec<serde_json::Value>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct ExecuteSqlResult {
+    #[serde(rename = "results", default)]
+    pub results: Vec<SqlStatementResult>,
+}
+
+/// A custom domain assigned to an app
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct DomainItem {
+    #[serde(rename = "name")]
+    pub name: String,
+    #[serde(rename = "validationStatus")]
+    pub validation_status: DomainValidationStatus,
+    /// The DNS records that must exist for the domain to be verified
+    #[serde(rename = "dnsRecords", default)]
+    pub dns_records: Vec<DnsRecord>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DomainValidationStatus {
+    #[default]
+    InProgress,
+    Provisioning,
+    Ready,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct DnsRecord {
+    #[serde(rename = "type")]
+    pub record_type: String,
+    #[serde(rename = "name")]
+    pub name: String,
+    #[serde(rename = "value")]
+    pub value: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct KeyList {
+    #[serde(rename = "keys", default)]
+    pub keys: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct DomainItemPage {
+    #[serde(rename = "items")]
+    pub items: Vec<DomainItem>,
+}
+
+/// Key/value metadata that groups apps, e.g. `team=payments`
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct AppLabels {
+    #[serde(rename = "labels", default)]
+    pub labels: BTreeMap<String, String>,
+}
@@ -0,0 +1,786 @@
+//! A [`CloudClientInterface`] that writes a copy of every call it passes on,
+//! with its arguments and response, to a directory. Tokens, device codes and
+//! the values of variables and key value pairs are redacted before anything
+//! is written, so that recordings can be shared to help reproduce problems
+//! with the API. Only the user can read the files written.
+//!
+//! Each call is written to a file of its own, numbered in the order the
+//! calls were made, holding the method name and arguments in the same shape
+//! as a [`RecordedClient`](crate::testing::RecordedClient) fixture uses.
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use cloud_openapi::models::{
+    AppItem, AppItemPage, Database, DeviceCodeItem, GetAppLogsVm, GetAppRawLogsVm,
+    KeyValueStoreItem, ResourceLabel, RevisionItemPage, TokenInfo,
+};
+use reqwest::{header::HeaderMap, Method};
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::client::RawResponse;
+use crate::models::{ChannelItem, DomainItem, SqlStatementResult};
+use crate::CloudClientInterface;
+
+const REDACTED: &str = "[redacted]";
+
+/// Writes recorded calls to a directory. One recorder is shared by every
+/// client in a process, so that its files are numbered in a single sequence.
+#[derive(Debug)]
+pub struct Recorder {
+    dir: PathBuf,
+    // Keeps the files of separate runs recorded to the same directory apart
+    run: String,
+    next: AtomicUsize,
+}
+
+impl Recorder {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create recording directory {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            run: Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string(),
+            next: AtomicUsize::new(1),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Recording is a diagnostic aid, so failing to write one never fails the call
+    fn write(
+        &self,
+        method: &str,
+        mut args: Value,
+        response: Result<Value, String>,
+        elapsed_ms: u128,
+    ) {
+        let number = self.next.fetch_add(1, Ordering::SeqCst);
+        redact(&mut args);
+        let mut record = json!({
+            "method": method,
+            "args": args,
+            "recordedAt": Utc::now().to_rfc3339(),
+            "durationMs": elapsed_ms,
+        });
+        match response {
+            Ok(mut response) => {
+                redact(&mut response);
+                record["response"] = response;
+            }
+            Err(error) => record["error"] = Value::String(error),
+        }
+        let path = self
+            .dir
+            .join(format!("{}-{number:04}-{method}.json", self.run));
+        match serde_json::to_string_pretty(&record) {
+            Ok(data) => {
+                if let Err(e) = write_private(&path, &data) {
+                    tracing::warn!("Failed to record {method} to {}: {e}", path.display());
+                }
+            }
+            Err(e) => tracing::warn!("Failed to record {method}: {e}"),
+        }
+    }
+}
+
+fn write_private(path: &Path, data: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(&mut options.open(path)?, data.as_bytes())
+}
+
+/// Replaces the value of every field that holds a credential, at any depth
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                if is_secret(name) {
+                    *field = Value::String(REDACTED.to_owned());
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+// Names are compared without case or underscores, so that `device_code` and
+// `deviceCode` are both caught
+fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_lowercase().replace('_', "");
+    name.contains("token")
+        || name.contains("password")
+        || name.contains("devicecode")
+        || name == "authorization"
+}
+
+/// Passes every call on to `inner`, recording it if a recorder is set
+pub struct RecordingClient<C> {
+    inner: C,
+    recorder: Option<Arc<Recorder>>,
+}
+
+impl<C> RecordingClient<C> {
+    pub fn new(inner: C, recorder: Option<Arc<Recorder>>) -> Self {
+        Self { inner, recorder }
+    }
+
+    async fn record<T: Serialize>(
+        &self,
+        method: &str,
+        args: Value,
+        call: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        self.record_as(method, args, call, |response| {
+            serde_json::to_value(response).map_err(|e| e.to_string())
+        })
+        .await
+    }
+
+    // Records the response as `describe` shows it, for responses that hold
+    // secrets no field name gives away
+    async fn record_as<T>(
+        &self,
+        method: &str,
+        args: Value,
+        call: impl Future<Output = Result<T>>,
+        describe: impl FnOnce(&T) -> Result<Value, String>,
+    ) -> Result<T> {
+        let Some(recorder) = &self.recorder else {
+            return call.await;
+        };
+        let start = Instant::now();
+        let result = call.await;
+        let response = match &result {
+            Ok(response) => describe(response),
+            Err(e) => Err(format!("{e:#}")),
+        };
+        recorder.write(method, args, response, start.elapsed().as_millis());
+        result
+    }
+}
+
+#[async_trait]
+impl<C: CloudClientInterface> CloudClientInterface for RecordingClient<C> {
+    async fn create_device_code(&self, client_id: Uuid) -> Result<DeviceCodeItem> {
+        self.record(
+            "create_device_code",
+            json!({ "client_id": client_id }),
+            self.inner.create_device_code(client_id),
+        )
+        .await
+    }
+
+    async fn login(&self, token: String) -> Result<TokenInfo> {
+        self.record("login", json!({ "token": token }), self.inner.login(token))
+            .await
+    }
+
+    async fn refresh_token(&self, token: String, refresh_token: String) -> Result<TokenInfo> {
+        let args = json!({ "token": token, "refresh_token": refresh_token });
+        self.record(
+            "refresh_token",
+            args,
+            self.inner.refresh_token(token, refresh_token),
+        )
+        .await
+    }
+
+    async fn add_app(&self, name: &str, storage_id: &str) -> Result<Uuid> {
+        let args = json!({ "name": name, "storage_id": storage_id });
+        self.record("add_app", args, self.inner.add_app(name, storage_id))
+            .await
+    }
+
+    async fn remove_app(&self, id: String) -> Result<()> {
+        self.record("remove_app", json!({ "id": id }), self.inner.remove_app(id))
+            .await
+    }
+
+    async fn get_app(&self, id: String) -> Result<AppItem> {
+        self.record("get_app", json!({ "id": id }), self.inner.get_app(id))
+            .await
+    }
+
+    async fn list_apps(&self, page_size: i32, page_index: Option<i32>) -> Result<AppItemPage> {
+        let args = json!({ "page_size": page_size, "page_index": page_index });
+        self.record(
+            "list_apps",
+            args,
+            self.inner.list_apps(page_size, page_index),
+        )
+        .await
+    }
+
+    async fn app_logs(&self, id: String) -> Result<GetAppLogsVm> {
+        self.record("app_logs", json!({ "id": id }), self.inner.app_logs(id))
+            .await
+    }
+
+    async fn app_logs_raw(
+        &self,
+        id: String,
+        max_lines: Option<i32>,
+        since: Option<String>,
+    ) -> Result<GetAppRawLogsVm> {
+        let args = json!({ "id": id, "max_lines": max_lines, "since": since });
+        self.record(
+            "app_logs_raw",
+            args,
+            self.inner.app_logs_raw(id, max_lines, since),
+        )
+        .await
+    }
+
+    async fn channel_logs_raw(
+        &self,
+        channel_id: Uuid,
+        max_lines: Option<i32>,
+        since: Option<String>,
+    ) -> Result<GetAppRawLogsVm> {
+        let args = json!({ "channel_id": channel_id, "max_lines": max_lines, "since": since });
+        self.record(
+            "channel_logs_raw",
+            args,
+            self.inner.channel_logs_raw(channel_id, max_lines, since),
+        )
+        .await
+    }
+
+    async fn list_channels(&self, app_id: Uuid) -> Result<Vec<ChannelItem>> {
+        self.record(
+            "list_channels",
+            json!({ "app_id": app_id }),
+            self.inner.list_channels(app_id),
+        )
+        .await
+    }
+
+    async fn set_channel_revision(&self, channel_id: Uuid, revision_id: Uuid) -> Result<()> {
+        let args = json!({ "channel_id": channel_id, "revision_id": revision_id });
+        self.record(
+            "set_channel_revision",
+            args,
+            self.inner.set_channel_revision(channel_id, revision_id),
+        )
+        .await
+    }
+
+    async fn follow_latest_revision(&self, channel_id: Uuid) -> Result<()> {
+        self.record(
+            "follow_latest_revision",
+            json!({ "channel_id": channel_id }),
+            self.inner.follow_latest_revision(channel_id),
+        )
+        .await
+    }
+
+    async fn add_channel(
+        &self,
+        app_id: Uuid,
+        name: String,
+        revision_id: Uuid,
+        traffic_percentage: Option<u8>,
+    ) -> Result<Uuid> {
+        let args = json!({
+            "app_id": app_id,
+            "name": name,
+            "revision_id": revision_id,
+            "traffic_percentage": traffic_percentage,
+        });
+        self.record(
+            "add_channel",
+            args,
+            self.inner
+                .add_channel(app_id, name, revision_id, traffic_percentage),
+        )
+        .await
+    }
+
+    async fn remove_channel(&self, channel_id: Uuid) -> Result<()> {
+        self.record(
+            "remove_channel",
+            json!({ "channel_id": channel_id }),
+            self.inner.remove_channel(channel_id),
+        )
+        .await
+    }
+
+    // Neither the headers nor either body are recorded, since they may hold
+    // anything, secrets included
+    async fn send_raw(
+        &self,
+        method: Method,
+        path: &str,
+        headers: HeaderMap,
+        body: Option<Vec<u8>>,
+    ) -> Result<RawResponse> {
+        self.record_as(
+            "send_raw",
+            json!({ "method": method.as_str(), "path": path }),
+            self.inner.send_raw(method.clone(), path, headers, body),
+            |response| {
+                Ok(json!({ "status": response.status.as_u16(), "bytes": response.body.len() }))
+            },
+        )
+        .await
+    }
+
+    async fn add_revision(
+        &self,
+        app_storage_id: String,
+        revision_number: String,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "app_storage_id": app_storage_id, "revision_number": revision_number });
+        self.record(
+            "add_revision",
+            args,
+            self.inner.add_revision(app_storage_id, revision_number),
+        )
+        .await
+    }
+
+    async fn list_revisions(&self) -> anyhow::Result<RevisionItemPage> {
+        self.record("list_revisions", json!({}), self.inner.list_revisions())
+            .await
+    }
+
+    async fn list_revisions_next(
+        &self,
+        previous: &RevisionItemPage,
+    ) -> anyhow::Result<RevisionItemPage> {
+        self.record(
+            "list_revisions_next",
+            json!({ "previous": previous }),
+            self.inner.list_revisions_next(previous),
+        )
+        .await
+    }
+
+    async fn add_key_value_pair(
+        &self,
+        app_id: Option<Uuid>,
+        store_name: String,
+        key: String,
+        value: String,
+    ) -> anyhow::Result<()> {
+        let args = json!({
+            "app_id": app_id,
+            "store_name": store_name,
+            "key": key,
+            "value": REDACTED,
+        });
+        self.record(
+            "add_key_value_pair",
+            args,
+            self.inner
+                .add_key_value_pair(app_id, store_name, key, value),
+        )
+        .await
+    }
+
+    async fn list_keys(&self, store_name: &str) -> anyhow::Result<Vec<String>> {
+        self.record(
+            "list_keys",
+            json!({ "store_name": store_name }),
+            self.inner.list_keys(store_name),
+        )
+        .await
+    }
+
+    async fn get_key_value(&self, store_name: &str, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let args = json!({ "store_name": store_name, "key": key });
+        self.record_as(
+            "get_key_value",
+            args,
+            self.inner.get_key_value(store_name, key),
+            |value| Ok(value.as_ref().map_or(Value::Null, |_| json!(REDACTED))),
+        )
+        .await
+    }
+
+    async fn put_key_value(
+        &self,
+        store_name: &str,
+        key: &str,
+        value: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let args = json!({
+            "store_name": store_name,
+            "key": key,
+            "value": REDACTED,
+        });
+        self.record(
+            "put_key_value",
+            args,
+            self.inner.put_key_value(store_name, key, value),
+        )
+        .await
+    }
+
+    async fn delete_key_value(&self, store_name: &str, key: &str) -> anyhow::Result<()> {
+        let args = json!({ "store_name": store_name, "key": key });
+        self.record(
+            "delete_key_value",
+            args,
+            self.inner.delete_key_value(store_name, key),
+        )
+        .await
+    }
+
+    async fn create_key_value_store(
+        &self,
+        store_name: &str,
+        resource_label: Option<ResourceLabel>,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "store_name": store_name, "resource_label": resource_label });
+        self.record(
+            "create_key_value_store",
+            args,
+            self.inner
+                .create_key_value_store(store_name, resource_label),
+        )
+        .await
+    }
+
+    async fn delete_key_value_store(&self, store_name: &str) -> anyhow::Result<()> {
+        let args = json!({ "store_name": store_name });
+        self.record(
+            "delete_key_value_store",
+            args,
+            self.inner.delete_key_value_store(store_name),
+        )
+        .await
+    }
+
+    async fn rename_key_value_store(&self, store_name: &str, new_name: &str) -> anyhow::Result<()> {
+        let args = json!({ "store_name": store_name, "new_name": new_name });
+        self.record(
+            "rename_key_value_store",
+            args,
+            self.inner.rename_key_value_store(store_name, new_name),
+        )
+        .await
+    }
+
+    async fn get_key_value_stores(
+        &self,
+        app_id: Option<Uuid>,
+    ) -> anyhow::Result<Vec<KeyValueStoreItem>> {
+        self.record(
+            "get_key_value_stores",
+            json!({ "app_id": app_id }),
+            self.inner.get_key_value_stores(app_id),
+        )
+        .await
+    }
+
+    async fn create_key_value_store_link(
+        &self,
+        key_value_store: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "key_value_store": key_value_store, "resource_label": resource_label });
+        self.record(
+            "create_key_value_store_link",
+            args,
+            self.inner
+                .create_key_value_store_link(key_value_store, resource_label),
+        )
+        .await
+    }
+
+    async fn remove_key_value_store_link(
+        &self,
+        key_value_store: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "key_value_store": key_value_store, "resource_label": resource_label });
+        self.record(
+            "remove_key_value_store_link",
+            args,
+            self.inner
+                .remove_key_value_store_link(key_value_store, resource_label),
+        )
+        .await
+    }
+
+    async fn add_variable_pair(
+        &self,
+        app_id: Uuid,
+        variable: String,
+        value: String,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "app_id": app_id, "variable": variable, "value": REDACTED });
+        self.record(
+            "add_variable_pair",
+            args,
+            self.inner.add_variable_pair(app_id, variable, value),
+        )
+        .await
+    }
+
+    async fn delete_variable_pair(&self, app_id: Uuid, variable: String) -> anyhow::Result<()> {
+        let args = json!({ "app_id": app_id, "variable": variable });
+        self.record(
+            "delete_variable_pair",
+            args,
+            self.inner.delete_variable_pair(app_id, variable),
+        )
+        .await
+    }
+
+    async fn get_variable_pairs(&self, app_id: Uuid) -> anyhow::Result<Vec<String>> {
+        self.record(
+            "get_variable_pairs",
+            json!({ "app_id": app_id }),
+            self.inner.get_variable_pairs(app_id),
+        )
+        .await
+    }
+
+    async fn create_database(
+        &self,
+        name: String,
+        resource_label: Option<ResourceLabel>,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "name": name, "resource_label": resource_label });
+        self.record(
+            "create_database",
+            args,
+            self.inner.create_database(name, resource_label),
+        )
+        .await
+    }
+
+    async fn execute_sql(
+        &self,
+        database: String,
+        statement: String,
+    ) -> anyhow::Result<Vec<SqlStatementResult>> {
+        let args = json!({ "database": database, "statement": statement });
+        self.record(
+            "execute_sql",
+            args,
+            self.inner.execute_sql(database, statement),
+        )
+        .await
+    }
+
+    async fn delete_database(&self, name: String) -> anyhow::Result<()> {
+        self.record(
+            "delete_database",
+            json!({ "name": name }),
+            self.inner.delete_database(name),
+        )
+        .await
+    }
+
+    async fn get_databases(&self, app_id: Option<Uuid>) -> anyhow::Result<Vec<Database>> {
+        self.record(
+            "get_databases",
+            json!({ "app_id": app_id }),
+            self.inner.get_databases(app_id),
+        )
+        .await
+    }
+
+    async fn create_database_link(
+        &self,
+        database: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "database": database, "resource_label": resource_label });
+        self.record(
+            "create_database_link",
+            args,
+            self.inner.create_database_link(database, resource_label),
+        )
+        .await
+    }
+
+    async fn remove_database_link(
+        &self,
+        database: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "database": database, "resource_label": resource_label });
+        self.record(
+            "remove_database_link",
+            args,
+            self.inner.remove_database_link(database, resource_label),
+        )
+        .await
+    }
+
+    async fn rename_database(&self, database: String, new_name: String) -> anyhow::Result<()> {
+        let args = json!({ "database": database, "new_name": new_name });
+        self.record(
+            "rename_database",
+            args,
+            self.inner.rename_database(database, new_name),
+        )
+        .await
+    }
+
+    async fn list_domains(&self, app_id: Uuid) -> anyhow::Result<Vec<DomainItem>> {
+        self.record(
+            "list_domains",
+            json!({ "app_id": app_id }),
+            self.inner.list_domains(app_id),
+        )
+        .await
+    }
+
+    async fn add_domain(&self, app_id: Uuid, name: String) -> anyhow::Result<DomainItem> {
+        let args = json!({ "app_id": app_id, "name": name });
+        self.record("add_domain", args, self.inner.add_domain(app_id, name))
+            .await
+    }
+
+    async fn remove_domain(&self, app_id: Uuid, name: String) -> anyhow::Result<()> {
+        let args = json!({ "app_id": app_id, "name": name });
+        self.record(
+            "remove_domain",
+            args,
+            self.inner.remove_domain(app_id, name),
+        )
+        .await
+    }
+
+    async fn rename_app(&self, app_id: Uuid, name: String) -> anyhow::Result<()> {
+        let args = json!({ "app_id": app_id, "name": name });
+        self.record("rename_app", args, self.inner.rename_app(app_id, name))
+            .await
+    }
+
+    async fn get_app_labels(&self, app_id: Uuid) -> anyhow::Result<BTreeMap<String, String>> {
+        self.record(
+            "get_app_labels",
+            json!({ "app_id": app_id }),
+            self.inner.get_app_labels(app_id),
+        )
+        .await
+    }
+
+    async fn set_app_labels(
+        &self,
+        app_id: Uuid,
+        labels: BTreeMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "app_id": app_id, "labels": labels });
+        self.record(
+            "set_app_labels",
+            args,
+            self.inner.set_app_labels(app_id, labels),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::RecordedClient;
+
+    #[tokio::test]
+    async fn test_calls_are_recorded_without_tokens() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("cloud-recording-{}", Uuid::new_v4()));
+        let recorder = Arc::new(Recorder::new(&dir)?);
+        let token_info = json!({
+            "token": "new-secret",
+            "refreshToken": "new-refresh",
+            "expiration": "2024-01-01T12:00:00Z"
+        });
+        let inner = RecordedClient::new()
+            .respond("refresh_token", token_info)
+            .fail("remove_app", "App not found");
+        let client = RecordingClient::new(inner, Some(recorder));
+
+        client
+            .refresh_token("old-secret".to_owned(), "old-refresh".to_owned())
+            .await?;
+        assert!(client.remove_app("app".to_owned()).await.is_err());
+
+        let mut files = std::fs::read_dir(&dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        files.sort();
+        assert_eq!(files.len(), 2);
+        let refresh = std::fs::read_to_string(&files[0])?;
+        assert!(!refresh.contains("secret") && !refresh.contains("-refresh"));
+        let refresh: Value = serde_json::from_str(&refresh)?;
+        assert_eq!(refresh["method"], "refresh_token");
+        assert_eq!(refresh["args"]["token"], REDACTED);
+        assert_eq!(refresh["response"]["expiration"], "2024-01-01T12:00:00Z");
+        let remove: Value = serde_json::from_str(&std::fs::read_to_string(&files[1])?)?;
+        assert_eq!(remove["args"]["id"], "app");
+        assert_eq!(remove["error"], "App not found");
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_device_codes_and_values_are_redacted() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("cloud-recording-{}", Uuid::new_v4()));
+        let recorder = Arc::new(Recorder::new(&dir)?);
+        let device_code = DeviceCodeItem {
+            device_code: "secret-device".to_owned(),
+            verification_url: "https://cloud.fermyon.com/device-authorization".to_owned(),
+            ..Default::default()
+        };
+        let inner = RecordedClient::new()
+            .respond("create_device_code", device_code)
+            .respond("add_variable_pair", ())
+            .respond("put_key_value", ())
+            .respond("get_key_value", Some(b"secret-kv".to_vec()));
+        let client = RecordingClient::new(inner, Some(recorder));
+
+        client.create_device_code(Uuid::new_v4()).await?;
+        client
+            .add_variable_pair(
+                Uuid::new_v4(),
+                "api_key".to_owned(),
+                "secret-var".to_owned(),
+            )
+            .await?;
+        client
+            .put_key_value("default", "k", b"secret-kv".to_vec())
+            .await?;
+        assert!(client.get_key_value("default", "k").await?.is_some());
+
+        let files = std::fs::read_dir(&dir)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        assert_eq!(files.len(), 4);
+        for file in &files {
+            let recorded = std::fs::read_to_string(file)?;
+            assert!(!recorded.contains("secret"), "{recorded}");
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                assert_eq!(file.metadata()?.permissions().mode() & 0o777, 0o600);
+            }
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,769 @@
+//! Retries transient Fermyon Cloud API failures with jittered exponential backoff.
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use cloud_openapi::models::{
+    AppItem, AppItemPage, Database, DeviceCodeItem, GetAppLogsVm, GetAppRawLogsVm,
+    KeyValueStoreItem, ResourceLabel, RevisionItemPage, TokenInfo,
+};
+use rand::Rng;
+use reqwest::{header::HeaderMap, Method};
+use uuid::Uuid;
+
+use crate::client::{RawResponse, ResponseError, API_LOG_TARGET};
+use crate::models::{ChannelItem, DomainItem, SqlStatementResult};
+use crate::CloudClientInterface;
+
+pub const DEFAULT_RETRIES: u32 = 3;
+pub const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+/// The longest `Retry-After` that is waited out. A rate limited call asked to
+/// wait longer fails instead of appearing to hang.
+pub const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Told how long a rate limited call will wait before it is retried
+pub type RateLimitNotice = fn(Duration);
+
+/// Told the name of each call once it has finished, and how long it took
+/// including any retries
+pub type CallNotice = fn(&str, Duration);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a failed call is retried. Zero disables retries.
+    pub retries: u32,
+    /// The delay before the first retry, doubled for each retry after it
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_RETRY_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            retries: 0,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    // Full exponential delay scaled by a random factor so that clients which
+    // failed together do not retry together.
+    fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self.backoff.saturating_mul(2u32.saturating_pow(attempt));
+        exponential.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+/// How safe it is to repeat a call whose outcome is unknown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Repeatable {
+    /// Repeating the call has the same effect as making it once
+    Always,
+    /// Only repeat the call if the service cannot have acted on it
+    IfUnprocessed,
+}
+
+/// Runs `call` until it succeeds, fails with an error that is not worth
+/// retrying, or runs out of retries.
+pub async fn retry<T, F, Fut>(policy: &RetryPolicy, call: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    retry_if(policy, Repeatable::Always, None, "request", call).await
+}
+
+async fn retry_if<T, F, Fut>(
+    policy: &RetryPolicy,
+    repeatable: Repeatable,
+    on_rate_limit: Option<RateLimitNotice>,
+    operation: &str,
+    mut call: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        let start = std::time::Instant::now();
+        let result = call().await;
+        let elapsed_ms = start.elapsed().as_millis();
+        match &result {
+            Ok(_) => tracing::info!(target: API_LOG_TARGET, "{operation}: ok in {elapsed_ms} ms"),
+            Err(e) => tracing::info!(
+                target: API_LOG_TARGET,
+                "{operation}: failed in {elapsed_ms} ms: {}",
+                failure_summary(e)
+            ),
+        }
+        let e = match result {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < policy.retries && is_retryable(&e, repeatable) => e,
+            Err(e) => return Err(e),
+        };
+        let delay = match retry_after(&e) {
+            Some(delay) if delay > MAX_RETRY_AFTER => return Err(e),
+            Some(delay) => delay,
+            None => policy.delay(attempt),
+        };
+        attempt += 1;
+        tracing::warn!(
+            "Request failed ({e:#}), retrying in {delay:?} (attempt {attempt} of {})",
+            policy.retries
+        );
+        if is_rate_limited(&e) {
+            if let Some(notice) = on_rate_limit {
+                notice(delay);
+            }
+        }
+        tokio::time::sleep(delay).await;
+    }
+}
+
+// A short description of a failed call for the request log: the status if
+// the service responded, or else the error itself
+fn failure_summary(error: &anyhow::Error) -> String {
+    match response_error(error) {
+        Some(e) => e.status.to_string(),
+        None => format!("{error:#}"),
+    }
+}
+
+fn response_error(error: &anyhow::Error) -> Option<&ResponseError> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<ResponseError>())
+}
+
+/// Whether the service rejected a call because too many were made
+pub fn is_rate_limited(error: &anyhow::Error) -> bool {
+    response_error(error).is_some_and(|e| e.status == reqwest::StatusCode::TOO_MANY_REQUESTS)
+}
+
+/// How long a rate limited call should wait before it is retried, if the
+/// service said
+pub fn retry_after(error: &anyhow::Error) -> Option<Duration> {
+    response_error(error)
+        .filter(|e| e.status == reqwest::StatusCode::TOO_MANY_REQUESTS)
+        .and_then(ResponseError::retry_after)
+}
+
+/// Whether an error is a transient failure: rate limiting, a server error
+/// or a dropped connection.
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    is_retryable(error, Repeatable::Always)
+}
+
+fn is_retryable(error: &anyhow::Error, repeatable: Repeatable) -> bool {
+    error.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<ResponseError>() {
+            // A rate limited request was rejected before it was processed
+            e.status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || (repeatable == Repeatable::Always && e.status.is_server_error())
+        } else if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            e.is_connect() || (repeatable == Repeatable::Always && e.is_timeout())
+        } else if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            repeatable == Repeatable::Always
+                && matches!(
+                    e.kind(),
+                    std::io::ErrorKind::ConnectionReset
+                        | std::io::ErrorKind::ConnectionAborted
+                        | std::io::ErrorKind::BrokenPipe
+                )
+        } else {
+            false
+        }
+    })
+}
+
+/// A client that retries the calls made through it according to a [`RetryPolicy`].
+/// Calls that create resources are only retried when the service cannot have
+/// acted on the original request.
+pub struct RetryingClient<C> {
+    inner: C,
+    policy: RetryPolicy,
+    on_rate_limit: Option<RateLimitNotice>,
+    on_call: Option<CallNotice>,
+}
+
+impl<C: CloudClientInterface> RetryingClient<C> {
+    pub fn new(inner: C, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            on_rate_limit: None,
+            on_call: None,
+        }
+    }
+
+    /// Calls `notice` before each retry of a rate limited call
+    pub fn on_rate_limit(mut self, notice: RateLimitNotice) -> Self {
+        self.on_rate_limit = Some(notice);
+        self
+    }
+
+    /// Calls `notice` as each call finishes, whether it succeeded or not
+    pub fn on_call(mut self, notice: CallNotice) -> Self {
+        self.on_call = Some(notice);
+        self
+    }
+
+    async fn retry<T, F, Fut>(&self, operation: &str, call: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.timed(
+            operation,
+            retry_if(
+                &self.policy,
+                Repeatable::Always,
+                self.on_rate_limit,
+                operation,
+                call,
+            ),
+        )
+        .await
+    }
+
+    async fn retry_unprocessed<T, F, Fut>(&self, operation: &str, call: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.timed(
+            operation,
+            retry_if(
+                &self.policy,
+                Repeatable::IfUnprocessed,
+                self.on_rate_limit,
+                operation,
+                call,
+            ),
+        )
+        .await
+    }
+
+    async fn timed<T>(&self, operation: &str, call: impl Future<Output = Result<T>>) -> Result<T> {
+        let start = std::time::Instant::now();
+        let result = call.await;
+        if let Some(notice) = self.on_call {
+            notice(operation, start.elapsed());
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<C: CloudClientInterface> CloudClientInterface for RetryingClient<C> {
+    async fn create_device_code(&self, client_id: Uuid) -> Result<DeviceCodeItem> {
+        self.retry("create_device_code", || {
+            self.inner.create_device_code(client_id)
+        })
+        .await
+    }
+
+    async fn login(&self, token: String) -> Result<TokenInfo> {
+        self.retry("login", || self.inner.login(token.clone()))
+            .await
+    }
+
+    // Refresh tokens are single use, so a refresh the service acted on cannot be repeated
+    async fn refresh_token(&self, token: String, refresh_token: String) -> Result<TokenInfo> {
+        self.retry_unprocessed("refresh_token", || {
+            self.inner
+                .refresh_token(token.clone(), refresh_token.clone())
+        })
+        .await
+    }
+
+    async fn add_app(&self, name: &str, storage_id: &str) -> Result<Uuid> {
+        self.retry_unprocessed("add_app", || self.inner.add_app(name, storage_id))
+            .await
+    }
+
+    async fn remove_app(&self, id: String) -> Result<()> {
+        self.retry("remove_app", || self.inner.remove_app(id.clone()))
+            .await
+    }
+
+    async fn get_app(&self, id: String) -> Result<AppItem> {
+        self.retry("get_app", || self.inner.get_app(id.clone()))
+            .await
+    }
+
+    async fn list_apps(&self, page_size: i32, page_index: Option<i32>) -> Result<AppItemPage> {
+        self.retry("list_apps", || self.inner.list_apps(page_size, page_index))
+            .await
+    }
+
+    async fn app_logs(&self, id: String) -> Result<GetAppLogsVm> {
+        self.retry("app_logs", || self.inner.app_logs(id.clone()))
+            .await
+    }
+
+    async fn app_logs_raw(
+        &self,
+        id: String,
+        max_lines: Option<i32>,
+        since: Option<String>,
+    ) -> Result<GetAppRawLogsVm> {
+        self.retry("app_logs_raw", || {
+            self.inner
+                .app_logs_raw(id.clone(), max_lines, since.clone())
+        })
+        .await
+    }
+
+    async fn channel_logs_raw(
+        &self,
+        channel_id: Uuid,
+        max_lines: Option<i32>,
+        since: Option<String>,
+    ) -> Result<GetAppRawLogsVm> {
+        self.retry("channel_logs_raw", || {
+            self.inner
+                .channel_logs_raw(channel_id, max_lines, since.clone())
+        })
+        .await
+    }
+
+    async fn list_channels(&self, app_id: Uuid) -> Result<Vec<ChannelItem>> {
+        self.retry("list_channels", || self.inner.list_channels(app_id))
+            .await
+    }
+
+    async fn set_channel_revision(&self, channel_id: Uuid, revision_id: Uuid) -> Result<()> {
+        self.retry("set_channel_revision", || {
+            self.inner.set_channel_revision(channel_id, revision_id)
+        })
+        .await
+    }
+
+    async fn follow_latest_revision(&self, channel_id: Uuid) -> Result<()> {
+        self.retry("follow_latest_revision", || {
+            self.inner.follow_latest_revision(channel_id)
+        })
+        .await
+    }
+
+    async fn add_channel(
+        &self,
+        app_id: Uuid,
+        name: String,
+        revision_id: Uuid,
+        traffic_percentage: Option<u8>,
+    ) -> Result<Uuid> {
+        self.retry_unprocessed("add_channel", || {
+            self.inner
+                .add_channel(app_id, name.clone(), revision_id, traffic_percentage)
+        })
+        .await
+    }
+
+    async fn remove_channel(&self, channel_id: Uuid) -> Result<()> {
+        self.retry("remove_channel", || self.inner.remove_channel(channel_id))
+            .await
+    }
+
+    async fn send_raw(
+        &self,
+        method: Method,
+        path: &str,
+        headers: HeaderMap,
+        body: Option<Vec<u8>>,
+    ) -> Result<RawResponse> {
+        let call = || {
+            self.inner
+                .send_raw(method.clone(), path, headers.clone(), body.clone())
+        };
+        if method.is_idempotent() {
+            self.retry("send_raw", call).await
+        } else {
+            self.retry_unprocessed("send_raw", call).await
+        }
+    }
+
+    async fn add_revision(
+        &self,
+        app_storage_id: String,
+        revision_number: String,
+    ) -> anyhow::Result<()> {
+        self.retry_unprocessed("add_revision", || {
+            self.inner
+                .add_revision(app_storage_id.clone(), revision_number.clone())
+        })
+        .await
+    }
+
+    async fn list_revisions(&self) -> anyhow::Result<RevisionItemPage> {
+        self.retry("list_revisions", || self.inner.list_revisions())
+            .await
+    }
+
+    async fn list_revisions_next(
+        &self,
+        previous: &RevisionItemPage,
+    ) -> anyhow::Result<RevisionItemPage> {
+        self.retry("list_revisions_next", || {
+            self.inner.list_revisions_next(previous)
+        })
+        .await
+    }
+
+    async fn add_key_value_pair(
+        &self,
+        app_id: Option<Uuid>,
+        store_name: String,
+        key: String,
+        value: String,
+    ) -> anyhow::Result<()> {
+        self.retry("add_key_value_pair", || {
+            self.inner
+                .add_key_value_pair(app_id, store_name.clone(), key.clone(), value.clone())
+        })
+        .await
+    }
+
+    async fn list_keys(&self, store_name: &str) -> anyhow::Result<Vec<String>> {
+        self.retry("list_keys", || self.inner.list_keys(store_name))
+            .await
+    }
+
+    async fn get_key_value(&self, store_name: &str, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.retry("get_key_value", || {
+            self.inner.get_key_value(store_name, key)
+        })
+        .await
+    }
+
+    async fn put_key_value(
+        &self,
+        store_name: &str,
+        key: &str,
+        value: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        self.retry("put_key_value", || {
+            self.inner.put_key_value(store_name, key, value.clone())
+        })
+        .await
+    }
+
+    async fn delete_key_value(&self, store_name: &str, key: &str) -> anyhow::Result<()> {
+        self.retry("delete_key_value", || {
+            self.inner.delete_key_value(store_name, key)
+        })
+        .await
+    }
+
+    async fn create_key_value_store(
+        &self,
+        store_name: &str,
+        resource_label: Option<ResourceLabel>,
+    ) -> anyhow::Result<()> {
+        self.retry_unprocessed("create_key_value_store", || {
+            self.inner
+                .create_key_value_store(store_name, resource_label.clone())
+        })
+        .await
+    }
+
+    async fn delete_key_value_store(&self, store_name: &str) -> anyhow::Result<()> {
+        self.retry("delete_key_value_store", || {
+            self.inner.delete_key_value_store(store_name)
+        })
+        .await
+    }
+
+    async fn rename_key_value_store(&self, store_name: &str, new_name: &str) -> anyhow::Result<()> {
+        self.retry("rename_key_value_store", || {
+            self.inner.rename_key_value_store(store_name, new_name)
+        })
+        .await
+    }
+
+    async fn get_key_value_stores(
+        &self,
+        app_id: Option<Uuid>,
+    ) -> anyhow::Result<Vec<KeyValueStoreItem>> {
+        self.retry("get_key_value_stores", || {
+            self.inner.get_key_value_stores(app_id)
+        })
+        .await
+    }
+
+    async fn create_key_value_store_link(
+        &self,
+        key_value_store: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        self.retry_unprocessed("create_key_value_store_link", || {
+            self.inner
+                .create_key_value_store_link(key_value_store, resource_label.clone())
+        })
+        .await
+    }
+
+    async fn remove_key_value_store_link(
+        &self,
+        key_value_store: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        self.retry("remove_key_value_store_link", || {
+            self.inner
+                .remove_key_value_store_link(key_value_store, resource_label.clone())
+        })
+        .await
+    }
+
+    async fn add_variable_pair(
+        &self,
+        app_id: Uuid,
+        variable: String,
+        value: String,
+    ) -> anyhow::Result<()> {
+        self.retry("add_variable_pair", || {
+            self.inner
+                .add_variable_pair(app_id, variable.clone(), value.clone())
+        })
+        .await
+    }
+
+    async fn delete_variable_pair(&self, app_id: Uuid, variable: String) -> anyhow::Result<()> {
+        self.retry("delete_variable_pair", || {
+            self.inner.delete_variable_pair(app_id, variable.clone())
+        })
+        .await
+    }
+
+    async fn get_variable_pairs(&self, app_id: Uuid) -> anyhow::Result<Vec<String>> {
+        self.retry("get_variable_pairs", || {
+            self.inner.get_variable_pairs(app_id)
+        })
+        .await
+    }
+
+    async fn create_database(
+        &self,
+        name: String,
+        resource_label: Option<ResourceLabel>,
+    ) -> anyhow::Result<()> {
+        self.retry_unprocessed("create_database", || {
+            self.inner
+                .create_database(name.clone(), resource_label.clone())
+        })
+        .await
+    }
+
+    // Statements may not be idempotent, so they are never repeated once sent
+    async fn execute_sql(
+        &self,
+        database: String,
+        statement: String,
+    ) -> anyhow::Result<Vec<SqlStatementResult>> {
+        self.retry_unprocessed("execute_sql", || {
+            self.inner.execute_sql(database.clone(), statement.clone())
+        })
+        .await
+    }
+
+    async fn delete_database(&self, name: String) -> anyhow::Result<()> {
+        self.retry("delete_database", || {
+            self.inner.delete_database(name.clone())
+        })
+        .await
+    }
+
+    async fn get_databases(&self, app_id: Option<Uuid>) -> anyhow::Result<Vec<Database>> {
+        self.retry("get_databases", || self.inner.get_databases(app_id))
+            .await
+    }
+
+    async fn create_database_link(
+        &self,
+        database: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        self.retry_unprocessed("create_database_link", || {
+            self.inner
+                .create_database_link(database, resource_label.clone())
+        })
+        .await
+    }
+
+    async fn remove_database_link(
+        &self,
+        database: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        self.retry("remove_database_link", || {
+            self.inner
+                .remove_database_link(database, resource_label.clone())
+        })
+        .await
+    }
+
+    async fn rename_database(&self, database: String, new_name: String) -> anyhow::Result<()> {
+        self.retry("rename_database", || {
+            self.inner
+                .rename_database(database.clone(), new_name.clone())
+        })
+        .await
+    }
+
+    async fn list_domains(&self, app_id: Uuid) -> anyhow::Result<Vec<DomainItem>> {
+        self.retry("list_domains", || self.inner.list_domains(app_id))
+            .await
+    }
+
+    async fn add_domain(&self, app_id: Uuid, name: String) -> anyhow::Result<DomainItem> {
+        self.retry_unprocessed("add_domain", || self.inner.add_domain(app_id, name.clone()))
+            .await
+    }
+
+    async fn remove_domain(&self, app_id: Uuid, name: String) -> anyhow::Result<()> {
+        self.retry("remove_domain", || {
+            self.inner.remove_domain(app_id, name.clone())
+        })
+        .await
+    }
+
+    async fn rename_app(&self, app_id: Uuid, name: String) -> anyhow::Result<()> {
+        self.retry("rename_app", || self.inner.rename_app(app_id, name.clone()))
+            .await
+    }
+
+    async fn get_app_labels(&self, app_id: Uuid) -> anyhow::Result<BTreeMap<String, String>> {
+        self.retry("get_app_labels", || self.inner.get_app_labels(app_id))
+            .await
+    }
+
+    async fn set_app_labels(
+        &self,
+        app_id: Uuid,
+        labels: BTreeMap<String, String>,
+    ) -> anyhow::Result<()> {
+        self.retry("set_app_labels", || {
+            self.inner.set_app_labels(app_id, labels.clone())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            retries: 2,
+            backoff: Duration::from_millis(1),
+        }
+    }
+
+    fn response_error(status: u16) -> anyhow::Error {
+        anyhow::Error::new(ResponseError::new(
+            reqwest::StatusCode::from_u16(status).unwrap(),
+            "failed".to_owned(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_until_success() {
+        let calls = AtomicU32::new(0);
+        let result = retry(&policy(), || async {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(response_error(503)),
+                _ => Ok("done"),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let calls = AtomicU32::new(0);
+        let result = retry(&policy(), || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(response_error(404))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_configured_retries() {
+        let calls = AtomicU32::new(0);
+        let result = retry(&policy(), || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(response_error(429))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn waits_as_long_as_a_rate_limited_call_is_told() {
+        let rate_limited = |seconds| {
+            anyhow::Error::new(
+                ResponseError::new(
+                    reqwest::StatusCode::TOO_MANY_REQUESTS,
+                    "slow down".to_owned(),
+                )
+                .with_retry_after(Duration::from_secs(seconds)),
+            )
+        };
+        assert_eq!(retry_after(&rate_limited(3)), Some(Duration::from_secs(3)));
+        assert_eq!(retry_after(&response_error(429)), None);
+        assert!(is_rate_limited(&response_error(429)));
+        assert!(!is_rate_limited(&response_error(503)));
+
+        // Too long a wait fails straight away
+        let calls = AtomicU32::new(0);
+        let result = retry(&policy(), || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(rate_limited(MAX_RETRY_AFTER.as_secs() + 1))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let calls = AtomicU32::new(0);
+        let result = retry(&policy(), || async {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(rate_limited(0)),
+                _ => Ok("done"),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), "done");
+    }
+
+    #[test]
+    fn unprocessed_only_retries_rate_limiting() {
+        assert!(is_retryable(
+            &response_error(429),
+            Repeatable::IfUnprocessed
+        ));
+        assert!(!is_retryable(
+            &response_error(502),
+            Repeatable::IfUnprocessed
+        ));
+        assert!(is_transient(&response_error(502)));
+    }
+}
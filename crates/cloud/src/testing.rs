@@ -0,0 +1,524 @@
+//! A [`CloudClientInterface`] that answers from recorded responses instead of
+//! Fermyon Cloud, so that commands can be exercised without network access.
+//!
+//! Responses are kept per client method, in the same JSON shape the API uses,
+//! and can be loaded from a fixture file:
+//!
+//! ```json
+//! {
+//!   "list_apps": [{ "items": [], "totalItems": 0, "pageIndex": 0, "pageSize": 50, "isLastPage": true }],
+//!   "get_variable_pairs": [["greeting"]],
+//!   "remove_app": [{ "error": "App not found" }]
+//! }
+//! ```
+//!
+//! Each call takes the next response recorded for its method, and the last
+//! response is repeated once the others are used up. Methods without a
+//! recorded response succeed if they return nothing (or an `Option`) and fail
+//! otherwise. Every call is kept, with its arguments, for assertions.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use cloud_openapi::models::{
+    AppItem, AppItemPage, Database, DeviceCodeItem, GetAppLogsVm, GetAppRawLogsVm,
+    KeyValueStoreItem, ResourceLabel, RevisionItemPage, TokenInfo,
+};
+use reqwest::{header::HeaderMap, Method};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::client::RawResponse;
+use crate::models::{ChannelItem, DomainItem, SqlStatementResult};
+use crate::CloudClientInterface;
+
+/// A call made to a [`RecordedClient`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+    pub method: String,
+    /// The arguments, keyed by parameter name
+    pub args: Value,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+enum Recorded {
+    Error(RecordedError),
+    Response(Value),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecordedError {
+    error: String,
+}
+
+// How a response to `send_raw` is recorded
+#[derive(Deserialize)]
+struct RawAnswer {
+    status: u16,
+    #[serde(default)]
+    body: String,
+}
+
+#[derive(Debug, Default)]
+pub struct RecordedClient {
+    responses: Mutex<HashMap<String, VecDeque<Recorded>>>,
+    calls: Mutex<Vec<Call>>,
+}
+
+impl RecordedClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A client answering with the responses in a JSON fixture, which maps
+    /// method names to lists of responses
+    pub fn from_fixture(json: &str) -> Result<Self> {
+        let fixture: HashMap<String, Vec<Recorded>> =
+            serde_json::from_str(json).context("Invalid client fixture")?;
+        let responses = fixture
+            .into_iter()
+            .map(|(method, responses)| (method, responses.into()))
+            .collect();
+        Ok(Self {
+            responses: Mutex::new(responses),
+            calls: Mutex::default(),
+        })
+    }
+
+    pub fn from_fixture_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read client fixture {}", path.display()))?;
+        Self::from_fixture(&json)
+    }
+
+    /// Queues `response` as the next answer to `method`
+    pub fn respond(self, method: &str, response: impl Serialize) -> Self {
+        let response = serde_json::to_value(response).expect("response should serialize to JSON");
+        self.queue(method, Recorded::Response(response))
+    }
+
+    /// Queues a failure with `message` as the next answer to `method`
+    pub fn fail(self, method: &str, message: impl Into<String>) -> Self {
+        let error = RecordedError {
+            error: message.into(),
+        };
+        self.queue(method, Recorded::Error(error))
+    }
+
+    /// The calls made so far, oldest first
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// The arguments of each call made to `method`, oldest first
+    pub fn calls_to(&self, method: &str) -> Vec<Value> {
+        self.calls()
+            .into_iter()
+            .filter(|call| call.method == method)
+            .map(|call| call.args)
+            .collect()
+    }
+
+    fn queue(self, method: &str, recorded: Recorded) -> Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .entry(method.to_owned())
+            .or_default()
+            .push_back(recorded);
+        self
+    }
+
+    fn answer<T: DeserializeOwned>(&self, method: &str, args: Value) -> Result<T> {
+        self.calls.lock().unwrap().push(Call {
+            method: method.to_owned(),
+            args,
+        });
+        let recorded = {
+            let mut responses = self.responses.lock().unwrap();
+            match responses.get_mut(method) {
+                Some(queue) if queue.len() > 1 => queue.pop_front(),
+                Some(queue) => queue.front().cloned(),
+                None => None,
+            }
+        };
+        match recorded {
+            Some(Recorded::Response(response)) => serde_json::from_value(response)
+                .with_context(|| format!("Recorded response for `{method}` has the wrong shape")),
+            Some(Recorded::Error(RecordedError { error })) => Err(anyhow!(error)),
+            None => serde_json::from_value(Value::Null)
+                .map_err(|_| anyhow!("No response recorded for `{method}`")),
+        }
+    }
+}
+
+#[async_trait]
+impl CloudClientInterface for RecordedClient {
+    async fn create_device_code(&self, client_id: Uuid) -> Result<DeviceCodeItem> {
+        self.answer("create_device_code", json!({ "client_id": client_id }))
+    }
+
+    async fn login(&self, token: String) -> Result<TokenInfo> {
+        self.answer("login", json!({ "token": token }))
+    }
+
+    async fn refresh_token(&self, token: String, refresh_token: String) -> Result<TokenInfo> {
+        let args = json!({ "token": token, "refresh_token": refresh_token });
+        self.answer("refresh_token", args)
+    }
+
+    async fn add_app(&self, name: &str, storage_id: &str) -> Result<Uuid> {
+        let args = json!({ "name": name, "storage_id": storage_id });
+        self.answer("add_app", args)
+    }
+
+    async fn remove_app(&self, id: String) -> Result<()> {
+        self.answer("remove_app", json!({ "id": id }))
+    }
+
+    async fn get_app(&self, id: String) -> Result<AppItem> {
+        self.answer("get_app", json!({ "id": id }))
+    }
+
+    async fn list_apps(&self, page_size: i32, page_index: Option<i32>) -> Result<AppItemPage> {
+        let args = json!({ "page_size": page_size, "page_index": page_index });
+        self.answer("list_apps", args)
+    }
+
+    async fn app_logs(&self, id: String) -> Result<GetAppLogsVm> {
+        self.answer("app_logs", json!({ "id": id }))
+    }
+
+    async fn app_logs_raw(
+        &self,
+        id: String,
+        max_lines: Option<i32>,
+        since: Option<String>,
+    ) -> Result<GetAppRawLogsVm> {
+        let args = json!({ "id": id, "max_lines": max_lines, "since": since });
+        self.answer("app_logs_raw", args)
+    }
+
+    async fn channel_logs_raw(
+        &self,
+        channel_id: Uuid,
+        max_lines: Option<i32>,
+        since: Option<String>,
+    ) -> Result<GetAppRawLogsVm> {
+        let args = json!({ "channel_id": channel_id, "max_lines": max_lines, "since": since });
+        self.answer("channel_logs_raw", args)
+    }
+
+    async fn list_channels(&self, app_id: Uuid) -> Result<Vec<ChannelItem>> {
+        self.answer("list_channels", json!({ "app_id": app_id }))
+    }
+
+    async fn set_channel_revision(&self, channel_id: Uuid, revision_id: Uuid) -> Result<()> {
+        let args = json!({ "channel_id": channel_id, "revision_id": revision_id });
+        self.answer("set_channel_revision", args)
+    }
+
+    async fn follow_latest_revision(&self, channel_id: Uuid) -> Result<()> {
+        self.answer(
+            "follow_latest_revision",
+            json!({ "channel_id": channel_id }),
+        )
+    }
+
+    async fn add_channel(
+        &self,
+        app_id: Uuid,
+        name: String,
+        revision_id: Uuid,
+        traffic_percentage: Option<u8>,
+    ) -> Result<Uuid> {
+        let args = json!({
+            "app_id": app_id,
+            "name": name,
+            "revision_id": revision_id,
+            "traffic_percentage": traffic_percentage,
+        });
+        self.answer("add_channel", args)
+    }
+
+    async fn remove_channel(&self, channel_id: Uuid) -> Result<()> {
+        self.answer("remove_channel", json!({ "channel_id": channel_id }))
+    }
+
+    async fn send_raw(
+        &self,
+        method: Method,
+        path: &str,
+        _headers: HeaderMap,
+        body: Option<Vec<u8>>,
+    ) -> Result<RawResponse> {
+        let args = json!({
+            "method": method.as_str(),
+            "path": path,
+            "body": body.map(|b| String::from_utf8_lossy(&b).into_owned()),
+        });
+        let answer: RawAnswer = self.answer("send_raw", args)?;
+        Ok(RawResponse {
+            status: reqwest::StatusCode::from_u16(answer.status)
+                .context("Recorded response for `send_raw` has an invalid status")?,
+            headers: HeaderMap::new(),
+            body: answer.body.into_bytes(),
+        })
+    }
+
+    async fn add_revision(
+        &self,
+        app_storage_id: String,
+        revision_number: String,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "app_storage_id": app_storage_id, "revision_number": revision_number });
+        self.answer("add_revision", args)
+    }
+
+    async fn list_revisions(&self) -> anyhow::Result<RevisionItemPage> {
+        self.answer("list_revisions", json!({}))
+    }
+
+    async fn list_revisions_next(
+        &self,
+        previous: &RevisionItemPage,
+    ) -> anyhow::Result<RevisionItemPage> {
+        self.answer("list_revisions_next", json!({ "previous": previous }))
+    }
+
+    async fn add_key_value_pair(
+        &self,
+        app_id: Option<Uuid>,
+        store_name: String,
+        key: String,
+        value: String,
+    ) -> anyhow::Result<()> {
+        let args = json!({
+            "app_id": app_id,
+            "store_name": store_name,
+            "key": key,
+            "value": value,
+        });
+        self.answer("add_key_value_pair", args)
+    }
+
+    async fn list_keys(&self, store_name: &str) -> anyhow::Result<Vec<String>> {
+        self.answer("list_keys", json!({ "store_name": store_name }))
+    }
+
+    async fn get_key_value(&self, store_name: &str, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let args = json!({ "store_name": store_name, "key": key });
+        self.answer("get_key_value", args)
+    }
+
+    async fn put_key_value(
+        &self,
+        store_name: &str,
+        key: &str,
+        value: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let args = json!({
+            "store_name": store_name,
+            "key": key,
+            "value": String::from_utf8_lossy(&value),
+        });
+        self.answer("put_key_value", args)
+    }
+
+    async fn delete_key_value(&self, store_name: &str, key: &str) -> anyhow::Result<()> {
+        let args = json!({ "store_name": store_name, "key": key });
+        self.answer("delete_key_value", args)
+    }
+
+    async fn create_key_value_store(
+        &self,
+        store_name: &str,
+        resource_label: Option<ResourceLabel>,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "store_name": store_name, "resource_label": resource_label });
+        self.answer("create_key_value_store", args)
+    }
+
+    async fn delete_key_value_store(&self, store_name: &str) -> anyhow::Result<()> {
+        let args = json!({ "store_name": store_name });
+        self.answer("delete_key_value_store", args)
+    }
+
+    async fn rename_key_value_store(&self, store_name: &str, new_name: &str) -> anyhow::Result<()> {
+        let args = json!({ "store_name": store_name, "new_name": new_name });
+        self.answer("rename_key_value_store", args)
+    }
+
+    async fn get_key_value_stores(
+        &self,
+        app_id: Option<Uuid>,
+    ) -> anyhow::Result<Vec<KeyValueStoreItem>> {
+        self.answer("get_key_value_stores", json!({ "app_id": app_id }))
+    }
+
+    async fn create_key_value_store_link(
+        &self,
+        key_value_store: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "key_value_store": key_value_store, "resource_label": resource_label });
+        self.answer("create_key_value_store_link", args)
+    }
+
+    async fn remove_key_value_store_link(
+        &self,
+        key_value_store: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "key_value_store": key_value_store, "resource_label": resource_label });
+        self.answer("remove_key_value_store_link", args)
+    }
+
+    async fn add_variable_pair(
+        &self,
+        app_id: Uuid,
+        variable: String,
+        value: String,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "app_id": app_id, "variable": variable, "value": value });
+        self.answer("add_variable_pair", args)
+    }
+
+    async fn delete_variable_pair(&self, app_id: Uuid, variable: String) -> anyhow::Result<()> {
+        let args = json!({ "app_id": app_id, "variable": variable });
+        self.answer("delete_variable_pair", args)
+    }
+
+    async fn get_variable_pairs(&self, app_id: Uuid) -> anyhow::Result<Vec<String>> {
+        self.answer("get_variable_pairs", json!({ "app_id": app_id }))
+    }
+
+    async fn create_database(
+        &self,
+        name: String,
+        resource_label: Option<ResourceLabel>,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "name": name, "resource_label": resource_label });
+        self.answer("create_database", args)
+    }
+
+    async fn execute_sql(
+        &self,
+        database: String,
+        statement: String,
+    ) -> anyhow::Result<Vec<SqlStatementResult>> {
+        let args = json!({ "database": database, "statement": statement });
+        self.answer("execute_sql", args)
+    }
+
+    async fn delete_database(&self, name: String) -> anyhow::Result<()> {
+        self.answer("delete_database", json!({ "name": name }))
+    }
+
+    async fn get_databases(&self, app_id: Option<Uuid>) -> anyhow::Result<Vec<Database>> {
+        self.answer("get_databases", json!({ "app_id": app_id }))
+    }
+
+    async fn create_database_link(
+        &self,
+        database: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "database": database, "resource_label": resource_label });
+        self.answer("create_database_link", args)
+    }
+
+    async fn remove_database_link(
+        &self,
+        database: &str,
+        resource_label: ResourceLabel,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "database": database, "resource_label": resource_label });
+        self.answer("remove_database_link", args)
+    }
+
+    async fn rename_database(&self, database: String, new_name: String) -> anyhow::Result<()> {
+        let args = json!({ "database": database, "new_name": new_name });
+        self.answer("rename_database", args)
+    }
+
+    async fn list_domains(&self, app_id: Uuid) -> anyhow::Result<Vec<DomainItem>> {
+        self.answer("list_domains", json!({ "app_id": app_id }))
+    }
+
+    async fn add_domain(&self, app_id: Uuid, name: String) -> anyhow::Result<DomainItem> {
+        let args = json!({ "app_id": app_id, "name": name });
+        self.answer("add_domain", args)
+    }
+
+    async fn remove_domain(&self, app_id: Uuid, name: String) -> anyhow::Result<()> {
+        let args = json!({ "app_id": app_id, "name": name });
+        self.answer("remove_domain", args)
+    }
+
+    async fn rename_app(&self, app_id: Uuid, name: String) -> anyhow::Result<()> {
+        let args = json!({ "app_id": app_id, "name": name });
+        self.answer("rename_app", args)
+    }
+
+    async fn get_app_labels(&self, app_id: Uuid) -> anyhow::Result<BTreeMap<String, String>> {
+        self.answer("get_app_labels", json!({ "app_id": app_id }))
+    }
+
+    async fn set_app_labels(
+        &self,
+        app_id: Uuid,
+        labels: BTreeMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let args = json!({ "app_id": app_id, "labels": labels });
+        self.answer("set_app_labels", args)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_answers_in_order_then_repeats_the_last() -> Result<()> {
+        let client = RecordedClient::new()
+            .respond("get_variable_pairs", ["first"])
+            .respond("get_variable_pairs", ["second"]);
+        let app_id = Uuid::new_v4();
+        assert_eq!(client.get_variable_pairs(app_id).await?, ["first"]);
+        assert_eq!(client.get_variable_pairs(app_id).await?, ["second"]);
+        assert_eq!(client.get_variable_pairs(app_id).await?, ["second"]);
+        assert_eq!(client.calls_to("get_variable_pairs").len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fixture_responses_errors_and_defaults() -> Result<()> {
+        let client = RecordedClient::from_fixture(
+            r#"{
+                "list_keys": [["a", "b"]],
+                "remove_app": [{ "error": "App not found" }]
+            }"#,
+        )?;
+        assert_eq!(client.list_keys("store").await?, ["a", "b"]);
+        let error = client.remove_app("id".to_owned()).await.unwrap_err();
+        assert_eq!(error.to_string(), "App not found");
+        // Nothing recorded: fine for a write, an error for a read
+        client.delete_key_value("store", "a").await?;
+        assert!(client.get_variable_pairs(Uuid::new_v4()).await.is_err());
+        assert_eq!(
+            client.calls()[0],
+            Call {
+                method: "list_keys".to_owned(),
+                args: json!({ "store_name": "store" }),
+            }
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+//! Sends a request to any endpoint of the Fermyon Cloud API, with the stored
+//! login, and prints the response as it came. Users can reach features of the
+//! API that no command covers yet.
+use std::io::{IsTerminal, Read, Write};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use cloud::{client::RawResponse, CloudClientInterface};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, StatusCode};
+
+use crate::commands::{create_cloud_client, CommonArgs};
+use crate::errors::CliError;
+
+/// Send an authenticated request to the Fermyon Cloud API and print the response
+#[derive(Parser, Debug)]
+pub struct ApiCommand {
+    /// The HTTP method, such as GET or POST
+    #[clap(value_parser = clap::builder::ValueParser::new(parse_method))]
+    method: Method,
+
+    /// The path of the endpoint, such as "/apps" or "/api/apps". Paths that
+    /// do not start with "/api" have it added. A query string may be included.
+    path: String,
+
+    /// The request body. Use "@FILE" to send the contents of a file, or "@-"
+    /// to send standard input.
+    #[clap(short = 'd', long = "data")]
+    data: Option<String>,
+
+    /// An extra request header, as "Name: value". Can be used multiple times.
+    #[clap(short = 'H', long = "header", value_parser = clap::builder::ValueParser::new(parse_header))]
+    headers: Vec<(HeaderName, HeaderValue)>,
+
+    /// Print the response status and headers before the body
+    #[clap(short = 'i', long = "include")]
+    include: bool,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+impl ApiCommand {
+    pub async fn run(self) -> Result<()> {
+        let client = create_cloud_client(self.common.deployment_env_id.as_deref()).await?;
+
+        let path = api_path(&self.path);
+        let body = self.data.as_deref().map(read_data).transpose()?;
+        let headers = self.headers.into_iter().collect::<HeaderMap>();
+        let response = client
+            .send_raw(self.method.clone(), &path, headers, body)
+            .await
+            .with_context(|| format!("Problem sending {} {path}", self.method))?;
+
+        print_response(&response, self.include)?;
+        match status_error(response.status) {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+fn parse_method(method: &str) -> Result<Method> {
+    Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+        .with_context(|| format!("'{method}' is not an HTTP method"))
+}
+
+fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue)> {
+    let Some((name, value)) = header.split_once(':') else {
+        bail!("headers must be of the form 'Name: value'");
+    };
+    Ok((
+        HeaderName::from_bytes(name.trim().as_bytes())
+            .with_context(|| format!("'{}' is not a valid header name", name.trim()))?,
+        HeaderValue::from_str(value.trim())
+            .with_context(|| format!("'{}' is not a valid header value", value.trim()))?,
+    ))
+}
+
+// Endpoints all live under /api, which is easy to leave out
+fn api_path(path: &str) -> String {
+    let path = match path.starts_with('/') {
+        true => path.to_owned(),
+        false => format!("/{path}"),
+    };
+    if path == "/api" || path.starts_with("/api/") || path.starts_with("/api?") {
+        path
+    } else {
+        format!("/api{path}")
+    }
+}
+
+fn read_data(data: &str) -> Result<Vec<u8>> {
+    match data.strip_prefix('@') {
+        Some("-") => {
+            let mut body = vec![];
+            std::io::stdin()
+                .read_to_end(&mut body)
+                .context("Could not read the request body from standard input")?;
+            Ok(body)
+        }
+        Some(path) => std::fs::read(path)
+            .with_context(|| format!("Could not read the request body from {path}")),
+        None => Ok(data.as_bytes().to_vec()),
+    }
+}
+
+fn print_response(response: &RawResponse, include: bool) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    if include {
+        writeln!(stdout, "{}", response.status)?;
+        for (name, value) in &response.headers {
+            writeln!(
+                stdout,
+                "{name}: {}",
+                String::from_utf8_lossy(value.as_bytes())
+            )?;
+        }
+        writeln!(stdout)?;
+    }
+    stdout.write_all(&response.body)?;
+    // Keeps the prompt off the end of the body, without changing what
+    // scripts read
+    if std::io::stdout().is_terminal()
+        && !response.body.is_empty()
+        && !response.body.ends_with(b"\n")
+    {
+        writeln!(stdout)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+// The body has already been printed, so the error only gives the status
+fn status_error(status: StatusCode) -> Option<anyhow::Error> {
+    if status.is_success() {
+        return None;
+    }
+    let message = format!("The request failed with status {status}");
+    Some(match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => CliError::auth(message)
+            .with_hint("Run `spin cloud login` to log in again")
+            .into(),
+        StatusCode::NOT_FOUND => CliError::not_found(message).into(),
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => {
+            CliError::validation(message).into()
+        }
+        _ => anyhow::anyhow!(message),
+    })
+}
+
+#[cfg(test)]
+mod api_tests {
+    use super::*;
+
+    #[test]
+    fn test_request_parts_are_parsed() {
+        assert_eq!(api_path("/apps"), "/api/apps");
+        assert_eq!(api_path("apps?page=2"), "/api/apps?page=2");
+        assert_eq!(api_path("/api/channels/x/logs"), "/api/channels/x/logs");
+        assert_eq!(api_path("/apiary"), "/api/apiary");
+
+        assert_eq!(parse_method("patch").unwrap(), Method::PATCH);
+        let (name, value) = parse_header("X-Request-Id: abc: 1").unwrap();
+        assert_eq!(name, "x-request-id");
+        assert_eq!(value, "abc: 1");
+        assert!(parse_header("no colon").is_err());
+
+        assert!(status_error(StatusCode::OK).is_none());
+        assert!(status_error(StatusCode::NOT_FOUND).is_some());
+    }
+}
@@ -0,0 +1,137 @@
+//! Lets commands that need an app ask for one when it was not given. Only an
+//! interactive terminal is prompted; scripts still get an error.
+use std::io::IsTerminal;
+
+use anyhow::{bail, Result};
+use cloud::{CloudClientInterface, DEFAULT_APPLIST_PAGE_SIZE};
+
+use crate::commands::cache::{self, ResponseCache};
+use crate::commands::create_cloud_client;
+use crate::errors::CliError;
+
+/// Returns `app`, or if it was omitted, an app the user picks from their apps
+pub(crate) async fn app_or_pick(
+    deployment_env_id: Option<&str>,
+    app: Option<String>,
+) -> Result<String> {
+    if let Some(app) = app {
+        return Ok(app);
+    }
+    // Prompts are drawn on stderr and read from stdin
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        bail!(CliError::usage("An app name is required")
+            .with_hint("Pass the name of the app, or run the command in a terminal to pick one"));
+    }
+    let client = create_cloud_client(deployment_env_id).await?;
+    let names = all_app_names(&client).await?;
+    let mut cache = ResponseCache::open(deployment_env_id)?;
+    cache::cache_app_list(&mut cache, &names);
+    if names.is_empty() {
+        bail!(CliError::not_found("You have no apps")
+            .with_hint("Deploy one with `spin cloud deploy`"));
+    }
+    pick(names)
+}
+
+async fn all_app_names(client: &impl CloudClientInterface) -> Result<Vec<String>> {
+    let mut page = client.list_apps(DEFAULT_APPLIST_PAGE_SIZE, None).await?;
+    let mut names = page
+        .items
+        .iter()
+        .map(|app| app.name.clone())
+        .collect::<Vec<_>>();
+    let mut page_index = 1;
+    while !page.is_last_page {
+        page = client
+            .list_apps(DEFAULT_APPLIST_PAGE_SIZE, Some(page_index))
+            .await?;
+        names.extend(page.items.iter().map(|app| app.name.clone()));
+        page_index += 1;
+    }
+    Ok(names)
+}
+
+// Asks for a search first, so that a long list of apps can be narrowed down
+// before choosing
+fn pick(mut names: Vec<String>) -> Result<String> {
+    names.sort();
+    loop {
+        let query: String = dialoguer::Input::new()
+            .with_prompt("Search apps (leave empty to show all)")
+            .allow_empty(true)
+            .interact_text()?;
+        let matches = fuzzy_matches(&names, &query);
+        if matches.is_empty() {
+            eprintln!("No apps match \"{query}\"");
+            continue;
+        }
+        let index = dialoguer::Select::new()
+            .with_prompt("Which app?")
+            .items(&matches)
+            .default(0)
+            .interact_opt()?;
+        match index {
+            Some(index) => return Ok(matches[index].to_owned()),
+            // Escape goes back to the search
+            None => continue,
+        }
+    }
+}
+
+/// The names containing the characters of `query` in order, ignoring case.
+/// Names with `query` as a substring come first, then those where the
+/// characters are closest together.
+fn fuzzy_matches<'a>(names: &'a [String], query: &str) -> Vec<&'a str> {
+    let query = query.trim().to_lowercase();
+    let mut matches = names
+        .iter()
+        .filter_map(|name| {
+            let lower = name.to_lowercase();
+            let spread = match_spread(&lower, &query)?;
+            Some(((!lower.contains(&query), spread), name.as_str()))
+        })
+        .collect::<Vec<_>>();
+    // Stable, so equally good matches keep their order
+    matches.sort_by_key(|(rank, _)| *rank);
+    matches.into_iter().map(|(_, name)| name).collect()
+}
+
+// How many characters the earliest match of `query` in `name` spans
+fn match_spread(name: &str, query: &str) -> Option<usize> {
+    let mut wanted = query.chars().peekable();
+    let mut start = None;
+    for (position, c) in name.chars().enumerate() {
+        if wanted.peek() == Some(&c) {
+            wanted.next();
+            start.get_or_insert(position);
+            if wanted.peek().is_none() {
+                return Some(position + 1 - start.unwrap_or(0));
+            }
+        }
+    }
+    wanted.peek().is_none().then_some(0)
+}
+
+#[cfg(test)]
+mod app_picker_tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_matches_rank_substrings_then_closeness() {
+        let names = [
+            "payments-api",
+            "pay-later",
+            "photo-app",
+            "apply",
+            "checkout",
+        ]
+        .map(str::to_owned);
+        assert_eq!(
+            fuzzy_matches(&names, "PAP"),
+            vec!["photo-app", "payments-api"]
+        );
+        assert_eq!(fuzzy_matches(&names, "app"), vec!["photo-app", "apply"]);
+        assert_eq!(fuzzy_matches(&names, "").len(), names.len());
+        assert!(fuzzy_matches(&names, "xyz").is_empty());
+    }
+}
@@ -1,8 +1,21 @@
-use crate::commands::{client_and_app_id, create_cloud_client, CommonArgs};
-use anyhow::{Context, Result};
+use crate::commands::cache::{self, ResponseCache};
+use crate::commands::{
+    app_picker::app_or_pick, client_and_app_id, create_cloud_client, http_config, CommonArgs,
+};
+use crate::errors::CliError;
+use crate::output;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
 use clap::Parser;
-use cloud::{CloudClientInterface, DEFAULT_APPLIST_PAGE_SIZE};
+use cloud::{
+    CloudClientExt, CloudClientInterface, DEFAULT_APPLIST_PAGE_SIZE, SPIN_DEPLOY_CHANNEL_NAME,
+};
 use cloud_openapi::models::{AppItem, AppItemPage, ValidationStatus};
+use comfy_table::presets::ASCII_BORDERS_ONLY_CONDENSED;
+use serde::Serialize;
+use uuid::Uuid;
 
 #[derive(Parser, Debug)]
 #[clap(about = "Manage applications deployed to Fermyon Cloud")]
@@ -13,26 +26,135 @@ pub enum AppsCommand {
     Delete(DeleteCommand),
     /// Get details about a deployed app in Fermyon Cloud
     Info(InfoCommand),
+    /// Check that a deployed app is responding, and how quickly
+    Status(StatusCommand),
+    /// Rename an app deployed in Fermyon Cloud
+    Rename(RenameCommand),
+    /// List the revisions deployed for an app, newest first, marking the active one
+    ///
+    /// Fermyon Cloud's list of revisions does not say who deployed each one,
+    /// when, or from which source digest, so the history cannot show them.
+    History(HistoryCommand),
+    /// Manage the labels that group apps, such as `team=payments`
+    #[clap(subcommand)]
+    Label(LabelCommand),
 }
 
 #[derive(Parser, Debug)]
 pub struct ListCommand {
     #[clap(flatten)]
     common: CommonArgs,
+    /// List the apps as of the last successful `apps list`, without
+    /// contacting Fermyon Cloud
+    #[clap(long = "cached")]
+    cached: bool,
+    /// Only list apps with this label (e.g. "team=payments"). Repeat to
+    /// require several labels.
+    #[clap(
+        long = "label",
+        multiple_occurrences = true,
+        conflicts_with = "cached",
+        value_parser = clap::builder::ValueParser::new(parse_label)
+    )]
+    labels: Vec<(String, String)>,
 }
 
 #[derive(Parser, Debug)]
 pub struct DeleteCommand {
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    pub app: Option<String>,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct InfoCommand {
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    pub app: Option<String>,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+/// Fermyon Cloud does not expose request metrics, so this makes requests
+/// to the app itself and reports their status and latency.
+#[derive(Parser, Debug)]
+pub struct StatusCommand {
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    pub app: Option<String>,
+    /// The path to request, relative to the app's URL
+    #[clap(long = "path", default_value = "/")]
+    pub path: String,
+    /// Keep checking, refreshing the report like `top`
+    #[clap(long = "watch", takes_value = false)]
+    pub watch: bool,
+    /// How often to check with `--watch` (e.g. "5s")
+    #[clap(
+        long = "interval",
+        default_value = "5s",
+        value_parser = clap::builder::ValueParser::new(humantime::parse_duration)
+    )]
+    pub interval: Duration,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct RenameCommand {
+    /// Current name of Spin app
+    pub app: String,
+    /// New name for the app
+    pub new_name: String,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct HistoryCommand {
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    pub app: Option<String>,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub enum LabelCommand {
+    /// Add labels to an app, or change their values
+    Set(LabelSetCommand),
+    /// Remove labels from an app
+    Unset(LabelUnsetCommand),
+    /// List the labels of an app
+    List(LabelListCommand),
+}
+
+#[derive(Parser, Debug)]
+pub struct LabelSetCommand {
     /// Name of Spin app
     pub app: String,
+    /// Labels to set, as key=value pairs
+    #[clap(
+        required = true,
+        value_parser = clap::builder::ValueParser::new(parse_label)
+    )]
+    pub labels: Vec<(String, String)>,
     #[clap(flatten)]
     common: CommonArgs,
 }
 
 #[derive(Parser, Debug)]
-pub struct InfoCommand {
+pub struct LabelUnsetCommand {
     /// Name of Spin app
     pub app: String,
+    /// Keys of the labels to remove
+    #[clap(required = true)]
+    pub keys: Vec<String>,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct LabelListCommand {
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    pub app: Option<String>,
     #[clap(flatten)]
     common: CommonArgs,
 }
@@ -43,14 +165,65 @@ impl AppsCommand {
             AppsCommand::List(cmd) => cmd.run().await,
             AppsCommand::Delete(cmd) => cmd.run().await,
             AppsCommand::Info(cmd) => cmd.run().await,
+            AppsCommand::Status(cmd) => cmd.run().await,
+            AppsCommand::Rename(cmd) => cmd.run().await,
+            AppsCommand::History(cmd) => cmd.run().await,
+            AppsCommand::Label(cmd) => cmd.run().await,
         }
     }
 }
 
 impl ListCommand {
     pub async fn run(self) -> Result<()> {
+        let mut cache = ResponseCache::open(self.common.deployment_env_id.as_deref())?;
+        if self.cached {
+            let Some((names, cached_at)) = cache::cached_app_list(&cache) else {
+                bail!("No cached app list. Run `spin cloud apps list` while online to cache it.");
+            };
+            if output::is_json() {
+                return output::print_json(&names);
+            }
+            eprintln!("Apps as of {}", cached_at.to_rfc3339());
+            for name in names {
+                println!("{name}");
+            }
+            return Ok(());
+        }
+
         let client = create_cloud_client(self.common.deployment_env_id.as_deref()).await?;
+        if !self.labels.is_empty() {
+            let names = apps_with_labels(&client, &self.labels)
+                .await?
+                .into_iter()
+                .map(|app| app.name)
+                .collect::<Vec<_>>();
+            if output::is_json() {
+                return output::print_json(&names);
+            }
+            if names.is_empty() {
+                eprintln!("No applications have the given labels");
+            }
+            for name in names {
+                println!("{name}");
+            }
+            return Ok(());
+        }
         let mut app_list_page = client.list_apps(DEFAULT_APPLIST_PAGE_SIZE, None).await?;
+        if output::is_json() {
+            // Collect every page so that a single JSON document is printed
+            let mut names = app_names(&app_list_page);
+            let mut page_index = 1;
+            while !app_list_page.is_last_page {
+                app_list_page = client
+                    .list_apps(DEFAULT_APPLIST_PAGE_SIZE, Some(page_index))
+                    .await?;
+                names.extend(app_names(&app_list_page));
+                page_index += 1;
+            }
+            cache::cache_app_list(&mut cache, &names);
+            return output::print_json(&names);
+        }
+        let mut names = app_names(&app_list_page);
         if app_list_page.total_items <= 0 {
             eprintln!("No applications found");
         } else {
@@ -61,37 +234,250 @@ impl ListCommand {
                     .list_apps(DEFAULT_APPLIST_PAGE_SIZE, Some(page_index))
                     .await?;
                 print_app_list(&app_list_page);
+                names.extend(app_names(&app_list_page));
                 page_index += 1;
             }
         }
+        cache::cache_app_list(&mut cache, &names);
         Ok(())
     }
 }
 
 impl DeleteCommand {
     pub async fn run(self) -> Result<()> {
+        let deployment_env_id = self.common.deployment_env_id.as_deref();
+        let app = app_or_pick(deployment_env_id, self.app.clone()).await?;
+        let (client, app_id) = client_and_app_id(deployment_env_id, &app).await?;
+        client
+            .remove_app(app_id.to_string())
+            .await
+            .with_context(|| format!("Problem deleting app named {app}"))?;
+        let mut cache = ResponseCache::open(deployment_env_id)?;
+        cache::forget_app(&mut cache, &app);
+        output::success(
+            &format!("Deleted app \"{app}\" successfully."),
+            serde_json::json!({ "app": &app }),
+        )
+    }
+}
+
+impl RenameCommand {
+    pub async fn run(self) -> Result<()> {
+        let new_name = self.new_name.trim();
+        if new_name.is_empty() {
+            bail!(CliError::validation("The new app name cannot be empty"));
+        }
         let (client, app_id) =
             client_and_app_id(self.common.deployment_env_id.as_deref(), &self.app).await?;
         client
-            .remove_app(app_id.to_string())
+            .rename_app(app_id, new_name.to_owned())
+            .await
+            .with_context(|| format!("Problem renaming app '{}' to '{new_name}'", self.app))?;
+        let mut cache = ResponseCache::open(self.common.deployment_env_id.as_deref())?;
+        cache::forget_app(&mut cache, &self.app);
+        cache::remember_new_app(&mut cache, new_name, app_id);
+        output::success(
+            &format!("Renamed app \"{}\" to \"{new_name}\"", self.app),
+            serde_json::json!({ "app": new_name, "previousName": &self.app }),
+        )
+    }
+}
+
+impl HistoryCommand {
+    pub async fn run(self) -> Result<()> {
+        let deployment_env_id = self.common.deployment_env_id.as_deref();
+        let app = app_or_pick(deployment_env_id, self.app.clone()).await?;
+        let (client, app_id) = client_and_app_id(deployment_env_id, &app).await?;
+        let history = revision_history(&client, app_id)
             .await
-            .with_context(|| format!("Problem deleting app named {}", &self.app))?;
-        println!("Deleted app \"{}\" successfully.", &self.app);
+            .with_context(|| format!("Problem fetching the revision history of app '{app}'"))?;
+        if output::is_json() {
+            return output::print_json(&history);
+        }
+        if history.is_empty() {
+            println!("App '{app}' has no revisions");
+            return Ok(());
+        }
+        let mut table = comfy_table::Table::new();
+        table.load_preset(ASCII_BORDERS_ONLY_CONDENSED);
+        table.set_header(["Revision", ""]);
+        table.add_rows(history.iter().map(|entry| {
+            [
+                entry.revision.as_str(),
+                if entry.active { "active" } else { "" },
+            ]
+        }));
+        println!("{table}");
         Ok(())
     }
 }
 
+/// A deployed revision, as shown by `apps history`. The revision list has
+/// nothing more to show about it.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryEntry {
+    revision: String,
+    active: bool,
+}
+
+// Newest first, marking the revision the deploy channel serves. Revisions are
+// read as `rollback` reads them, so the two always agree.
+async fn revision_history(
+    client: &impl CloudClientInterface,
+    app_id: Uuid,
+) -> Result<Vec<HistoryEntry>> {
+    let (channel, revisions) = tokio::try_join!(
+        client.get_channel(app_id, SPIN_DEPLOY_CHANNEL_NAME),
+        client.get_app_revisions(app_id),
+    )?;
+    Ok(revisions
+        .into_iter()
+        .rev()
+        .map(|revision| HistoryEntry {
+            active: channel.active_revision_id == Some(revision.id),
+            revision: revision.revision_number,
+        })
+        .collect())
+}
+
+impl LabelCommand {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Set(cmd) => {
+                let (client, app_id) =
+                    client_and_app_id(cmd.common.deployment_env_id.as_deref(), &cmd.app).await?;
+                let labels = update_labels(&client, app_id, |labels| {
+                    labels.extend(cmd.labels.iter().cloned())
+                })
+                .await
+                .with_context(|| format!("Problem setting labels of app '{}'", cmd.app))?;
+                print_labels(&cmd.app, &labels)
+            }
+            Self::Unset(cmd) => {
+                let (client, app_id) =
+                    client_and_app_id(cmd.common.deployment_env_id.as_deref(), &cmd.app).await?;
+                let labels = update_labels(&client, app_id, |labels| {
+                    labels.retain(|key, _| !cmd.keys.contains(key))
+                })
+                .await
+                .with_context(|| format!("Problem removing labels of app '{}'", cmd.app))?;
+                print_labels(&cmd.app, &labels)
+            }
+            Self::List(cmd) => {
+                let deployment_env_id = cmd.common.deployment_env_id.as_deref();
+                let app = app_or_pick(deployment_env_id, cmd.app.clone()).await?;
+                let (client, app_id) = client_and_app_id(deployment_env_id, &app).await?;
+                let labels = client
+                    .get_app_labels(app_id)
+                    .await
+                    .with_context(|| format!("Problem fetching labels of app '{app}'"))?;
+                print_labels(&app, &labels)
+            }
+        }
+    }
+}
+
+// Labels are replaced as a whole, so a change is made to the current set
+async fn update_labels(
+    client: &impl CloudClientInterface,
+    app_id: Uuid,
+    change: impl FnOnce(&mut BTreeMap<String, String>),
+) -> Result<BTreeMap<String, String>> {
+    let mut labels = client.get_app_labels(app_id).await?;
+    let before = labels.clone();
+    change(&mut labels);
+    if labels != before {
+        client.set_app_labels(app_id, labels.clone()).await?;
+    }
+    Ok(labels)
+}
+
+fn print_labels(app: &str, labels: &BTreeMap<String, String>) -> Result<()> {
+    if output::is_json() {
+        return output::print_json(labels);
+    }
+    if labels.is_empty() {
+        println!("App '{app}' has no labels");
+    }
+    for (key, value) in labels {
+        println!("{key}={value}");
+    }
+    Ok(())
+}
+
+/// Every app that has all of the given labels
+pub(crate) async fn apps_with_labels(
+    client: &impl CloudClientInterface,
+    selector: &[(String, String)],
+) -> Result<Vec<AppItem>> {
+    let mut apps = vec![];
+    let mut page_index = None;
+    loop {
+        let page = client
+            .list_apps(DEFAULT_APPLIST_PAGE_SIZE, page_index)
+            .await
+            .context("Could not fetch apps")?;
+        apps.extend(page.items);
+        if page.is_last_page {
+            break;
+        }
+        page_index = Some(page_index.unwrap_or(0) + 1);
+    }
+    let ids = apps.iter().map(|app| app.id).collect::<Vec<_>>();
+    let labels = client.get_labels_for_apps(&ids).await?;
+    Ok(apps
+        .into_iter()
+        .zip(labels)
+        .filter(|(_, labels)| has_labels(labels, selector))
+        .map(|(app, _)| app)
+        .collect())
+}
+
+fn has_labels(labels: &BTreeMap<String, String>, selector: &[(String, String)]) -> bool {
+    selector
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value))
+}
+
+/// Parses a label given as "key=value". Keys are restricted so that they
+/// can be used unquoted in selectors.
+pub(crate) fn parse_label(label: &str) -> Result<(String, String)> {
+    let Some((key, value)) = label.split_once('=') else {
+        bail!("Label '{label}' must be of the form key=value");
+    };
+    let valid_key = !key.is_empty()
+        && key.len() <= 63
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'));
+    if !valid_key {
+        bail!("Label key '{key}' may only contain letters, digits, '-', '_', '.' and '/'");
+    }
+    Ok((key.to_owned(), value.to_owned()))
+}
+
 impl InfoCommand {
     pub async fn run(self) -> Result<()> {
-        let (client, app_id) =
-            client_and_app_id(self.common.deployment_env_id.as_deref(), &self.app).await?;
+        let deployment_env_id = self.common.deployment_env_id.as_deref();
+        let name = app_or_pick(deployment_env_id, self.app.clone()).await?;
+        let (client, app_id) = client_and_app_id(deployment_env_id, &name).await?;
         let app = client
             .get_app(app_id.to_string())
             .await
-            .with_context(|| format!("Error: could not get details about {}", &self.app))?;
+            .with_context(|| format!("Error: could not get details about {name}"))?;
 
         let (current_domain, in_progress_domain) = domains_current_and_in_progress(&app);
 
+        if output::is_json() {
+            return output::print_json(&serde_json::json!({
+                "name": &app.name,
+                "description": &app.description,
+                "url": current_domain.map(|d| format!("https://{d}")),
+                "domainValidationInProgress": in_progress_domain,
+            }));
+        }
+
         println!("Name: {}", &app.name);
         print_if_present("Description: ", app.description.as_ref());
         print_if_present("URL: https://", current_domain);
@@ -103,6 +489,141 @@ impl InfoCommand {
     }
 }
 
+impl StatusCommand {
+    pub async fn run(self) -> Result<()> {
+        let deployment_env_id = self.common.deployment_env_id.as_deref();
+        let name = app_or_pick(deployment_env_id, self.app.clone()).await?;
+        let (client, app_id) = client_and_app_id(deployment_env_id, &name).await?;
+        let app = client
+            .get_app(app_id.to_string())
+            .await
+            .with_context(|| format!("Error: could not get details about {name}"))?;
+        let (Some(domain), _) = domains_current_and_in_progress(&app) else {
+            bail!("App '{name}' has no URL to check");
+        };
+        let url = format!("https://{domain}/{}", self.path.trim_start_matches('/'));
+        let http = http_config()
+            .apply(reqwest::Client::builder())
+            .timeout(PROBE_TIMEOUT)
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        let mut stats = ProbeStats::default();
+        loop {
+            let probe = probe(&http, &url).await;
+            stats.record(&probe);
+            if output::is_json() {
+                output::print_json(&serde_json::json!({
+                    "app": &name,
+                    "url": &url,
+                    "status": probe.status,
+                    "error": probe.error,
+                    "latencyMs": probe.latency.as_millis(),
+                    "checks": stats.checks,
+                    "failures": stats.failures,
+                }))?;
+            } else {
+                if self.watch {
+                    // Clear the screen and move to the top left, as `top` does
+                    print!("\x1b[2J\x1b[H");
+                }
+                for line in status_lines(&name, &url, &probe, &stats) {
+                    println!("{line}");
+                }
+            }
+            if !self.watch {
+                return match probe.error {
+                    Some(error) => Err(anyhow::anyhow!(error)),
+                    None => Ok(()),
+                };
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+}
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The outcome of one request to the app
+struct Probe {
+    status: Option<u16>,
+    error: Option<String>,
+    latency: Duration,
+}
+
+async fn probe(http: &reqwest::Client, url: &str) -> Probe {
+    let start = Instant::now();
+    let response = http.get(url).send().await;
+    let latency = start.elapsed();
+    match response {
+        Ok(response) if response.status().is_server_error() => Probe {
+            status: Some(response.status().as_u16()),
+            error: Some(format!("The app responded with {}", response.status())),
+            latency,
+        },
+        Ok(response) => Probe {
+            status: Some(response.status().as_u16()),
+            error: None,
+            latency,
+        },
+        Err(e) => Probe {
+            status: None,
+            error: Some(format!("{:#}", anyhow::Error::new(e))),
+            latency,
+        },
+    }
+}
+
+/// What a `--watch` session has seen so far
+#[derive(Default)]
+struct ProbeStats {
+    checks: u32,
+    failures: u32,
+    // Latencies of successful requests, for the summary
+    latencies: Vec<Duration>,
+}
+
+impl ProbeStats {
+    fn record(&mut self, probe: &Probe) {
+        self.checks += 1;
+        match probe.error {
+            Some(_) => self.failures += 1,
+            None => self.latencies.push(probe.latency),
+        }
+    }
+
+    fn median_latency(&self) -> Option<Duration> {
+        let mut sorted = self.latencies.clone();
+        sorted.sort();
+        sorted.get(sorted.len() / 2).copied()
+    }
+}
+
+fn status_lines(app: &str, url: &str, probe: &Probe, stats: &ProbeStats) -> Vec<String> {
+    let result = match (&probe.error, probe.status) {
+        (None, Some(status)) => format!("{status} in {} ms", probe.latency.as_millis()),
+        (Some(error), _) => format!("failing: {error}"),
+        (None, None) => "unknown".to_owned(),
+    };
+    let mut lines = vec![
+        format!("App: {app}"),
+        format!("URL: {url}"),
+        format!("Last check: {result}"),
+    ];
+    if stats.checks > 1 {
+        let failure_rate = f64::from(stats.failures) * 100.0 / f64::from(stats.checks);
+        let mut summary = format!(
+            "Checks: {}, failed: {} ({failure_rate:.1}%)",
+            stats.checks, stats.failures
+        );
+        if let Some(median) = stats.median_latency() {
+            summary.push_str(&format!(", median latency: {} ms", median.as_millis()));
+        }
+        lines.push(summary);
+    }
+    lines
+}
+
 fn domains_current_and_in_progress(app: &AppItem) -> (Option<&String>, Option<&String>) {
     let auto_domain = &app.subdomain;
     match &app.domain {
@@ -125,8 +646,160 @@ fn print_if_present(prefix: &str, value: Option<&String>) {
     }
 }
 
+fn app_names(page: &AppItemPage) -> Vec<String> {
+    page.items.iter().map(|app| app.name.clone()).collect()
+}
+
 fn print_app_list(page: &AppItemPage) {
     for app in &page.items {
         println!("{}", app.name);
     }
 }
+
+#[cfg(test)]
+mod apps_tests {
+    use super::*;
+    use cloud::models::ChannelItem;
+    use cloud::testing::RecordedClient;
+    use cloud::MockCloudClientInterface;
+    use cloud_openapi::models::{RevisionItem, RevisionItemPage};
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(
+            parse_label("team=payments").unwrap(),
+            ("team".to_owned(), "payments".to_owned())
+        );
+        assert_eq!(
+            parse_label("url=a=b").unwrap(),
+            ("url".to_owned(), "a=b".to_owned())
+        );
+        assert!(parse_label("team").is_err());
+        assert!(parse_label("=payments").is_err());
+        assert!(parse_label("my team=payments").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_apps_with_labels_requires_every_label() -> Result<()> {
+        let app = |name: &str| AppItem {
+            id: Uuid::new_v4(),
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        let (checkout, billing) = (app("checkout"), app("billing"));
+        let checkout_id = checkout.id;
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_list_apps().return_once(move |_, _| {
+            Ok(AppItemPage {
+                items: vec![checkout, billing],
+                total_items: 2,
+                is_last_page: true,
+                ..Default::default()
+            })
+        });
+        mock.expect_get_app_labels().returning(move |id| {
+            let mut labels = BTreeMap::from([("team".to_owned(), "payments".to_owned())]);
+            if id == checkout_id {
+                labels.insert("tier".to_owned(), "web".to_owned());
+            }
+            Ok(labels)
+        });
+        let selector = [
+            ("team".to_owned(), "payments".to_owned()),
+            ("tier".to_owned(), "web".to_owned()),
+        ];
+        let names = apps_with_labels(&mock, &selector)
+            .await?
+            .into_iter()
+            .map(|app| app.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["checkout"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_labels_are_not_written() -> Result<()> {
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_get_app_labels()
+            .returning(|_| Ok(BTreeMap::from([("team".to_owned(), "payments".to_owned())])));
+        mock.expect_set_app_labels().never();
+        let labels = update_labels(&mock, Uuid::new_v4(), |labels| {
+            labels.retain(|key, _| key != "tier")
+        })
+        .await?;
+        assert_eq!(labels.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revision_history_is_newest_first_and_marks_the_active_revision() -> Result<()> {
+        let app_id = Uuid::new_v4();
+        let revision = |app_id: Uuid, number: &str| RevisionItem {
+            id: Uuid::new_v4(),
+            app_id,
+            revision_number: number.to_owned(),
+            ..Default::default()
+        };
+        let (first, second) = (revision(app_id, "1.0.0"), revision(app_id, "1.1.0"));
+        let other_app = revision(Uuid::new_v4(), "9.0.0");
+        let channel = ChannelItem {
+            app_id,
+            name: SPIN_DEPLOY_CHANNEL_NAME.to_owned(),
+            active_revision_id: Some(first.id),
+            ..Default::default()
+        };
+        let client = RecordedClient::new()
+            .respond("list_channels", [channel])
+            .respond(
+                "list_revisions",
+                RevisionItemPage {
+                    items: vec![first, other_app, second],
+                    is_last_page: true,
+                    ..Default::default()
+                },
+            );
+
+        let history = revision_history(&client, app_id).await?;
+        assert_eq!(
+            history
+                .iter()
+                .map(|entry| (entry.revision.as_str(), entry.active))
+                .collect::<Vec<_>>(),
+            vec![("1.1.0", false), ("1.0.0", true)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_lines_summarize_checks() {
+        let ok = Probe {
+            status: Some(200),
+            error: None,
+            latency: Duration::from_millis(120),
+        };
+        let failed = Probe {
+            status: Some(503),
+            error: Some("The app responded with 503 Service Unavailable".to_owned()),
+            latency: Duration::from_millis(40),
+        };
+        let mut stats = ProbeStats::default();
+        stats.record(&ok);
+        assert_eq!(
+            status_lines("myapp", "https://myapp.fermyon.app/", &ok, &stats),
+            vec![
+                "App: myapp",
+                "URL: https://myapp.fermyon.app/",
+                "Last check: 200 in 120 ms",
+            ]
+        );
+
+        stats.record(&failed);
+        assert_eq!(
+            status_lines("myapp", "https://myapp.fermyon.app/", &failed, &stats)[2..],
+            [
+                "Last check: failing: The app responded with 503 Service Unavailable",
+                "Checks: 2, failed: 1 (50.0%), median latency: 120 ms",
+            ]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! A small on-disk cache of lookups that rarely change, such as the id of an
+//! app with a given name, so that repeated commands do not resolve them
+//! again on every invocation. Each environment has its own cache file under
+//! the config directory, and entries are trusted for the `--cache-ttl`.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser};
+use cloud::{CloudClientExt, CloudClientInterface, LogStream, SPIN_DEPLOY_CHANNEL_NAME};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::commands::{channels::find_channel, env::resolve_environment, login::config_root_dir};
+use crate::errors::CliError;
+use crate::output;
+
+const CACHE_DIR: &str = "cache";
+const APP_LIST_KEY: &str = "app-list";
+
+static CACHE_TTL: OnceLock<Duration> = OnceLock::new();
+
+/// Manage the cache of app and channel lookups
+#[derive(Parser, Debug)]
+pub enum CacheCommand {
+    /// Remove all cached lookups, for every environment
+    Clear(ClearCommand),
+}
+
+#[derive(Parser, Debug)]
+pub struct ClearCommand {}
+
+#[derive(Debug, Args)]
+pub(crate) struct CacheArgs {
+    /// How long cached app and channel lookups are trusted (e.g. "5m"). Use "0s" to disable the cache.
+    #[clap(
+        long = "cache-ttl",
+        global = true,
+        env = "SPIN_CLOUD_CACHE_TTL",
+        default_value = "5m",
+        value_parser = clap::builder::ValueParser::new(humantime::parse_duration)
+    )]
+    pub cache_ttl: Duration,
+}
+
+/// Sets the cache TTL for the rest of the process. Only the first call has any effect.
+pub(crate) fn set_cache_ttl(args: &CacheArgs) {
+    _ = CACHE_TTL.set(args.cache_ttl);
+}
+
+impl CacheCommand {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Clear(_) => {
+                let dir = config_root_dir()?.join(CACHE_DIR);
+                match std::fs::remove_dir_all(&dir) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => {
+                        return Err(e)
+                            .with_context(|| format!("Failed to remove {}", dir.display()))
+                    }
+                }
+                output::success("Cache cleared", serde_json::json!({}))
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedValue {
+    cached_at: DateTime<Utc>,
+    value: serde_json::Value,
+}
+
+/// The cached lookups for one environment. Failing to read or write the
+/// cache never fails a command; it only means the lookup is made again.
+pub(crate) struct ResponseCache {
+    // None for a cache that is never saved
+    path: Option<PathBuf>,
+    ttl: Duration,
+    entries: HashMap<String, CachedValue>,
+}
+
+impl ResponseCache {
+    pub fn open(deployment_env_id: Option<&str>) -> Result<Self> {
+        let environment = resolve_environment(deployment_env_id)?;
+        let file = format!("{}.json", environment.as_deref().unwrap_or("config"));
+        let path = config_root_dir()?.join(CACHE_DIR).join(file);
+        let ttl = CACHE_TTL.get().copied().unwrap_or(Duration::from_secs(300));
+        Ok(Self::at(path, ttl))
+    }
+
+    pub fn at(path: PathBuf, ttl: Duration) -> Self {
+        let entries = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            ttl,
+            entries,
+        }
+    }
+
+    /// A cache that remembers nothing, for connections without a saved login
+    pub fn disabled() -> Self {
+        Self {
+            path: None,
+            ttl: Duration::ZERO,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns the value cached under `key` if it is younger than the TTL
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let (value, cached_at) = self.get_stale(key)?;
+        let age = (Utc::now() - cached_at).to_std().unwrap_or_default();
+        (age < self.ttl).then_some(value)
+    }
+
+    /// Returns the value cached under `key` however old it is, and when it was cached
+    pub fn get_stale<T: DeserializeOwned>(&self, key: &str) -> Option<(T, DateTime<Utc>)> {
+        let cached = self.entries.get(key)?;
+        let value = serde_json::from_value(cached.value.clone()).ok()?;
+        Some((value, cached.cached_at))
+    }
+
+    pub fn put<T: Serialize>(&mut self, key: &str, value: &T) {
+        let Ok(value) = serde_json::to_value(value) else {
+            return;
+        };
+        let cached = CachedValue {
+            cached_at: Utc::now(),
+            value,
+        };
+        self.entries.insert(key.to_owned(), cached);
+        self.save();
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        if self.entries.remove(key).is_some() {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(e) = write_atomically(path, &self.entries) {
+            tracing::debug!("Failed to write cache {}: {e:#}", path.display());
+        }
+    }
+}
+
+// Concurrent commands may share a cache file, so readers must never see a partial write
+fn write_atomically(path: &Path, entries: &HashMap<String, CachedValue>) -> Result<()> {
+    let dir = path
+        .parent()
+        .context("Cache file has no parent directory")?;
+    std::fs::create_dir_all(dir)?;
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(&mut file, entries)?;
+    file.persist(path)?;
+    Ok(())
+}
+
+fn app_id_key(app: &str) -> String {
+    format!("app-id/{app}")
+}
+
+fn channel_id_key(app_id: Uuid, channel: &str) -> String {
+    format!("channel-id/{app_id}/{channel}")
+}
+
+/// Looks up the id of the app with the given name, from the cache if possible
+pub(crate) async fn app_id(
+    client: &impl CloudClientInterface,
+    cache: &mut ResponseCache,
+    app: &str,
+) -> Result<Option<Uuid>> {
+    let key = app_id_key(app);
+    if let Some(id) = cache.get(&key) {
+        return Ok(Some(id));
+    }
+    let id = client.get_app_id(app).await?;
+    if let Some(id) = &id {
+        cache.put(&key, id);
+    }
+    Ok(id)
+}
+
+/// Looks up the id of an app's channel, from the cache if possible
+pub(crate) async fn channel_id(
+    client: &impl CloudClientInterface,
+    cache: &mut ResponseCache,
+    app_id: Uuid,
+    channel: &str,
+) -> Result<Uuid> {
+    let key = channel_id_key(app_id, channel);
+    if let Some(id) = cache.get(&key) {
+        return Ok(id);
+    }
+    let id = client.get_channel(app_id, channel).await?.id;
+    cache.put(&key, &id);
+    Ok(id)
+}
+
+/// Looks up the named app, and where to read the logs of its channel with
+/// the given name, from the cache if possible. With both ids cached, no
+/// request is made at all.
+pub(crate) async fn resolve_app_channel(
+    client: &impl CloudClientInterface,
+    cache: &mut ResponseCache,
+    app: &str,
+    channel: Option<&str>,
+) -> Result<(Uuid, LogStream)> {
+    let app_id = app_id(client, cache, app)
+        .await
+        .with_context(|| format!("Error finding app_id for app '{app}'"))?
+        .with_context(|| CliError::not_found(format!("Could not find app '{app}'")))?;
+    let stream = channel_stream(client, cache, app, app_id, channel).await?;
+    Ok((app_id, stream))
+}
+
+/// Where to read the logs of an app's channel with the given name. The deploy
+/// channel's logs are the app's own, so it needs no lookup.
+pub(crate) async fn channel_stream(
+    client: &impl CloudClientInterface,
+    cache: &mut ResponseCache,
+    app: &str,
+    app_id: Uuid,
+    channel: Option<&str>,
+) -> Result<LogStream> {
+    let channel = match channel {
+        None | Some(SPIN_DEPLOY_CHANNEL_NAME) => return Ok(LogStream::App(app_id)),
+        Some(channel) => channel,
+    };
+    let key = channel_id_key(app_id, channel);
+    if let Some(id) = cache.get(&key) {
+        return Ok(LogStream::Channel(id));
+    }
+    let id = find_channel(client, app_id, app, channel).await?.id;
+    cache.put(&key, &id);
+    Ok(LogStream::Channel(id))
+}
+
+/// Records a newly created app, which the cached app list does not include
+pub(crate) fn remember_new_app(cache: &mut ResponseCache, app: &str, app_id: Uuid) {
+    cache.put(&app_id_key(app), &app_id);
+    cache.remove(APP_LIST_KEY);
+}
+
+/// Forgets everything cached about an app, e.g. once it has been deleted
+pub(crate) fn forget_app(cache: &mut ResponseCache, app: &str) {
+    cache.remove(&app_id_key(app));
+    cache.remove(APP_LIST_KEY);
+}
+
+pub(crate) fn cached_app_list(cache: &ResponseCache) -> Option<(Vec<String>, DateTime<Utc>)> {
+    cache.get_stale(APP_LIST_KEY)
+}
+
+pub(crate) fn cache_app_list(cache: &mut ResponseCache, names: &[String]) {
+    cache.put(APP_LIST_KEY, &names);
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::*;
+
+    #[test]
+    fn test_values_survive_reopening_until_they_expire() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("cache").join("config.json");
+        let id = Uuid::new_v4();
+
+        let mut cache = ResponseCache::at(path.clone(), Duration::from_secs(60));
+        cache.put(&app_id_key("myapp"), &id);
+        let cache = ResponseCache::at(path.clone(), Duration::from_secs(60));
+        assert_eq!(cache.get::<Uuid>(&app_id_key("myapp")), Some(id));
+        assert_eq!(cache.get::<Uuid>(&app_id_key("other")), None);
+
+        // A zero TTL disables lookups, but the value is still there when asked for explicitly
+        let cache = ResponseCache::at(path, Duration::ZERO);
+        assert_eq!(cache.get::<Uuid>(&app_id_key("myapp")), None);
+        assert_eq!(
+            cache
+                .get_stale::<Uuid>(&app_id_key("myapp"))
+                .map(|(v, _)| v),
+            Some(id)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_forget_app_and_corrupt_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config.json");
+        let mut cache = ResponseCache::at(path.clone(), Duration::from_secs(60));
+        cache.put(&app_id_key("myapp"), &Uuid::new_v4());
+        cache_app_list(&mut cache, &["myapp".to_owned()]);
+        forget_app(&mut cache, "myapp");
+        assert_eq!(cache.get::<Uuid>(&app_id_key("myapp")), None);
+        assert!(cached_app_list(&cache).is_none());
+
+        std::fs::write(&path, "not json")?;
+        assert!(ResponseCache::at(path, Duration::from_secs(60))
+            .entries
+            .is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_channel_id_is_fetched_once() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut cache = ResponseCache::at(dir.path().join("config.json"), Duration::from_secs(60));
+        let app_id = Uuid::new_v4();
+        let channel = cloud::models::ChannelItem {
+            id: Uuid::new_v4(),
+            app_id,
+            name: "spin-deploy".to_owned(),
+            ..Default::default()
+        };
+        let expected = channel.id;
+
+        let mut mock = cloud::MockCloudClientInterface::new();
+        mock.expect_list_channels()
+            .times(1)
+            .return_once(move |_| Ok(vec![channel]));
+        for _ in 0..2 {
+            let id = channel_id(&mock, &mut cache, app_id, "spin-deploy").await?;
+            assert_eq!(id, expected);
+        }
+        Ok(())
+    }
+}
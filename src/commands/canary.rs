@@ -0,0 +1,514 @@
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use cloud::{CloudClientExt, CloudClientInterface, SPIN_DEPLOY_CHANNEL_NAME};
+use cloud_openapi::models::ChannelRevisionSelectionStrategy;
+use uuid::Uuid;
+
+use crate::commands::{
+    app_picker::app_or_pick,
+    cache::{self, ResponseCache},
+    client_and_app_id, CommonArgs,
+};
+use crate::ops::DeployProgress;
+use crate::output;
+
+// The secondary channel that receives a share of traffic during a canary deploy
+pub(crate) const CANARY_CHANNEL_NAME: &str = "spin-canary";
+
+/// Manage canary rollouts started with `spin cloud deploy --canary`
+#[derive(Parser, Debug)]
+#[clap(about = "Manage canary rollouts started with `spin cloud deploy --canary`")]
+pub enum CanaryCommand {
+    /// Route all traffic to the canary revision and finish the rollout
+    Promote(PromoteCommand),
+    /// Remove the canary and keep serving the previous revision
+    Abort(AbortCommand),
+}
+
+#[derive(Parser, Debug)]
+pub struct PromoteCommand {
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    pub app: Option<String>,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct AbortCommand {
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    pub app: Option<String>,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+impl CanaryCommand {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Promote(cmd) => {
+                let deployment_env_id = cmd.common.deployment_env_id.as_deref();
+                let app = app_or_pick(deployment_env_id, cmd.app.clone()).await?;
+                let (client, app_id) = client_and_app_id(deployment_env_id, &app).await?;
+                let mut cache = ResponseCache::open(deployment_env_id)?;
+                cmd.run(client, app_id, &app, &mut cache).await
+            }
+            Self::Abort(cmd) => {
+                let deployment_env_id = cmd.common.deployment_env_id.as_deref();
+                let app = app_or_pick(deployment_env_id, cmd.app.clone()).await?;
+                let (client, app_id) = client_and_app_id(deployment_env_id, &app).await?;
+                cmd.run(client, app_id, &app).await
+            }
+        }
+    }
+}
+
+impl PromoteCommand {
+    async fn run(
+        self,
+        client: impl CloudClientInterface,
+        app_id: Uuid,
+        app: &str,
+        cache: &mut ResponseCache,
+    ) -> Result<()> {
+        let (canary, channel_id) = tokio::try_join!(find_canary(&client, app_id, app), async {
+            cache::channel_id(&client, cache, app_id, SPIN_DEPLOY_CHANNEL_NAME)
+                .await
+                .with_context(|| format!("Problem finding the deploy channel for app '{app}'"))
+        },)?;
+        let revision_id = canary
+            .active_revision_id
+            .with_context(|| format!("The canary for app '{app}' has no revision"))?;
+        client
+            .set_channel_revision(channel_id, revision_id)
+            .await
+            .with_context(|| format!("Problem promoting the canary for app '{app}'"))?;
+        client
+            .remove_channel(canary.id)
+            .await
+            .context("Problem removing the canary channel")?;
+        let revision = canary.active_revision_number.unwrap_or_default();
+        output::success(
+            &format!("Canary revision \"{revision}\" promoted for app \"{app}\""),
+            serde_json::json!({ "app": app, "revision": revision }),
+        )
+    }
+}
+
+impl AbortCommand {
+    async fn run(self, client: impl CloudClientInterface, app_id: Uuid, app: &str) -> Result<()> {
+        let canary = find_canary(&client, app_id, app).await?;
+        client
+            .remove_channel(canary.id)
+            .await
+            .context("Problem removing the canary channel")?;
+        output::success(
+            &format!("Canary for app \"{app}\" aborted"),
+            serde_json::json!({ "app": app }),
+        )
+    }
+}
+
+async fn find_canary(
+    client: &impl CloudClientInterface,
+    app_id: Uuid,
+    app: &str,
+) -> Result<cloud::models::ChannelItem> {
+    let channels = client
+        .list_channels(app_id)
+        .await
+        .with_context(|| format!("Problem listing channels for app '{app}'"))?;
+    match channels.into_iter().find(|c| c.name == CANARY_CHANNEL_NAME) {
+        Some(canary) => Ok(canary),
+        None => bail!("App '{app}' has no canary in progress"),
+    }
+}
+
+/// Registers a revision for an existing app and makes its deploy channel
+/// serve it. A canary or a rollback leaves the channel pinned to a revision,
+/// which it would otherwise keep serving whatever is deployed after.
+pub(crate) async fn add_served_revision(
+    client: &impl CloudClientInterface,
+    app_id: Uuid,
+    storage_id: String,
+    version: String,
+) -> Result<()> {
+    client.add_revision(storage_id, version).await?;
+    let channel = client
+        .get_channel(app_id, SPIN_DEPLOY_CHANNEL_NAME)
+        .await
+        .context("Problem finding the deploy channel")?;
+    if channel.revision_selection_strategy != Some(ChannelRevisionSelectionStrategy::UseRangeRule) {
+        client
+            .follow_latest_revision(channel.id)
+            .await
+            .context("Problem moving the deploy channel to the new revision")?;
+    }
+    Ok(())
+}
+
+/// Registers a revision for an existing app on the canary channel only,
+/// leaving the deploy channel pinned to the revision it was serving. The next
+/// deploy unpins it, through `add_served_revision`.
+///
+/// Only the code is split between the revisions. Variables and key/value
+/// pairs belong to the app, so any written during the canary are seen by the
+/// stable revision too.
+pub(crate) async fn deploy_canary(
+    client: &impl CloudClientInterface,
+    app_id: Uuid,
+    storage_id: String,
+    version: String,
+    traffic_percentage: u8,
+    progress: &dyn DeployProgress,
+) -> Result<()> {
+    let channel = client
+        .get_channel(app_id, SPIN_DEPLOY_CHANNEL_NAME)
+        .await
+        .context("Problem finding the deploy channel")?;
+    let Some(stable_revision_id) = channel.active_revision_id else {
+        bail!("Canary deploys require the app to have an active revision");
+    };
+
+    // Registering a revision moves a deploy channel that follows the latest
+    // revision, so pin it first for none of its traffic to reach the canary
+    client
+        .set_channel_revision(channel.id, stable_revision_id)
+        .await
+        .context("Problem pinning the deploy channel to its current revision")?;
+    client
+        .add_revision(storage_id, version.clone())
+        .await
+        .with_context(|| format!("Unable to upload {version}"))?;
+    let canary_revision_id = client.get_revision_id(app_id, &version).await?;
+
+    let channels = client.list_channels(app_id).await?;
+    if let Some(existing) = channels.iter().find(|c| c.name == CANARY_CHANNEL_NAME) {
+        client
+            .remove_channel(existing.id)
+            .await
+            .context("Problem replacing the existing canary channel")?;
+    }
+    client
+        .add_channel(
+            app_id,
+            CANARY_CHANNEL_NAME.to_owned(),
+            canary_revision_id,
+            Some(traffic_percentage),
+        )
+        .await
+        .context("Problem creating the canary channel")?;
+
+    progress.notice(&format!(
+        "Revision {version} is receiving {traffic_percentage}% of traffic. Run `spin cloud canary promote` or `spin cloud canary abort` to finish the rollout."
+    ));
+    Ok(())
+}
+
+pub(crate) fn parse_canary_percentage(arg: &str) -> Result<u8> {
+    let value: u8 = arg.parse()?;
+    if value == 0 || value >= 100 {
+        bail!("canary percentage must be between 1 and 99");
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+pub(crate) mod canary_tests {
+    use super::*;
+    use cloud::{models::ChannelItem, testing::RecordedClient, MockCloudClientInterface};
+    use cloud_openapi::models::{RevisionItem, RevisionItemPage};
+    use std::sync::{Arc, Mutex};
+
+    /// The revisions and channels of an app, kept by `FakeApp::client` as
+    /// Fermyon Cloud would, to check which revision ends up served
+    #[derive(Clone)]
+    pub(crate) struct FakeApp {
+        pub(crate) app_id: Uuid,
+        deploy_channel_id: Uuid,
+        state: Arc<Mutex<FakeAppState>>,
+    }
+
+    #[derive(Default)]
+    struct FakeAppState {
+        revisions: Vec<RevisionItem>,
+        pinned: Option<Uuid>,
+        canary: Option<ChannelItem>,
+    }
+
+    impl FakeApp {
+        pub(crate) fn new() -> Self {
+            Self {
+                app_id: Uuid::new_v4(),
+                deploy_channel_id: Uuid::new_v4(),
+                state: Default::default(),
+            }
+        }
+
+        /// The revision number the deploy channel serves
+        pub(crate) fn served(&self) -> String {
+            let state = self.state.lock().unwrap();
+            let served = state.pinned.or(state.revisions.last().map(|r| r.id));
+            let revision = state.revisions.iter().find(|r| Some(r.id) == served);
+            revision
+                .map(|r| r.revision_number.clone())
+                .unwrap_or_default()
+        }
+
+        pub(crate) fn client(&self) -> MockCloudClientInterface {
+            let mut mock = MockCloudClientInterface::new();
+            let app = self.clone();
+            mock.expect_list_channels().returning(move |_| {
+                let state = app.state.lock().unwrap();
+                let strategy = match state.pinned {
+                    Some(_) => ChannelRevisionSelectionStrategy::UseSpecifiedRevision,
+                    None => ChannelRevisionSelectionStrategy::UseRangeRule,
+                };
+                let deploy = ChannelItem {
+                    id: app.deploy_channel_id,
+                    active_revision_id: state.pinned.or(state.revisions.last().map(|r| r.id)),
+                    revision_selection_strategy: Some(strategy),
+                    ..channel(app.app_id, SPIN_DEPLOY_CHANNEL_NAME, Uuid::nil())
+                };
+                Ok([Some(deploy), state.canary.clone()]
+                    .into_iter()
+                    .flatten()
+                    .collect())
+            });
+            let app = self.clone();
+            mock.expect_list_revisions().returning(move || {
+                Ok(RevisionItemPage {
+                    items: app.state.lock().unwrap().revisions.clone(),
+                    is_last_page: true,
+                    ..Default::default()
+                })
+            });
+            let app = self.clone();
+            mock.expect_add_revision().returning(move |_, version| {
+                app.state.lock().unwrap().revisions.push(RevisionItem {
+                    id: Uuid::new_v4(),
+                    app_id: app.app_id,
+                    revision_number: version,
+                    ..Default::default()
+                });
+                Ok(())
+            });
+            let app = self.clone();
+            mock.expect_set_channel_revision()
+                .returning(move |channel_id, revision_id| {
+                    assert_eq!(channel_id, app.deploy_channel_id);
+                    app.state.lock().unwrap().pinned = Some(revision_id);
+                    Ok(())
+                });
+            let app = self.clone();
+            mock.expect_follow_latest_revision()
+                .returning(move |channel_id| {
+                    assert_eq!(channel_id, app.deploy_channel_id);
+                    app.state.lock().unwrap().pinned = None;
+                    Ok(())
+                });
+            let app = self.clone();
+            mock.expect_add_channel()
+                .returning(move |app_id, name, revision_id, _| {
+                    let canary = channel(app_id, &name, revision_id);
+                    let id = canary.id;
+                    app.state.lock().unwrap().canary = Some(canary);
+                    Ok(id)
+                });
+            let app = self.clone();
+            mock.expect_remove_channel().returning(move |channel_id| {
+                let mut state = app.state.lock().unwrap();
+                assert_eq!(state.canary.as_ref().map(|c| c.id), Some(channel_id));
+                state.canary = None;
+                Ok(())
+            });
+            mock
+        }
+
+        /// Deploys `version` as `spin cloud deploy` does for an existing app
+        pub(crate) async fn deploy(&self, version: &str) -> Result<()> {
+            add_served_revision(
+                &self.client(),
+                self.app_id,
+                "oci://app".to_owned(),
+                version.to_owned(),
+            )
+            .await
+        }
+    }
+
+    fn channel(app_id: Uuid, name: &str, revision_id: Uuid) -> ChannelItem {
+        ChannelItem {
+            id: Uuid::new_v4(),
+            app_id,
+            name: name.to_owned(),
+            active_revision_id: Some(revision_id),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_canary_percentage() {
+        assert_eq!(parse_canary_percentage("10").unwrap(), 10);
+        assert!(parse_canary_percentage("0").is_err());
+        assert!(parse_canary_percentage("100").is_err());
+        assert!(parse_canary_percentage("ten").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_promote_moves_deploy_channel_to_canary_revision() -> Result<()> {
+        let app_id = Uuid::new_v4();
+        let canary_revision = Uuid::new_v4();
+        let deploy = channel(app_id, SPIN_DEPLOY_CHANNEL_NAME, Uuid::new_v4());
+        let canary = channel(app_id, CANARY_CHANNEL_NAME, canary_revision);
+        let (deploy_id, canary_id) = (deploy.id, canary.id);
+        let channels = vec![deploy, canary];
+
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_list_channels()
+            .returning(move |_| Ok(channels.clone()));
+        mock.expect_set_channel_revision()
+            .withf(move |c, r| *c == deploy_id && *r == canary_revision)
+            .returning(|_, _| Ok(()));
+        mock.expect_remove_channel()
+            .withf(move |c| *c == canary_id)
+            .returning(|_| Ok(()));
+
+        let dir = tempfile::tempdir()?;
+        let mut cache = ResponseCache::at(
+            dir.path().join("config.json"),
+            std::time::Duration::from_secs(60),
+        );
+        let command = PromoteCommand {
+            app: Some("app".to_owned()),
+            common: Default::default(),
+        };
+        command.run(mock, app_id, "app", &mut cache).await
+    }
+
+    #[tokio::test]
+    async fn test_deploy_canary_pins_deploy_channel_before_registering() -> Result<()> {
+        let app_id = Uuid::new_v4();
+        let (stable_revision, canary_revision) = (Uuid::new_v4(), Uuid::new_v4());
+        let deploy = channel(app_id, SPIN_DEPLOY_CHANNEL_NAME, stable_revision);
+        let revisions = RevisionItemPage {
+            items: vec![RevisionItem {
+                id: canary_revision,
+                app_id,
+                revision_number: "2.0.0".to_owned(),
+                ..Default::default()
+            }],
+            is_last_page: true,
+            ..Default::default()
+        };
+        let client = RecordedClient::new()
+            .respond("list_channels", vec![deploy])
+            .respond("list_revisions", revisions)
+            .respond("add_channel", Uuid::new_v4());
+
+        deploy_canary(
+            &client,
+            app_id,
+            "storage".to_owned(),
+            "2.0.0".to_owned(),
+            10,
+            &|_: &str| {},
+        )
+        .await?;
+
+        let calls = client
+            .calls()
+            .into_iter()
+            .map(|c| c.method)
+            .filter(|m| m == "set_channel_revision" || m == "add_revision")
+            .collect::<Vec<_>>();
+        assert_eq!(calls, vec!["set_channel_revision", "add_revision"]);
+        assert_eq!(
+            client.calls_to("set_channel_revision")[0]["revision_id"],
+            stable_revision.to_string()
+        );
+        assert_eq!(
+            client.calls_to("add_channel")[0]["revision_id"],
+            canary_revision.to_string()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deploy_after_promote_serves_the_new_revision() -> Result<()> {
+        let app = FakeApp::new();
+        app.deploy("1.0.0").await?;
+        deploy_canary(
+            &app.client(),
+            app.app_id,
+            "oci://app".to_owned(),
+            "2.0.0".to_owned(),
+            10,
+            &|_: &str| {},
+        )
+        .await?;
+        assert_eq!(app.served(), "1.0.0");
+
+        let command = PromoteCommand {
+            app: Some("app".to_owned()),
+            common: Default::default(),
+        };
+        command
+            .run(
+                app.client(),
+                app.app_id,
+                "app",
+                &mut ResponseCache::disabled(),
+            )
+            .await?;
+        assert_eq!(app.served(), "2.0.0");
+
+        app.deploy("3.0.0").await?;
+        assert_eq!(app.served(), "3.0.0");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deploy_after_abort_serves_the_new_revision() -> Result<()> {
+        let app = FakeApp::new();
+        app.deploy("1.0.0").await?;
+        deploy_canary(
+            &app.client(),
+            app.app_id,
+            "oci://app".to_owned(),
+            "2.0.0".to_owned(),
+            10,
+            &|_: &str| {},
+        )
+        .await?;
+        let command = AbortCommand {
+            app: Some("app".to_owned()),
+            common: Default::default(),
+        };
+        command.run(app.client(), app.app_id, "app").await?;
+        assert_eq!(app.served(), "1.0.0");
+
+        app.deploy("3.0.0").await?;
+        assert_eq!(app.served(), "3.0.0");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_abort_without_canary_then_error() -> Result<()> {
+        let app_id = Uuid::new_v4();
+        let deploy = channel(app_id, SPIN_DEPLOY_CHANNEL_NAME, Uuid::new_v4());
+
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_list_channels()
+            .return_once(move |_| Ok(vec![deploy]));
+
+        let command = AbortCommand {
+            app: Some("app".to_owned()),
+            common: Default::default(),
+        };
+        let result = command.run(mock, app_id, "app").await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "App 'app' has no canary in progress"
+        );
+        Ok(())
+    }
+}
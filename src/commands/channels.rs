@@ -0,0 +1,133 @@
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use cloud::{models::ChannelItem, CloudClientInterface};
+use comfy_table::presets::ASCII_BORDERS_ONLY_CONDENSED;
+use uuid::Uuid;
+
+use crate::commands::{app_picker::app_or_pick, client_and_app_id, CommonArgs};
+use crate::errors::CliError;
+use crate::output;
+
+/// Inspect the channels an app is served on, such as its deploy and canary channels
+#[derive(Parser, Debug)]
+#[clap(about = "Inspect the channels an app is served on")]
+pub enum ChannelsCommand {
+    /// List the channels of an app
+    List(ListCommand),
+}
+
+#[derive(Parser, Debug)]
+pub struct ListCommand {
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    pub app: Option<String>,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+impl ChannelsCommand {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::List(cmd) => cmd.run().await,
+        }
+    }
+}
+
+impl ListCommand {
+    async fn run(self) -> Result<()> {
+        let deployment_env_id = self.common.deployment_env_id.as_deref();
+        let app = app_or_pick(deployment_env_id, self.app.clone()).await?;
+        let (client, app_id) = client_and_app_id(deployment_env_id, &app).await?;
+        let mut channels = client
+            .list_channels(app_id)
+            .await
+            .with_context(|| format!("Problem listing channels for app '{app}'"))?;
+        channels.sort_by(|a, b| a.name.cmp(&b.name));
+        if output::is_json() {
+            return output::print_json(&channels);
+        }
+        if channels.is_empty() {
+            println!("App '{app}' has no channels");
+            return Ok(());
+        }
+        let mut table = comfy_table::Table::new();
+        table.load_preset(ASCII_BORDERS_ONLY_CONDENSED);
+        table.set_header(["Channel", "Revision", "Domain"]);
+        table.add_rows(channels.iter().map(|channel| {
+            [
+                channel.name.clone(),
+                channel.active_revision_number.clone().unwrap_or_default(),
+                channel.domain.clone().unwrap_or_default(),
+            ]
+        }));
+        println!("{table}");
+        Ok(())
+    }
+}
+
+/// The channel of an app with the given name. If there is none, the error
+/// lists the channels the app does have.
+pub(crate) async fn find_channel(
+    client: &impl CloudClientInterface,
+    app_id: Uuid,
+    app: &str,
+    name: &str,
+) -> Result<ChannelItem> {
+    let channels = client
+        .list_channels(app_id)
+        .await
+        .with_context(|| format!("Problem listing channels for app '{app}'"))?;
+    let names = channel_names(&channels);
+    match channels.into_iter().find(|c| c.name == name) {
+        Some(channel) => Ok(channel),
+        None => bail!(
+            CliError::not_found(format!("App '{app}' has no channel named '{name}'"))
+                .with_hint(format!("Available channels: {names}"))
+        ),
+    }
+}
+
+fn channel_names(channels: &[ChannelItem]) -> String {
+    let mut names = channels.iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+    names.sort();
+    match names.is_empty() {
+        true => "none".to_owned(),
+        false => names.join(", "),
+    }
+}
+
+#[cfg(test)]
+mod channels_tests {
+    use super::*;
+    use cloud::testing::RecordedClient;
+
+    fn channel(name: &str) -> ChannelItem {
+        ChannelItem {
+            id: Uuid::new_v4(),
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_channel_lists_the_available_ones() {
+        let client = RecordedClient::new().respond(
+            "list_channels",
+            [channel("spin-deploy"), channel("preview")],
+        );
+        let preview = find_channel(&client, Uuid::new_v4(), "app", "preview")
+            .await
+            .unwrap();
+        assert_eq!(preview.name, "preview");
+
+        let err = find_channel(&client, Uuid::new_v4(), "app", "canary")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "App 'app' has no channel named 'canary'");
+        let (kind, hint) = crate::errors::classify(&err).unwrap();
+        assert_eq!(kind, crate::errors::ErrorKind::NotFound);
+        assert_eq!(
+            hint.as_deref(),
+            Some("Available channels: preview, spin-deploy")
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+
+use crate::errors::CliError;
+use crate::opts::{DEFAULT_MANIFEST_FILE, SPIN_AUTH_TOKEN};
+use crate::output;
+
+// The repository secret holding a Fermyon Cloud personal access token
+const TOKEN_SECRET: &str = "FERMYON_CLOUD_TOKEN";
+
+/// Generate continuous integration configuration for deploying to Fermyon Cloud
+#[derive(Parser, Debug)]
+pub enum CiCommand {
+    /// Write a CI definition that builds and deploys the app in the current directory
+    #[clap(subcommand)]
+    Generate(GenerateCommand),
+}
+
+#[derive(Parser, Debug)]
+pub enum GenerateCommand {
+    /// Write a GitHub Actions workflow that deploys on each push
+    GithubActions(GithubActionsCommand),
+}
+
+/// The workflow logs in with the personal access token in the
+/// FERMYON_CLOUD_TOKEN secret, then builds and deploys the app. Variables the
+/// app requires are passed to `spin cloud deploy` from secrets of the same
+/// name in upper case.
+#[derive(Parser, Debug)]
+pub struct GithubActionsCommand {
+    /// The GitHub environment the workflow deploys to, which holds its
+    /// secrets and any protection rules
+    #[clap(long = "environment", default_value = "production")]
+    pub environment: String,
+    /// The branch whose pushes are deployed
+    #[clap(long = "branch", default_value = "main")]
+    pub branch: String,
+    /// The application manifest, relative to the repository root
+    #[clap(short = 'f', long = "from", default_value = DEFAULT_MANIFEST_FILE)]
+    pub manifest: PathBuf,
+    /// Where to write the workflow, or "-" to print it. Defaults to
+    /// .github/workflows/fermyon-cloud-<environment>.yml
+    #[clap(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+    /// Overwrite the workflow file if it already exists
+    #[clap(long = "force")]
+    pub force: bool,
+}
+
+impl CiCommand {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Generate(GenerateCommand::GithubActions(cmd)) => cmd.run(),
+        }
+    }
+}
+
+impl GithubActionsCommand {
+    fn run(self) -> Result<()> {
+        let workflow = Workflow {
+            environment: &self.environment,
+            branch: &self.branch,
+            manifest: &self.manifest.to_string_lossy(),
+            variables: required_variables(&self.manifest)?,
+        };
+        let contents = workflow.render();
+        let path = self.output.clone().unwrap_or_else(|| {
+            Path::new(".github/workflows").join(format!("fermyon-cloud-{}.yml", self.environment))
+        });
+        if path == Path::new("-") {
+            print!("{contents}");
+            return Ok(());
+        }
+        if path.exists() && !self.force {
+            bail!(CliError::validation(format!(
+                "{} already exists. Use `--force` to overwrite it.",
+                path.display()
+            )));
+        }
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        let mut secrets = vec![TOKEN_SECRET.to_owned()];
+        secrets.extend(workflow.variables.iter().map(|v| secret_name(v)));
+        if output::is_json() {
+            return output::print_json(&serde_json::json!({
+                "path": path,
+                "environment": self.environment,
+                "secrets": secrets,
+            }));
+        }
+        println!("Wrote {}", path.display());
+        println!(
+            "Add these secrets to the \"{}\" environment of your GitHub repository:",
+            self.environment
+        );
+        for secret in secrets {
+            println!("  - {secret}");
+        }
+        Ok(())
+    }
+}
+
+// The variables `spin cloud deploy` checks for, i.e. those without defaults
+fn required_variables(manifest: &Path) -> Result<Vec<String>> {
+    if !manifest.exists() {
+        bail!(CliError::not_found(format!(
+            "No manifest at {}. Run this from your application directory, or pass `--from`.",
+            manifest.display()
+        )));
+    }
+    let manifest = spin_manifest::manifest_from_file(manifest)
+        .with_context(|| format!("Failed to read {}", manifest.display()))?;
+    Ok(manifest
+        .variables
+        .iter()
+        .filter(|(_, variable)| variable.default.is_none())
+        .map(|(name, _)| name.to_string())
+        .collect())
+}
+
+fn secret_name(variable: &str) -> String {
+    variable.to_ascii_uppercase()
+}
+
+struct Workflow<'a> {
+    environment: &'a str,
+    branch: &'a str,
+    manifest: &'a str,
+    variables: Vec<String>,
+}
+
+impl Workflow<'_> {
+    fn render(&self) -> String {
+        // Secrets are passed through the environment rather than expanded
+        // into the script, so that their values are never parsed as shell
+        let manifest = shell_quote(self.manifest);
+        let mut deploy = format!("spin cloud deploy --from {manifest} --wait");
+        let mut deploy_env = String::new();
+        for variable in &self.variables {
+            let secret = secret_name(variable);
+            deploy.push_str(&format!(
+                " \\\n            --variable {variable}=\"${secret}\""
+            ));
+            deploy_env.push_str(&format!(
+                "\n          {secret}: ${{{{ secrets.{secret} }}}}"
+            ));
+        }
+        let deploy_env = match deploy_env.is_empty() {
+            true => String::new(),
+            false => format!("\n        env:{deploy_env}"),
+        };
+        format!(
+            r#"# Generated by `spin cloud ci generate github-actions`
+name: {name}
+
+on:
+  push:
+    branches: [{branch}]
+  workflow_dispatch:
+
+concurrency:
+  group: {group}
+  cancel-in-progress: false
+
+jobs:
+  deploy:
+    runs-on: ubuntu-latest
+    environment: {environment}
+    steps:
+      - uses: actions/checkout@v4
+
+      - name: Install Spin and the cloud plugin
+        uses: fermyon/actions/spin/setup@v1
+        with:
+          plugins: cloud
+
+      # Install the language toolchains that `spin build` needs here
+
+      - name: Build
+        run: {build}
+
+      # Runners have no keychain to keep the token in
+      - name: Log in to Fermyon Cloud
+        run: spin cloud login --token-storage file
+        env:
+          {token_env}: ${{{{ secrets.{token_secret} }}}}
+
+      - name: Deploy
+        run: |
+          {deploy}{deploy_env}
+"#,
+            name = yaml_string(&format!("Deploy to Fermyon Cloud ({})", self.environment)),
+            branch = yaml_string(self.branch),
+            group = yaml_string(&format!("fermyon-cloud-{}", self.environment)),
+            environment = yaml_string(self.environment),
+            build = yaml_string(&format!("spin build --from {manifest}")),
+            token_env = SPIN_AUTH_TOKEN,
+            token_secret = TOKEN_SECRET,
+        )
+    }
+}
+
+// A double-quoted YAML scalar, which is also a JSON string, so that names
+// such as "a: b" cannot change the structure of the workflow
+fn yaml_string(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+// Quotes a word for the shell if it has characters the shell would interpret
+fn shell_quote(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:@+,".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        return word.to_owned();
+    }
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod ci_tests {
+    use super::*;
+
+    #[test]
+    fn test_workflow_passes_required_variables_from_secrets() {
+        let workflow = Workflow {
+            environment: "staging",
+            branch: "main",
+            manifest: "spin.toml",
+            variables: vec!["api_key".to_owned()],
+        }
+        .render();
+        assert!(workflow.contains("environment: \"staging\""));
+        assert!(workflow.contains("run: spin cloud login --token-storage file"));
+        assert!(workflow.contains("SPIN_AUTH_TOKEN: ${{ secrets.FERMYON_CLOUD_TOKEN }}"));
+        assert!(workflow.contains("--variable api_key=\"$API_KEY\""));
+        assert!(workflow.contains("API_KEY: ${{ secrets.API_KEY }}"));
+    }
+
+    #[test]
+    fn test_workflow_without_variables_has_no_deploy_env() {
+        let workflow = Workflow {
+            environment: "production",
+            branch: "release",
+            manifest: "app/spin.toml",
+            variables: vec![],
+        }
+        .render();
+        assert!(workflow.contains("branches: [\"release\"]"));
+        assert!(workflow.ends_with("spin cloud deploy --from app/spin.toml --wait\n"));
+    }
+
+    #[test]
+    fn test_workflow_quotes_names_and_paths() {
+        let workflow = Workflow {
+            environment: "prod: eu",
+            branch: "release/#1",
+            manifest: "my app/spin.toml",
+            variables: vec![],
+        }
+        .render();
+        assert!(workflow.contains("environment: \"prod: eu\""));
+        assert!(workflow.contains("branches: [\"release/#1\"]"));
+        assert!(workflow.contains("run: \"spin build --from 'my app/spin.toml'\""));
+        assert!(workflow.ends_with("spin cloud deploy --from 'my app/spin.toml' --wait\n"));
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}
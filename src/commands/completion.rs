@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Parser;
+use clap_complete::Shell;
+use cloud::{
+    client::{Client, ConnectionConfig},
+    retry::{RetryPolicy, RetryingClient},
+    CloudClientInterface, DEFAULT_APPLIST_PAGE_SIZE,
+};
+
+use crate::commands::{
+    credentials,
+    deploy::{config_file_path, expires_within},
+    env::resolve_environment,
+    http_config, CommonArgs,
+};
+
+// Completion runs on every key press, so app names are only offered when
+// they can be fetched quickly
+const APP_NAME_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Print a shell completion script for `spin cloud` commands.
+///
+/// The script adds to the completions Spin installs, rather than replacing
+/// them, so load it after those. It also completes app names, which are
+/// fetched from the instance you are logged in to. For example:
+///
+///     bash: add `source <(spin cloud completion bash)` to ~/.bashrc
+///     zsh:  add `source <(spin cloud completion zsh)` to ~/.zshrc, after `compinit`
+///     fish: spin cloud completion fish > ~/.config/fish/conf.d/spin-cloud.fish
+#[derive(Parser, Debug)]
+pub struct CompletionCommand {
+    /// The shell to generate completions for
+    #[clap(value_enum)]
+    shell: CompletionShell,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Print the names of your apps for shell completion. Prints nothing, rather
+/// than failing or prompting to log in, if the names cannot be fetched quickly.
+#[derive(Parser, Debug)]
+pub struct CompleteAppsCommand {
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+impl CompletionCommand {
+    pub fn run(self, cli: clap::Command<'_>) -> Result<()> {
+        print!("{}", script(self.shell, cli)?);
+        Ok(())
+    }
+}
+
+impl CompleteAppsCommand {
+    pub async fn run(self) -> Result<()> {
+        let names = tokio::time::timeout(
+            APP_NAME_TIMEOUT,
+            app_names(self.common.deployment_env_id.as_deref()),
+        )
+        .await;
+        if let Ok(Ok(names)) = names {
+            for name in names {
+                println!("{name}");
+            }
+        }
+        Ok(())
+    }
+}
+
+// Unlike other commands, this never logs in or refreshes the token: an
+// expired login simply completes nothing.
+async fn app_names(deployment_env_id: Option<&str>) -> Result<Vec<String>> {
+    let environment = resolve_environment(deployment_env_id)?;
+    let path = config_file_path(environment.as_deref())?;
+    let connection = credentials::parse(environment.as_deref(), &std::fs::read_to_string(&path)?)?;
+    if expires_within(&connection, chrono::Duration::zero())? {
+        return Ok(vec![]);
+    }
+    let client = RetryingClient::new(
+        Client::new(ConnectionConfig {
+            url: connection.url.to_string(),
+            insecure: connection.danger_accept_invalid_certs,
+            token: connection.token,
+            http: http_config(),
+        }),
+        RetryPolicy::none(),
+    );
+    let page = client.list_apps(DEFAULT_APPLIST_PAGE_SIZE, None).await?;
+    Ok(page.items.into_iter().map(|app| app.name).collect())
+}
+
+// The command the bash and zsh scripts complete in place of `spin cloud`
+const FRAGMENT_BIN: &str = "spin-cloud";
+
+// Bash and zsh take a single completion function per command, so a script
+// for `spin` would replace Spin's own. Instead the generated script completes
+// `spin-cloud`, and a hook hands it `spin cloud ...` and anything else to
+// the function that was there before. Fish merges the completions of every
+// script, so there `cloud` is simply described as a subcommand of `spin`.
+fn script(shell: CompletionShell, cli: clap::Command<'_>) -> Result<String> {
+    let mut script = vec![];
+    let hook = match shell {
+        CompletionShell::Bash => {
+            let mut cloud = cli.name(FRAGMENT_BIN);
+            clap_complete::generate(Shell::Bash, &mut cloud, FRAGMENT_BIN, &mut script);
+            BASH_HOOK
+        }
+        CompletionShell::Zsh => {
+            let mut cloud = cli.name(FRAGMENT_BIN);
+            clap_complete::generate(Shell::Zsh, &mut cloud, FRAGMENT_BIN, &mut script);
+            ZSH_HOOK
+        }
+        CompletionShell::Fish => {
+            let mut spin = clap::Command::new("spin")
+                .disable_help_flag(true)
+                .disable_help_subcommand(true)
+                .subcommand(cli.name("cloud"));
+            clap_complete::generate(Shell::Fish, &mut spin, "spin", &mut script);
+            FISH_HOOK
+        }
+    };
+    let mut script = String::from_utf8(script)?;
+    if shell == CompletionShell::Zsh {
+        // The generated script completes as soon as it is run, which only
+        // works when zsh autoloads it as a completion function
+        let call = format!("_{FRAGMENT_BIN} \"$@\"\n");
+        if script.ends_with(&call) {
+            script.truncate(script.len() - call.len());
+        }
+    }
+    script.push_str(hook);
+    Ok(script)
+}
+
+// Static scripts cannot know app names, so the hooks ask `__complete-apps`
+// for values of `--app` and of the app argument of `spin cloud logs`.
+const BASH_HOOK: &str = r#"
+if [[ "$(complete -p spin 2>/dev/null)" != *" _spin_cloud_dispatch "* ]]; then
+    _spin_cloud_previous="$(complete -p spin 2>/dev/null | sed -n 's/.*-F \([^ ]*\) .*/\1/p')"
+fi
+_spin_cloud_dispatch() {
+    if [[ "${COMP_WORDS[1]}" != "cloud" ]]; then
+        if [[ -n "$_spin_cloud_previous" ]]; then
+            "$_spin_cloud_previous" "$@"
+        fi
+        return
+    fi
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    if [[ "$prev" == "--app" || "$prev" == "-a" ||
+        ( "${COMP_WORDS[2]}" == "logs" && $COMP_CWORD -eq 3 && "$cur" != -* ) ]]; then
+        COMPREPLY=( $(compgen -W "$(spin cloud __complete-apps 2>/dev/null)" -- "$cur") )
+        return 0
+    fi
+    COMP_WORDS=( spin-cloud "${COMP_WORDS[@]:2}" )
+    COMP_CWORD=$((COMP_CWORD - 1))
+    _spin-cloud spin-cloud "$cur" "${COMP_WORDS[COMP_CWORD-1]}"
+}
+complete -F _spin_cloud_dispatch -o bashdefault -o default spin
+"#;
+
+const ZSH_HOOK: &str = r#"
+if [[ "$_comps[spin]" != _spin_cloud_dispatch ]]; then
+    _spin_cloud_previous="$_comps[spin]"
+fi
+_spin_cloud_dispatch() {
+    if [[ "$words[2]" != cloud ]]; then
+        if [[ -n "$_spin_cloud_previous" ]]; then
+            "$_spin_cloud_previous" "$@"
+        fi
+        return
+    fi
+    if [[ "$words[CURRENT-1]" == (--app|-a) ]] ||
+        [[ "$words[3]" == logs && $CURRENT -eq 4 && "$words[CURRENT]" != -* ]]; then
+        compadd -- ${(f)"$(spin cloud __complete-apps 2>/dev/null)"}
+        return
+    fi
+    shift words
+    (( CURRENT-- ))
+    words[1]=spin-cloud
+    _spin-cloud "$@"
+}
+compdef _spin_cloud_dispatch spin
+"#;
+
+const FISH_HOOK: &str = r#"
+complete -c spin -n "__fish_seen_subcommand_from cloud" -s a -l app -f -a "(spin cloud __complete-apps 2>/dev/null)"
+complete -c spin -n "__fish_seen_subcommand_from logs; and __fish_seen_subcommand_from cloud" -f -a "(spin cloud __complete-apps 2>/dev/null)"
+"#;
+
+#[cfg(test)]
+mod completion_tests {
+    use super::*;
+
+    fn cli() -> clap::Command<'static> {
+        clap::Command::new("cloud-plugin")
+            .subcommand(clap::Command::new("logs").arg(clap::Arg::new("app")))
+    }
+
+    #[test]
+    fn test_bash_script_completes_spin_cloud_without_replacing_spin() {
+        let script = script(CompletionShell::Bash, cli()).unwrap();
+        assert!(script.contains("_spin-cloud() {"));
+        assert!(
+            script.contains("complete -F _spin_cloud_dispatch -o bashdefault -o default spin\n")
+        );
+        assert!(!script.contains("_spin() {"));
+        assert!(script.contains("spin cloud __complete-apps"));
+    }
+
+    #[test]
+    fn test_zsh_script_can_be_sourced() {
+        let script = script(CompletionShell::Zsh, cli()).unwrap();
+        assert!(script.contains("_spin-cloud() {"));
+        assert!(script.contains("compdef _spin_cloud_dispatch spin\n"));
+        assert!(!script.contains("\n_spin-cloud \"$@\"\n"));
+        assert!(script.contains("spin cloud __complete-apps"));
+    }
+
+    #[test]
+    fn test_fish_script_describes_cloud_under_spin() {
+        let script = script(CompletionShell::Fish, cli()).unwrap();
+        assert!(script.contains(r#"-n "__fish_use_subcommand" -f -a "cloud""#));
+        assert!(script.contains("spin cloud __complete-apps"));
+    }
+}
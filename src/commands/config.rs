@@ -0,0 +1,185 @@
+//! The files in the config directory, such as saved logins and the active
+//! environment. CI jobs that share a home directory may run the plugin many
+//! times at once, so files are always replaced in a single rename, and any
+//! change that depends on what a file held before is made while holding the
+//! directory's lock.
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use fs4::FileExt;
+
+use crate::commands::{
+    deploy::config_file_path, env::resolve_environment, login::config_root_dir, CommonArgs,
+};
+use crate::errors::CliError;
+use crate::output;
+
+// Locked, never written to, so that its contents do not matter
+const LOCK_FILE: &str = ".lock";
+
+/// Inspect the plugin's configuration
+#[derive(Parser, Debug)]
+#[clap(about = "Inspect the plugin's configuration")]
+pub enum ConfigCommand {
+    /// Print the path of the login file for an environment
+    Path(PathCommand),
+    /// Show the saved login for an environment, without its tokens
+    View(ViewCommand),
+}
+
+#[derive(Parser, Debug)]
+pub struct PathCommand {
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct ViewCommand {
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+impl ConfigCommand {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Path(cmd) => cmd.run(),
+            Self::View(cmd) => cmd.run(),
+        }
+    }
+}
+
+impl PathCommand {
+    fn run(self) -> Result<()> {
+        let environment = resolve_environment(self.common.deployment_env_id.as_deref())?;
+        let path = config_file_path(environment.as_deref())?;
+        if output::is_json() {
+            return output::print_json(&serde_json::json!({
+                "environment": environment,
+                "path": path,
+                "directory": config_root_dir()?,
+            }));
+        }
+        println!("{}", path.display());
+        Ok(())
+    }
+}
+
+impl ViewCommand {
+    fn run(self) -> Result<()> {
+        let environment = resolve_environment(self.common.deployment_env_id.as_deref())?;
+        let path = config_file_path(environment.as_deref())?;
+        let data = match std::fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                anyhow::bail!(CliError::not_found(format!(
+                    "No login is saved at {}",
+                    path.display()
+                ))
+                .with_hint("Run `spin cloud login` to log in"));
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let mut login: serde_json::Value = serde_json::from_str(&data)
+            .with_context(|| format!("Cannot parse login information in {}", path.display()))?;
+        cloud::recording::redact(&mut login);
+        if output::is_json() {
+            return output::print_json(&login);
+        }
+        println!("# {}", path.display());
+        println!("{}", serde_json::to_string_pretty(&login)?);
+        Ok(())
+    }
+}
+
+/// The lock on the config directory, held until dropped. It is advisory, so
+/// it only keeps out other invocations of the plugin that also take it.
+pub(crate) struct ConfigLock {
+    file: File,
+}
+
+impl ConfigLock {
+    /// Waits until no other invocation holds the lock on the config directory
+    pub(crate) fn acquire() -> Result<Self> {
+        Self::acquire_in(&config_root_dir()?)
+    }
+
+    /// Waits until no other invocation holds the lock on the directory `root`
+    pub(crate) fn acquire_in(root: &Path) -> Result<Self> {
+        std::fs::create_dir_all(root)
+            .with_context(|| format!("Failed to create config directory {}", root.display()))?;
+        let path = root.join(LOCK_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        file.lock_exclusive()
+            .with_context(|| format!("Failed to lock {}", path.display()))?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock anyway
+        _ = self.file.unlock();
+    }
+}
+
+/// Replaces the file at `path` with `contents`. Other invocations reading it
+/// at the same time see either the old file or the new one, never a mix, and
+/// a crash part way through leaves the old file as it was.
+pub(crate) fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let dir = path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let mut file = tempfile::NamedTempFile::new_in(&dir)
+        .with_context(|| format!("Failed to create a file in {}", dir.display()))?;
+    file.write_all(contents.as_ref())?;
+    file.as_file().sync_all()?;
+    file.persist(path)
+        .map_err(|e| e.error)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+
+    #[test]
+    fn test_locked_updates_are_not_lost() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        let path = root.path().join("counter");
+        write_atomic(&path, "0")?;
+        let barrier = Arc::new(Barrier::new(8));
+        let threads = (0..8)
+            .map(|_| {
+                let (root, path, barrier) = (root.path().to_owned(), path.clone(), barrier.clone());
+                std::thread::spawn(move || -> Result<()> {
+                    barrier.wait();
+                    let _lock = ConfigLock::acquire_in(&root)?;
+                    let count: u32 = std::fs::read_to_string(&path)?.parse()?;
+                    write_atomic(&path, (count + 1).to_string())
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap()?;
+        }
+        assert_eq!(std::fs::read_to_string(&path)?, "8");
+        // Only the file itself is left behind
+        let names = std::fs::read_dir(root.path())?
+            .map(|e| Ok(e?.file_name().to_string_lossy().to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&LOCK_FILE.to_owned()));
+        Ok(())
+    }
+}
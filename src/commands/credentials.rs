@@ -0,0 +1,197 @@
+//! Where the tokens of a saved login are kept. By default they go in the OS
+//! keychain, and the login file under the config directory records only the
+//! instance and when the token expires. Machines without a keychain, such as
+//! CI runners, can log in with `--token-storage file` to keep the tokens in
+//! the login file instead. Where the keychain turns out to be unavailable,
+//! the tokens are kept in the file with a warning.
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::commands::{config, login::LoginConnection};
+use crate::errors::CliError;
+
+const KEYCHAIN_SERVICE: &str = "fermyon-cloud";
+
+const FILE_STORAGE_HINT: &str =
+    "Log in with `--token-storage file` to keep the tokens in the login file instead";
+
+/// Where the tokens of a login are stored
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStorage {
+    #[clap(name = "keychain")]
+    Keychain,
+    /// Logins saved before the keychain was supported have no storage
+    /// recorded, and their tokens are in the file
+    #[default]
+    #[clap(name = "file")]
+    File,
+}
+
+/// The secrets kept in the keychain for a login
+#[derive(Deserialize, Serialize)]
+struct StoredTokens {
+    token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
+}
+
+/// Writes the login for `environment` to `path`, and its tokens to wherever
+/// the login says
+pub(crate) fn save(
+    path: &Path,
+    environment: Option<&str>,
+    connection: &LoginConnection,
+) -> Result<()> {
+    let on_disk = match connection.token_storage {
+        TokenStorage::File => connection.clone(),
+        TokenStorage::Keychain => {
+            let (on_disk, tokens) = split_tokens(connection);
+            let saved = keychain_entry(environment, &connection.url).and_then(|entry| {
+                entry
+                    .set_password(&serde_json::to_string(&tokens)?)
+                    .map_err(keychain_error)
+            });
+            match saved {
+                Ok(()) => on_disk,
+                Err(e) => {
+                    eprintln!(
+                        "Warning: {e:#}. The login's tokens are saved in {} instead.",
+                        path.display()
+                    );
+                    LoginConnection {
+                        token_storage: TokenStorage::File,
+                        ..connection.clone()
+                    }
+                }
+            }
+        }
+    };
+    config::write_atomic(path, serde_json::to_string_pretty(&on_disk)?)
+        .with_context(|| format!("Failed to save login information to {}", path.display()))
+}
+
+/// Parses the login for `environment` read from `data`, fetching its tokens
+/// from the keychain if they are kept there
+pub(crate) fn parse(environment: Option<&str>, data: &str) -> Result<LoginConnection> {
+    let mut connection: LoginConnection = serde_json::from_str(data)?;
+    if connection.token_storage == TokenStorage::Keychain {
+        let secret = match keychain_entry(environment, &connection.url)?.get_password() {
+            Ok(secret) => secret,
+            Err(keyring::Error::NoEntry) => bail!(CliError::auth(
+                "The login's tokens are missing from the keychain"
+            )),
+            Err(e) => return Err(keychain_error(e)),
+        };
+        let tokens: StoredTokens = serde_json::from_str(&secret)
+            .context("Cannot parse the login's tokens from the keychain")?;
+        connection.token = tokens.token;
+        connection.refresh_token = tokens.refresh_token;
+    }
+    Ok(connection)
+}
+
+/// Forgets the tokens saved in the keychain for the login for `environment`
+/// at `path`. Logins stored in the file have nothing there, and a login that
+/// cannot be read has nothing to forget, which are not errors.
+pub(crate) fn remove(path: &Path, environment: Option<&str>) -> Result<()> {
+    // Only the instance and storage are needed, so the tokens are not
+    // fetched from the keychain
+    let stored = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str::<LoginConnection>(&data).ok());
+    let Some(stored) = stored.filter(|c| c.token_storage == TokenStorage::Keychain) else {
+        return Ok(());
+    };
+    match keychain_entry(environment, &stored.url)?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(keychain_error(e)).context("Failed to remove the login from the keychain"),
+    }
+}
+
+// Each login has its own keychain entry, named after its environment and
+// instance, so that logins are kept apart whatever their files are called
+fn keychain_entry(environment: Option<&str>, url: &url::Url) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, &keychain_user(environment, url)).map_err(keychain_error)
+}
+
+// Environment names cannot contain spaces or parentheses, so no environment
+// shares the default login's entry
+fn keychain_user(environment: Option<&str>, url: &url::Url) -> String {
+    format!("{} {url}", environment.unwrap_or("(default)"))
+}
+
+fn keychain_error(e: keyring::Error) -> anyhow::Error {
+    CliError::auth(format!("The keychain is not available: {e}"))
+        .with_hint(FILE_STORAGE_HINT)
+        .into()
+}
+
+// The login as written to disk, without its tokens, and the tokens
+fn split_tokens(connection: &LoginConnection) -> (LoginConnection, StoredTokens) {
+    let mut on_disk = connection.clone();
+    let tokens = StoredTokens {
+        token: std::mem::take(&mut on_disk.token),
+        refresh_token: on_disk.refresh_token.take(),
+    };
+    (on_disk, tokens)
+}
+
+#[cfg(test)]
+mod credentials_tests {
+    use super::*;
+
+    #[test]
+    fn test_keychain_logins_keep_no_tokens_on_disk() -> Result<()> {
+        let connection: LoginConnection = serde_json::from_str(
+            r#"{
+                "url": "https://cloud.fermyon.com/",
+                "danger_accept_invalid_certs": false,
+                "token": "secret-token",
+                "refresh_token": "secret-refresh",
+                "expiration": "2024-01-01T12:00:00Z",
+                "token_storage": "keychain"
+            }"#,
+        )?;
+        let (on_disk, tokens) = split_tokens(&connection);
+        let written = serde_json::to_string(&on_disk)?;
+        assert!(!written.contains("secret"));
+        assert!(written.contains("2024-01-01T12:00:00Z"));
+        assert_eq!(tokens.token, "secret-token");
+        assert_eq!(tokens.refresh_token.as_deref(), Some("secret-refresh"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_older_logins_keep_their_tokens_in_the_file() -> Result<()> {
+        let data = r#"{
+            "url": "https://cloud.fermyon.com/",
+            "danger_accept_invalid_certs": false,
+            "token": "secret-token"
+        }"#;
+        let connection = parse(None, data)?;
+        assert_eq!(connection.token_storage, TokenStorage::File);
+        assert_eq!(connection.token, "secret-token");
+        Ok(())
+    }
+
+    #[test]
+    fn test_keychain_entries_are_named_after_the_environment_and_instance() {
+        let url = url::Url::parse("https://cloud.fermyon.com/").unwrap();
+        assert_eq!(
+            keychain_user(None, &url),
+            "(default) https://cloud.fermyon.com/"
+        );
+        assert_ne!(
+            keychain_user(Some("config"), &url),
+            keychain_user(None, &url)
+        );
+        let other = url::Url::parse("https://cloud.example.com/").unwrap();
+        assert_ne!(
+            keychain_user(Some("staging"), &url),
+            keychain_user(Some("staging"), &other)
+        );
+    }
+}
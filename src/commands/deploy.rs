@@ -13,8 +13,8 @@ use tokio::fs;
 use tracing::instrument;
 
 use std::{
-    collections::HashSet,
-    io::{self, Write},
+    collections::{BTreeMap, HashSet},
+    io::{self, IsTerminal},
     path::{Path, PathBuf},
     str::FromStr,
 };
@@ -22,11 +22,20 @@ use url::Url;
 
 use crate::{
     commands::{
+        cache::{self, ResponseCache},
+        canary,
+        config::ConfigLock,
+        credentials,
+        env::resolve_environment,
+        http_config,
         links_output::ResourceType,
+        timings,
         variables::{get_variables, set_variables},
-        DEFAULT_CLOUD_URL,
+        DEFAULT_CLOUD_URL, TOKEN_REFRESH_MARGIN_MINUTES,
     },
-    spin,
+    errors::CliError,
+    ops::{Cloud, DeployOptions, DeployProgress, DeploySource, Deployment},
+    output, spin,
 };
 
 use crate::{
@@ -35,6 +44,7 @@ use crate::{
 };
 
 mod resource;
+mod watch;
 
 const DEVELOPER_CLOUD_FAQ: &str = "https://developer.fermyon.com/cloud/faq";
 const SPIN_DEFAULT_KV_STORE: &str = "default";
@@ -44,7 +54,7 @@ const SPIN_DEFAULT_KV_STORE: &str = "default";
 const CLOUD_SUPPORTED_FEATURES: &[&str] = &[];
 
 /// Package and upload an application to the Fermyon Cloud.
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[clap(about = "Package and upload an application to the Fermyon Cloud")]
 pub struct DeployCommand {
     /// The application to deploy. This may be a manifest (spin.toml) file, a
@@ -69,10 +79,11 @@ pub struct DeployCommand {
     )]
     pub file_source: Option<PathBuf>,
 
-    /// The application to deploy. This is the same as `--from` but forces the
-    /// application to be interpreted as an OCI registry reference.
+    /// An application already pushed to a registry with `spin registry push`
+    /// (e.g. "ghcr.io/org/app:v1"). No local source or build is needed, so
+    /// the same artifact can be promoted from one environment to the next.
+    /// For private registries, log in first with `spin registry login`.
     #[clap(
-        hide = true,
         name = FROM_REGISTRY_OPT,
         long = "from-registry",
         group = "source",
@@ -113,6 +124,18 @@ pub struct DeployCommand {
     #[clap(long = "variable", parse(try_from_str = parse_kv))]
     pub variables: Vec<(String, String)>,
 
+    /// Set variables from a TOML file of `variable = "value"` entries. Entries
+    /// in an `[env.<name>]` table override the others when deploying to that
+    /// environment, so `env` cannot be used as a variable name in the file.
+    /// Values given with `--variable` take precedence.
+    #[clap(name = "variables-file", long = "variables-file")]
+    pub variables_file: Option<PathBuf>,
+
+    /// The `[env.<name>]` table of the variables file to apply. Defaults to
+    /// the environment being deployed to.
+    #[clap(long = "variables-env", requires = "variables-file")]
+    pub variables_env: Option<String>,
+
     /// Specifies how application labels (such as SQLite databases) should
     /// be linked if they are not already linked. This is intended for
     /// non-interactive environments such as release pipelines; therefore,
@@ -123,19 +146,135 @@ pub struct DeployCommand {
     /// will be created.
     #[clap(long = "link")]
     pub links: Vec<String>,
+
+    /// Deploy the new revision as a canary that receives the given percentage
+    /// of traffic, while the rest continues to be served by the current
+    /// revision. Finish the rollout with `spin cloud canary promote` or
+    /// `spin cloud canary abort`. Only available for apps that are already deployed.
+    /// Variables and key/value pairs are shared by both revisions, so any set
+    /// during the rollout also apply to the current revision.
+    #[clap(long = "canary", value_parser = clap::builder::ValueParser::new(canary::parse_canary_percentage))]
+    pub canary: Option<u8>,
+
+    /// Keep running after the deploy, and redeploy whenever the application's
+    /// files change. With `--build`, changed sources are rebuilt first.
+    #[clap(long = "watch", takes_value = false, conflicts_with = "canary")]
+    pub watch: bool,
+
+    /// A glob, relative to the application directory, of files whose changes
+    /// trigger a redeploy with `--watch`. Can be used multiple times. Defaults
+    /// to every source file with `--build`, or else to the manifest and Wasm
+    /// files. With `--build`, build output in `target` and `.spin` is never watched.
+    #[clap(long = "watch-glob", requires = "watch")]
+    pub watch_globs: Vec<String>,
+
+    /// How long files must stay unchanged before `--watch` redeploys (e.g. "500ms")
+    #[clap(
+        long = "watch-debounce",
+        default_value = "1s",
+        requires = "watch",
+        value_parser = clap::builder::ValueParser::new(humantime::parse_duration)
+    )]
+    pub watch_debounce: std::time::Duration,
+
+    /// Fail the deploy if the new revision of an HTTP application does not
+    /// become ready, rather than only reporting it. Intended for CI pipelines.
+    #[clap(long = "wait", takes_value = false, conflicts_with = "canary")]
+    pub wait: bool,
+
+    /// How long `--wait` waits for the new revision to become ready (e.g.
+    /// "2m"). Defaults to the `--readiness-timeout`.
+    #[clap(
+        long = "timeout",
+        requires = "wait",
+        value_parser = clap::builder::ValueParser::new(humantime::parse_duration)
+    )]
+    pub timeout: Option<std::time::Duration>,
 }
 
 impl DeployCommand {
-    pub async fn run(self) -> Result<()> {
-        if self.build {
-            self.run_spin_build().await?;
+    pub async fn run(mut self) -> Result<()> {
+        let environment = resolve_environment(self.deployment_env_id.as_deref())?;
+        self.variables = self.merged_variables(environment.as_deref())?;
+        if self.watch {
+            return self.run_watch().await;
+        }
+        self.run_once().await
+    }
+
+    async fn run_once(&self) -> Result<()> {
+        let options = self.deploy_options()?;
+        let cloud = Cloud::connect(self.deployment_env_id.as_deref()).await?;
+        let deployment = cloud
+            .deploy_with_progress(options, &TerminalProgress)
+            .await?;
+        if output::is_json() {
+            output::print_json(&deployment)?;
         }
+        Ok(())
+    }
 
-        let login_connection = login_connection(self.deployment_env_id.as_deref()).await?;
+    async fn run_watch(self) -> Result<()> {
+        let AppSource::File(manifest) = self.resolve_app_source() else {
+            bail!("`--watch` can only be used with an application on the local file system");
+        };
+        let root = match manifest.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+            _ => PathBuf::from("."),
+        };
+        let globs = match self.watch_globs.as_slice() {
+            [] => {
+                let manifest_name = manifest.file_name().unwrap_or_default().to_string_lossy();
+                watch::default_globs(self.build, &manifest_name)
+            }
+            globs => globs.to_vec(),
+        };
+        // What the build writes must not trigger another deploy
+        let watcher = watch::Watcher::new(&root, &globs, self.build, self.watch_debounce)?;
+
+        loop {
+            // Taken before the deploy, so that changes made while it runs
+            // trigger the next one
+            let baseline = watcher.snapshot().await;
+            // A failed deploy is reported but keeps the watch going, since the
+            // next change may well fix it
+            if let Err(e) = self.run_once().await {
+                eprintln!("Error: {e:?}");
+            }
+            output::progress(&format!("Watching for changes in {}...", root.display()));
+            let changed = watcher.wait_for_change(&baseline).await;
+            output::progress(&format!("\n{} changed, redeploying...", changed.display()));
+        }
+    }
 
-        self.deploy_cloud(login_connection)
-            .await
-            .map_err(|e| anyhow!("{:?}\n\nLearn more at {}", e, DEVELOPER_CLOUD_FAQ))
+    /// The options the flags ask for
+    fn deploy_options(&self) -> Result<DeployOptions> {
+        let source = match self.resolve_app_source() {
+            AppSource::File(manifest) => DeploySource::Manifest(manifest),
+            AppSource::OciRegistry(reference) => DeploySource::Registry(reference),
+            AppSource::None => bail!(CliError::usage(format!(
+                "Default file '{DEFAULT_MANIFEST_FILE}' not found."
+            ))),
+            AppSource::Unresolvable(err) => bail!(CliError::usage(err)),
+        };
+        let readiness_timeout = self
+            .timeout
+            .unwrap_or(std::time::Duration::from_secs(u64::from(
+                self.readiness_timeout_secs,
+            )));
+        let options = DeployOptions {
+            source,
+            build: self.build,
+            variables: self.variables.clone(),
+            key_values: self.key_values.clone(),
+            links: self.links.clone(),
+            interactive: true,
+            canary: self.canary,
+            readiness_timeout,
+            wait: self.wait,
+        };
+        check_wait_timeout(&options)?;
+        Ok(options)
     }
 
     fn resolve_app_source(&self) -> AppSource {
@@ -175,176 +314,328 @@ impl DeployCommand {
         }
     }
 
-    async fn deploy_cloud(self, login_connection: LoginConnection) -> Result<()> {
+    // The variables file, overridden by `--variable` flags. Variables that
+    // neither sets keep their existing values. The overrides applied are
+    // those of `--variables-env`, or else of `environment`, the environment
+    // being deployed to.
+    fn merged_variables(&self, environment: Option<&str>) -> Result<Vec<(String, String)>> {
+        let Some(path) = &self.variables_file else {
+            return Ok(self.variables.clone());
+        };
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read variables file {}", path.display()))?;
+        let file = VariablesFile::parse(&content)
+            .with_context(|| format!("Invalid variables file {}", path.display()))?;
+        let environment = match &self.variables_env {
+            Some(name) if !file.environments.contains_key(name) => {
+                bail!(CliError::validation(format!(
+                    "The variables file {} has no [env.{name}] table",
+                    path.display()
+                )))
+            }
+            Some(name) => Some(name.clone()),
+            None => environment.map(str::to_owned),
+        };
+        let mut merged = file.for_environment(environment.as_deref());
+        merged.extend(self.variables.iter().cloned());
+        Ok(merged.into_iter().collect())
+    }
+}
+
+/// Reports a deploy's progress as the other commands do
+struct TerminalProgress;
+
+impl DeployProgress for TerminalProgress {
+    fn step(&self, message: &str) {
+        output::progress(message);
+    }
+
+    fn notice(&self, message: &str) {
+        output::notice(message);
+    }
+}
+
+// Waiting for no time at all could only ever fail the deploy
+fn check_wait_timeout(options: &DeployOptions) -> Result<()> {
+    if options.wait && options.readiness_timeout.is_zero() {
+        bail!(
+            CliError::usage("`--wait` needs a readiness timeout above zero")
+                .with_hint("Set `--timeout`, or a `--readiness-timeout` other than 0")
+        );
+    }
+    Ok(())
+}
+
+/// Deploys an app as `options` say, building it first if they ask to.
+/// Returns `None` if the user cancels.
+pub(crate) async fn deploy(
+    login_connection: &LoginConnection,
+    client: &impl CloudClientInterface,
+    cache: &mut ResponseCache,
+    options: &DeployOptions,
+    progress: &dyn DeployProgress,
+) -> Result<Option<Deployment>> {
+    check_wait_timeout(options)?;
+    let deploy = Deploy { options, progress };
+    if options.build {
+        if let DeploySource::Manifest(manifest) = &options.source {
+            timings::time("spin build", build(&resolve_manifest(manifest)?)).await?;
+        }
+    }
+    deploy
+        .deploy_cloud(login_connection, client, cache)
+        .await
+        .with_context(|| format!("Deploy failed. Learn more at {DEVELOPER_CLOUD_FAQ}"))
+}
+
+/// A deploy in progress
+struct Deploy<'a> {
+    options: &'a DeployOptions,
+    progress: &'a dyn DeployProgress,
+}
+
+impl Deploy<'_> {
+    async fn deploy_cloud(
+        &self,
+        login_connection: &LoginConnection,
+        client: &impl CloudClientInterface,
+        cache: &mut ResponseCache,
+    ) -> Result<Option<Deployment>> {
         let connection_config = ConnectionConfig {
             url: login_connection.url.to_string(),
             insecure: login_connection.danger_accept_invalid_certs,
             token: login_connection.token.clone(),
+            http: http_config(),
         };
 
-        let client = CloudClient::new(connection_config.clone());
         let interact = self.interaction_strategy()?;
 
         let dir = tempfile::tempdir()?;
 
-        let application = self.load_cloud_app(dir.path()).await?;
+        let application = timings::time("loading the app", self.load_cloud_app(dir.path())).await?;
 
         validate_cloud_app(&application)?;
-        self.validate_deployment_environment(&application, &client)
+        self.validate_deployment_environment(&application, client)
             .await?;
 
-        let digest = self
-            .push_oci(application.clone(), connection_config.clone())
-            .await?;
+        let digest = timings::time(
+            "uploading the app",
+            self.push_oci(application.clone(), connection_config.clone()),
+        )
+        .await?;
 
         let name = sanitize_app_name(application.name()?);
-        let storage_id = format!("oci://{}", name);
         let version = sanitize_app_version(application.version()?);
 
         let kv_labels = application.key_value_stores();
-        if !kv_labels.contains(SPIN_DEFAULT_KV_STORE) && !self.key_values.is_empty() {
-            bail!("The `key_values` flag can only be used to set key/value pairs in the default key/value store. The application does not reference a key/value store with the label 'default'");
+        if !kv_labels.contains(SPIN_DEFAULT_KV_STORE) && !self.options.key_values.is_empty() {
+            bail!(CliError::validation("The `key_values` flag can only be used to set key/value pairs in the default key/value store. The application does not reference a key/value store with the label 'default'"));
         }
-        let db_labels = application.sqlite_databases();
 
-        println!("Deploying...");
+        self.progress.step("Deploying...");
+
+        let Some(app_id) = self
+            .create_or_update_app(
+                client,
+                cache,
+                &name,
+                &version,
+                &application,
+                interact.as_ref(),
+            )
+            .await?
+        else {
+            return Ok(None); // User canceled terminal interaction
+        };
+
+        let app = client
+            .get_app(app_id.to_string())
+            .await
+            .context("Problem getting app by id")?;
+
+        let app_base_url = build_app_base_url(&app.subdomain, &login_connection.url)?;
+        let (http_base, http_router) = application.http_routes()?;
+        if http_router.routes().next().is_some() {
+            // A canary only serves part of the traffic, so the app as a whole
+            // never reports the new version
+            let readiness_timeout = match self.options.canary {
+                Some(_) => std::time::Duration::ZERO,
+                None => self.options.readiness_timeout,
+            };
+            let readiness = timings::time(
+                "waiting for readiness",
+                wait_for_ready(
+                    &app_base_url,
+                    &digest.unwrap_or_default(),
+                    readiness_timeout,
+                    Destination::Cloud(connection_config.clone().url),
+                    self.progress,
+                ),
+            )
+            .await;
+            if self.options.wait && readiness != Readiness::Ready {
+                bail!(
+                    "The new revision of '{name}' did not become ready within {}",
+                    humantime::format_duration(readiness_timeout)
+                );
+            }
+            let base = http_base.unwrap_or("/");
+            self.progress.notice(&available_routes(
+                &application,
+                &name,
+                &app_base_url,
+                base,
+                &http_router,
+            ));
+        } else {
+            self.progress
+                .notice(&format!("Application is running at {}", app.subdomain));
+        }
+
+        Ok(Some(Deployment {
+            app_id,
+            name,
+            version,
+            url: app_base_url,
+        }))
+    }
+
+    /// Creates the app, or adds a revision to it if it is already deployed,
+    /// along with its resources, key/value pairs and variables. Returns `None`
+    /// if the user cancels.
+    async fn create_or_update_app(
+        &self,
+        client: &impl CloudClientInterface,
+        cache: &mut ResponseCache,
+        name: &str,
+        version: &str,
+        application: &DeployableApp,
+        interact: &dyn resource::InteractionStrategy,
+    ) -> Result<Option<uuid::Uuid>> {
+        let storage_id = format!("oci://{}", name);
+        let version = version.to_owned();
+        let kv_labels = application.key_value_stores();
+        let db_labels = application.sqlite_databases();
+        let options = self.options;
 
-        // Create or update app
-        let app_id = match client.get_app_id(&name).await? {
+        let app_id = match client.get_app_id(name).await? {
             Some(app_id) => {
                 resource::create_and_link_resources_for_existing_app(
-                    &client,
-                    &name,
-                    app_id,
-                    db_labels,
-                    kv_labels,
-                    interact.as_ref(),
+                    client, name, app_id, db_labels, kv_labels, interact,
                 )
                 .await?;
-                client
-                    .add_revision(storage_id.clone(), version.clone())
-                    .await?;
-                // We have already checked that default kv store exists
-                for kv in self.key_values {
-                    client
-                        .add_key_value_pair(
-                            Some(app_id),
-                            SPIN_DEFAULT_KV_STORE.to_string(),
-                            kv.0,
-                            kv.1,
+                match options.canary {
+                    Some(percentage) => {
+                        canary::deploy_canary(
+                            client,
+                            app_id,
+                            storage_id.clone(),
+                            version.clone(),
+                            percentage,
+                            self.progress,
                         )
-                        .await
-                        .context("Problem creating key/value")?;
+                        .await?
+                    }
+                    None => {
+                        canary::add_served_revision(
+                            client,
+                            app_id,
+                            storage_id.clone(),
+                            version.clone(),
+                        )
+                        .await?
+                    }
                 }
+                // We have already checked that default kv store exists
+                client
+                    .add_key_value_pairs(Some(app_id), SPIN_DEFAULT_KV_STORE, &options.key_values)
+                    .await?;
 
-                set_variables(&client, app_id, &self.variables).await?;
+                set_variables(client, cache, app_id, &options.variables).await?;
 
                 app_id
             }
             None => {
+                if options.canary.is_some() {
+                    bail!("Canary deploys are only available for apps that are already deployed. Deploy without `--canary` first.");
+                }
                 let resources_to_link = match resource::create_resources_for_new_app(
-                    &client,
-                    &name,
+                    client,
+                    name,
                     db_labels,
                     kv_labels,
-                    interact.as_ref(),
+                    interact,
+                    self.progress,
                 )
                 .await?
                 {
                     Some(dbs) => dbs,
                     // TODO: Clean up created databases and kv stores
-                    None => return Ok(()), // User canceled terminal interaction
+                    None => return Ok(None), // User canceled terminal interaction
                 };
                 let app_id = client
-                    .add_app(&name, &storage_id)
+                    .add_app(name, &storage_id)
                     .await
                     .context("Unable to create app")?;
+                cache::remember_new_app(cache, name, app_id);
 
                 // Now that the app has been created, we can link resources to it.
-                resource::link_resources(&client, &name, app_id, resources_to_link).await?;
+                resource::link_resources(client, name, app_id, resources_to_link).await?;
                 client
                     .add_revision(storage_id.clone(), version.clone())
                     .await
                     .context(format!("Unable to upload {}", version.clone()))?;
 
                 // Have already checked that default kv store exists
-                for kv in self.key_values {
-                    client
-                        .add_key_value_pair(
-                            Some(app_id),
-                            SPIN_DEFAULT_KV_STORE.to_string(),
-                            kv.0,
-                            kv.1,
-                        )
-                        .await
-                        .context("Problem creating key/value")?;
-                }
+                client
+                    .add_key_value_pairs(Some(app_id), SPIN_DEFAULT_KV_STORE, &options.key_values)
+                    .await?;
 
-                set_variables(&client, app_id, &self.variables).await?;
+                set_variables(client, cache, app_id, &options.variables).await?;
 
                 app_id
             }
         };
-
-        let app = client
-            .get_app(app_id.to_string())
-            .await
-            .context("Problem getting app by id")?;
-
-        let app_base_url = build_app_base_url(&app.subdomain, &login_connection.url)?;
-        let (http_base, http_router) = application.http_routes()?;
-        if http_router.routes().next().is_some() {
-            wait_for_ready(
-                &app_base_url,
-                &digest.unwrap_or_default(),
-                self.readiness_timeout_secs,
-                Destination::Cloud(connection_config.clone().url),
-            )
-            .await;
-            let base = http_base.unwrap_or("/");
-            print_available_routes(&application, &name, &app_base_url, base, &http_router);
-        } else {
-            println!("Application is running at {}", app.subdomain);
-        }
-
-        Ok(())
+        Ok(Some(app_id))
     }
 
+    // Labels not covered by links are asked about only in a terminal, where
+    // prompts are drawn on stderr and read from stdin. Elsewhere, the empty
+    // script fails the deploy on the first of them.
     fn interaction_strategy(&self) -> anyhow::Result<Box<dyn resource::InteractionStrategy>> {
-        if self.links.is_empty() {
-            return Ok(Box::new(resource::Interactive));
+        if !self.options.links.is_empty() {
+            return Ok(Box::new(parse_linkage_specs(&self.options.links)?));
+        }
+        let in_terminal = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+        if self.options.interactive && in_terminal {
+            Ok(Box::new(resource::Interactive))
+        } else {
+            Ok(Box::new(resource::Scripted::default()))
         }
-
-        let script = parse_linkage_specs(&self.links)?;
-        Ok(Box::new(script))
     }
 
     async fn load_cloud_app(&self, working_dir: &Path) -> Result<DeployableApp, anyhow::Error> {
-        let app_source = self.resolve_app_source();
-
-        let locked_app = match &app_source {
-            AppSource::File(app_file) => {
+        let locked_app = match &self.options.source {
+            DeploySource::Manifest(manifest) => {
                 spin_loader::from_file(
-                    &app_file,
+                    resolve_manifest(manifest)?,
                     spin_loader::FilesMountStrategy::Copy(working_dir.to_owned()),
                     None,
                 )
                 .await?
             }
-            AppSource::OciRegistry(reference) => {
+            DeploySource::Registry(reference) => {
                 let mut oci_client = spin_oci::Client::new(false, None)
                     .await
                     .context("cannot create registry client")?;
 
+                self.progress.step(&format!("Pulling {reference}..."));
                 spin_oci::OciLoader::new(working_dir)
                     .load_app(&mut oci_client, reference)
-                    .await?
-            }
-            AppSource::None => {
-                anyhow::bail!("Default file '{DEFAULT_MANIFEST_FILE}' not found.");
-            }
-            AppSource::Unresolvable(err) => {
-                anyhow::bail!("{err}");
+                    .await
+                    .with_context(|| {
+                        format!("Failed to pull '{reference}'. If the registry is private, log in with `spin registry login`")
+                    })?
             }
         };
 
@@ -355,14 +646,16 @@ impl DeployCommand {
             .map(|t| format!("'{}'", t.trigger_type))
             .collect::<Vec<_>>();
         if !unsupported_triggers.is_empty() {
-            bail!(
+            bail!(CliError::validation(format!(
                 "Non-HTTP triggers are not supported - app uses {}",
                 unsupported_triggers.join(", ")
-            );
+            )));
         }
 
         if let Err(unsupported) = locked_app.ensure_needs_only(CLOUD_SUPPORTED_FEATURES) {
-            bail!("This app requires features that are not yet available on Fermyon Cloud: {unsupported}");
+            bail!(CliError::validation(format!(
+                "This app requires features that are not yet available on Fermyon Cloud: {unsupported}"
+            )));
         }
 
         let locked_app = ensure_http_base_set(locked_app);
@@ -374,7 +667,7 @@ impl DeployCommand {
     async fn validate_deployment_environment(
         &self,
         app: &DeployableApp,
-        client: &CloudClient,
+        client: &impl CloudClientInterface,
     ) -> Result<()> {
         let required_variables = app
             .0
@@ -393,11 +686,11 @@ impl DeployCommand {
     async fn ensure_variables_present(
         &self,
         required_variables: &HashSet<&String>,
-        client: &CloudClient,
+        client: &impl CloudClientInterface,
         name: &str,
     ) -> Result<()> {
         // Are all required variables satisifed by variables passed in this command?
-        let provided_variables = self.variables.iter().map(|(k, _)| k).collect();
+        let provided_variables = self.options.variables.iter().map(|(k, _)| k).collect();
         let unprovided_variables = required_variables
             .difference(&provided_variables)
             .copied()
@@ -407,19 +700,20 @@ impl DeployCommand {
         }
 
         // Are all remaining required variables satisfied by variables already in the cloud?
+        let unconfirmed = format!("Unable to confirm variables {unprovided_variables:?} are defined. Check your app after deployment.");
         let extant_variables = match client.get_app_id(name).await {
             Ok(Some(app_id)) => match get_variables(client, app_id).await {
                 Ok(variables) => variables,
                 Err(_) => {
                     // Don't block deployment for being unable to check the variables.
-                    eprintln!("Unable to confirm variables {unprovided_variables:?} are defined. Check your app after deployment.");
+                    self.progress.notice(&unconfirmed);
                     return Ok(());
                 }
             },
             Ok(None) => vec![],
             Err(_) => {
                 // Don't block deployment for being unable to check the variables.
-                eprintln!("Unable to confirm variables {unprovided_variables:?} are defined. Check your app after deployment.");
+                self.progress.notice(&unconfirmed);
                 return Ok(());
             }
         };
@@ -470,18 +764,36 @@ impl DeployCommand {
             )
             .await;
 
-        println!(
+        self.progress.step(&format!(
             "Uploading {} version {} to Fermyon Cloud...",
             &oci_ref.repository(),
             &oci_ref.tag().unwrap_or(application.version()?)
-        );
+        ));
         let digest = client.push_locked(application.0, reference, None).await?;
 
         Ok(digest)
     }
+}
+
+// A manifest option may name the directory containing the manifest
+pub(crate) fn resolve_manifest(path: &Path) -> Result<PathBuf> {
+    spin_common::paths::resolve_manifest_file_path(path.to_owned()).map_err(|e| anyhow!("{e}"))
+}
+
+async fn build(manifest_path: &Path) -> anyhow::Result<()> {
+    let spin_bin = spin::bin_path()?;
 
-    async fn run_spin_build(&self) -> Result<()> {
-        self.resolve_app_source().build().await
+    let result = tokio::process::Command::new(spin_bin)
+        .args(["build", "-f"])
+        .arg(manifest_path)
+        .status()
+        .await
+        .context("Failed to execute `spin build` command")?;
+
+    if result.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("Build failed: deployment cancelled"))
     }
 }
 
@@ -522,28 +834,6 @@ impl AppSource {
     fn unresolvable(message: impl Into<String>) -> Self {
         Self::Unresolvable(message.into())
     }
-
-    async fn build(&self) -> anyhow::Result<()> {
-        match self {
-            Self::File(manifest_path) => {
-                let spin_bin = spin::bin_path()?;
-
-                let result = tokio::process::Command::new(spin_bin)
-                    .args(["build", "-f"])
-                    .arg(manifest_path)
-                    .status()
-                    .await
-                    .context("Failed to execute `spin build` command")?;
-
-                if result.success() {
-                    Ok(())
-                } else {
-                    Err(anyhow!("Build failed: deployment cancelled"))
-                }
-            }
-            _ => Ok(()),
-        }
-    }
 }
 
 // SAFE_APP_NAME regex to only allow letters/numbers/underscores/dashes
@@ -726,14 +1016,22 @@ enum Destination {
     Cloud(String),
 }
 
+#[derive(Debug, PartialEq, Eq)]
+enum Readiness {
+    Ready,
+    NotReady,
+    NotChecked,
+}
+
 async fn wait_for_ready(
     app_base_url: &Url,
     app_version: &str,
-    readiness_timeout_secs: u16,
+    readiness_timeout: std::time::Duration,
     destination: Destination,
-) {
-    if readiness_timeout_secs == 0 {
-        return;
+    progress: &dyn DeployProgress,
+) -> Readiness {
+    if readiness_timeout.is_zero() {
+        return Readiness::NotChecked;
     }
 
     let app_info_url = app_base_url
@@ -744,38 +1042,32 @@ async fn wait_for_ready(
         .to_string();
 
     let start = std::time::Instant::now();
-    let readiness_timeout = std::time::Duration::from_secs(u64::from(readiness_timeout_secs));
     let poll_interval = tokio::time::Duration::from_secs(READINESS_POLL_INTERVAL_SECS);
 
-    print!("Waiting for application to become ready");
-    let _ = std::io::stdout().flush();
+    progress.step("Waiting for application to become ready...");
     loop {
         match is_ready(&app_info_url, app_version).await {
             Err(err) => {
-                println!("... readiness check failed: {err:?}");
-                return;
+                progress.notice(&format!("Readiness check failed: {err:?}"));
+                return Readiness::NotReady;
             }
             Ok(true) => {
-                println!("... ready");
-                return;
+                progress.step("Application is ready");
+                return Readiness::Ready;
             }
             Ok(false) => {}
         }
 
-        print!(".");
-        let _ = std::io::stdout().flush();
-
         if start.elapsed() >= readiness_timeout {
-            println!();
-            println!("Application deployed, but Spin could not establish readiness");
+            progress.notice("Application deployed, but Spin could not establish readiness");
             match destination {
                 Destination::Cloud(url) => {
-                    println!(
+                    progress.notice(&format!(
                         "Check the Fermyon Cloud dashboard to see the application status: {url}"
-                    );
+                    ));
                 }
             }
-            return;
+            return Readiness::NotReady;
         }
         tokio::time::sleep(poll_interval).await;
     }
@@ -784,7 +1076,8 @@ async fn wait_for_ready(
 #[instrument(level = "debug")]
 async fn is_ready(app_info_url: &str, expected_version: &str) -> Result<bool> {
     // If the request fails, we assume the app isn't ready
-    let resp = match reqwest::get(app_info_url).await {
+    let http = http_config().apply(reqwest::Client::builder()).build()?;
+    let resp = match http.get(app_info_url).send().await {
         Ok(resp) => resp,
         Err(err) => {
             tracing::warn!("Readiness check failed: {err:?}");
@@ -808,13 +1101,13 @@ async fn is_ready(app_info_url: &str, expected_version: &str) -> Result<bool> {
     Ok(true)
 }
 
-fn print_available_routes(
+fn available_routes(
     app: &DeployableApp,
     app_name: &str,
     app_base_url: &Url,
     base: &str,
     router: &Router,
-) {
+) -> String {
     // Strip any trailing slash from base URL
     let app_base_url = app_base_url.to_string();
     let route_prefix = app_base_url.strip_suffix('/').unwrap_or(&app_base_url);
@@ -829,31 +1122,37 @@ fn print_available_routes(
     let app_root_url = format!("{route_prefix}{base}");
     let admin_url = format!("{}app/{app_name}", DEFAULT_CLOUD_URL); // URL already has scheme and /
 
-    println!();
-    println!("View application:   {app_root_url}");
+    let mut text = format!("\nView application:   {app_root_url}\n");
 
     if router
         .routes()
         .any(|(route, _)| route.to_string() != " (wildcard)")
     {
-        println!("  Routes:");
+        text.push_str("  Routes:\n");
         for (route, component_id) in router.routes() {
-            println!("  - {}: {}{}", component_id, route_prefix, route);
+            text.push_str(&format!(
+                "  - {}: {}{}\n",
+                component_id, route_prefix, route
+            ));
             if let Some(description) = app.component_description(component_id) {
-                println!("    {}", description);
+                text.push_str(&format!("    {}\n", description));
             }
         }
     }
 
-    println!("Manage application: {admin_url}");
+    text.push_str(&format!("Manage application: {admin_url}"));
+    text
 }
 
-// Check if the token has expired.
+// Check if the token has expired, or will within `margin`.
 // If the expiration is None, assume the token has not expired
-fn has_expired(login_connection: &LoginConnection) -> Result<bool> {
+pub(crate) fn expires_within(
+    login_connection: &LoginConnection,
+    margin: chrono::Duration,
+) -> Result<bool> {
     match &login_connection.expiration {
         Some(expiration) => match DateTime::parse_from_rfc3339(expiration) {
-            Ok(time) => Ok(Utc::now() > time),
+            Ok(time) => Ok(Utc::now() + margin > time),
             Err(err) => Err(anyhow!(
                 "Failed to parse token expiration time '{}'. Error: {}",
                 expiration,
@@ -864,7 +1163,20 @@ fn has_expired(login_connection: &LoginConnection) -> Result<bool> {
     }
 }
 
+fn login_hint(deployment_env_id: Option<&str>) -> String {
+    match deployment_env_id {
+        Some(name) => format!("Run `spin login --environment-name {name}` to log in again"),
+        None => "Run `spin login` to log in again".to_owned(),
+    }
+}
+
 pub async fn login_connection(deployment_env_id: Option<&str>) -> Result<LoginConnection> {
+    timings::time("auth", load_login_connection(deployment_env_id)).await
+}
+
+async fn load_login_connection(deployment_env_id: Option<&str>) -> Result<LoginConnection> {
+    let deployment_env_id = resolve_environment(deployment_env_id)?;
+    let deployment_env_id = deployment_env_id.as_deref();
     let path = config_file_path(deployment_env_id)?;
 
     // log in if config.json does not exist or cannot be read
@@ -874,9 +1186,12 @@ pub async fn login_connection(deployment_env_id: Option<&str>) -> Result<LoginCo
             match deployment_env_id {
                 Some(name) => {
                     // TODO: allow auto redirect to login preserving the name
-                    eprintln!("You have no instance saved as '{}'", name);
-                    eprintln!("Run `spin login --environment-name {}` to log in", name);
-                    std::process::exit(1);
+                    bail!(
+                        CliError::auth(format!("You have no instance saved as '{name}'"))
+                            .with_hint(format!(
+                                "Run `spin login --environment-name {name}` to log in"
+                            ))
+                    );
                 }
                 None => {
                     // log in, then read config
@@ -891,13 +1206,17 @@ pub async fn login_connection(deployment_env_id: Option<&str>) -> Result<LoginCo
         }
     };
 
-    let mut login_connection: LoginConnection = serde_json::from_str(&data)?;
-    let expired = match has_expired(&login_connection) {
+    let mut login_connection = credentials::parse(deployment_env_id, &data)?;
+    // Refreshable tokens are renewed a little early so that requests made
+    // just before expiry do not fail mid-flight
+    let margin = match login_connection.refresh_token {
+        Some(_) => chrono::Duration::minutes(TOKEN_REFRESH_MARGIN_MINUTES),
+        None => chrono::Duration::zero(),
+    };
+    let expired = match expires_within(&login_connection, margin) {
         Ok(val) => val,
         Err(err) => {
-            eprintln!("{}\n", err);
-            eprintln!("Run `spin login` to log in again");
-            std::process::exit(1);
+            bail!(CliError::auth(format!("{err:#}")).with_hint(login_hint(deployment_env_id)))
         }
     };
 
@@ -905,11 +1224,24 @@ pub async fn login_connection(deployment_env_id: Option<&str>) -> Result<LoginCo
         // if we have a refresh token available, let's try to refresh the token
         match login_connection.refresh_token {
             Some(refresh_token) => {
+                // A refresh token can only be used once, so invocations
+                // running at the same time take turns, and those that had to
+                // wait use the token saved by the one before them
+                let _lock = ConfigLock::acquire()?;
+                let latest =
+                    credentials::parse(deployment_env_id, &fs::read_to_string(&path).await?)?;
+                if !expires_within(&latest, margin).unwrap_or(true) {
+                    return Ok(latest);
+                }
+                let refresh_token = latest.refresh_token.clone().unwrap_or(refresh_token);
+                login_connection = latest;
+
                 // Only Cloud has support for refresh tokens
                 let connection_config = ConnectionConfig {
                     url: login_connection.url.to_string(),
                     insecure: login_connection.danger_accept_invalid_certs,
                     token: login_connection.token.clone(),
+                    http: http_config(),
                 };
                 let client = CloudClient::new(connection_config.clone());
 
@@ -922,37 +1254,19 @@ pub async fn login_connection(deployment_env_id: Option<&str>) -> Result<LoginCo
                         login_connection.refresh_token = Some(token_info.refresh_token);
                         login_connection.expiration = Some(token_info.expiration);
                         // save new token info
-                        let path = config_file_path(deployment_env_id)?;
-                        std::fs::write(path, serde_json::to_string_pretty(&login_connection)?)?;
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to refresh token: {}", e);
-                        match deployment_env_id {
-                            Some(name) => {
-                                eprintln!(
-                                    "Run `spin login --environment-name {}` to log in again",
-                                    name
-                                );
-                            }
-                            None => {
-                                eprintln!("Run `spin login` to log in again");
-                            }
-                        }
-                        std::process::exit(1);
+                        credentials::save(&path, deployment_env_id, &login_connection)?;
                     }
+                    Err(e) => bail!(CliError::auth(format!("Failed to refresh token: {e}"))
+                        .with_hint(login_hint(deployment_env_id))),
                 }
             }
             None => {
                 // session has expired and we have no way to refresh the token - log back in
                 match deployment_env_id {
-                    Some(name) => {
+                    Some(_) => {
                         // TODO: allow auto redirect to login preserving the name
-                        eprintln!("Your login to this environment has expired");
-                        eprintln!(
-                            "Run `spin login --environment-name {}` to log in again",
-                            name
-                        );
-                        std::process::exit(1);
+                        bail!(CliError::auth("Your login to this environment has expired")
+                            .with_hint(login_hint(deployment_env_id)));
                     }
                     None => {
                         LoginCommand::parse_from(vec!["login"]).run().await?;
@@ -960,7 +1274,7 @@ pub async fn login_connection(deployment_env_id: Option<&str>) -> Result<LoginCo
                             "Cannot find spin config at {}",
                             path.to_string_lossy()
                         ))?;
-                        login_connection = serde_json::from_str(&new_data)?;
+                        login_connection = credentials::parse(deployment_env_id, &new_data)?;
                     }
                 }
             }
@@ -987,6 +1301,61 @@ pub fn config_file_path(deployment_env_id: Option<&str>) -> Result<PathBuf> {
     Ok(path)
 }
 
+/// The contents of a `--variables-file`
+#[derive(Debug, Default, PartialEq)]
+struct VariablesFile {
+    variables: BTreeMap<String, String>,
+    /// The overrides in each `[env.<name>]` table
+    environments: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl VariablesFile {
+    fn parse(content: &str) -> Result<Self> {
+        let mut table: toml::Table = toml::from_str(content)?;
+        let mut file = Self::default();
+        match table.remove("env") {
+            Some(toml::Value::Table(environments)) => {
+                for (name, overrides) in environments {
+                    let toml::Value::Table(overrides) = overrides else {
+                        bail!("`env.{name}` must be a table of variables");
+                    };
+                    file.environments.insert(name, variable_values(overrides)?);
+                }
+            }
+            Some(_) => bail!(
+                "`env` is reserved for `[env.<name>]` tables of overrides, and cannot be a variable"
+            ),
+            None => {}
+        }
+        file.variables = variable_values(table)?;
+        Ok(file)
+    }
+
+    /// The variables, with the overrides for `environment` applied
+    fn for_environment(mut self, environment: Option<&str>) -> BTreeMap<String, String> {
+        if let Some(overrides) = environment.and_then(|name| self.environments.remove(name)) {
+            self.variables.extend(overrides);
+        }
+        self.variables
+    }
+}
+
+fn variable_values(table: toml::Table) -> Result<BTreeMap<String, String>> {
+    table
+        .into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => {
+                    value.to_string()
+                }
+                _ => bail!("Variable `{name}` must be a string, number or boolean"),
+            };
+            Ok((name, value))
+        })
+        .collect()
+}
+
 fn parse_linkage_specs(links: &[impl AsRef<str>]) -> anyhow::Result<resource::Scripted> {
     // TODO: would this be nicer as a fold?
     let mut strategy = resource::Scripted::default();
@@ -1086,6 +1455,19 @@ mod test {
         );
     }
 
+    #[test]
+    fn registry_reference_needs_no_local_source() {
+        let cmd = DeployCommand {
+            file_source: None,
+            registry_source: Some("ghcr.io/org/app:v1".to_owned()),
+            ..deploy_cmd_for_test_file("minimal_v2.toml")
+        };
+        assert_eq!(
+            cmd.resolve_app_source(),
+            AppSource::OciRegistry("ghcr.io/org/app:v1".to_owned())
+        );
+    }
+
     fn deploy_cmd_for_test_file(filename: &str) -> DeployCommand {
         let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
             .join("testdata")
@@ -1099,10 +1481,74 @@ mod test {
             deployment_env_id: None,
             key_values: vec![],
             variables: vec![],
+            variables_file: None,
+            variables_env: None,
             links: vec![],
+            canary: None,
+            watch: false,
+            watch_globs: vec![],
+            watch_debounce: std::time::Duration::from_secs(1),
+            wait: false,
+            timeout: None,
         }
     }
 
+    fn options_for_test_file(filename: &str) -> DeployOptions {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join(filename);
+        DeployOptions::new(DeploySource::Manifest(path))
+    }
+
+    async fn load_test_app(filename: &str, working_dir: &Path) -> Result<DeployableApp> {
+        let options = options_for_test_file(filename);
+        let deploy = Deploy {
+            options: &options,
+            progress: &|_: &str| {},
+        };
+        deploy.load_cloud_app(working_dir).await
+    }
+
+    #[test]
+    fn deploy_flags_become_deploy_options() -> Result<()> {
+        let cmd = DeployCommand {
+            file_source: None,
+            registry_source: Some("ghcr.io/org/app:v1".to_owned()),
+            variables: vec![("greeting".to_owned(), "hello".to_owned())],
+            wait: true,
+            timeout: Some(std::time::Duration::from_secs(120)),
+            ..deploy_cmd_for_test_file("minimal_v2.toml")
+        };
+        let options = cmd.deploy_options()?;
+        assert!(
+            matches!(&options.source, DeploySource::Registry(reference) if reference == "ghcr.io/org/app:v1")
+        );
+        assert_eq!(options.variables, cmd.variables);
+        assert_eq!(
+            options.readiness_timeout,
+            std::time::Duration::from_secs(120)
+        );
+        assert!(options.wait && options.interactive);
+
+        let cmd = DeployCommand {
+            registry_source: Some("ghcr.io/org/app:v1".to_owned()),
+            ..deploy_cmd_for_test_file("minimal_v2.toml")
+        };
+        let err = cmd.deploy_options().unwrap_err();
+        let (kind, _) = crate::errors::classify(&err).unwrap();
+        assert_eq!(kind, crate::errors::ErrorKind::Usage);
+
+        let cmd = DeployCommand {
+            readiness_timeout_secs: 0,
+            wait: true,
+            ..deploy_cmd_for_test_file("minimal_v2.toml")
+        };
+        let err = cmd.deploy_options().unwrap_err();
+        let (kind, _) = crate::errors::classify(&err).unwrap();
+        assert_eq!(kind, crate::errors::ErrorKind::Usage);
+        Ok(())
+    }
+
     fn get_trigger_base(mut app: DeployableApp) -> String {
         let serde_json::map::Entry::Occupied(trigger) = app.0.metadata.entry("trigger") else {
             panic!("Expected trigger metadata but entry was vacant");
@@ -1122,13 +1568,15 @@ mod test {
     async fn if_http_base_is_set_then_it_is_respected() {
         let temp_dir = tempfile::tempdir().unwrap();
 
-        let cmd = deploy_cmd_for_test_file("based_v1.toml");
-        let app = cmd.load_cloud_app(temp_dir.path()).await.unwrap();
+        let app = load_test_app("based_v1.toml", temp_dir.path())
+            .await
+            .unwrap();
         let base = get_trigger_base(app);
         assert_eq!("/base", base);
 
-        let cmd = deploy_cmd_for_test_file("based_v2.toml");
-        let app = cmd.load_cloud_app(temp_dir.path()).await.unwrap();
+        let app = load_test_app("based_v2.toml", temp_dir.path())
+            .await
+            .unwrap();
         let base = get_trigger_base(app);
         assert_eq!("/base", base);
     }
@@ -1137,13 +1585,15 @@ mod test {
     async fn if_http_base_is_not_set_then_it_is_inserted() {
         let temp_dir = tempfile::tempdir().unwrap();
 
-        let cmd = deploy_cmd_for_test_file("unbased_v1.toml");
-        let app = cmd.load_cloud_app(temp_dir.path()).await.unwrap();
+        let app = load_test_app("unbased_v1.toml", temp_dir.path())
+            .await
+            .unwrap();
         let base = get_trigger_base(app);
         assert_eq!("/", base);
 
-        let cmd = deploy_cmd_for_test_file("unbased_v2.toml");
-        let app = cmd.load_cloud_app(temp_dir.path()).await.unwrap();
+        let app = load_test_app("unbased_v2.toml", temp_dir.path())
+            .await
+            .unwrap();
         let base = get_trigger_base(app);
         assert_eq!("/", base);
     }
@@ -1152,12 +1602,168 @@ mod test {
     async fn plugin_version_should_be_set() {
         let temp_dir = tempfile::tempdir().unwrap();
 
-        let cmd = deploy_cmd_for_test_file("minimal_v2.toml");
-        let app = cmd.load_cloud_app(temp_dir.path()).await.unwrap();
+        let app = load_test_app("minimal_v2.toml", temp_dir.path())
+            .await
+            .unwrap();
         let version = app.0.metadata.get("cloud_plugin_version").unwrap();
         assert_eq!(crate::VERSION, version);
     }
 
+    #[tokio::test]
+    async fn existing_app_gets_a_new_revision_and_variables() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let options = DeployOptions {
+            variables: vec![("greeting".to_owned(), "hello".to_owned())],
+            ..options_for_test_file("minimal_v2.toml")
+        };
+        let deploy = Deploy {
+            options: &options,
+            progress: &|_: &str| {},
+        };
+        let application = deploy.load_cloud_app(temp_dir.path()).await?;
+        let app_id = uuid::Uuid::new_v4();
+        let client = cloud::testing::RecordedClient::new()
+            .respond(
+                "list_apps",
+                cloud_openapi::models::AppItemPage {
+                    items: vec![cloud_openapi::models::AppItem {
+                        id: app_id,
+                        name: "minimal-v2".to_owned(),
+                        ..Default::default()
+                    }],
+                    is_last_page: true,
+                    ..Default::default()
+                },
+            )
+            .respond(
+                "list_channels",
+                vec![cloud::models::ChannelItem {
+                    app_id,
+                    name: cloud::SPIN_DEPLOY_CHANNEL_NAME.to_owned(),
+                    revision_selection_strategy: Some(
+                        cloud_openapi::models::ChannelRevisionSelectionStrategy::UseRangeRule,
+                    ),
+                    ..Default::default()
+                }],
+            );
+
+        let deployed = deploy
+            .create_or_update_app(
+                &client,
+                &mut ResponseCache::disabled(),
+                "minimal-v2",
+                "0.1.0",
+                &application,
+                &resource::Interactive,
+            )
+            .await?;
+
+        assert_eq!(deployed, Some(app_id));
+        assert!(client.calls_to("add_app").is_empty());
+        assert!(client.calls_to("follow_latest_revision").is_empty());
+        assert_eq!(
+            client.calls_to("add_revision"),
+            [serde_json::json!({
+                "app_storage_id": "oci://minimal-v2",
+                "revision_number": "0.1.0",
+            })]
+        );
+        assert_eq!(
+            client.calls_to("add_variable_pair"),
+            [serde_json::json!({ "app_id": app_id, "variable": "greeting", "value": "hello" })]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn variables_file_applies_environment_overrides() -> Result<()> {
+        let file = VariablesFile::parse(
+            r#"
+            api_url = "https://example.com"
+            retries = 3
+            debug = false
+
+            [env.staging]
+            api_url = "https://staging.example.com"
+            "#,
+        )?;
+        let staging = file.for_environment(Some("staging"));
+        assert_eq!(staging["api_url"], "https://staging.example.com");
+        assert_eq!(staging["retries"], "3");
+        assert_eq!(staging["debug"], "false");
+
+        let file = VariablesFile::parse("api_url = \"https://example.com\"")?;
+        assert_eq!(
+            file.for_environment(Some("production"))["api_url"],
+            "https://example.com"
+        );
+
+        assert!(VariablesFile::parse("ports = [80, 443]").is_err());
+        assert!(VariablesFile::parse("env = \"staging\"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn variable_flags_override_the_variables_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("vars.toml");
+        std::fs::write(
+            &path,
+            "region = \"eu\"\ntier = \"free\"\n\n[env.production]\nregion = \"us\"\n",
+        )?;
+        let cmd = DeployCommand {
+            variables: vec![("tier".to_owned(), "paid".to_owned())],
+            variables_file: Some(path),
+            ..deploy_cmd_for_test_file("minimal_v2.toml")
+        };
+        assert_eq!(
+            cmd.merged_variables(None)?,
+            vec![
+                ("region".to_owned(), "eu".to_owned()),
+                ("tier".to_owned(), "paid".to_owned())
+            ]
+        );
+        assert_eq!(
+            cmd.merged_variables(Some("production"))?,
+            vec![
+                ("region".to_owned(), "us".to_owned()),
+                ("tier".to_owned(), "paid".to_owned())
+            ]
+        );
+
+        let cmd = DeployCommand {
+            variables_env: Some("staging".to_owned()),
+            ..cmd
+        };
+        assert!(cmd.merged_variables(None).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unlinked_labels_fail_without_a_terminal() {
+        let options = options_for_test_file("minimal_v2.toml");
+        let deploy = Deploy {
+            options: &options,
+            progress: &|_: &str| {},
+        };
+        let interact = deploy.interaction_strategy().unwrap();
+
+        let mut client = cloud::MockCloudClientInterface::new();
+        client.expect_get_databases().returning(|_| Ok(vec![]));
+        let err = resource::create_resources_for_new_app(
+            &client,
+            "test:unlinked",
+            string_set(&["default"]),
+            HashSet::new(),
+            interact.as_ref(),
+            &|_: &str| {},
+        )
+        .await
+        .unwrap_err();
+        let (kind, _) = crate::errors::classify(&err).unwrap();
+        assert_eq!(kind, crate::errors::ErrorKind::Usage);
+    }
+
     fn string_set(strs: &[&str]) -> HashSet<String> {
         strs.iter().map(|s| s.to_string()).collect()
     }
@@ -1187,6 +1793,7 @@ mod test {
             db_labels,
             HashSet::new(),
             &linkages,
+            &|_: &str| {},
         )
         .await
         .unwrap()
@@ -1246,6 +1853,7 @@ mod test {
             HashSet::new(),
             kv_labels,
             &linkages,
+            &|_: &str| {},
         )
         .await
         .unwrap()
@@ -1316,6 +1924,7 @@ mod test {
             db_labels,
             kv_labels,
             &linkages,
+            &|_: &str| {},
         )
         .await
         .unwrap()
@@ -1,11 +1,13 @@
 //! Functions for creating and linking resources (such as key value stores and
 //! databases) to apps during application deployment
-use anyhow::{anyhow, bail, Context, Result};
+use anyhow::{bail, Context, Result};
 use cloud::CloudClientInterface;
 use cloud_openapi::models::ResourceLabel;
 
 use crate::commands::links_output::ResourceLinks;
 use crate::commands::links_output::ResourceType;
+use crate::errors::CliError;
+use crate::ops::DeployProgress;
 use crate::random_name::RandomNameGenerator;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
@@ -240,9 +242,18 @@ impl Scripted {
             ResourceType::Database => self.db_labels_to_resource.get(label),
             ResourceType::KeyValueStore => self.kv_labels_to_resource.get(label),
         };
+        let kind = match resource_type {
+            ResourceType::Database => "sqlite",
+            ResourceType::KeyValueStore => "kv",
+        };
         match resource {
             Some(resource_ref) => Ok(resource_ref),
-            None => Err(anyhow!("No link specified for label '{label}'")),
+            None => bail!(CliError::usage(format!(
+                "No link specified for {resource_type} label '{label}'"
+            ))
+            .with_hint(format!(
+                "Link it with `--link {kind}:{label}=NAME`, along with any other links"
+            ))),
         }
     }
 }
@@ -257,6 +268,7 @@ pub(super) async fn create_resources_for_new_app(
     db_labels: HashSet<String>,
     kv_labels: HashSet<String>,
     interact: &dyn InteractionStrategy,
+    progress: &dyn DeployProgress,
 ) -> anyhow::Result<Option<Vec<LinkageSpec>>> {
     let mut resources_to_link: Vec<LinkageSpec> = Vec::new();
     let db_label_types = db_labels.into_iter().map(|l| (l, ResourceType::Database));
@@ -278,7 +290,7 @@ pub(super) async fn create_resources_for_new_app(
         {
             ResourceSelection::Existing(r) => r,
             ResourceSelection::New(r) => {
-                println!("Creating {resource_type} named '{r}'");
+                progress.step(&format!("Creating {resource_type} named '{r}'"));
                 match resource_type {
                     ResourceType::Database => {
                         client
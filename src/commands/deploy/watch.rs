@@ -0,0 +1,196 @@
+//! Polls an application's files for changes so that `deploy --watch` can
+//! redeploy. Polling needs no platform support and the file sets involved
+//! are small enough that comparing modification times is cheap.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use glob::Pattern;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Directories that never hold anything worth deploying, and can be large
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules"];
+// Build output, which is skipped too when watching sources to rebuild them
+const BUILD_OUTPUT_DIRS: &[&str] = &["target", ".spin"];
+
+/// The default globs: sources when `--build` rebuilds the app, or else the
+/// manifest and the Wasm files built outside of Spin.
+pub(super) fn default_globs(build: bool, manifest_name: &str) -> Vec<String> {
+    if build {
+        vec!["**/*".to_owned()]
+    } else {
+        vec![Pattern::escape(manifest_name), "**/*.wasm".to_owned()]
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct Watcher {
+    root: PathBuf,
+    patterns: Vec<Pattern>,
+    skipped_dirs: Vec<&'static str>,
+    debounce: Duration,
+}
+
+type Snapshot = BTreeMap<PathBuf, SystemTime>;
+
+impl Watcher {
+    pub fn new(
+        root: &Path,
+        globs: &[String],
+        skip_build_output: bool,
+        debounce: Duration,
+    ) -> Result<Self> {
+        let patterns = globs
+            .iter()
+            .map(|g| Pattern::new(g).with_context(|| format!("Invalid watch glob '{g}'")))
+            .collect::<Result<_>>()?;
+        let mut skipped_dirs = SKIPPED_DIRS.to_vec();
+        if skip_build_output {
+            skipped_dirs.extend(BUILD_OUTPUT_DIRS);
+        }
+        Ok(Self {
+            root: root.to_owned(),
+            patterns,
+            skipped_dirs,
+            debounce,
+        })
+    }
+
+    /// Records the current state of the watched files. The walk happens on
+    /// a blocking thread, since a large tree can take a while to read.
+    pub async fn snapshot(&self) -> Snapshot {
+        let watcher = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut snapshot = Snapshot::new();
+            watcher.scan(&watcher.root, &mut snapshot);
+            snapshot
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    /// Waits until the watched files differ from `baseline` and then stay
+    /// unchanged for the debounce period, and returns one of the changed paths.
+    pub async fn wait_for_change(&self, baseline: &Snapshot) -> PathBuf {
+        let (mut changed, mut current) = loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let current = self.snapshot().await;
+            if let Some(path) = first_difference(baseline, &current) {
+                break (path, current);
+            }
+        };
+        // Editors and builds write several files in quick succession
+        loop {
+            tokio::time::sleep(self.debounce).await;
+            let next = self.snapshot().await;
+            match first_difference(&current, &next) {
+                Some(path) => {
+                    changed = path;
+                    current = next;
+                }
+                None => return changed,
+            }
+        }
+    }
+
+    fn scan(&self, dir: &Path, snapshot: &mut Snapshot) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                let name = entry.file_name();
+                if !self.skipped_dirs.iter().any(|d| name == *d) {
+                    self.scan(&path, snapshot);
+                }
+            } else if self.is_watched(&path) {
+                if let Ok(modified) = metadata.modified() {
+                    snapshot.insert(path, modified);
+                }
+            }
+        }
+    }
+
+    fn is_watched(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        self.patterns.iter().any(|p| p.matches_path(relative))
+    }
+}
+
+// A file that was added, removed or modified
+fn first_difference(before: &Snapshot, after: &Snapshot) -> Option<PathBuf> {
+    after
+        .iter()
+        .find(|(path, modified)| before.get(*path) != Some(modified))
+        .map(|(path, _)| path.clone())
+        .or_else(|| before.keys().find(|p| !after.contains_key(*p)).cloned())
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_snapshot_honours_globs_and_skipped_dirs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src"))?;
+        std::fs::create_dir_all(root.join("target"))?;
+        std::fs::write(root.join("spin.toml"), "")?;
+        std::fs::write(root.join("src/lib.rs"), "")?;
+        std::fs::write(root.join("target/app.wasm"), "")?;
+
+        let watcher = Watcher::new(
+            root,
+            &default_globs(true, "spin.toml"),
+            true,
+            Duration::ZERO,
+        )?;
+        let watched = watcher.snapshot().await.into_keys().collect::<Vec<_>>();
+        assert_eq!(
+            watched,
+            vec![root.join("spin.toml"), root.join("src/lib.rs")]
+        );
+
+        let watcher = Watcher::new(
+            root,
+            &default_globs(false, "spin.toml"),
+            false,
+            Duration::ZERO,
+        )?;
+        let watched = watcher.snapshot().await.into_keys().collect::<Vec<_>>();
+        assert_eq!(
+            watched,
+            vec![root.join("spin.toml"), root.join("target/app.wasm")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_difference() {
+        let time = SystemTime::UNIX_EPOCH;
+        let later = time + Duration::from_secs(1);
+        let before = Snapshot::from([(PathBuf::from("a"), time), (PathBuf::from("b"), time)]);
+        assert_eq!(first_difference(&before, &before.clone()), None);
+
+        let modified = Snapshot::from([(PathBuf::from("a"), time), (PathBuf::from("b"), later)]);
+        assert_eq!(
+            first_difference(&before, &modified),
+            Some(PathBuf::from("b"))
+        );
+
+        let removed = Snapshot::from([(PathBuf::from("a"), time)]);
+        assert_eq!(
+            first_difference(&before, &removed),
+            Some(PathBuf::from("b"))
+        );
+    }
+}
@@ -0,0 +1,425 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use cloud::{client::ResponseError, CloudClientInterface};
+use serde::Serialize;
+
+use crate::commands::{
+    client_for_connection, credentials,
+    deploy::{config_file_path, login_connection, resolve_manifest},
+    env::resolve_environment,
+    http_config,
+    login::LoginConnection,
+    CommonArgs, TOKEN_REFRESH_MARGIN_MINUTES,
+};
+use crate::opts::{APP_MANIFEST_FILE_OPT, DEFAULT_MANIFEST_FILE};
+use crate::output;
+
+/// Check for common problems with your login, network and application
+#[derive(Parser, Debug)]
+pub struct DoctorCommand {
+    /// The application to check, as a manifest (spin.toml) file or a
+    /// directory containing one. If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file"
+    )]
+    pub app_source: Option<PathBuf>,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// The outcome of one diagnostic
+#[derive(Serialize, Debug, PartialEq)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    message: String,
+    hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+impl DoctorCommand {
+    pub async fn run(self) -> Result<()> {
+        let environment = resolve_environment(self.common.deployment_env_id.as_deref())?;
+        let login_hint = match &environment {
+            Some(name) => format!("Run `spin cloud login --environment-name {name}` to log in"),
+            None => "Run `spin cloud login` to log in".to_owned(),
+        };
+
+        let mut checks = vec![];
+        match read_login(
+            &config_file_path(environment.as_deref())?,
+            environment.as_deref(),
+        ) {
+            Err(check) => checks.push(check),
+            Ok(connection) => {
+                checks.push(check_token_expiry(&connection, &login_hint, Utc::now()));
+                checks.push(check_connectivity(&connection).await);
+                // The remaining server checks are meaningless if it cannot be reached
+                if checks.last().map(|c| c.status) == Some(Status::Pass) {
+                    // Log in as other commands do, which refreshes an expired token
+                    let env = self.common.deployment_env_id.as_deref();
+                    let result = match login_connection(env).await {
+                        Ok(connection) => client_for_connection(&connection)
+                            .list_apps(1, None)
+                            .await
+                            .map(|_| ()),
+                        Err(e) => Err(e),
+                    };
+                    checks.extend(check_api_access(result, &login_hint));
+                }
+            }
+        }
+        let manifest = match &self.app_source {
+            Some(source) => source.clone(),
+            None => PathBuf::from(DEFAULT_MANIFEST_FILE),
+        };
+        checks.push(check_manifest(&manifest).await);
+
+        print_checks(&checks)?;
+        let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+        if failed > 0 {
+            bail!("{failed} of {} checks failed", checks.len());
+        }
+        Ok(())
+    }
+}
+
+fn print_checks(checks: &[Check]) -> Result<()> {
+    if output::is_json() {
+        return output::print_json(checks);
+    }
+    for check in checks {
+        let marker = match check.status {
+            Status::Pass => "[pass]",
+            Status::Warn => "[warn]",
+            Status::Fail => "[FAIL]",
+        };
+        println!("{marker} {}: {}", check.name, check.message);
+        if let Some(hint) = &check.hint {
+            println!("       {hint}");
+        }
+    }
+    Ok(())
+}
+
+// A failed read is reported as the failed check, since nothing else about
+// the login can be checked without it
+fn read_login(
+    path: &Path,
+    environment: Option<&str>,
+) -> std::result::Result<LoginConnection, Check> {
+    const NAME: &str = "login";
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Check::fail(
+                NAME,
+                format!("No login found at {}", path.display()),
+                "Run `spin cloud login` to log in",
+            ))
+        }
+        Err(e) => {
+            return Err(Check::fail(
+                NAME,
+                format!("Cannot read {}: {e}", path.display()),
+                "Check the permissions of the file",
+            ))
+        }
+    };
+    credentials::parse(environment, &data).map_err(|e| {
+        Check::fail(
+            NAME,
+            format!("Cannot load {}: {e:#}", path.display()),
+            "Run `spin cloud login` to log in again",
+        )
+    })
+}
+
+fn check_token_expiry(connection: &LoginConnection, login_hint: &str, now: DateTime<Utc>) -> Check {
+    const NAME: &str = "token expiry";
+    let Some(expiration) = &connection.expiration else {
+        return Check::pass(NAME, "Token does not expire");
+    };
+    let Ok(time) = DateTime::parse_from_rfc3339(expiration) else {
+        return Check::fail(
+            NAME,
+            format!("Unrecognized token expiration time '{expiration}'"),
+            login_hint,
+        );
+    };
+    let refreshable = connection.refresh_token.is_some();
+    let remaining = time.with_timezone(&Utc) - now;
+    if remaining > chrono::Duration::minutes(TOKEN_REFRESH_MARGIN_MINUTES) {
+        Check::pass(NAME, format!("Token is valid until {expiration}"))
+    } else if refreshable {
+        Check::pass(
+            NAME,
+            format!("Token expires at {expiration} and will be refreshed on next use"),
+        )
+    } else if remaining > chrono::Duration::zero() {
+        Check::warn(
+            NAME,
+            format!("Token expires soon, at {expiration}"),
+            login_hint,
+        )
+    } else {
+        Check::fail(NAME, format!("Token expired at {expiration}"), login_hint)
+    }
+}
+
+// Any HTTP response shows the server can be reached; authentication is checked separately
+async fn check_connectivity(connection: &LoginConnection) -> Check {
+    const NAME: &str = "connectivity";
+    let client = http_config()
+        .apply(reqwest::Client::builder())
+        .danger_accept_invalid_certs(connection.danger_accept_invalid_certs)
+        .timeout(std::time::Duration::from_secs(10))
+        .build();
+    let response = match client {
+        Ok(client) => client.get(connection.url.clone()).send().await,
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("Cannot create HTTP client: {e}"),
+                "Check the TLS configuration of this machine",
+            )
+        }
+    };
+    match response {
+        Ok(_) => Check::pass(NAME, format!("Reached {}", connection.url)),
+        Err(e) => {
+            let hint = if e.is_timeout() {
+                "Check your network connection and any proxy settings"
+            } else if is_tls_error(&e) {
+                "If the instance uses a self-signed certificate, log in again with `--insecure`"
+            } else {
+                "Check your network connection and the instance URL"
+            };
+            Check::fail(
+                NAME,
+                format!(
+                    "Cannot reach {}: {:#}",
+                    connection.url,
+                    anyhow::Error::new(e)
+                ),
+                hint,
+            )
+        }
+    }
+}
+
+// Failed TLS handshakes, such as with a certificate that is not trusted
+fn is_tls_error(e: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        if cause.is::<native_tls::Error>() {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
+// An authenticated request made with this plugin's API version shows both
+// that the token is accepted and that the instance supports the version
+fn check_api_access(result: Result<()>, login_hint: &str) -> Vec<Check> {
+    const TOKEN: &str = "token validity";
+    const API: &str = "API compatibility";
+    let compatible = Check::pass(
+        API,
+        format!(
+            "Plugin version {} is supported by the instance",
+            env!("CARGO_PKG_VERSION")
+        ),
+    );
+    let Err(e) = result else {
+        return vec![Check::pass(TOKEN, "Token was accepted"), compatible];
+    };
+    let status = e
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<ResponseError>())
+        .map(|e| e.status.as_u16());
+    match status {
+        Some(401 | 403) => vec![Check::fail(TOKEN, "Token was rejected", login_hint)],
+        Some(400 | 404 | 406 | 410 | 415) => vec![
+            Check::pass(TOKEN, "Token was accepted"),
+            Check::fail(
+                API,
+                format!(
+                    "Plugin version {} is not supported by the instance: {e:#}",
+                    env!("CARGO_PKG_VERSION")
+                ),
+                "Run `spin plugins update && spin plugins upgrade cloud` to upgrade the plugin",
+            ),
+        ],
+        _ => vec![Check::fail(
+            TOKEN,
+            format!("Cannot verify token: {e:#}"),
+            "Try again later, or check https://status.fermyon.com",
+        )],
+    }
+}
+
+// `source` is resolved as deploy resolves it, so a directory stands for the
+// manifest inside it
+async fn check_manifest(source: &Path) -> Check {
+    const NAME: &str = "manifest";
+    let path = match resolve_manifest(source) {
+        Ok(path) if path.exists() => path,
+        _ if source == Path::new(DEFAULT_MANIFEST_FILE) => {
+            return Check::warn(
+                NAME,
+                format!("No {DEFAULT_MANIFEST_FILE} in the current directory"),
+                "Run commands such as `spin cloud deploy` from your application directory, or pass `--from`",
+            )
+        }
+        _ => {
+            return Check::warn(
+                NAME,
+                format!("No manifest found at {}", source.display()),
+                "Pass `--from` the manifest, or the directory containing it",
+            )
+        }
+    };
+    let path = path.as_path();
+    let loaded = match tempfile::tempdir() {
+        Ok(dir) => spin_loader::from_file(
+            path,
+            spin_loader::FilesMountStrategy::Copy(dir.path().to_owned()),
+            None,
+        )
+        .await
+        .map(|_| ()),
+        Err(e) => Err(e.into()),
+    };
+    match loaded {
+        Ok(()) => Check::pass(NAME, format!("{} can be deployed", path.display())),
+        Err(e) => Check::fail(
+            NAME,
+            format!("{} cannot be loaded: {e:#}", path.display()),
+            "Fix the manifest, then check it with `spin build`",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod doctor_tests {
+    use super::*;
+
+    fn connection(refresh_token: Option<&str>, expiration: Option<&str>) -> LoginConnection {
+        LoginConnection {
+            url: url::Url::parse("https://cloud.fermyon.com/").unwrap(),
+            danger_accept_invalid_certs: false,
+            token: "token".to_owned(),
+            refresh_token: refresh_token.map(|t| t.to_owned()),
+            expiration: expiration.map(|e| e.to_owned()),
+            token_storage: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_token_expiry() {
+        let now = DateTime::parse_from_rfc3339("2024-01-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let status = |refresh, expiration| {
+            check_token_expiry(&connection(refresh, expiration), "log in", now).status
+        };
+        assert_eq!(status(None, None), Status::Pass);
+        assert_eq!(status(None, Some("2024-01-01T12:00:00Z")), Status::Pass);
+        assert_eq!(status(None, Some("2024-01-01T10:01:00Z")), Status::Warn);
+        assert_eq!(status(None, Some("2024-01-01T09:00:00Z")), Status::Fail);
+        assert_eq!(
+            status(Some("refresh"), Some("2024-01-01T09:00:00Z")),
+            Status::Pass
+        );
+        assert_eq!(status(None, Some("yesterday")), Status::Fail);
+    }
+
+    #[test]
+    fn test_api_access_classifies_response_errors() {
+        let error = |status: u16| {
+            Err(anyhow::Error::new(ResponseError::new(
+                reqwest::StatusCode::from_u16(status).unwrap(),
+                "nope".to_owned(),
+            )))
+        };
+        let statuses = |result| {
+            check_api_access(result, "log in")
+                .into_iter()
+                .map(|c| (c.name, c.status))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            statuses(Ok(())),
+            vec![
+                ("token validity", Status::Pass),
+                ("API compatibility", Status::Pass)
+            ]
+        );
+        assert_eq!(statuses(error(401)), vec![("token validity", Status::Fail)]);
+        assert_eq!(
+            statuses(error(406)),
+            vec![
+                ("token validity", Status::Pass),
+                ("API compatibility", Status::Fail)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_manifest_directory_is_resolved_to_its_manifest() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let check = check_manifest(dir.path()).await;
+        assert_eq!(check.status, Status::Warn);
+
+        std::fs::write(dir.path().join(DEFAULT_MANIFEST_FILE), "not a manifest")?;
+        let check = check_manifest(dir.path()).await;
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.message.contains(DEFAULT_MANIFEST_FILE));
+        Ok(())
+    }
+}
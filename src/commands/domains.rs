@@ -0,0 +1,395 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use cloud::{
+    models::{DomainItem, DomainValidationStatus},
+    CloudClientInterface,
+};
+use comfy_table::presets::ASCII_BORDERS_ONLY_CONDENSED;
+use uuid::Uuid;
+
+use crate::commands::{app_picker::app_or_pick, client_and_app_id, CommonArgs};
+use crate::errors::CliError;
+use crate::output;
+
+#[derive(Parser, Debug)]
+#[clap(about = "Manage custom domains for apps deployed to Fermyon Cloud")]
+pub enum DomainsCommand {
+    /// Add a custom domain to an app
+    Add(AddCommand),
+    /// List the custom domains of an app
+    List(ListCommand),
+    /// Remove a custom domain from an app
+    #[clap(alias = "rm")]
+    Remove(RemoveCommand),
+    /// Print the DNS records a domain needs, and wait until it is verified
+    Verify(VerifyCommand),
+}
+
+#[derive(Parser, Debug)]
+pub struct AddCommand {
+    /// Name of Spin app
+    pub app: String,
+    /// The domain to serve the app from (e.g. "www.example.com")
+    #[clap(value_parser = clap::builder::ValueParser::new(parse_hostname))]
+    pub hostname: String,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct ListCommand {
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    pub app: Option<String>,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct RemoveCommand {
+    /// Name of Spin app
+    pub app: String,
+    /// The domain to remove
+    #[clap(value_parser = clap::builder::ValueParser::new(parse_hostname))]
+    pub hostname: String,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct VerifyCommand {
+    /// Name of Spin app
+    pub app: String,
+    /// The domain to verify
+    #[clap(value_parser = clap::builder::ValueParser::new(parse_hostname))]
+    pub hostname: String,
+    /// How long to wait for verification (e.g. "30m"). DNS changes can take
+    /// a while to propagate; verification continues in the background if
+    /// this runs out.
+    #[clap(
+        long = "timeout",
+        default_value = "10m",
+        value_parser = clap::builder::ValueParser::new(humantime::parse_duration)
+    )]
+    pub timeout: Duration,
+    /// How often to check whether the domain has been verified
+    #[clap(
+        long = "interval",
+        default_value = "10s",
+        value_parser = clap::builder::ValueParser::new(humantime::parse_duration)
+    )]
+    pub interval: Duration,
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+impl DomainsCommand {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Add(cmd) => {
+                let (client, app_id) =
+                    client_and_app_id(cmd.common.deployment_env_id.as_deref(), &cmd.app).await?;
+                cmd.run(client, app_id).await
+            }
+            Self::List(cmd) => {
+                let deployment_env_id = cmd.common.deployment_env_id.as_deref();
+                let app = app_or_pick(deployment_env_id, cmd.app.clone()).await?;
+                let (client, app_id) = client_and_app_id(deployment_env_id, &app).await?;
+                cmd.run(client, app_id, &app).await
+            }
+            Self::Remove(cmd) => {
+                let (client, app_id) =
+                    client_and_app_id(cmd.common.deployment_env_id.as_deref(), &cmd.app).await?;
+                cmd.run(client, app_id).await
+            }
+            Self::Verify(cmd) => {
+                let (client, app_id) =
+                    client_and_app_id(cmd.common.deployment_env_id.as_deref(), &cmd.app).await?;
+                cmd.run(client, app_id).await
+            }
+        }
+    }
+}
+
+impl AddCommand {
+    async fn run(self, client: impl CloudClientInterface, app_id: Uuid) -> Result<()> {
+        let domain = client
+            .add_domain(app_id, self.hostname.clone())
+            .await
+            .with_context(|| {
+                format!(
+                    "Problem adding domain '{}' to app '{}'",
+                    self.hostname, self.app
+                )
+            })?;
+        if output::is_json() {
+            return output::print_json(&domain_json(&domain));
+        }
+        println!("Domain \"{}\" added to app \"{}\"", domain.name, self.app);
+        print_dns_records(&domain);
+        println!(
+            "Once the records exist, run `spin cloud domains verify {} {}` to wait for verification.",
+            self.app, domain.name
+        );
+        Ok(())
+    }
+}
+
+impl ListCommand {
+    async fn run(self, client: impl CloudClientInterface, app_id: Uuid, app: &str) -> Result<()> {
+        let domains = client
+            .list_domains(app_id)
+            .await
+            .with_context(|| format!("Problem listing domains for app '{app}'"))?;
+        if output::is_json() {
+            return output::print_json(&domains.iter().map(domain_json).collect::<Vec<_>>());
+        }
+        if domains.is_empty() {
+            println!("App '{app}' has no custom domains");
+            return Ok(());
+        }
+        let mut table = comfy_table::Table::new();
+        table.load_preset(ASCII_BORDERS_ONLY_CONDENSED);
+        table.set_header(vec!["Domain", "Status"]);
+        table.add_rows(
+            domains
+                .iter()
+                .map(|d| [d.name.clone(), status_name(d.validation_status).to_owned()]),
+        );
+        println!("{table}");
+        Ok(())
+    }
+}
+
+impl RemoveCommand {
+    async fn run(self, client: impl CloudClientInterface, app_id: Uuid) -> Result<()> {
+        client
+            .remove_domain(app_id, self.hostname.clone())
+            .await
+            .with_context(|| {
+                format!(
+                    "Problem removing domain '{}' from app '{}'",
+                    self.hostname, self.app
+                )
+            })?;
+        output::success(
+            &format!(
+                "Domain \"{}\" removed from app \"{}\"",
+                self.hostname, self.app
+            ),
+            serde_json::json!({ "app": self.app, "domain": self.hostname }),
+        )
+    }
+}
+
+impl VerifyCommand {
+    async fn run(self, client: impl CloudClientInterface, app_id: Uuid) -> Result<()> {
+        let domain = find_domain(&client, app_id, &self.app, &self.hostname).await?;
+        if domain.validation_status != DomainValidationStatus::Ready && !output::is_json() {
+            print_dns_records(&domain);
+            output::progress(&format!(
+                "Waiting for \"{}\" to be verified...",
+                domain.name
+            ));
+        }
+        let domain = wait_for_verification(
+            &client,
+            app_id,
+            &self.app,
+            domain,
+            self.interval,
+            self.timeout,
+        )
+        .await?;
+        output::success(
+            &format!(
+                "Domain \"{}\" is verified and serving app \"{}\"",
+                domain.name, self.app
+            ),
+            domain_json(&domain),
+        )
+    }
+}
+
+async fn find_domain(
+    client: &impl CloudClientInterface,
+    app_id: Uuid,
+    app: &str,
+    hostname: &str,
+) -> Result<DomainItem> {
+    let domains = client
+        .list_domains(app_id)
+        .await
+        .with_context(|| format!("Problem listing domains for app '{app}'"))?;
+    match domains
+        .into_iter()
+        .find(|d| d.name.eq_ignore_ascii_case(hostname))
+    {
+        Some(domain) => Ok(domain),
+        None => bail!(
+            CliError::not_found(format!("App '{app}' has no domain '{hostname}'")).with_hint(
+                format!("Run `spin cloud domains add {app} {hostname}` to add it")
+            )
+        ),
+    }
+}
+
+async fn wait_for_verification(
+    client: &impl CloudClientInterface,
+    app_id: Uuid,
+    app: &str,
+    mut domain: DomainItem,
+    interval: Duration,
+    timeout: Duration,
+) -> Result<DomainItem> {
+    let start = std::time::Instant::now();
+    loop {
+        match domain.validation_status {
+            DomainValidationStatus::Ready => return Ok(domain),
+            DomainValidationStatus::Error => bail!(
+                "Verification of '{}' failed. Check the DNS records, then remove and add the domain again.",
+                domain.name
+            ),
+            DomainValidationStatus::InProgress | DomainValidationStatus::Provisioning => {}
+        }
+        if start.elapsed() >= timeout {
+            bail!(
+                "'{}' was not verified within {}. Verification continues in the background; run this command again to check on it.",
+                domain.name,
+                humantime::format_duration(timeout)
+            );
+        }
+        tokio::time::sleep(interval).await;
+        domain = find_domain(client, app_id, app, &domain.name).await?;
+    }
+}
+
+fn print_dns_records(domain: &DomainItem) {
+    if domain.dns_records.is_empty() {
+        return;
+    }
+    println!("Create these DNS records with your DNS provider:");
+    let mut table = comfy_table::Table::new();
+    table.load_preset(ASCII_BORDERS_ONLY_CONDENSED);
+    table.set_header(vec!["Type", "Name", "Value"]);
+    table.add_rows(
+        domain
+            .dns_records
+            .iter()
+            .map(|r| [r.record_type.as_str(), r.name.as_str(), r.value.as_str()]),
+    );
+    println!("{table}");
+}
+
+fn status_name(status: DomainValidationStatus) -> &'static str {
+    match status {
+        DomainValidationStatus::InProgress => "awaiting DNS records",
+        DomainValidationStatus::Provisioning => "provisioning certificate",
+        DomainValidationStatus::Ready => "ready",
+        DomainValidationStatus::Error => "error",
+    }
+}
+
+fn domain_json(domain: &DomainItem) -> serde_json::Value {
+    serde_json::json!({
+        "domain": domain.name,
+        "status": status_name(domain.validation_status),
+        "dnsRecords": domain.dns_records,
+    })
+}
+
+fn parse_hostname(hostname: &str) -> Result<String> {
+    let hostname = hostname.trim().trim_end_matches('.').to_ascii_lowercase();
+    let valid_label = |label: &str| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    if hostname.len() > 253
+        || hostname.split('.').count() < 2
+        || !hostname.split('.').all(valid_label)
+    {
+        bail!(CliError::validation(format!(
+            "'{hostname}' is not a valid domain name, such as \"www.example.com\""
+        )));
+    }
+    Ok(hostname)
+}
+
+#[cfg(test)]
+mod domains_tests {
+    use super::*;
+    use cloud::MockCloudClientInterface;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn domain(status: DomainValidationStatus) -> DomainItem {
+        DomainItem {
+            name: "www.example.com".to_owned(),
+            validation_status: status,
+            dns_records: vec![],
+        }
+    }
+
+    #[test]
+    fn test_parse_hostname() {
+        assert_eq!(
+            parse_hostname("WWW.Example.com.").unwrap(),
+            "www.example.com"
+        );
+        assert!(parse_hostname("localhost").is_err());
+        assert!(parse_hostname("-bad.example.com").is_err());
+        assert!(parse_hostname("https://example.com").is_err());
+    }
+
+    #[test]
+    fn test_remove_and_verify_normalize_the_hostname() {
+        let remove =
+            RemoveCommand::try_parse_from(["remove", "myapp", "WWW.Example.com."]).unwrap();
+        assert_eq!(remove.hostname, "www.example.com");
+        assert!(RemoveCommand::try_parse_from(["remove", "myapp", "example.com/x"]).is_err());
+        let verify = VerifyCommand::try_parse_from(["verify", "myapp", "Example.com"]).unwrap();
+        assert_eq!(verify.hostname, "example.com");
+    }
+
+    #[tokio::test]
+    async fn test_verify_polls_until_ready() -> Result<()> {
+        let calls = AtomicU32::new(0);
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_list_domains().returning(move |_| {
+            let status = match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => DomainValidationStatus::Provisioning,
+                _ => DomainValidationStatus::Ready,
+            };
+            Ok(vec![domain(status)])
+        });
+        let verified = wait_for_verification(
+            &mock,
+            Uuid::new_v4(),
+            "app",
+            domain(DomainValidationStatus::InProgress),
+            Duration::ZERO,
+            Duration::from_secs(60),
+        )
+        .await?;
+        assert_eq!(verified.validation_status, DomainValidationStatus::Ready);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_fails_on_error_status() {
+        let mock = MockCloudClientInterface::new();
+        let result = wait_for_verification(
+            &mock,
+            Uuid::new_v4(),
+            "app",
+            domain(DomainValidationStatus::Error),
+            Duration::ZERO,
+            Duration::from_secs(60),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
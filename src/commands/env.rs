@@ -0,0 +1,298 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+
+use crate::commands::config::{self, ConfigLock};
+use crate::commands::credentials;
+use crate::commands::login::{config_root_dir, LoginCommand};
+use crate::commands::DEFAULT_CLOUD_URL;
+use crate::errors::CliError;
+use crate::output;
+
+// The file in the config directory that records the environment selected with `env use`
+const ACTIVE_ENVIRONMENT_FILE: &str = "active-environment";
+// The name under which the default (unnamed) login is shown and selected
+const DEFAULT_ENVIRONMENT_NAME: &str = "(default)";
+
+/// Manage the Fermyon instances you are logged into
+#[derive(Parser, Debug)]
+#[clap(about = "Manage the Fermyon instances you are logged into")]
+pub enum EnvCommand {
+    /// Log into a Fermyon instance and save it under a name
+    Add(AddCommand),
+    /// List saved environments
+    List(ListCommand),
+    /// Use an environment for commands that are not given `--environment-name`
+    Use(UseCommand),
+    /// Log out of an environment and forget it
+    #[clap(alias = "rm")]
+    Remove(RemoveCommand),
+}
+
+#[derive(Parser, Debug)]
+pub struct AddCommand {
+    /// Name to save the environment under
+    #[clap(value_parser = clap::builder::ValueParser::new(parse_environment_name))]
+    name: String,
+
+    /// URL of the Fermyon instance
+    #[clap(long = "url", default_value = DEFAULT_CLOUD_URL)]
+    url: String,
+
+    /// Log in with a personal access token instead of GitHub
+    #[clap(long = "token")]
+    token: Option<String>,
+
+    /// Ignore server certificate errors
+    #[clap(short = 'k', long = "insecure", takes_value = false)]
+    insecure: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct ListCommand {}
+
+#[derive(Parser, Debug)]
+pub struct UseCommand {
+    /// Name of the environment, or "(default)" for the unnamed login
+    name: String,
+}
+
+#[derive(Parser, Debug)]
+pub struct RemoveCommand {
+    /// Name of the environment to remove
+    #[clap(value_parser = clap::builder::ValueParser::new(parse_environment_name))]
+    name: String,
+}
+
+impl EnvCommand {
+    pub async fn run(self) -> Result<()> {
+        let root = config_root_dir()?;
+        match self {
+            Self::Add(cmd) => cmd.run().await,
+            Self::List(_) => list(&root),
+            Self::Use(cmd) => cmd.run(&root),
+            Self::Remove(cmd) => cmd.run(&root),
+        }
+    }
+}
+
+impl AddCommand {
+    async fn run(self) -> Result<()> {
+        let mut args = vec![
+            "login".to_owned(),
+            "--url".to_owned(),
+            self.url,
+            "--environment-name".to_owned(),
+            self.name.clone(),
+        ];
+        if let Some(token) = self.token {
+            args.extend(["--token".to_owned(), token]);
+        }
+        if self.insecure {
+            args.push("--insecure".to_owned());
+        }
+        LoginCommand::parse_from(args).run().await?;
+        output::success(
+            &format!(
+                "Environment '{}' added. Run `spin cloud env use {}` to make it the default.",
+                self.name, self.name
+            ),
+            serde_json::json!({ "environment": self.name }),
+        )
+    }
+}
+
+impl UseCommand {
+    fn run(self, root: &Path) -> Result<()> {
+        let active_file = root.join(ACTIVE_ENVIRONMENT_FILE);
+        if self.name == DEFAULT_ENVIRONMENT_NAME {
+            remove_if_exists(&active_file)?;
+            return output::success(
+                "Using the default environment",
+                serde_json::json!({ "environment": DEFAULT_ENVIRONMENT_NAME }),
+            );
+        }
+        let name = parse_environment_name(&self.name)?;
+        if !environment_file(root, &name).is_file() {
+            bail!(
+                CliError::not_found(format!("No environment named '{name}'"))
+                    .with_hint(format!("Run `spin cloud env add {name}` to add it"))
+            );
+        }
+        config::write_atomic(&active_file, &name)
+            .with_context(|| format!("Failed to write {}", active_file.display()))?;
+        output::success(
+            &format!("Using environment '{name}'"),
+            serde_json::json!({ "environment": name }),
+        )
+    }
+}
+
+impl RemoveCommand {
+    fn run(self, root: &Path) -> Result<()> {
+        let path = environment_file(root, &self.name);
+        if !path.is_file() {
+            bail!(CliError::not_found(format!(
+                "No environment named '{}'",
+                self.name
+            )));
+        }
+        let _lock = ConfigLock::acquire_in(root)?;
+        credentials::remove(&path, Some(&self.name))?;
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+        let message = if read_active_environment(root)?.as_deref() == Some(self.name.as_str()) {
+            remove_if_exists(&root.join(ACTIVE_ENVIRONMENT_FILE))?;
+            format!(
+                "Environment '{}' removed. Commands will now use the default environment.",
+                self.name
+            )
+        } else {
+            format!("Environment '{}' removed", self.name)
+        };
+        output::success(&message, serde_json::json!({ "environment": self.name }))
+    }
+}
+
+fn list(root: &Path) -> Result<()> {
+    let active = read_active_environment(root)?;
+    let names = environment_names(root)?;
+    if output::is_json() {
+        let active = active.unwrap_or_else(|| DEFAULT_ENVIRONMENT_NAME.to_owned());
+        return output::print_json(&serde_json::json!({
+            "active": names.contains(&active).then_some(active),
+            "environments": names,
+        }));
+    }
+    if names.is_empty() {
+        println!("No environments found. Run `spin cloud login` to log in.");
+        return Ok(());
+    }
+    for name in names {
+        let is_active = match &active {
+            Some(active) => active == &name,
+            None => name == DEFAULT_ENVIRONMENT_NAME,
+        };
+        let marker = if is_active { "*" } else { " " };
+        println!("{marker} {name}");
+    }
+    Ok(())
+}
+
+/// Resolves the environment to use when none was given on the command line,
+/// falling back to the one selected with `spin cloud env use`.
+pub(crate) fn resolve_environment(deployment_env_id: Option<&str>) -> Result<Option<String>> {
+    match deployment_env_id {
+        Some(id) => Ok(Some(id.to_owned())),
+        None => read_active_environment(&config_root_dir()?),
+    }
+}
+
+fn read_active_environment(root: &Path) -> Result<Option<String>> {
+    let path = root.join(ACTIVE_ENVIRONMENT_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(name) if name.trim().is_empty() => Ok(None),
+        Ok(name) => Ok(Some(name.trim().to_owned())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn environment_names(root: &Path) -> Result<Vec<String>> {
+    let entries = match std::fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", root.display()));
+        }
+    };
+    let mut names = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .map(|stem| {
+            if stem == "config" {
+                DEFAULT_ENVIRONMENT_NAME.to_owned()
+            } else {
+                stem
+            }
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    Ok(names)
+}
+
+fn environment_file(root: &Path, name: &str) -> PathBuf {
+    root.join(format!("{name}.json"))
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Failed to remove {}", path.display())),
+    }
+}
+
+// Environment names become file names in the config directory
+fn parse_environment_name(name: &str) -> Result<String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && !name.starts_with('.');
+    if !valid {
+        bail!("environment names may only contain letters, digits, '-', '_' and '.'");
+    }
+    if name == "config" {
+        bail!("'config' is reserved for the default environment");
+    }
+    Ok(name.to_owned())
+}
+
+#[cfg(test)]
+mod env_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_environment_name() {
+        assert_eq!(parse_environment_name("staging").unwrap(), "staging");
+        assert!(parse_environment_name("config").is_err());
+        assert!(parse_environment_name("../etc").is_err());
+        assert!(parse_environment_name("").is_err());
+    }
+
+    #[test]
+    fn test_use_and_remove_track_active_environment() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        std::fs::write(root.path().join("config.json"), "{}")?;
+        std::fs::write(root.path().join("staging.json"), "{}")?;
+        assert_eq!(
+            environment_names(root.path())?,
+            vec!["(default)", "staging"]
+        );
+
+        UseCommand {
+            name: "staging".to_owned(),
+        }
+        .run(root.path())?;
+        assert_eq!(
+            read_active_environment(root.path())?.as_deref(),
+            Some("staging")
+        );
+
+        RemoveCommand {
+            name: "staging".to_owned(),
+        }
+        .run(root.path())?;
+        assert_eq!(read_active_environment(root.path())?, None);
+        assert!(UseCommand {
+            name: "staging".to_owned()
+        }
+        .run(root.path())
+        .is_err());
+        Ok(())
+    }
+}
@@ -1,12 +1,16 @@
 use crate::commands::links_output::{
-    print_json, print_table, prompt_delete_resource, ListFormat, ResourceGroupBy, ResourceLinks,
-    ResourceType,
+    print_json, print_table, prompt_delete_resource, ResourceGroupBy, ResourceLinks, ResourceType,
 };
 use crate::commands::links_target::ResourceTarget;
 use crate::commands::{create_cloud_client, disallow_empty, CommonArgs};
+use crate::errors::CliError;
+use crate::output::{self, OutputFormat};
+use std::io::Write;
+
 use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use clap::{Parser, ValueEnum};
-use cloud::CloudClientInterface;
+use cloud::{CloudClientExt, CloudClientInterface};
 use cloud_openapi::models::KeyValueStoreItem;
 use spin_common::arg_parser::parse_kv;
 
@@ -16,13 +20,20 @@ pub enum KeyValueCommand {
     /// Create a new key value store
     Create(CreateCommand),
     /// Delete a key value store
+    #[clap(alias = "destroy")]
     Delete(DeleteCommand),
     /// List key value stores
     List(ListCommand),
     /// Set a key value pair in a store
     Set(SetCommand),
+    /// Get the value of a key in a store
+    Get(GetCommand),
+    /// Delete keys from a store
+    Unset(UnsetCommand),
     /// Rename a key value store. All existing links will automatically link to the store's new name.
     Rename(RenameCommand),
+    /// Copy the keys of one store into another, which may be in another environment
+    Copy(CopyCommand),
 }
 
 #[derive(Parser, Debug)]
@@ -58,9 +69,6 @@ pub struct ListCommand {
     /// Grouping strategy of tabular list [default: app]
     #[clap(value_enum, short = 'g', long = "group-by")]
     group_by: Option<GroupBy>,
-    /// Format of list
-    #[clap(value_enum, long = "format", default_value = "table")]
-    format: ListFormat,
     #[clap(flatten)]
     common: CommonArgs,
 }
@@ -100,6 +108,57 @@ pub struct SetCommand {
     #[clap(parse(try_from_str = parse_kv))]
     pub key_values: Vec<(String, String)>,
 
+    /// Treat values as base64 and store the decoded bytes
+    #[clap(long = "base64", takes_value = false)]
+    pub base64: bool,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct GetCommand {
+    /// The name of the key value store
+    #[clap(name = "STORE", short = 's', long = "store", value_parser = clap::builder::ValueParser::new(disallow_empty), required_unless_present_all = ["LABEL", "APP"], conflicts_with_all = &["LABEL", "APP"])]
+    pub store: Option<String>,
+
+    /// Label of the key value store to read from
+    #[clap(name = "LABEL", short = 'l', long = "label", value_parser = clap::builder::ValueParser::new(disallow_empty), requires = "APP", required_unless_present = "STORE")]
+    pub label: Option<String>,
+
+    /// App to which label relates
+    #[clap(name = "APP", short = 'a', long = "app", value_parser = clap::builder::ValueParser::new(disallow_empty), requires = "LABEL", required_unless_present = "STORE")]
+    pub app: Option<String>,
+
+    /// The key to read
+    pub key: String,
+
+    /// Print the value base64 encoded, for values that are not text
+    #[clap(long = "base64", takes_value = false)]
+    pub base64: bool,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+#[derive(Parser, Debug)]
+pub struct UnsetCommand {
+    /// The name of the key value store
+    #[clap(name = "STORE", short = 's', long = "store", value_parser = clap::builder::ValueParser::new(disallow_empty), required_unless_present_all = ["LABEL", "APP"], conflicts_with_all = &["LABEL", "APP"])]
+    pub store: Option<String>,
+
+    /// Label of the key value store to delete keys from
+    #[clap(name = "LABEL", short = 'l', long = "label", value_parser = clap::builder::ValueParser::new(disallow_empty), requires = "APP", required_unless_present = "STORE")]
+    pub label: Option<String>,
+
+    /// App to which label relates
+    #[clap(name = "APP", short = 'a', long = "app", value_parser = clap::builder::ValueParser::new(disallow_empty), requires = "LABEL", required_unless_present = "STORE")]
+    pub app: Option<String>,
+
+    /// A key to delete from the store. Can be used multiple times.
+    #[clap(required = true)]
+    pub keys: Vec<String>,
+
     #[clap(flatten)]
     common: CommonArgs,
 }
@@ -116,6 +175,34 @@ pub struct RenameCommand {
     common: CommonArgs,
 }
 
+/// Keys whose value already matches the destination are skipped, and keys
+/// only in the destination are left alone.
+#[derive(Parser, Debug)]
+pub struct CopyCommand {
+    /// The name of the key value store to copy from
+    #[clap(long = "from-store", value_parser = clap::builder::ValueParser::new(disallow_empty))]
+    pub from_store: String,
+
+    /// The name of the key value store to copy into
+    #[clap(long = "to-store", value_parser = clap::builder::ValueParser::new(disallow_empty))]
+    pub to_store: String,
+
+    /// The environment of the store to copy from. Defaults to the current environment.
+    #[clap(long = "from-env")]
+    pub from_env: Option<String>,
+
+    /// The environment of the store to copy into. Defaults to the current environment.
+    #[clap(long = "to-env")]
+    pub to_env: Option<String>,
+
+    /// Show which keys would be created or updated without writing them
+    #[clap(long = "dry-run", takes_value = false)]
+    pub dry_run: bool,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
 impl KeyValueCommand {
     pub async fn run(&self) -> Result<()> {
         match self {
@@ -135,10 +222,23 @@ impl KeyValueCommand {
                 let client = create_cloud_client(cmd.common.deployment_env_id.as_deref()).await?;
                 cmd.run(client).await
             }
+            KeyValueCommand::Get(cmd) => {
+                let client = create_cloud_client(cmd.common.deployment_env_id.as_deref()).await?;
+                cmd.run(client).await
+            }
+            KeyValueCommand::Unset(cmd) => {
+                let client = create_cloud_client(cmd.common.deployment_env_id.as_deref()).await?;
+                cmd.run(client).await
+            }
             KeyValueCommand::Rename(cmd) => {
                 let client = create_cloud_client(cmd.common.deployment_env_id.as_deref()).await?;
                 cmd.run(client).await
             }
+            KeyValueCommand::Copy(cmd) => {
+                let from = create_cloud_client(cmd.from_env().as_deref()).await?;
+                let to = create_cloud_client(cmd.to_env().as_deref()).await?;
+                cmd.run(from, to).await
+            }
         }
     }
 }
@@ -184,7 +284,7 @@ impl DeleteCommand {
 
 impl ListCommand {
     pub async fn run(&self, client: impl CloudClientInterface) -> Result<()> {
-        if let (ListFormat::Json, Some(_)) = (&self.format, self.group_by) {
+        if let (OutputFormat::Json, Some(_)) = (output::format(), self.group_by) {
             bail!("Grouping is not supported with JSON format output")
         }
         let key_value_stores = client
@@ -192,7 +292,7 @@ impl ListCommand {
             .await
             .with_context(|| "Error listing key value stores")?;
 
-        if key_value_stores.is_empty() {
+        if key_value_stores.is_empty() && !output::is_json() {
             println!("No key value stores found");
             return Ok(());
         }
@@ -200,13 +300,13 @@ impl ListCommand {
             .into_iter()
             .map(|kv| ResourceLinks::new(kv.name, kv.links))
             .collect();
-        match self.format {
-            ListFormat::Json => print_json(
+        match output::format() {
+            OutputFormat::Json => print_json(
                 resource_links,
                 self.app.as_deref(),
                 ResourceType::KeyValueStore,
             ),
-            ListFormat::Table => print_table(
+            OutputFormat::Table => print_table(
                 resource_links,
                 self.app.as_deref(),
                 self.group_by.map(Into::into),
@@ -218,15 +318,18 @@ impl ListCommand {
 
 impl SetCommand {
     pub async fn run(&self, client: impl CloudClientInterface) -> Result<()> {
-        let target = ResourceTarget::from_inputs(&self.store, &self.label, &self.app)?;
-        let stores = client
-            .get_key_value_stores(None)
-            .await
-            .context("Problem fetching key value stores")?;
-        let store = target
-            .find_in(to_resource_links(stores), ResourceType::KeyValueStore)?
-            .name;
+        let store = find_store(&client, &self.store, &self.label, &self.app).await?;
         for (key, value) in &self.key_values {
+            if self.base64 {
+                let bytes = BASE64
+                    .decode(value)
+                    .with_context(|| format!("Value for key '{key}' is not valid base64"))?;
+                client
+                    .put_key_value(&store, key, bytes)
+                    .await
+                    .with_context(|| format!("Error setting key '{key}' in store '{}'", store))?;
+                continue;
+            }
             client
                 .add_key_value_pair(None, store.clone(), key.clone(), value.clone())
                 .await
@@ -241,6 +344,56 @@ impl SetCommand {
     }
 }
 
+impl GetCommand {
+    pub async fn run(&self, client: impl CloudClientInterface) -> Result<()> {
+        let store = find_store(&client, &self.store, &self.label, &self.app).await?;
+        let value = client
+            .get_key_value(&store, &self.key)
+            .await
+            .with_context(|| format!("Error reading key '{}' from store '{store}'", self.key))?
+            .with_context(|| {
+                CliError::not_found(format!("Key '{}' not found in store '{store}'", self.key))
+            })?;
+        let mut stdout = std::io::stdout();
+        if self.base64 {
+            writeln!(stdout, "{}", BASE64.encode(value))?;
+        } else {
+            stdout.write_all(&value)?;
+        }
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+impl UnsetCommand {
+    pub async fn run(&self, client: impl CloudClientInterface) -> Result<()> {
+        let store = find_store(&client, &self.store, &self.label, &self.app).await?;
+        for key in &self.keys {
+            client
+                .delete_key_value(&store, key)
+                .await
+                .with_context(|| format!("Error deleting key '{key}' from store '{store}'"))?;
+        }
+        Ok(())
+    }
+}
+
+async fn find_store(
+    client: &impl CloudClientInterface,
+    store: &Option<String>,
+    label: &Option<String>,
+    app: &Option<String>,
+) -> Result<String> {
+    let target = ResourceTarget::from_inputs(store, label, app)?;
+    let stores = client
+        .get_key_value_stores(None)
+        .await
+        .context("Problem fetching key value stores")?;
+    Ok(target
+        .find_in(to_resource_links(stores), ResourceType::KeyValueStore)?
+        .name)
+}
+
 impl RenameCommand {
     pub async fn run(&self, client: impl CloudClientInterface) -> Result<()> {
         let list = client
@@ -262,6 +415,129 @@ impl RenameCommand {
     }
 }
 
+// How many keys are read and written at a time, which bounds how many values
+// are held in memory during a copy
+const COPY_CHUNK_SIZE: usize = 100;
+
+#[derive(Debug, Default, PartialEq)]
+struct CopySummary {
+    created: Vec<String>,
+    updated: Vec<String>,
+    skipped: Vec<String>,
+}
+
+impl CopyCommand {
+    fn from_env(&self) -> Option<String> {
+        self.from_env
+            .clone()
+            .or_else(|| self.common.deployment_env_id.clone())
+    }
+
+    fn to_env(&self) -> Option<String> {
+        self.to_env
+            .clone()
+            .or_else(|| self.common.deployment_env_id.clone())
+    }
+
+    pub async fn run(
+        &self,
+        from: impl CloudClientInterface,
+        to: impl CloudClientInterface,
+    ) -> Result<()> {
+        if self.from_store == self.to_store && self.from_env() == self.to_env() {
+            bail!(CliError::validation(
+                "The source and destination are the same store"
+            ));
+        }
+        ensure_store_exists(&from, &self.from_store).await?;
+        ensure_store_exists(&to, &self.to_store).await?;
+        let summary = self.copy(&from, &to).await?;
+
+        if output::is_json() {
+            return output::print_json(&serde_json::json!({
+                "created": summary.created,
+                "updated": summary.updated,
+                "skipped": summary.skipped,
+                "dryRun": self.dry_run,
+            }));
+        }
+        for key in &summary.created {
+            println!("+ {key}");
+        }
+        for key in &summary.updated {
+            println!("~ {key}");
+        }
+        println!(
+            "{} {} key(s): {} created, {} updated, {} skipped",
+            if self.dry_run { "Would copy" } else { "Copied" },
+            summary.created.len() + summary.updated.len(),
+            summary.created.len(),
+            summary.updated.len(),
+            summary.skipped.len()
+        );
+        Ok(())
+    }
+
+    async fn copy(
+        &self,
+        from: &impl CloudClientInterface,
+        to: &impl CloudClientInterface,
+    ) -> Result<CopySummary> {
+        let keys = from
+            .list_keys(&self.from_store)
+            .await
+            .with_context(|| format!("Error listing keys in store '{}'", self.from_store))?;
+        let mut summary = CopySummary::default();
+        for chunk in keys.chunks(COPY_CHUNK_SIZE) {
+            let values = from
+                .get_key_values(&self.from_store, chunk)
+                .await
+                .with_context(|| format!("Error reading from store '{}'", self.from_store))?;
+            let existing = to
+                .get_key_values(&self.to_store, chunk)
+                .await
+                .with_context(|| format!("Error reading from store '{}'", self.to_store))?;
+            let mut changed = vec![];
+            for ((key, value), existing) in chunk.iter().zip(values).zip(existing) {
+                match (value, existing) {
+                    // The key was deleted after it was listed
+                    (None, _) => summary.skipped.push(key.clone()),
+                    (Some(value), Some(existing)) if value == existing => {
+                        summary.skipped.push(key.clone())
+                    }
+                    (Some(value), Some(_)) => {
+                        summary.updated.push(key.clone());
+                        changed.push((key.clone(), value));
+                    }
+                    (Some(value), None) => {
+                        summary.created.push(key.clone());
+                        changed.push((key.clone(), value));
+                    }
+                }
+            }
+            if !self.dry_run {
+                to.put_key_values(&self.to_store, &changed)
+                    .await
+                    .with_context(|| format!("Error writing to store '{}'", self.to_store))?;
+            }
+        }
+        Ok(summary)
+    }
+}
+
+async fn ensure_store_exists(client: &impl CloudClientInterface, name: &str) -> Result<()> {
+    let stores = client
+        .get_key_value_stores(None)
+        .await
+        .context("Problem fetching key value stores")?;
+    if !stores.iter().any(|kv| kv.name == name) {
+        bail!(CliError::not_found(format!(
+            "No key value store found with name \"{name}\""
+        )));
+    }
+    Ok(())
+}
+
 fn to_resource_links(stores: Vec<KeyValueStoreItem>) -> Vec<ResourceLinks> {
     stores
         .into_iter()
@@ -351,4 +627,100 @@ mod key_value_tests {
 
         command.run(mock).await
     }
+
+    #[tokio::test]
+    async fn test_get_if_key_does_not_exist_then_error() -> Result<()> {
+        let command = GetCommand {
+            store: Some("kv1".to_string()),
+            label: None,
+            app: None,
+            key: "missing".to_string(),
+            base64: false,
+            common: Default::default(),
+        };
+
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_get_key_value_stores()
+            .returning(move |_| Ok(vec![KeyValueStoreItem::new("kv1".to_string(), vec![])]));
+        mock.expect_get_key_value()
+            .withf(|store, key| store == "kv1" && key == "missing")
+            .returning(|_, _| Ok(None));
+
+        let result = command.run(mock).await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Key 'missing' not found in store 'kv1'"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_with_base64_stores_decoded_bytes() -> Result<()> {
+        let command = SetCommand {
+            store: Some("kv1".to_string()),
+            label: None,
+            app: None,
+            key_values: vec![("blob".to_string(), "AAH/".to_string())],
+            base64: true,
+            common: Default::default(),
+        };
+
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_get_key_value_stores()
+            .returning(move |_| Ok(vec![KeyValueStoreItem::new("kv1".to_string(), vec![])]));
+        mock.expect_put_key_value()
+            .withf(|store, key, value| store == "kv1" && key == "blob" && value == &[0, 1, 255])
+            .returning(|_, _, _| Ok(()));
+
+        command.run(mock).await
+    }
+
+    #[tokio::test]
+    async fn test_copy_creates_and_updates_only_changed_keys() -> Result<()> {
+        let command = CopyCommand {
+            from_store: "staging".to_string(),
+            to_store: "prod".to_string(),
+            from_env: None,
+            to_env: None,
+            dry_run: false,
+            common: Default::default(),
+        };
+
+        let mut from = MockCloudClientInterface::new();
+        from.expect_list_keys()
+            .withf(|store| store == "staging")
+            .returning(|_| {
+                Ok(vec![
+                    "new".to_string(),
+                    "same".to_string(),
+                    "changed".to_string(),
+                ])
+            });
+        from.expect_get_key_value()
+            .withf(|store, _| store == "staging")
+            .returning(|_, key| Ok(Some(key.as_bytes().to_vec())));
+        let mut to = MockCloudClientInterface::new();
+        to.expect_get_key_value()
+            .withf(|store, _| store == "prod")
+            .returning(|_, key| match key {
+                "same" => Ok(Some(b"same".to_vec())),
+                "changed" => Ok(Some(b"old".to_vec())),
+                _ => Ok(None),
+            });
+        to.expect_put_key_value()
+            .withf(|store, key, value| store == "prod" && key != "same" && value == key.as_bytes())
+            .times(2)
+            .returning(|_, _, _| Ok(()));
+
+        let summary = command.copy(&from, &to).await?;
+        assert_eq!(
+            summary,
+            CopySummary {
+                created: vec!["new".to_string()],
+                updated: vec!["changed".to_string()],
+                skipped: vec!["same".to_string()],
+            }
+        );
+        Ok(())
+    }
 }
@@ -1,5 +1,6 @@
 use crate::commands::links_output::{capitalize, find_resource_link, ResourceLinks, ResourceType};
 use crate::commands::{client_and_app_id, CommonArgs};
+use crate::errors::CliError;
 use anyhow::{Context, Result};
 use clap::Parser;
 use cloud::CloudClientInterface;
@@ -21,7 +22,11 @@ pub struct SqliteLinkCommand {
     #[clap(flatten)]
     common: CommonArgs,
     /// The name by which the application will refer to the database
-    label: String,
+    #[clap(name = "LABEL", required_unless_present = "label")]
+    label: Option<String>,
+    /// The label, given as a flag rather than positionally
+    #[clap(name = "label", long = "label", conflicts_with = "LABEL")]
+    label_flag: Option<String>,
     #[clap(short = 'a', long = "app")]
     /// The app that will be using the database
     app: String,
@@ -35,7 +40,11 @@ pub struct KeyValueStoreLinkCommand {
     #[clap(flatten)]
     common: CommonArgs,
     /// The name by which the application will refer to the key value store
-    label: String,
+    #[clap(name = "LABEL", required_unless_present = "label")]
+    label: Option<String>,
+    /// The label, given as a flag rather than positionally
+    #[clap(name = "label", long = "label", conflicts_with = "LABEL")]
+    label_flag: Option<String>,
     #[clap(short = 'a', long = "app")]
     /// The app that will be using the key value store
     app: String,
@@ -62,11 +71,15 @@ impl LinkCommand {
 }
 
 impl SqliteLinkCommand {
+    fn label(&self) -> Result<&str> {
+        label(&self.label, &self.label_flag)
+    }
+
     async fn link(self, client: impl CloudClientInterface, app_id: Uuid) -> Result<()> {
         let stores = client
             .get_databases(None)
             .await
-            .context("could not fetch key value stores")?;
+            .context("could not fetch databases")?;
         let resources = stores
             .into_iter()
             .map(|s| ResourceLinks::new(s.name, s.links))
@@ -75,7 +88,7 @@ impl SqliteLinkCommand {
             client,
             &self.database,
             &self.app,
-            &self.label,
+            self.label()?,
             app_id,
             resources,
             ResourceType::Database,
@@ -85,6 +98,10 @@ impl SqliteLinkCommand {
 }
 
 impl KeyValueStoreLinkCommand {
+    fn label(&self) -> Result<&str> {
+        label(&self.label, &self.label_flag)
+    }
+
     async fn link(self, client: impl CloudClientInterface, app_id: Uuid) -> Result<()> {
         let stores = client
             .get_key_value_stores(None)
@@ -98,7 +115,7 @@ impl KeyValueStoreLinkCommand {
             client,
             &self.store,
             &self.app,
-            &self.label,
+            self.label()?,
             app_id,
             resources,
             ResourceType::KeyValueStore,
@@ -118,11 +135,11 @@ async fn link(
 ) -> Result<()> {
     let exists = resources.iter().any(|s| s.name == resource_name);
     if !exists {
-        anyhow::bail!(
+        anyhow::bail!(CliError::not_found(format!(
             r#"{} "{}" does not exist"#,
             capitalize(&resource_type.to_string()),
             resource_name
-        );
+        )));
     }
     let stores_for_app = resources
         .into_iter()
@@ -259,13 +276,21 @@ pub struct SqliteUnlinkCommand {
     #[clap(flatten)]
     common: CommonArgs,
     /// The name by which the application refers to the database
-    label: String,
+    #[clap(name = "LABEL", required_unless_present = "label")]
+    label: Option<String>,
+    /// The label, given as a flag rather than positionally
+    #[clap(name = "label", long = "label", conflicts_with = "LABEL")]
+    label_flag: Option<String>,
     #[clap(short = 'a', long = "app")]
     /// The app that will be using the database
     app: String,
 }
 
 impl SqliteUnlinkCommand {
+    fn label(&self) -> Result<&str> {
+        label(&self.label, &self.label_flag)
+    }
+
     async fn unlink(self, client: impl CloudClientInterface, app_id: Uuid) -> Result<()> {
         let databases = client
             .get_databases(Some(app_id))
@@ -278,7 +303,7 @@ impl SqliteUnlinkCommand {
         unlink(
             client,
             &self.app,
-            &self.label,
+            self.label()?,
             resources,
             ResourceType::Database,
         )
@@ -291,13 +316,21 @@ pub struct KeyValueStoreUnlinkCommand {
     #[clap(flatten)]
     common: CommonArgs,
     /// The name by which the application refers to the key value store
-    label: String,
+    #[clap(name = "LABEL", required_unless_present = "label")]
+    label: Option<String>,
+    /// The label, given as a flag rather than positionally
+    #[clap(name = "label", long = "label", conflicts_with = "LABEL")]
+    label_flag: Option<String>,
     #[clap(short = 'a', long = "app")]
     /// The app that will be using the key value store
     app: String,
 }
 
 impl KeyValueStoreUnlinkCommand {
+    fn label(&self) -> Result<&str> {
+        label(&self.label, &self.label_flag)
+    }
+
     async fn unlink(self, client: impl CloudClientInterface, app_id: Uuid) -> Result<()> {
         let stores = client
             .get_key_value_stores(Some(app_id))
@@ -310,7 +343,7 @@ impl KeyValueStoreUnlinkCommand {
         unlink(
             client,
             &self.app,
-            &self.label,
+            self.label()?,
             resources,
             ResourceType::KeyValueStore,
         )
@@ -335,7 +368,9 @@ pub async fn unlink(
                 })
                 .map(|l| (d.name, l))
         })
-        .with_context(|| format!("no database was linked to app '{app}' with label '{label}'"))?;
+        .with_context(|| {
+            format!("no {resource_type} was linked to app '{app}' with label '{label}'")
+        })?;
     match resource_type {
         ResourceType::Database => {
             client
@@ -355,6 +390,14 @@ pub async fn unlink(
     Ok(())
 }
 
+// The label may be passed positionally or with `--label`; clap ensures exactly one is given
+fn label<'a>(positional: &'a Option<String>, flag: &'a Option<String>) -> Result<&'a str> {
+    positional
+        .as_deref()
+        .or(flag.as_deref())
+        .context("a label is required")
+}
+
 /// A Link structure to ease grouping a resource with it's app and label
 #[derive(Clone, PartialEq)]
 pub struct Link {
@@ -388,7 +431,8 @@ mod link_tests {
         let command = SqliteLinkCommand {
             app: "app".to_string(),
             database: "does-not-exist".to_string(),
-            label: "label".to_string(),
+            label: Some("label".to_string()),
+            label_flag: None,
             common: Default::default(),
         };
         let app_id = Uuid::new_v4();
@@ -413,7 +457,8 @@ mod link_tests {
         let command = SqliteLinkCommand {
             app: "app".to_string(),
             database: "db1".to_string(),
-            label: "label".to_string(),
+            label: Some("label".to_string()),
+            label_flag: None,
             common: Default::default(),
         };
         let app_id = Uuid::new_v4();
@@ -423,7 +468,7 @@ mod link_tests {
         ];
         let expected_resource_label = ResourceLabel {
             app_id,
-            label: command.label.clone(),
+            label: command.label()?.to_owned(),
             app_name: None,
         };
 
@@ -441,7 +486,8 @@ mod link_tests {
         let command = SqliteLinkCommand {
             app: "app".to_string(),
             database: "db1".to_string(),
-            label: "label".to_string(),
+            label: Some("label".to_string()),
+            label_flag: None,
             common: Default::default(),
         };
         let app_id = Uuid::new_v4();
@@ -450,7 +496,7 @@ mod link_tests {
                 "db1".to_string(),
                 vec![ResourceLabel {
                     app_id,
-                    label: command.label.clone(),
+                    label: command.label()?.to_owned(),
                     app_name: Some("app".to_string()),
                 }],
             ),
@@ -473,7 +519,8 @@ mod link_tests {
         let command = KeyValueStoreLinkCommand {
             app: "app".to_string(),
             store: "does-not-exist".to_string(),
-            label: "label".to_string(),
+            label: Some("label".to_string()),
+            label_flag: None,
             common: Default::default(),
         };
         let app_id = Uuid::new_v4();
@@ -499,7 +546,8 @@ mod link_tests {
         let command = KeyValueStoreLinkCommand {
             app: "app".to_string(),
             store: "kv1".to_string(),
-            label: "label".to_string(),
+            label: Some("label".to_string()),
+            label_flag: None,
             common: Default::default(),
         };
         let app_id = Uuid::new_v4();
@@ -509,7 +557,7 @@ mod link_tests {
         ];
         let expected_resource_label = ResourceLabel {
             app_id,
-            label: command.label.clone(),
+            label: command.label()?.to_owned(),
             app_name: None,
         };
 
@@ -527,7 +575,8 @@ mod link_tests {
     async fn test_key_value_store_unlink_error_store_does_not_exist() -> Result<()> {
         let command = KeyValueStoreUnlinkCommand {
             app: "app".to_string(),
-            label: "label".to_string(),
+            label: Some("label".to_string()),
+            label_flag: None,
             common: Default::default(),
         };
         let app_id = Uuid::new_v4();
@@ -550,7 +599,7 @@ mod link_tests {
         let result = command.unlink(mock, app_id).await;
         assert_eq!(
             result.unwrap_err().to_string(),
-            "no database was linked to app 'app' with label 'label'"
+            "no key value store was linked to app 'app' with label 'label'"
         );
         Ok(())
     }
@@ -559,7 +608,8 @@ mod link_tests {
     async fn test_key_value_store_unlink_succeeds_when_link_exists() -> Result<()> {
         let command = KeyValueStoreUnlinkCommand {
             app: "app".to_string(),
-            label: "label".to_string(),
+            label: Some("label".to_string()),
+            label_flag: None,
             common: Default::default(),
         };
         let app_id = Uuid::new_v4();
@@ -568,7 +618,7 @@ mod link_tests {
                 "kv1".to_string(),
                 vec![ResourceLabel {
                     app_id,
-                    label: command.label.clone(),
+                    label: command.label()?.to_owned(),
                     app_name: Some("app".to_string()),
                 }],
             ),
@@ -584,6 +634,32 @@ mod link_tests {
         command.unlink(mock, app_id).await
     }
 
+    #[tokio::test]
+    async fn test_sqlite_link_accepts_label_flag() -> Result<()> {
+        let command = SqliteLinkCommand {
+            app: "app".to_string(),
+            database: "db1".to_string(),
+            label: None,
+            label_flag: Some("default".to_string()),
+            common: Default::default(),
+        };
+        let app_id = Uuid::new_v4();
+        let dbs = vec![Database::new("db1".to_string(), vec![])];
+        let expected_resource_label = ResourceLabel {
+            app_id,
+            label: "default".to_string(),
+            app_name: None,
+        };
+
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_get_databases().return_once(move |_| Ok(dbs));
+        mock.expect_create_database_link()
+            .withf(move |db, rl| db == "db1" && rl == &expected_resource_label)
+            .returning(|_, _| Ok(()));
+
+        command.link(mock, app_id).await
+    }
+
     // TODO: add test test_sqlite_link_errors_when_link_exists_with_different_database()
     // once there is a flag to avoid prompts
 }
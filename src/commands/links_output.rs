@@ -1,6 +1,5 @@
 /// This module provides functions for printing links in various formats
 use anyhow::Result;
-use clap::ValueEnum;
 use cloud_openapi::models::ResourceLabel;
 use comfy_table::presets::ASCII_BORDERS_ONLY_CONDENSED;
 use dialoguer::Input;
@@ -9,12 +8,6 @@ use std::collections::BTreeMap;
 
 use super::link::Link;
 
-#[derive(ValueEnum, Clone, Debug)]
-pub enum ListFormat {
-    Table,
-    Json,
-}
-
 #[derive(PartialEq, Debug, Clone)]
 pub struct ResourceLinks {
     pub name: String,
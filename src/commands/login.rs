@@ -2,6 +2,8 @@ use std::path::PathBuf;
 use std::time::Duration;
 
 use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
 use clap::Parser;
 use cloud::{
     client::{Client, ConnectionConfig},
@@ -21,8 +23,12 @@ use crate::opts::{
     CLOUD_SERVER_URL_OPT, CLOUD_URL_ENV, DEPLOYMENT_ENV_NAME_ENV, INSECURE_OPT, SPIN_AUTH_TOKEN,
     TOKEN,
 };
+use crate::output;
 
-use super::DEFAULT_CLOUD_URL;
+use super::config::ConfigLock;
+use super::credentials::{self, TokenStorage};
+use super::env::resolve_environment;
+use super::{http_config, DEFAULT_CLOUD_URL};
 
 // this is the client ID registered in the Cloud's backend
 const SPIN_CLIENT_ID: &str = "583e63e9-461f-4fbe-a246-23e0fb1cad10";
@@ -119,6 +125,18 @@ pub struct LoginCommand {
         conflicts_with = "check-device-code"
     )]
     pub list: bool,
+
+    /// Where to keep the login's tokens. Use `file` where there is no OS
+    /// keychain, such as on CI runners. If the keychain turns out to be
+    /// unavailable, the tokens are kept in the file, with a warning.
+    #[clap(
+        name = "token-storage",
+        long = "token-storage",
+        env = "SPIN_CLOUD_TOKEN_STORAGE",
+        default_value = "keychain",
+        value_enum
+    )]
+    pub token_storage: TokenStorage,
 }
 
 /// Log out of Fermyon Cloud.
@@ -189,7 +207,22 @@ impl LoginCommand {
         let data = fs::read_to_string(&path)
             .await
             .context("Cannot display login information")?;
-        println!("{}", data);
+        let environment = resolve_environment(self.deployment_env_id.as_deref())?;
+        let connection = credentials::parse(environment.as_deref(), &data)
+            .context("Cannot parse login information")?;
+        let environment = environment.as_deref().unwrap_or("(default)");
+        if output::is_json() {
+            return output::print_json(&json!({
+                "environment": environment,
+                "account": token_account(&connection.token),
+                "url": connection.url,
+                "method": login_method(&connection),
+                "expiration": connection.expiration,
+            }));
+        }
+        for line in status_lines(&connection, environment, Utc::now()) {
+            println!("{line}");
+        }
         Ok(())
     }
 
@@ -246,6 +279,7 @@ impl LoginCommand {
             url: self.cloud_url.to_string(),
             insecure: self.insecure,
             token: token.clone(),
+            http: http_config(),
         })
         // Just getting the first app as we just use it to test credentials
         .list_apps(1, None)
@@ -258,7 +292,7 @@ impl LoginCommand {
     async fn run_interactive_gh_login(&self) -> Result<LoginConnection> {
         // log in to the cloud API
         let connection_config = self.anon_connection_config();
-        let token_info = github_token(connection_config).await?;
+        let token_info = github_token(&Client::new(connection_config)).await?;
 
         Ok(self.login_connection_for_token_info(token_info))
     }
@@ -270,6 +304,7 @@ impl LoginCommand {
             token,
             refresh_token: None,
             expiration: None,
+            token_storage: self.token_storage,
         }
     }
 
@@ -280,6 +315,7 @@ impl LoginCommand {
             token: token_info.token,
             refresh_token: Some(token_info.refresh_token),
             expiration: Some(token_info.expiration),
+            token_storage: self.token_storage,
         }
     }
 
@@ -288,7 +324,8 @@ impl LoginCommand {
 
         ensure(&root)?;
 
-        let file_stem = match &self.deployment_env_id {
+        let deployment_env_id = resolve_environment(self.deployment_env_id.as_deref())?;
+        let file_stem = match &deployment_env_id {
             None => "config",
             Some(id) => id,
         };
@@ -304,6 +341,7 @@ impl LoginCommand {
             url: self.cloud_url.to_string(),
             insecure: self.insecure,
             token: Default::default(),
+            http: http_config(),
         }
     }
 
@@ -321,16 +359,27 @@ impl LoginCommand {
 
     fn save_login_info(&self, login_connection: &LoginConnection) -> Result<(), anyhow::Error> {
         let path = self.config_file_path()?;
-        std::fs::write(path, serde_json::to_string_pretty(login_connection)?)?;
-        Ok(())
+        let environment = resolve_environment(self.deployment_env_id.as_deref())?;
+        let _lock = ConfigLock::acquire()?;
+        credentials::save(&path, environment.as_deref(), login_connection)
     }
 }
 
 impl LogoutCommand {
     pub async fn run(&self) -> Result<()> {
         let path = self.config_file_path()?;
-        if path.is_file() {
-            std::fs::remove_file(path)?;
+        if !path.is_file() {
+            println!("Not logged in");
+            return Ok(());
+        }
+        let environment = resolve_environment(self.deployment_env_id.as_deref())?;
+        let _lock = ConfigLock::acquire()?;
+        credentials::remove(&path, environment.as_deref())?;
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove login information {}", path.display()))?;
+        match environment {
+            Some(name) => println!("Logged out of environment '{name}'"),
+            None => println!("Logged out"),
         }
         Ok(())
     }
@@ -338,7 +387,8 @@ impl LogoutCommand {
     fn config_file_path(&self) -> Result<PathBuf> {
         let root = config_root_dir()?;
 
-        let file_stem = match &self.deployment_env_id {
+        let deployment_env_id = resolve_environment(self.deployment_env_id.as_deref())?;
+        let file_stem = match &deployment_env_id {
             None => "config",
             Some(id) => id,
         };
@@ -350,20 +400,16 @@ impl LogoutCommand {
     }
 }
 
-fn config_root_dir() -> Result<PathBuf, anyhow::Error> {
+pub(crate) fn config_root_dir() -> Result<PathBuf, anyhow::Error> {
     let root = dirs::config_dir()
         .context("Cannot find configuration directory")?
         .join("fermyon");
     Ok(root)
 }
 
-async fn github_token(
-    connection_config: ConnectionConfig,
-) -> Result<cloud_openapi::models::TokenInfo> {
-    let client = Client::new(connection_config);
-
+async fn github_token(client: &impl CloudClientInterface) -> Result<TokenInfo> {
     // Generate a device code and a user code to activate it with
-    let device_code = create_device_code(&client).await?;
+    let device_code = create_device_code(client).await?;
 
     println!(
         "\nCopy your one-time code:\n\n{}\n",
@@ -401,7 +447,7 @@ async fn github_token(
     }
 }
 
-async fn create_device_code(client: &Client) -> Result<DeviceCodeItem> {
+async fn create_device_code(client: &impl CloudClientInterface) -> Result<DeviceCodeItem> {
     client
         .create_device_code(Uuid::parse_str(SPIN_CLIENT_ID)?)
         .await
@@ -418,6 +464,58 @@ pub struct LoginConnection {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub expiration: Option<String>,
+    #[serde(default)]
+    pub token_storage: TokenStorage,
+}
+
+fn login_method(connection: &LoginConnection) -> &'static str {
+    // Only GitHub logins are issued a refresh token
+    match connection.refresh_token {
+        Some(_) => "GitHub",
+        None => "personal access token",
+    }
+}
+
+// Tokens from the GitHub device flow are JWTs whose claims name the account.
+// Personal access tokens are opaque, so their account is not known.
+fn token_account(token: &str) -> Option<String> {
+    let claims = token.split('.').nth(1)?;
+    let claims = URL_SAFE_NO_PAD.decode(claims.trim_end_matches('=')).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&claims).ok()?;
+    ["preferred_username", "name", "email", "sub"]
+        .iter()
+        .find_map(|claim| Some(claims.get(claim)?.as_str()?.to_owned()))
+}
+
+// Describes a saved login without revealing its tokens.
+fn status_lines(
+    connection: &LoginConnection,
+    environment: &str,
+    now: DateTime<Utc>,
+) -> Vec<String> {
+    let method = login_method(connection);
+    let expiry = match &connection.expiration {
+        None => "never".to_owned(),
+        Some(expiration) => match DateTime::parse_from_rfc3339(expiration) {
+            Ok(time) if time > now => {
+                let remaining = (time.with_timezone(&Utc) - now).num_minutes();
+                format!("{expiration} (in {}h {}m)", remaining / 60, remaining % 60)
+            }
+            Ok(_) if connection.refresh_token.is_some() => {
+                format!("{expiration} (expired, will be refreshed on next use)")
+            }
+            Ok(_) => format!("{expiration} (expired)"),
+            Err(_) => format!("{expiration} (unrecognized format)"),
+        },
+    };
+    let account = token_account(&connection.token).unwrap_or_else(|| "unknown".to_owned());
+    vec![
+        format!("Environment: {environment}"),
+        format!("Account: {account}"),
+        format!("Instance URL: {}", connection.url),
+        format!("Logged in with: {method}"),
+        format!("Token expires: {expiry}"),
+    ]
 }
 
 #[derive(Deserialize, Serialize)]
@@ -506,3 +604,40 @@ fn parse_url_ensures_trailing_slash() {
     let url = parse_url("https://localhost:12345/foo/bar").unwrap();
     assert_eq!(url.to_string(), "https://localhost:12345/foo/bar/");
 }
+
+#[test]
+fn status_lines_report_expiry_without_tokens() {
+    let connection = LoginConnection {
+        url: parse_url("https://cloud.fermyon.com").unwrap(),
+        danger_accept_invalid_certs: false,
+        token: "secret-token".to_owned(),
+        refresh_token: Some("secret-refresh".to_owned()),
+        expiration: Some("2024-01-01T12:00:00Z".to_owned()),
+        token_storage: TokenStorage::Keychain,
+    };
+    let now = DateTime::parse_from_rfc3339("2024-01-01T10:30:00Z")
+        .unwrap()
+        .with_timezone(&Utc);
+    let lines = status_lines(&connection, "(default)", now);
+    assert_eq!(
+        lines,
+        vec![
+            "Environment: (default)",
+            "Account: unknown",
+            "Instance URL: https://cloud.fermyon.com/",
+            "Logged in with: GitHub",
+            "Token expires: 2024-01-01T12:00:00Z (in 1h 30m)",
+        ]
+    );
+    assert!(!lines.iter().any(|l| l.contains("secret")));
+}
+
+#[test]
+fn token_account_is_read_from_jwt_claims() {
+    let claims = URL_SAFE_NO_PAD.encode(r#"{"sub":"github|42","preferred_username":"octocat"}"#);
+    assert_eq!(
+        token_account(&format!("eyJhbGciOiJSUzI1NiJ9.{claims}.signature")).as_deref(),
+        Some("octocat")
+    );
+    assert_eq!(token_account("opaque-personal-access-token"), None);
+}
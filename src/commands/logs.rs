@@ -1,16 +1,25 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
 use std::ops::Sub;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::{bail, Context, Result};
-use chrono::Utc;
-use cloud::{CloudClientExt, CloudClientInterface};
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Local, Utc};
+use cloud::retry::{is_rate_limited, retry_after, MAX_RETRY_AFTER};
+use cloud::{CloudClientExt, CloudClientInterface, LogStream};
 use cloud_openapi::models::Entry;
 use std::option::Option;
 
-use crate::commands::create_cloud_client;
+use crate::commands::app_picker::app_or_pick;
+use crate::commands::apps::{apps_with_labels, parse_label};
+use crate::commands::cache::{self, ResponseCache};
+use crate::commands::{print_rate_limited, retry_policy, ClientSource, CloudClientSession};
+use crate::errors::CliError;
 use crate::opts::*;
+use crate::output;
 use clap::Parser;
-use uuid::Uuid;
+use regex::Regex;
 
 /// fetch logs for an app from Fermyon Cloud
 #[derive(Parser, Debug)]
@@ -25,26 +34,57 @@ pub struct LogsCommand {
     )]
     pub deployment_env_id: Option<String>,
 
-    /// App name
-    pub app: String,
+    /// App name. If neither this nor `--selector` is given in a terminal,
+    /// you are asked to pick an app.
+    #[clap(name = "app")]
+    pub app: Option<String>,
+
+    /// Show the logs of every app with this label (e.g. "team=payments")
+    /// instead of a single app. Repeat to require several labels.
+    #[clap(
+        name = "selector",
+        long = "selector",
+        multiple_occurrences = true,
+        conflicts_with = "app",
+        value_parser = clap::builder::ValueParser::new(parse_label)
+    )]
+    pub selector: Vec<(String, String)>,
+
+    /// Show the logs of this channel of the app, such as "spin-canary",
+    /// instead of its deploy channel. See an app's channels with
+    /// `spin cloud channels list`.
+    #[clap(name = "channel", long = "channel")]
+    pub channel: Option<String>,
 
     /// Follow logs output
     #[clap(name = "follow", long = "follow")]
     pub follow: bool,
 
-    /// Number of lines to show from the end of the logs
-    #[clap(name = "tail", long = "tail", default_value = "10")]
-    pub max_lines: i32,
+    /// Number of lines to show from the end of the logs, or "all" to show
+    /// every line since `--since`. Use 0 to show only new lines when following.
+    #[clap(value_parser = clap::builder::ValueParser::new(parse_tail), name = "tail", long = "tail", default_value = "10")]
+    pub tail: Tail,
+
+    /// Do not show historical lines. The same as `--tail 0`.
+    #[clap(name = "no-tail", long = "no-tail", conflicts_with = "tail")]
+    pub no_tail: bool,
+
+    /// The most historical lines to fetch. With `--tail all`, lines are
+    /// fetched oldest first, so this keeps the start of the `--since` window.
+    #[clap(name = "limit", long = "limit")]
+    pub limit: Option<usize>,
 
     /// Interval in seconds to refresh logs from cloud
-    #[clap(parse(try_from_str = parse_interval), name="interval", long="interval", default_value = "2")]
+    #[clap(value_parser = clap::builder::ValueParser::new(parse_interval), name="interval", long="interval", default_value = "2")]
     pub interval_secs: std::time::Duration,
 
     /// Only return logs newer than a relative duration. The duration format is a number
     /// and a unit, where the unit is 's' for seconds, 'm' for minutes, 'h' for hours
     /// or 'd' for days (e.g. "30m" for 30 minutes ago).  The default it 7 days.
-    #[clap(parse(try_from_str = parse_duration), name="since", long="since", default_value = "7d")]
-    pub since: std::time::Duration,
+    /// An RFC3339 timestamp, such as the cursor printed when `--follow` is
+    /// interrupted, is also accepted.
+    #[clap(value_parser = clap::builder::ValueParser::new(parse_since), name="since", long="since", default_value = "7d")]
+    pub since: Since,
 
     /// Show timestamps
     #[clap(
@@ -54,109 +94,881 @@ pub struct LogsCommand {
         action = clap::ArgAction::Set
     )]
     pub show_timestamp: bool,
+
+    /// How to show timestamps: "utc" prints them in RFC3339 as recorded,
+    /// "local" converts them to local time. Implies `--show-timestamps true`.
+    #[clap(
+        value_enum,
+        name = "timestamps",
+        long = "timestamps",
+        min_values = 0,
+        default_missing_value = "utc"
+    )]
+    pub timestamps: Option<TimestampFormat>,
+
+    /// Prefix each line with the name of the app it came from
+    #[clap(name = "prefix", long = "prefix")]
+    pub prefix: bool,
+
+    /// Only print lines matching this regular expression
+    #[clap(value_parser = clap::builder::ValueParser::new(Regex::new), name = "grep", long = "grep")]
+    pub grep: Option<Regex>,
+
+    /// Print only the lines that do not match `--grep`
+    #[clap(name = "invert-match", long = "invert-match", requires = "grep")]
+    pub invert_match: bool,
+
+    /// When to color lines by their log level: "auto" colors only when
+    /// printing to a terminal and `NO_COLOR` is not set
+    #[clap(value_enum, name = "color", long = "color", default_value = "auto")]
+    pub color: ColorChoice,
+
+    /// Render JSON log lines as their level and message, followed by the
+    /// fields given with `--fields`, instead of the raw JSON
+    #[clap(name = "pretty", long = "pretty")]
+    pub pretty: bool,
+
+    /// With `--pretty`, the fields to show after the level and message,
+    /// separated by commas (e.g. "status,path")
+    #[clap(
+        name = "fields",
+        long = "fields",
+        requires = "pretty",
+        value_delimiter = ','
+    )]
+    pub fields: Vec<String>,
+
+    /// Print each line in this format instead, e.g. "{{.time}} [{{.app}}] {{.line}}".
+    /// The fields are .time, .app, .level and .line. With `--timestamps
+    /// local`, .time is in local time.
+    #[clap(
+        value_parser = clap::builder::ValueParser::new(parse_output_template),
+        name = "output-template",
+        long = "output-template",
+        conflicts_with_all = &["show-timestamps", "prefix", "pretty"]
+    )]
+    pub output_template: Option<OutputTemplate>,
+
+    /// When following, how many consecutive failed fetches to tolerate before giving up
+    #[clap(
+        name = "max-reconnect-attempts",
+        long = "max-reconnect-attempts",
+        default_value = "10"
+    )]
+    pub max_reconnect_attempts: u32,
+
+    /// When following, give up with exit code 8 once no new lines have been
+    /// printed for this long (e.g. "5m")
+    #[clap(
+        name = "idle-timeout",
+        long = "idle-timeout",
+        requires = "follow",
+        value_parser = clap::builder::ValueParser::new(humantime::parse_duration)
+    )]
+    pub idle_timeout: Option<Duration>,
+
+    /// When following, print a marker line to stderr each time this long
+    /// passes without new lines (default "30s"), to show it is still running.
+    /// A duration must be joined to the flag, as in `--heartbeat=1m`.
+    #[clap(
+        name = "heartbeat",
+        long = "heartbeat",
+        requires = "follow",
+        min_values = 0,
+        require_equals = true,
+        default_missing_value = "30s",
+        value_parser = clap::builder::ValueParser::new(humantime::parse_duration)
+    )]
+    pub heartbeat: Option<Duration>,
 }
 
 impl LogsCommand {
+    fn line_printer(&self, app: &str) -> LinePrinter {
+        let timestamps = match (self.timestamps, self.show_timestamp) {
+            (Some(format), _) => Some(format),
+            (None, true) => Some(TimestampFormat::Utc),
+            (None, false) => None,
+        };
+        // Lines from several apps can only be told apart by their prefix
+        let prefix = self.prefix || !self.selector.is_empty();
+        LinePrinter {
+            timestamps,
+            prefix: prefix.then(|| app.to_owned()),
+            app: app.to_owned(),
+            template: self.output_template.clone(),
+            grep: self.grep.clone(),
+            invert_match: self.invert_match,
+            color: self.color.enabled() && !output::is_json(),
+            pretty: self.pretty,
+            fields: self.fields.clone(),
+            json: output::is_json(),
+        }
+    }
+
+    // The name of each app whose logs are shown, and where they are read from
+    async fn streams(
+        &self,
+        session: &mut impl ClientSource,
+        cache: &mut ResponseCache,
+    ) -> Result<Vec<(String, LogStream)>> {
+        let channel = self.channel.as_deref();
+        if !self.selector.is_empty() {
+            let client = session.client().await?;
+            let apps = apps_with_labels(client, &self.selector).await?;
+            if apps.is_empty() {
+                let selector = self
+                    .selector
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                bail!(CliError::not_found(format!(
+                    "No apps have the labels {selector}"
+                )));
+            }
+            let mut streams = vec![];
+            for app in apps {
+                let stream =
+                    cache::channel_stream(client, cache, &app.name, app.id, channel).await?;
+                streams.push((app.name, stream));
+            }
+            return Ok(streams);
+        }
+        let app = app_or_pick(self.deployment_env_id.as_deref(), self.app.clone()).await?;
+        let client = session.client().await?;
+        let (_, stream) = cache::resolve_app_channel(client, cache, &app, channel).await?;
+        Ok(vec![(app, stream)])
+    }
+
     pub async fn run(self) -> Result<()> {
-        let client = create_cloud_client(self.deployment_env_id.as_deref()).await?;
-        self.logs(&client).await
+        // A follow session can outlive the token, so fetch the client from the
+        // session before each request to pick up refreshed tokens
+        let mut session = CloudClientSession::new(self.deployment_env_id.as_deref()).await?;
+        let mut cache = ResponseCache::open(self.deployment_env_id.as_deref())?;
+        self.logs(&mut session, &mut cache).await
     }
 
-    async fn logs(self, client: &impl CloudClientInterface) -> Result<()> {
-        let app_id = client
-            .get_app_id(&self.app)
-            .await
-            .with_context(|| format!("failed to find app with name {:?}", &self.app))?
-            .with_context(|| format!("app with name {:?} not found", &self.app))?;
+    async fn logs(self, session: &mut impl ClientSource, cache: &mut ResponseCache) -> Result<()> {
+        let streams = self.streams(session, cache).await?;
 
-        fetch_logs_and_print_loop(
-            client,
-            app_id,
-            self.follow,
-            self.interval_secs,
-            self.max_lines,
-            self.since,
-            self.show_timestamp,
-        )
-        .await?;
+        let tail = if self.no_tail {
+            Tail::Lines(0)
+        } else {
+            self.tail
+        };
+        let since = match tail {
+            // Nothing historical is shown, so start following from now
+            Tail::Lines(0) => Utc::now().to_rfc3339(),
+            _ => match self.since {
+                Since::Ago(duration) => Utc::now().sub(duration).to_rfc3339(),
+                Since::At(time) => time.to_rfc3339(),
+            },
+        };
+        let client = session.client().await?;
+        let mut sources = vec![];
+        for (name, stream) in streams {
+            sources.push(LogSource {
+                stream,
+                cursor: LogCursor::new(since.clone()),
+                printer: self.line_printer(&name),
+                shown: ShownLines::default(),
+                failures: 0,
+                name,
+            });
+        }
+        // Interrupting a follow session prints where to resume from, even
+        // while its history is still being fetched
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        // History for several apps is fetched at once, and printed merged
+        // in the order it was logged
+        let history = {
+            let spinner = output::spinner("Fetching logs");
+            let fetch = futures::future::join_all(sources.iter().map(|source| async {
+                match tail {
+                    Tail::Lines(0) => Ok(vec![]),
+                    Tail::Lines(lines) => {
+                        let lines = usize::try_from(lines).unwrap_or_default();
+                        let lines = match self.limit {
+                            Some(limit) => lines.min(limit),
+                            None => lines,
+                        };
+                        client
+                            .get_logs_tail(source.stream, source.cursor.since.clone(), lines)
+                            .await
+                    }
+                    Tail::All => {
+                        client
+                            .get_logs_since(source.stream, source.cursor.since.clone(), self.limit)
+                            .await
+                    }
+                }
+            }));
+            match self.follow {
+                true => tokio::select! {
+                    history = fetch => history,
+                    _ = &mut ctrl_c => {
+                        drop(spinner);
+                        print_follow_summary(&sources);
+                        return Ok(());
+                    }
+                },
+                false => fetch.await,
+            }
+        };
+        let limit = match tail {
+            Tail::All => self.limit,
+            Tail::Lines(_) => None,
+        };
+        let outcomes = print_merged(&mut sources, history, limit);
+        if outcomes.iter().all(|outcome| outcome.is_err()) {
+            if let Some(Err(e)) = outcomes.into_iter().next() {
+                return Err(e);
+            }
+        } else {
+            for (source, outcome) in sources.iter().zip(outcomes) {
+                if let Err(e) = outcome {
+                    output::stream_progress(&format!(
+                        "Warning: failed to fetch logs for {} ({e:#})",
+                        source.name
+                    ));
+                }
+            }
+        }
 
-        Ok(())
+        if !self.follow {
+            return Ok(());
+        }
+
+        // How long the service asked us to back off for, which replaces the
+        // usual delay before the next fetch
+        let mut rate_limit_wait = None;
+        let mut quiet = QuietSpell::new(Instant::now());
+        loop {
+            // Only back off once every app is failing, so that one app's
+            // failures do not hold up the others' logs
+            let failures = sources.iter().map(|s| s.failures).min().unwrap_or_default();
+            let delay = rate_limit_wait
+                .take()
+                .unwrap_or_else(|| reconnect_delay(self.interval_secs, failures));
+            let fetch = async {
+                tokio::time::sleep(delay).await;
+                match session.client().await {
+                    Ok(client) => fetch_and_print_all(client, &mut sources).await,
+                    // Without a client, every app's fetch fails the same way
+                    Err(e) => sources.iter().map(|_| Err(anyhow!("{e:#}"))).collect(),
+                }
+            };
+            // A fetch that hangs cannot hold off the idle timeout
+            let idle = async {
+                match quiet.deadline(self.idle_timeout) {
+                    Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                    None => std::future::pending().await,
+                }
+            };
+            // Printing happens between awaits, so interrupting a fetch never
+            // leaves a cursor ahead of the lines actually printed
+            let outcomes = tokio::select! {
+                outcomes = fetch => outcomes,
+                _ = idle => {
+                    std::io::stdout().flush()?;
+                    print_follow_summary(&sources);
+                    return Err(idle_timed_out(quiet.quiet_for(Instant::now())));
+                }
+                _ = &mut ctrl_c => {
+                    std::io::stdout().flush()?;
+                    print_follow_summary(&sources);
+                    return Ok(());
+                }
+            };
+            let mut given_up = vec![];
+            for (index, (source, outcome)) in sources.iter_mut().zip(outcomes).enumerate() {
+                match outcome {
+                    Ok(()) => source.failures = 0,
+                    // Being rate limited is not a failure to reach the service, so
+                    // it is waited out rather than counted, unless retries are off
+                    Err(e) if is_rate_limited(&e) && retry_policy().retries > 0 => {
+                        // A wait longer than retries would accept is cut short,
+                        // so that following carries on
+                        let wait = retry_after(&e).map_or_else(
+                            || reconnect_delay(self.interval_secs, source.failures + 1),
+                            |wait| wait.min(MAX_RETRY_AFTER),
+                        );
+                        rate_limit_wait = rate_limit_wait.max(Some(wait));
+                    }
+                    // The cursor only moves on success, so the next fetch resumes
+                    // from the last line printed and nothing is skipped
+                    Err(e) if source.failures < self.max_reconnect_attempts => {
+                        source.failures += 1;
+                        output::stream_progress(&format!(
+                            "Warning: failed to fetch logs for {} ({e:#}). Reconnecting (attempt {} of {})",
+                            source.name, source.failures, self.max_reconnect_attempts
+                        ));
+                    }
+                    Err(e) => given_up.push((index, e)),
+                }
+            }
+            if let Some(wait) = rate_limit_wait {
+                print_rate_limited(wait);
+            }
+            // An app that keeps failing is dropped, and following only ends
+            // once no app is left
+            for (index, e) in given_up.into_iter().rev() {
+                let source = sources.remove(index);
+                let e = e.context(format!(
+                    "Giving up after {} failed attempts to fetch logs for {}",
+                    source.failures + 1,
+                    source.name
+                ));
+                if sources.is_empty() {
+                    return Err(e);
+                }
+                output::stream_progress(&format!("Warning: {e:#}"));
+            }
+            let shown = sources.iter().map(|source| source.shown.count).sum();
+            match quiet.check(shown, Instant::now(), self.heartbeat, self.idle_timeout) {
+                Quiet::No => {}
+                Quiet::Heartbeat(quiet_for) => output::stream_progress(&format!(
+                    "-- no new lines for {}, still following --",
+                    humantime::format_duration(quiet_for)
+                )),
+                Quiet::TimedOut(quiet_for) => {
+                    std::io::stdout().flush()?;
+                    print_follow_summary(&sources);
+                    return Err(idle_timed_out(quiet_for));
+                }
+            }
+        }
     }
 }
 
-async fn fetch_logs_and_print_loop(
+fn idle_timed_out(quiet_for: Duration) -> anyhow::Error {
+    CliError::timed_out(format!(
+        "No new log lines for {}",
+        humantime::format_duration(quiet_for)
+    ))
+    .into()
+}
+
+/// An app whose logs are being printed, and how far they have been printed
+struct LogSource {
+    name: String,
+    stream: LogStream,
+    cursor: LogCursor,
+    printer: LinePrinter,
+    shown: ShownLines,
+    /// Fetches that have failed in a row
+    failures: u32,
+}
+
+// Fetches the new lines of every app at once, and prints them merged in the
+// order they were logged. Returns whether each app's fetch succeeded.
+async fn fetch_and_print_all(
     client: &impl CloudClientInterface,
-    app_id: Uuid,
-    follow: bool,
-    interval: Duration,
-    max_lines: i32,
-    since: Duration,
-    show_timestamp: bool,
-) -> Result<()> {
-    let mut curr_since = Utc::now().sub(since).to_rfc3339();
-    curr_since =
-        fetch_logs_and_print_once(client, app_id, Some(max_lines), curr_since, show_timestamp)
-            .await?;
+    sources: &mut [LogSource],
+) -> Vec<Result<()>> {
+    let fetched =
+        futures::future::join_all(sources.iter().map(|source| {
+            client.get_logs_raw(source.stream, None, Some(source.cursor.since.clone()))
+        }))
+        .await;
+    print_merged(sources, fetched, None)
+}
 
-    if !follow {
-        return Ok(());
+// Prints the lines fetched for each app that are new, at most `limit` per
+// app, in timestamp order across apps. A failed fetch leaves the app's cursor
+// where it was, so its lines are printed once a later fetch succeeds.
+fn print_merged(
+    sources: &mut [LogSource],
+    fetched: Vec<Result<Vec<Entry>>>,
+    limit: Option<usize>,
+) -> Vec<Result<()>> {
+    let (lines, outcomes) = merge_new_lines(sources, fetched, limit);
+    for (index, time, line) in lines {
+        let source = &mut sources[index];
+        if source.printer.matches(&line) {
+            println!("{}", source.printer.format(&time, &line));
+            source.shown.record(&time);
+        }
     }
+    outcomes
+}
 
-    loop {
-        tokio::time::sleep(interval).await;
-        curr_since =
-            fetch_logs_and_print_once(client, app_id, None, curr_since, show_timestamp).await?;
+// A line to print: the index of its app, its time and the line itself
+type NewLine = (usize, String, String);
+
+// The new lines of every app, oldest first
+fn merge_new_lines(
+    sources: &mut [LogSource],
+    fetched: Vec<Result<Vec<Entry>>>,
+    limit: Option<usize>,
+) -> (Vec<NewLine>, Vec<Result<()>>) {
+    let mut lines = vec![];
+    let mut outcomes = vec![];
+    for (index, (source, entries)) in sources.iter_mut().zip(fetched).enumerate() {
+        match entries {
+            Ok(entries) => {
+                let new_lines = source.cursor.advance(timed_lines(&entries), limit);
+                lines.extend(
+                    new_lines
+                        .into_iter()
+                        .map(|(time, line)| (index, time.to_owned(), line.to_owned())),
+                );
+                outcomes.push(Ok(()));
+            }
+            Err(e) => outcomes.push(Err(e)),
+        }
     }
+    // Stable, so each app's lines keep the order its cursor gave them
+    lines.sort_by(|(_, a, _), (_, b, _)| compare_timestamps(a, b));
+    (lines, outcomes)
 }
 
-async fn fetch_logs_and_print_once(
-    client: &impl CloudClientInterface,
-    app_id: Uuid,
-    max_lines: Option<i32>,
-    since: String,
-    show_timestamp: bool,
-) -> Result<String> {
-    let entries = client
-        .app_logs_raw(app_id.to_string(), max_lines, Some(since.to_string()))
-        .await?
-        .entries;
+/// How long following has gone without printing a line, which is whole
+/// seconds since the service is polled every few seconds anyway
+struct QuietSpell {
+    shown: usize,
+    since: Instant,
+    last_heartbeat: Instant,
+}
 
-    if entries.is_empty() {
-        return Ok(since.to_owned());
+#[derive(Debug, PartialEq, Eq)]
+enum Quiet {
+    No,
+    Heartbeat(Duration),
+    TimedOut(Duration),
+}
+
+impl QuietSpell {
+    fn new(now: Instant) -> Self {
+        Self {
+            shown: 0,
+            since: now,
+            last_heartbeat: now,
+        }
+    }
+
+    // `shown` is the number of lines printed so far, across all apps
+    fn check(
+        &mut self,
+        shown: usize,
+        now: Instant,
+        heartbeat: Option<Duration>,
+        idle_timeout: Option<Duration>,
+    ) -> Quiet {
+        if shown != self.shown {
+            *self = Self::new(now);
+            self.shown = shown;
+            return Quiet::No;
+        }
+        let quiet_for = self.quiet_for(now);
+        if idle_timeout.is_some_and(|timeout| quiet_for >= timeout) {
+            return Quiet::TimedOut(quiet_for);
+        }
+        match heartbeat {
+            Some(heartbeat) if now.duration_since(self.last_heartbeat) >= heartbeat => {
+                self.last_heartbeat = now;
+                Quiet::Heartbeat(quiet_for)
+            }
+            _ => Quiet::No,
+        }
+    }
+
+    /// When the idle timeout runs out if nothing more is printed
+    fn deadline(&self, idle_timeout: Option<Duration>) -> Option<Instant> {
+        idle_timeout.map(|timeout| self.since + timeout)
     }
 
-    let updated_since = print_logs(&entries, show_timestamp);
-    if let Some(u) = updated_since {
-        return Ok(u.to_owned());
+    // Whole seconds, for messages
+    fn quiet_for(&self, now: Instant) -> Duration {
+        Duration::from_secs(now.duration_since(self.since).as_secs())
     }
+}
 
-    Ok(since)
+/// How many lines have been printed for an app, and when they were logged
+#[derive(Debug, Default, PartialEq)]
+struct ShownLines {
+    count: usize,
+    first: Option<String>,
+    last: Option<String>,
+}
+
+impl ShownLines {
+    fn record(&mut self, time: &str) {
+        self.count += 1;
+        if self.first.is_none() {
+            self.first = Some(time.to_owned());
+        }
+        self.last = Some(time.to_owned());
+    }
 }
 
-fn print_logs(entries: &[Entry], show_timestamp: bool) -> Option<&str> {
-    let mut since = None;
-    for entry in entries.iter().rev() {
-        let Some(log_lines) = entry.log_lines.as_ref() else {
-            continue;
+// Printed to stderr when following is interrupted, so that output piped
+// elsewhere only ever holds log lines
+fn print_follow_summary(sources: &[LogSource]) {
+    eprintln!();
+    for source in sources {
+        let range = match (&source.shown.first, &source.shown.last) {
+            (Some(first), Some(last)) => format!(" from {first} to {last}"),
+            _ => String::new(),
         };
+        eprintln!(
+            "{}: {} line(s) shown{range}. Resume with `--since {} --tail all`",
+            source.name, source.shown.count, source.cursor.since
+        );
+    }
+}
 
-        for log_entry in log_lines {
-            let Some(log) = log_entry.line.as_ref() else {
-                continue;
-            };
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimestampFormat {
+    Utc,
+    Local,
+}
+
+impl TimestampFormat {
+    // Lines keep the recorded time if it cannot be parsed
+    fn render(self, time: &str) -> String {
+        match self {
+            Self::Utc => time.to_owned(),
+            Self::Local => DateTime::parse_from_rfc3339(time)
+                .map(|t| t.with_timezone(&Local).to_rfc3339())
+                .unwrap_or_else(|_| time.to_owned()),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        }
+    }
+}
+
+/// Which log lines are printed, and how each is decorated
+struct LinePrinter {
+    timestamps: Option<TimestampFormat>,
+    prefix: Option<String>,
+    app: String,
+    template: Option<OutputTemplate>,
+    grep: Option<Regex>,
+    invert_match: bool,
+    color: bool,
+    pretty: bool,
+    fields: Vec<String>,
+    /// Print each line as a JSON object, for `--format json`
+    json: bool,
+}
+
+impl LinePrinter {
+    // Filtering applies to the line as logged, not to the added decoration
+    fn matches(&self, line: &str) -> bool {
+        match &self.grep {
+            Some(pattern) => pattern.is_match(line) != self.invert_match,
+            None => true,
+        }
+    }
+
+    fn format(&self, time: &str, line: &str) -> String {
+        if self.json {
+            return self.format_json(time, line);
+        }
+        if let Some(template) = &self.template {
+            return self.format_template(template, time, line);
+        }
+        let mut formatted = String::new();
+        if let Some(format) = self.timestamps {
+            formatted.push_str(&format!("[{}] ", format.render(time)));
+        }
+        if let Some(prefix) = &self.prefix {
+            formatted.push_str(&format!("{prefix} | "));
+        }
+        let json = self
+            .pretty
+            .then(|| serde_json::from_str::<serde_json::Value>(line).ok())
+            .flatten();
+        let body = json
+            .as_ref()
+            .and_then(|json| json.as_object())
+            .and_then(|json| pretty_json(json, &self.fields))
+            .unwrap_or_else(|| line.to_owned());
+        match LogLevel::detect(line).filter(|_| self.color) {
+            Some(level) => formatted.push_str(&level.paint(&body)),
+            None => formatted.push_str(&body),
+        }
+        formatted
+    }
+
+    // One object per line, so that output can be processed as it arrives
+    fn format_json(&self, time: &str, line: &str) -> String {
+        let format = self.timestamps.unwrap_or(TimestampFormat::Utc);
+        serde_json::json!({
+            "app": self.app,
+            "time": format.render(time),
+            "level": LogLevel::detect(line).map(LogLevel::name),
+            "line": line,
+        })
+        .to_string()
+    }
+
+    fn format_template(&self, template: &OutputTemplate, time: &str, line: &str) -> String {
+        let level = LogLevel::detect(line);
+        let mut formatted = String::new();
+        for part in &template.0 {
+            match part {
+                TemplatePart::Text(text) => formatted.push_str(text),
+                TemplatePart::Field(TemplateField::Time) => {
+                    let format = self.timestamps.unwrap_or(TimestampFormat::Utc);
+                    formatted.push_str(&format.render(time));
+                }
+                TemplatePart::Field(TemplateField::App) => formatted.push_str(&self.app),
+                TemplatePart::Field(TemplateField::Level) => {
+                    formatted.push_str(level.map(LogLevel::name).unwrap_or_default())
+                }
+                TemplatePart::Field(TemplateField::Line) => formatted.push_str(line),
+            }
+        }
+        match level.filter(|_| self.color) {
+            Some(level) => level.paint(&formatted),
+            None => formatted,
+        }
+    }
+}
+
+/// The parsed form of `--output-template`: text interspersed with
+/// `{{.field}}` placeholders.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputTemplate(Vec<TemplatePart>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TemplatePart {
+    Text(String),
+    Field(TemplateField),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TemplateField {
+    Time,
+    App,
+    Level,
+    Line,
+}
+
+fn parse_output_template(arg: &str) -> anyhow::Result<OutputTemplate> {
+    let mut parts = vec![];
+    let mut rest = arg;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            parts.push(TemplatePart::Text(rest[..start].to_owned()));
+        }
+        let Some(end) = rest[start..].find("}}") else {
+            bail!("unclosed \"{{{{\" in template");
+        };
+        let field = match rest[start + 2..start + end].trim() {
+            ".time" => TemplateField::Time,
+            ".app" => TemplateField::App,
+            ".level" => TemplateField::Level,
+            ".line" => TemplateField::Line,
+            other => {
+                bail!("unknown template field {other:?}; expected .time, .app, .level or .line")
+            }
+        };
+        parts.push(TemplatePart::Field(field));
+        rest = &rest[start + end + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push(TemplatePart::Text(rest.to_owned()));
+    }
+    Ok(OutputTemplate(parts))
+}
+
+// Fields that structured loggers commonly use for the level and the message
+const JSON_LEVEL_FIELDS: &[&str] = &["level", "lvl", "severity"];
+const JSON_MESSAGE_FIELDS: &[&str] = &["msg", "message"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    /// Guesses the level of a line, from its `level` field if it is a JSON
+    /// object, or else from the first level-like word in it (e.g. "ERROR").
+    fn detect(line: &str) -> Option<Self> {
+        if let Ok(serde_json::Value::Object(fields)) = serde_json::from_str(line) {
+            return JSON_LEVEL_FIELDS
+                .iter()
+                .find_map(|name| fields.get(*name)?.as_str())
+                .and_then(|level| Self::parse(&level.to_ascii_uppercase()));
+        }
+        // Only upper case words count, so that prose such as "no errors" is not flagged
+        line.split(|c: char| !c.is_ascii_alphabetic())
+            .find_map(Self::parse)
+    }
+
+    fn parse(word: &str) -> Option<Self> {
+        match word {
+            "ERROR" | "ERR" | "FATAL" | "PANIC" | "CRITICAL" => Some(Self::Error),
+            "WARN" | "WARNING" => Some(Self::Warn),
+            "INFO" => Some(Self::Info),
+            "DEBUG" | "TRACE" => Some(Self::Debug),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+        }
+    }
+
+    fn paint(self, text: &str) -> String {
+        let code = match self {
+            Self::Error => "31",
+            Self::Warn => "33",
+            Self::Info => "32",
+            Self::Debug => "2",
+        };
+        format!("\x1b[{code}m{text}\x1b[0m")
+    }
+}
 
-            if let Some(time) = &log_entry.time {
-                if show_timestamp {
-                    println!("[{time}] {log}");
-                } else {
-                    println!("{log}");
+// Renders a JSON log line as "LEVEL message key=value ...", with a key=value
+// pair for each selected field the line has, in the order they were selected.
+// Lines with none of these fields are left as they are.
+fn pretty_json(
+    json: &serde_json::Map<String, serde_json::Value>,
+    fields: &[String],
+) -> Option<String> {
+    let text = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) if !s.is_empty() && !s.contains(char::is_whitespace) => {
+            s.clone()
+        }
+        other => other.to_string(),
+    };
+    let mut parts = vec![];
+    if let Some(level) = JSON_LEVEL_FIELDS.iter().find_map(|name| json.get(*name)) {
+        parts.push(text(level).to_ascii_uppercase());
+    }
+    if let Some(message) = JSON_MESSAGE_FIELDS.iter().find_map(|name| json.get(*name)) {
+        match message {
+            serde_json::Value::String(s) => parts.push(s.clone()),
+            other => parts.push(other.to_string()),
+        }
+    }
+    for name in fields {
+        if let Some(value) = json.get(name) {
+            parts.push(format!("{name}={}", text(value)));
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+// Flattens entries into (timestamp, line) pairs, oldest entry first. Lines
+// without a timestamp cannot be placed in order and are skipped.
+pub(crate) fn timed_lines(entries: &[Entry]) -> Vec<(&str, &str)> {
+    entries
+        .iter()
+        .rev()
+        .filter_map(|entry| entry.log_lines.as_ref())
+        .flatten()
+        .filter_map(|l| Some((l.time.as_deref()?, l.line.as_deref()?)))
+        .collect()
+}
+
+/// Tracks how far through the logs printing has got. Log lines carry no id
+/// and fetches are inclusive of `since`, so the cursor also remembers the
+/// lines already printed at that exact timestamp to avoid printing them twice.
+struct LogCursor {
+    since: String,
+    printed_at_since: HashMap<String, usize>,
+}
+
+impl LogCursor {
+    fn new(since: String) -> Self {
+        Self {
+            since,
+            printed_at_since: HashMap::new(),
+        }
+    }
+
+    /// Returns the lines that have not been printed yet, in timestamp order
+    /// and at most `limit` of them, and moves the cursor past them.
+    fn advance<'a>(
+        &mut self,
+        mut lines: Vec<(&'a str, &'a str)>,
+        limit: Option<usize>,
+    ) -> Vec<(&'a str, &'a str)> {
+        // Stable, so lines sharing a timestamp keep the order the service gave
+        lines.sort_by(|(a, _), (b, _)| compare_timestamps(a, b));
+
+        let mut seen_at_since = HashMap::<&str, usize>::new();
+        let mut unseen = vec![];
+        for (time, line) in lines {
+            if limit.is_some_and(|limit| unseen.len() >= limit) {
+                break;
+            }
+            match compare_timestamps(time, &self.since) {
+                Ordering::Less => continue,
+                Ordering::Equal => {
+                    let seen = seen_at_since.entry(line).or_default();
+                    *seen += 1;
+                    if *seen <= self.printed_at_since.get(line).copied().unwrap_or(0) {
+                        continue;
+                    }
+                }
+                Ordering::Greater => {
+                    self.since = time.to_owned();
+                    self.printed_at_since.clear();
+                    seen_at_since.clear();
+                    seen_at_since.insert(line, 1);
                 }
-                since = Some(time.as_str());
             }
+            *self.printed_at_since.entry(line.to_owned()).or_default() += 1;
+            unseen.push((time, line));
         }
+        unseen
     }
+}
+
+// Timestamps may differ in precision and offset, so compare them as instants
+// where possible.
+fn compare_timestamps(a: &str, b: &str) -> Ordering {
+    match (
+        DateTime::parse_from_rfc3339(a),
+        DateTime::parse_from_rfc3339(b),
+    ) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
 
-    since
+// Waits the polling interval while logs are flowing, and backs off
+// exponentially (up to a minute) while fetches are failing.
+fn reconnect_delay(interval: Duration, failures: u32) -> Duration {
+    const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+    if failures == 0 {
+        return interval;
+    }
+    interval
+        .saturating_mul(2u32.saturating_pow(failures))
+        .min(MAX_RECONNECT_DELAY)
+        .max(interval)
 }
 
 fn parse_duration(arg: &str) -> anyhow::Result<std::time::Duration> {
@@ -173,12 +985,44 @@ fn parse_duration(arg: &str) -> anyhow::Result<std::time::Duration> {
         let value: u64 = parg.parse()?;
         std::time::Duration::from_secs(value * 24 * 60 * 60)
     } else {
-        bail!(r#"since must be a number followed by an allowed unit ("300s", "5m", "4h" or "1d")"#);
+        bail!(
+            r#"since must be a number followed by an allowed unit ("300s", "5m", "4h" or "1d"), or an RFC3339 timestamp"#
+        );
     };
 
     Ok(duration)
 }
 
+/// Where the logs start: a relative duration before now, or a fixed time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Since {
+    Ago(std::time::Duration),
+    At(DateTime<Utc>),
+}
+
+fn parse_since(arg: &str) -> anyhow::Result<Since> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(arg) {
+        return Ok(Since::At(time.with_timezone(&Utc)));
+    }
+    parse_duration(arg).map(Since::Ago)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tail {
+    Lines(i32),
+    All,
+}
+
+fn parse_tail(arg: &str) -> anyhow::Result<Tail> {
+    if arg == "all" {
+        return Ok(Tail::All);
+    }
+    match arg.parse::<i32>() {
+        Ok(lines) if lines >= 0 => Ok(Tail::Lines(lines)),
+        _ => bail!(r#"tail must be a number of lines or "all""#),
+    }
+}
+
 fn parse_interval(arg: &str) -> anyhow::Result<std::time::Duration> {
     let value = arg.parse()?;
     if value < 2 {
@@ -187,3 +1031,398 @@ fn parse_interval(arg: &str) -> anyhow::Result<std::time::Duration> {
 
     Ok(std::time::Duration::from_secs(value))
 }
+
+#[cfg(test)]
+mod logs_tests {
+    use super::*;
+    use cloud::testing::RecordedClient;
+    use uuid::Uuid;
+
+    // A printer that prints each line as it was logged
+    fn plain_printer() -> LinePrinter {
+        LinePrinter {
+            timestamps: None,
+            prefix: None,
+            app: "myapp".to_owned(),
+            template: None,
+            grep: None,
+            invert_match: false,
+            color: false,
+            pretty: false,
+            fields: vec![],
+            json: false,
+        }
+    }
+
+    #[test]
+    fn test_cursor_orders_lines_and_skips_those_already_printed() {
+        let mut cursor = LogCursor::new("2024-01-01T00:00:00Z".to_owned());
+        let printed = cursor.advance(
+            vec![
+                ("2024-01-01T00:00:02Z", "b"),
+                ("2024-01-01T00:00:01Z", "a"),
+                ("2024-01-01T00:00:02Z", "c"),
+            ],
+            None,
+        );
+        assert_eq!(
+            printed,
+            vec![
+                ("2024-01-01T00:00:01Z", "a"),
+                ("2024-01-01T00:00:02Z", "b"),
+                ("2024-01-01T00:00:02Z", "c"),
+            ]
+        );
+
+        // The next fetch starts at the last timestamp, so it repeats "b" and "c"
+        let printed = cursor.advance(
+            vec![
+                ("2024-01-01T00:00:02Z", "b"),
+                ("2024-01-01T00:00:02Z", "c"),
+                ("2024-01-01T00:00:02Z", "d"),
+                ("2024-01-01T00:00:03Z", "e"),
+            ],
+            None,
+        );
+        assert_eq!(
+            printed,
+            vec![("2024-01-01T00:00:02Z", "d"), ("2024-01-01T00:00:03Z", "e")]
+        );
+    }
+
+    #[test]
+    fn test_cursor_prints_repeated_lines_sharing_a_timestamp() {
+        let mut cursor = LogCursor::new("2024-01-01T00:00:00Z".to_owned());
+        let lines = vec![
+            ("2024-01-01T00:00:01Z", "ok"),
+            ("2024-01-01T00:00:01Z", "ok"),
+        ];
+        assert_eq!(cursor.advance(lines.clone(), None).len(), 2);
+        assert!(cursor.advance(lines, None).is_empty());
+    }
+
+    #[test]
+    fn test_cursor_stops_at_the_limit() {
+        let mut cursor = LogCursor::new("2024-01-01T00:00:00Z".to_owned());
+        let lines = vec![
+            ("2024-01-01T00:00:01Z", "a"),
+            ("2024-01-01T00:00:02Z", "b"),
+            ("2024-01-01T00:00:02Z", "c"),
+            ("2024-01-01T00:00:03Z", "d"),
+        ];
+        assert_eq!(
+            cursor.advance(lines.clone(), Some(2)),
+            vec![("2024-01-01T00:00:01Z", "a"), ("2024-01-01T00:00:02Z", "b")]
+        );
+        // The lines past the limit are still to be printed
+        assert_eq!(
+            cursor.advance(lines, None),
+            vec![("2024-01-01T00:00:02Z", "c"), ("2024-01-01T00:00:03Z", "d")]
+        );
+    }
+
+    #[test]
+    fn test_line_format() {
+        let format = LinePrinter {
+            timestamps: Some(TimestampFormat::Utc),
+            prefix: Some("myapp".to_owned()),
+            ..plain_printer()
+        };
+        assert_eq!(
+            format.format("2024-01-01T00:00:01Z", "hello"),
+            "[2024-01-01T00:00:01Z] myapp | hello"
+        );
+        let format = plain_printer();
+        assert_eq!(format.format("2024-01-01T00:00:01Z", "hello"), "hello");
+    }
+
+    #[test]
+    fn test_grep_and_invert_match() {
+        let mut printer = LinePrinter {
+            grep: Some(Regex::new("^ERROR").unwrap()),
+            ..plain_printer()
+        };
+        assert!(printer.matches("ERROR boom"));
+        assert!(!printer.matches("INFO fine ERROR"));
+        printer.invert_match = true;
+        assert!(!printer.matches("ERROR boom"));
+        assert!(printer.matches("INFO fine ERROR"));
+    }
+
+    #[test]
+    fn test_detect_log_level() {
+        assert_eq!(LogLevel::detect("ERROR: boom"), Some(LogLevel::Error));
+        assert_eq!(LogLevel::detect("[WARN] slow"), Some(LogLevel::Warn));
+        assert_eq!(
+            LogLevel::detect(r#"{"level":"info","msg":"ERROR in text"}"#),
+            Some(LogLevel::Info)
+        );
+        assert_eq!(LogLevel::detect("no errors here"), None);
+    }
+
+    #[test]
+    fn test_pretty_json_and_color() {
+        let printer = LinePrinter {
+            color: true,
+            pretty: true,
+            fields: vec!["status".to_owned(), "path".to_owned()],
+            ..plain_printer()
+        };
+        assert_eq!(
+            printer.format(
+                "2024-01-01T00:00:01Z",
+                r#"{"status":500,"msg":"request failed","level":"error","path":"/api"}"#
+            ),
+            "\x1b[31mERROR request failed status=500 path=/api\x1b[0m"
+        );
+        // Fields that were not selected are left out
+        assert_eq!(
+            printer.format(
+                "2024-01-01T00:00:01Z",
+                r#"{"msg":"started","level":"info","pid":7}"#
+            ),
+            "\x1b[32mINFO started\x1b[0m"
+        );
+        assert_eq!(
+            printer.format("2024-01-01T00:00:01Z", r#"{"pid":7}"#),
+            r#"{"pid":7}"#
+        );
+        assert_eq!(printer.format("2024-01-01T00:00:01Z", "plain"), "plain");
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let printer = LinePrinter {
+            prefix: Some("myapp".to_owned()),
+            pretty: true,
+            json: true,
+            ..plain_printer()
+        };
+        let line: serde_json::Value =
+            serde_json::from_str(&printer.format("2024-01-01T00:00:01Z", "ERROR boom")).unwrap();
+        assert_eq!(
+            line,
+            serde_json::json!({
+                "app": "myapp",
+                "time": "2024-01-01T00:00:01Z",
+                "level": "ERROR",
+                "line": "ERROR boom",
+            })
+        );
+    }
+
+    #[test]
+    fn test_output_template() {
+        let printer = LinePrinter {
+            template: Some(
+                parse_output_template("{{.time}} [{{ .app }}] {{.level}}: {{.line}}").unwrap(),
+            ),
+            ..plain_printer()
+        };
+        assert_eq!(
+            printer.format("2024-01-01T00:00:01Z", "warning: slow"),
+            "2024-01-01T00:00:01Z [myapp] : warning: slow"
+        );
+        assert_eq!(
+            printer.format("2024-01-01T00:00:01Z", "ERROR boom"),
+            "2024-01-01T00:00:01Z [myapp] ERROR: ERROR boom"
+        );
+        assert!(parse_output_template("{{.time").is_err());
+        assert!(parse_output_template("{{.host}}").is_err());
+    }
+
+    #[test]
+    fn test_parse_since_accepts_durations_and_cursors() {
+        assert_eq!(
+            parse_since("30m").unwrap(),
+            Since::Ago(Duration::from_secs(30 * 60))
+        );
+        assert_eq!(
+            parse_since("2024-01-01T01:00:00.5+01:00").unwrap(),
+            Since::At("2024-01-01T00:00:00.5Z".parse().unwrap())
+        );
+        assert!(parse_since("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_shown_lines_track_time_range() {
+        let mut shown = ShownLines::default();
+        shown.record("2024-01-01T00:00:01Z");
+        shown.record("2024-01-01T00:00:05Z");
+        assert_eq!(shown.count, 2);
+        assert_eq!(shown.first.as_deref(), Some("2024-01-01T00:00:01Z"));
+        assert_eq!(shown.last.as_deref(), Some("2024-01-01T00:00:05Z"));
+    }
+
+    #[test]
+    fn test_parse_tail() {
+        assert_eq!(parse_tail("0").unwrap(), Tail::Lines(0));
+        assert_eq!(parse_tail("25").unwrap(), Tail::Lines(25));
+        assert_eq!(parse_tail("all").unwrap(), Tail::All);
+        assert!(parse_tail("-1").is_err());
+        assert!(parse_tail("some").is_err());
+    }
+
+    #[test]
+    fn test_heartbeat_duration_must_be_joined_to_the_flag() {
+        let command = LogsCommand::parse_from(["logs", "--follow", "--heartbeat", "myapp"]);
+        assert_eq!(command.heartbeat, Some(Duration::from_secs(30)));
+        assert_eq!(command.app.as_deref(), Some("myapp"));
+        let command = LogsCommand::parse_from(["logs", "myapp", "--follow", "--heartbeat=1m"]);
+        assert_eq!(command.heartbeat, Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_quiet_spell_heartbeats_then_times_out() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let heartbeat = Some(Duration::from_secs(30));
+        let timeout = Some(Duration::from_secs(60));
+        let mut quiet = QuietSpell::new(start);
+        assert_eq!(quiet.check(0, at(29), heartbeat, timeout), Quiet::No);
+        assert_eq!(
+            quiet.check(0, at(30), heartbeat, timeout),
+            Quiet::Heartbeat(Duration::from_secs(30))
+        );
+        assert_eq!(quiet.check(0, at(45), heartbeat, timeout), Quiet::No);
+        // A new line starts the quiet spell over
+        assert_eq!(quiet.check(3, at(50), heartbeat, timeout), Quiet::No);
+        assert_eq!(quiet.deadline(timeout), Some(at(110)));
+        assert_eq!(quiet.deadline(None), None);
+        assert_eq!(quiet.check(3, at(100), None, timeout), Quiet::No);
+        assert_eq!(
+            quiet.check(3, at(110), heartbeat, timeout),
+            Quiet::TimedOut(Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn test_lines_of_several_apps_are_merged_by_time() {
+        let source = |name: &str| LogSource {
+            name: name.to_owned(),
+            stream: LogStream::App(Uuid::new_v4()),
+            cursor: LogCursor::new("2024-01-01T00:00:00Z".to_owned()),
+            printer: LogsCommand::parse_from(["logs", name]).line_printer(name),
+            shown: ShownLines::default(),
+            failures: 0,
+        };
+        let entries = |lines: &[(&str, &str)]| {
+            let lines = lines
+                .iter()
+                .map(|(time, line)| serde_json::json!({ "time": time, "line": line }))
+                .collect::<Vec<_>>();
+            Ok(vec![serde_json::from_value::<Entry>(
+                serde_json::json!({ "logLines": lines }),
+            )
+            .unwrap()])
+        };
+        let mut sources = vec![source("a"), source("b"), source("c")];
+        let fetched = vec![
+            entries(&[
+                ("2024-01-01T00:00:01Z", "a1"),
+                ("2024-01-01T00:00:03Z", "a3"),
+            ]),
+            Err(anyhow!("unavailable")),
+            entries(&[("2024-01-01T00:00:02Z", "c2")]),
+        ];
+
+        let (lines, outcomes) = merge_new_lines(&mut sources, fetched, None);
+        let lines = lines
+            .iter()
+            .map(|(index, _, line)| (*index, line.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [(0, "a1"), (2, "c2"), (0, "a3")]);
+        // The failed app is reported, and its cursor stays where it was
+        assert!(outcomes[0].is_ok() && outcomes[1].is_err() && outcomes[2].is_ok());
+        assert_eq!(sources[1].cursor.since, "2024-01-01T00:00:00Z");
+        assert_eq!(sources[0].cursor.since, "2024-01-01T00:00:03Z");
+    }
+
+    #[tokio::test]
+    async fn test_logs_are_fetched_for_the_named_app() -> Result<()> {
+        let app_id = Uuid::new_v4();
+        let apps = cloud_openapi::models::AppItemPage {
+            items: vec![cloud_openapi::models::AppItem {
+                id: app_id,
+                name: "myapp".to_owned(),
+                ..Default::default()
+            }],
+            is_last_page: true,
+            ..Default::default()
+        };
+        let logs = serde_json::json!({ "entries": [] });
+        let client = RecordedClient::new()
+            .respond("list_apps", apps)
+            .respond("app_logs_raw", logs);
+        let dir = tempfile::tempdir()?;
+        let mut cache = ResponseCache::at(dir.path().join("config.json"), Duration::from_secs(60));
+
+        let command = LogsCommand::parse_from(["logs", "myapp", "--tail", "5"]);
+        command.logs(&mut &client, &mut cache).await?;
+
+        let fetched = client.calls_to("app_logs_raw");
+        assert!(!fetched.is_empty());
+        assert!(fetched.iter().all(|args| args["id"] == app_id.to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_channel_logs_use_the_cached_channel_id() -> Result<()> {
+        let app_id = Uuid::new_v4();
+        let channel_id = Uuid::new_v4();
+        let apps = cloud_openapi::models::AppItemPage {
+            items: vec![cloud_openapi::models::AppItem {
+                id: app_id,
+                name: "myapp".to_owned(),
+                ..Default::default()
+            }],
+            is_last_page: true,
+            ..Default::default()
+        };
+        let channels = vec![cloud::models::ChannelItem {
+            id: channel_id,
+            app_id,
+            name: "spin-canary".to_owned(),
+            ..Default::default()
+        }];
+        let logs = serde_json::json!({ "entries": [] });
+        let client = RecordedClient::new()
+            .respond("list_apps", apps)
+            .respond("list_channels", channels)
+            .respond("channel_logs_raw", logs);
+        let dir = tempfile::tempdir()?;
+        let mut cache = ResponseCache::at(dir.path().join("config.json"), Duration::from_secs(60));
+
+        for _ in 0..2 {
+            let command = LogsCommand::parse_from([
+                "logs",
+                "myapp",
+                "--channel",
+                "spin-canary",
+                "--tail",
+                "5",
+            ]);
+            command.logs(&mut &client, &mut cache).await?;
+        }
+
+        // The second run finds both ids in the cache
+        assert_eq!(client.calls_to("list_apps").len(), 1);
+        assert_eq!(client.calls_to("list_channels").len(), 1);
+        let fetched = client.calls_to("channel_logs_raw");
+        assert!(!fetched.is_empty());
+        assert!(fetched
+            .iter()
+            .all(|args| args["channel_id"] == channel_id.to_string()));
+        assert!(client.calls_to("app_logs_raw").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconnect_delay_backs_off_up_to_a_minute() {
+        let interval = Duration::from_secs(2);
+        assert_eq!(reconnect_delay(interval, 0), interval);
+        assert_eq!(reconnect_delay(interval, 1), Duration::from_secs(4));
+        assert_eq!(reconnect_delay(interval, 3), Duration::from_secs(16));
+        assert_eq!(reconnect_delay(interval, 10), Duration::from_secs(60));
+    }
+}
@@ -1,33 +1,223 @@
+pub mod api;
+pub mod app_picker;
 pub mod apps;
+pub mod cache;
+pub mod canary;
+pub mod channels;
+pub mod ci;
+pub mod completion;
+pub mod config;
+pub mod credentials;
 pub mod deploy;
+pub mod doctor;
+pub mod domains;
+pub mod env;
 pub mod key_value;
 pub mod link;
 pub mod links_output;
 pub mod links_target;
 pub mod login;
 pub mod logs;
+pub mod rollback;
 pub mod sqlite;
+pub mod sqlite_dump;
+pub mod sqlite_shell;
+pub mod support;
+pub mod timings;
+pub mod upgrade;
 pub mod variables;
 
-use crate::{commands::deploy::login_connection, opts::DEPLOYMENT_ENV_NAME_ENV};
+use crate::{
+    commands::{
+        deploy::{expires_within, login_connection},
+        login::LoginConnection,
+    },
+    errors::CliError,
+    opts::DEPLOYMENT_ENV_NAME_ENV,
+    output,
+};
 use anyhow::{Context, Result};
 use clap::Args;
 use cloud::{
-    client::{Client as CloudClient, ConnectionConfig},
-    CloudClientExt,
+    client::{Client, ConnectionConfig, HttpConfig},
+    recording::RecordingClient,
+    retry::{RetryPolicy, RetryingClient, DEFAULT_RETRIES},
+    CloudClientExt, CloudClientInterface,
 };
+use std::sync::OnceLock;
 use uuid::Uuid;
 
 const DEFAULT_CLOUD_URL: &str = "https://cloud.fermyon.com/";
 
+// How long before expiry a refreshable token is renewed
+const TOKEN_REFRESH_MARGIN_MINUTES: i64 = 5;
+
+static RETRY_POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+static HTTP_CONFIG: OnceLock<HttpConfig> = OnceLock::new();
+
+/// The client used by commands, which retries transient failures according
+/// to the global `--retries` and `--retry-backoff` flags, and records each
+/// attempt when `--record` is given.
+pub(crate) type CloudClient = RetryingClient<RecordingClient<Client>>;
+
+#[derive(Debug, Args)]
+pub(crate) struct RetryArgs {
+    /// How many times to retry requests that fail with a transient error
+    #[clap(long = "retries", global = true, default_value_t = DEFAULT_RETRIES)]
+    pub retries: u32,
+
+    /// Delay before the first retry, which doubles for each further retry (e.g. "500ms" or "2s")
+    #[clap(
+        long = "retry-backoff",
+        global = true,
+        default_value = "500ms",
+        value_parser = clap::builder::ValueParser::new(humantime::parse_duration)
+    )]
+    pub retry_backoff: std::time::Duration,
+
+    /// Fail as soon as a request fails, even when rate limited or the failure looks transient
+    #[clap(long = "no-retry", global = true, conflicts_with = "retries")]
+    pub no_retry: bool,
+}
+
+/// Sets the retry policy for clients created from here on. Only the first call has any effect.
+pub(crate) fn set_retry_policy(args: &RetryArgs) {
+    let policy = if args.no_retry {
+        RetryPolicy::none()
+    } else {
+        RetryPolicy {
+            retries: args.retries,
+            backoff: args.retry_backoff,
+        }
+    };
+    _ = RETRY_POLICY.set(policy);
+}
+
+pub(crate) fn retry_policy() -> RetryPolicy {
+    RETRY_POLICY.get().copied().unwrap_or_default()
+}
+
+/// Tells the user why a command has gone quiet while it waits out rate
+/// limiting. Like other progress, it is left out with `--quiet` or JSON output.
+pub(crate) fn print_rate_limited(delay: std::time::Duration) {
+    if output::is_quiet() || output::is_json() {
+        return;
+    }
+    eprintln!(
+        "Rate limited by Fermyon Cloud, retrying in {}s",
+        delay.as_secs_f64().ceil()
+    );
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct HttpArgs {
+    /// Time limit for each HTTP request, such as "30s". By default requests may take as long as they need.
+    #[clap(
+        long = "http-timeout",
+        global = true,
+        env = "SPIN_CLOUD_HTTP_TIMEOUT",
+        value_parser = clap::builder::ValueParser::new(humantime::parse_duration)
+    )]
+    pub http_timeout: Option<std::time::Duration>,
+
+    /// Time limit for connecting to the server, such as "10s"
+    #[clap(
+        long = "connect-timeout",
+        global = true,
+        env = "SPIN_CLOUD_CONNECT_TIMEOUT",
+        value_parser = clap::builder::ValueParser::new(humantime::parse_duration)
+    )]
+    pub connect_timeout: Option<std::time::Duration>,
+}
+
+/// Sets the timeouts, and reads the proxy settings, for HTTP clients created
+/// from here on. Only the first call has any effect.
+pub(crate) fn set_http_config(args: &HttpArgs) -> Result<()> {
+    let config = HttpConfig::from_env(args.http_timeout, args.connect_timeout)?;
+    _ = HTTP_CONFIG.set(config);
+    Ok(())
+}
+
+/// The network settings every HTTP client should be created with
+pub(crate) fn http_config() -> HttpConfig {
+    HTTP_CONFIG.get().cloned().unwrap_or_default()
+}
+
 pub(crate) async fn create_cloud_client(deployment_env_id: Option<&str>) -> Result<CloudClient> {
     let login_connection = login_connection(deployment_env_id).await?;
-    let connection_config = ConnectionConfig {
+    Ok(client_for_connection(&login_connection))
+}
+
+/// A cloud client for long-running commands such as `logs --follow`, which
+/// may outlive the token the client was created with. Fetch the client from
+/// the session before each request and it is rebuilt with a refreshed token
+/// once the current one nears expiry.
+pub(crate) struct CloudClientSession {
+    deployment_env_id: Option<String>,
+    login_connection: LoginConnection,
+    client: CloudClient,
+}
+
+impl CloudClientSession {
+    pub async fn new(deployment_env_id: Option<&str>) -> Result<Self> {
+        let login_connection = login_connection(deployment_env_id).await?;
+        let client = client_for_connection(&login_connection);
+        Ok(Self {
+            deployment_env_id: deployment_env_id.map(|id| id.to_owned()),
+            login_connection,
+            client,
+        })
+    }
+
+    pub async fn client(&mut self) -> Result<&CloudClient> {
+        let margin = chrono::Duration::minutes(TOKEN_REFRESH_MARGIN_MINUTES);
+        // Tokens without a refresh token cannot be renewed ahead of time
+        let refreshable = self.login_connection.refresh_token.is_some();
+        if refreshable && expires_within(&self.login_connection, margin)? {
+            // `login_connection` refreshes and saves the token
+            self.login_connection = login_connection(self.deployment_env_id.as_deref()).await?;
+            self.client = client_for_connection(&self.login_connection);
+        }
+        Ok(&self.client)
+    }
+}
+
+/// Where a command gets its client from before each request. Long-running
+/// commands take one so that tests can hand them a fixed client in place of a
+/// [`CloudClientSession`].
+pub(crate) trait ClientSource {
+    type Client: CloudClientInterface;
+
+    async fn client(&mut self) -> Result<&Self::Client>;
+}
+
+impl ClientSource for CloudClientSession {
+    type Client = CloudClient;
+
+    async fn client(&mut self) -> Result<&CloudClient> {
+        CloudClientSession::client(self).await
+    }
+}
+
+impl<C: CloudClientInterface> ClientSource for &C {
+    type Client = C;
+
+    async fn client(&mut self) -> Result<&C> {
+        Ok(*self)
+    }
+}
+
+pub(crate) fn client_for_connection(login_connection: &LoginConnection) -> CloudClient {
+    let client = Client::new(ConnectionConfig {
         url: login_connection.url.to_string(),
         insecure: login_connection.danger_accept_invalid_certs,
-        token: login_connection.token,
-    };
-    Ok(CloudClient::new(connection_config))
+        token: login_connection.token.clone(),
+        http: http_config(),
+    });
+    let client = RecordingClient::new(client, support::recorder());
+    RetryingClient::new(client, retry_policy())
+        .on_rate_limit(print_rate_limited)
+        .on_call(timings::record_api_call)
 }
 
 async fn client_and_app_id(
@@ -35,11 +225,11 @@ async fn client_and_app_id(
     app: &str,
 ) -> Result<(CloudClient, Uuid)> {
     let client = create_cloud_client(deployment_env_id).await?;
-    let app_id = client
-        .get_app_id(app)
+    let mut cache = cache::ResponseCache::open(deployment_env_id)?;
+    let app_id = cache::app_id(&client, &mut cache, app)
         .await
         .with_context(|| format!("Error finding app_id for app '{}'", app))?
-        .with_context(|| format!("Could not find app '{}'", app))?;
+        .with_context(|| CliError::not_found(format!("Could not find app '{}'", app)))?;
     Ok((client, app_id))
 }
 
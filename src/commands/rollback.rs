@@ -0,0 +1,267 @@
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use cloud::{CloudClientExt, CloudClientInterface, SPIN_DEPLOY_CHANNEL_NAME};
+use cloud_openapi::models::RevisionItem;
+use uuid::Uuid;
+
+use crate::commands::{app_picker::app_or_pick, client_and_app_id, CommonArgs};
+use crate::output;
+
+/// Roll back an app to a previously deployed revision. The app keeps serving
+/// that revision until the next `spin cloud deploy`.
+#[derive(Parser, Debug)]
+pub struct RollbackCommand {
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    pub app: Option<String>,
+
+    /// The revision (application version) to roll back to. If omitted, the
+    /// revision deployed immediately before the active one is used.
+    #[clap(name = "to", long = "to")]
+    pub revision: Option<String>,
+
+    /// List the revisions available to roll back to
+    #[clap(
+        name = "list",
+        long = "list",
+        takes_value = false,
+        conflicts_with = "to"
+    )]
+    pub list: bool,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+impl RollbackCommand {
+    pub async fn run(self) -> Result<()> {
+        let deployment_env_id = self.common.deployment_env_id.as_deref();
+        let app = app_or_pick(deployment_env_id, self.app.clone()).await?;
+        let (client, app_id) = client_and_app_id(deployment_env_id, &app).await?;
+        self.rollback(client, app_id, &app).await
+    }
+
+    async fn rollback(
+        self,
+        client: impl CloudClientInterface,
+        app_id: Uuid,
+        app: &str,
+    ) -> Result<()> {
+        // Both lookups only need the app id, so make them together rather
+        // than paying the round trip twice
+        let (channel, revisions) = tokio::try_join!(
+            async {
+                client
+                    .get_channel(app_id, SPIN_DEPLOY_CHANNEL_NAME)
+                    .await
+                    .with_context(|| format!("Problem finding the deploy channel for app '{app}'"))
+            },
+            async {
+                client
+                    .get_app_revisions(app_id)
+                    .await
+                    .with_context(|| format!("Problem listing revisions for app '{app}'"))
+            },
+        )?;
+
+        if self.list {
+            return print_revisions(&revisions, channel.active_revision_id);
+        }
+
+        let target = match &self.revision {
+            Some(version) => {
+                let Some(revision) = revisions.iter().find(|r| &r.revision_number == version)
+                else {
+                    bail!("App '{app}' has no revision '{version}'");
+                };
+                revision
+            }
+            None => {
+                let Some(revision) = previous_revision(&revisions, channel.active_revision_id)
+                else {
+                    bail!("App '{app}' has no earlier revision to roll back to");
+                };
+                revision
+            }
+        };
+
+        if channel.active_revision_id == Some(target.id) {
+            bail!(
+                "Revision '{}' is already active for app '{}'",
+                target.revision_number,
+                app
+            );
+        }
+
+        client
+            .set_channel_revision(channel.id, target.id)
+            .await
+            .with_context(|| {
+                format!(
+                    "Problem rolling back app '{}' to revision '{}'",
+                    app, target.revision_number
+                )
+            })?;
+        output::success(
+            &format!(
+                "App \"{}\" rolled back to revision \"{}\". It serves this revision until the next deploy.",
+                app, target.revision_number
+            ),
+            serde_json::json!({ "app": app, "revision": target.revision_number }),
+        )
+    }
+}
+
+// Revisions are listed in the order they were registered, so the one before
+// the active revision is the previous deploy.
+fn previous_revision(
+    revisions: &[RevisionItem],
+    active_revision_id: Option<Uuid>,
+) -> Option<&RevisionItem> {
+    let active_index = match active_revision_id {
+        Some(id) => revisions.iter().position(|r| r.id == id)?,
+        None => revisions.len(),
+    };
+    active_index.checked_sub(1).and_then(|i| revisions.get(i))
+}
+
+fn print_revisions(revisions: &[RevisionItem], active_revision_id: Option<Uuid>) -> Result<()> {
+    if output::is_json() {
+        let revisions = revisions
+            .iter()
+            .rev()
+            .map(|revision| {
+                serde_json::json!({
+                    "revision": revision.revision_number,
+                    "active": Some(revision.id) == active_revision_id,
+                })
+            })
+            .collect::<Vec<_>>();
+        return output::print_json(&revisions);
+    }
+    if revisions.is_empty() {
+        println!("No revisions found");
+        return Ok(());
+    }
+    for revision in revisions.iter().rev() {
+        if Some(revision.id) == active_revision_id {
+            println!("{} (active)", revision.revision_number);
+        } else {
+            println!("{}", revision.revision_number);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod rollback_tests {
+    use super::*;
+    use cloud::{models::ChannelItem, MockCloudClientInterface};
+
+    fn revision(app_id: Uuid, number: &str) -> RevisionItem {
+        RevisionItem {
+            id: Uuid::new_v4(),
+            app_id,
+            revision_number: number.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn command(revision: Option<&str>) -> RollbackCommand {
+        RollbackCommand {
+            app: Some("app".to_owned()),
+            revision: revision.map(|r| r.to_owned()),
+            list: false,
+            common: Default::default(),
+        }
+    }
+
+    fn channel(app_id: Uuid, active: &RevisionItem) -> ChannelItem {
+        ChannelItem {
+            id: Uuid::new_v4(),
+            app_id,
+            name: SPIN_DEPLOY_CHANNEL_NAME.to_owned(),
+            active_revision_id: Some(active.id),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_previous_revision_is_the_one_before_active() {
+        let app_id = Uuid::new_v4();
+        let revisions = vec![
+            revision(app_id, "1.0.0"),
+            revision(app_id, "1.1.0"),
+            revision(app_id, "1.2.0"),
+        ];
+        let previous = previous_revision(&revisions, Some(revisions[2].id)).unwrap();
+        assert_eq!(previous.revision_number, "1.1.0");
+        assert!(previous_revision(&revisions, Some(revisions[0].id)).is_none());
+        assert!(previous_revision(&revisions, Some(Uuid::new_v4())).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rollback_without_target_uses_previous_revision() -> Result<()> {
+        let app_id = Uuid::new_v4();
+        let revisions = vec![revision(app_id, "1.0.0"), revision(app_id, "1.1.0")];
+        let channel = channel(app_id, &revisions[1]);
+        let channel_id = channel.id;
+        let expected_revision_id = revisions[0].id;
+
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_list_channels()
+            .return_once(move |_| Ok(vec![channel]));
+        mock.expect_list_revisions().return_once(move || {
+            Ok(cloud_openapi::models::RevisionItemPage {
+                items: revisions,
+                is_last_page: true,
+                ..Default::default()
+            })
+        });
+        mock.expect_set_channel_revision()
+            .withf(move |c, r| *c == channel_id && *r == expected_revision_id)
+            .returning(|_, _| Ok(()));
+
+        command(None).rollback(mock, app_id, "app").await
+    }
+
+    #[tokio::test]
+    async fn test_deploy_after_rollback_serves_the_new_revision() -> Result<()> {
+        let app = crate::commands::canary::canary_tests::FakeApp::new();
+        app.deploy("1.0.0").await?;
+        app.deploy("1.1.0").await?;
+
+        command(None)
+            .rollback(app.client(), app.app_id, "app")
+            .await?;
+        assert_eq!(app.served(), "1.0.0");
+
+        app.deploy("1.2.0").await?;
+        assert_eq!(app.served(), "1.2.0");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rollback_to_unknown_revision_then_error() -> Result<()> {
+        let app_id = Uuid::new_v4();
+        let revisions = vec![revision(app_id, "1.0.0"), revision(app_id, "1.1.0")];
+        let channel = channel(app_id, &revisions[1]);
+
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_list_channels()
+            .return_once(move |_| Ok(vec![channel]));
+        mock.expect_list_revisions().return_once(move || {
+            Ok(cloud_openapi::models::RevisionItemPage {
+                items: revisions,
+                is_last_page: true,
+                ..Default::default()
+            })
+        });
+
+        let result = command(Some("0.9.0")).rollback(mock, app_id, "app").await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "App 'app' has no revision '0.9.0'"
+        );
+        Ok(())
+    }
+}
@@ -1,17 +1,22 @@
 use crate::commands::links_target::ResourceTarget;
+use crate::commands::sqlite_dump;
+use crate::commands::sqlite_shell;
 use crate::commands::{create_cloud_client, disallow_empty, CommonArgs};
 use anyhow::bail;
 use anyhow::{Context, Result};
 use clap::{Parser, ValueEnum};
-use cloud::CloudClientInterface;
+use cloud::{models::SqlStatementResult, CloudClientInterface};
 use cloud_openapi::models::Database;
+use comfy_table::presets::ASCII_BORDERS_ONLY_CONDENSED;
 
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use crate::commands::links_output::{
-    print_json, print_table, prompt_delete_resource, ListFormat, ResourceGroupBy, ResourceLinks,
-    ResourceType,
+    print_json, print_table, prompt_delete_resource, ResourceGroupBy, ResourceLinks, ResourceType,
 };
+use crate::errors::CliError;
+use crate::output::{self, OutputFormat};
 
 /// Manage Fermyon Cloud SQLite databases
 #[derive(Parser, Debug)]
@@ -21,12 +26,18 @@ pub enum SqliteCommand {
     Create(CreateCommand),
     /// Delete a SQLite database
     Delete(DeleteCommand),
+    /// Write the schema and rows of a SQLite database as SQL statements
+    Dump(DumpCommand),
     /// Execute SQL statements against a SQLite database
     Execute(ExecuteCommand),
     /// List all your SQLite databases
     List(ListCommand),
     /// Rename a SQLite database. All existing links will automatically link to the database's new name.
     Rename(RenameCommand),
+    /// Execute the SQL statements in a dump file against a SQLite database
+    Restore(RestoreCommand),
+    /// Open an interactive SQL shell on a SQLite database
+    Shell(ShellCommand),
 }
 
 #[derive(Parser, Debug)]
@@ -66,8 +77,61 @@ pub struct ExecuteCommand {
     app: Option<String>,
 
     ///Statement to execute
-    #[clap(value_parser = clap::builder::ValueParser::new(disallow_empty))]
-    statement: String,
+    #[clap(name = "STATEMENT", value_parser = clap::builder::ValueParser::new(disallow_empty), required_unless_present = "FILE")]
+    statement: Option<String>,
+
+    /// File of SQL statements to execute, such as a schema
+    #[clap(name = "FILE", long = "file", conflicts_with = "STATEMENT")]
+    file: Option<PathBuf>,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+/// Virtual tables, such as full-text indexes, are created by the dump but
+/// their rows are not written, so they are empty once restored.
+#[derive(Parser, Debug)]
+pub struct DumpCommand {
+    /// Name of database to dump
+    name: String,
+
+    /// File to write the dump to. If omitted, the dump is printed.
+    #[clap(short = 'o', long = "output")]
+    output: Option<PathBuf>,
+
+    /// The most rows to fetch in each request
+    #[clap(long = "rows-per-request", default_value = "500", value_parser = clap::value_parser!(u64).range(1..))]
+    rows_per_request: u64,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+/// Meta-commands such as `.tables` and `.schema` are available, and input
+/// piped to the shell is run as a script.
+#[derive(Parser, Debug)]
+pub struct ShellCommand {
+    /// Name of database to open
+    name: String,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+/// Statements are sent in batches, each executed as it is read, so a restore
+/// that fails part way leaves the statements before the failing batch applied.
+#[derive(Parser, Debug)]
+pub struct RestoreCommand {
+    /// Name of database to restore into
+    name: String,
+
+    /// Dump file of SQL statements, such as one written by `sqlite dump`
+    #[clap(long = "file")]
+    file: PathBuf,
+
+    /// The most statements to execute in each request
+    #[clap(long = "statements-per-request", default_value = "200", value_parser = clap::value_parser!(u64).range(1..))]
+    statements_per_request: u64,
 
     #[clap(flatten)]
     common: CommonArgs,
@@ -98,9 +162,6 @@ pub struct ListCommand {
     /// Grouping strategy of tabular list [default: app]
     #[clap(value_enum, short = 'g', long = "group-by")]
     group_by: Option<GroupBy>,
-    /// Format of list
-    #[clap(value_enum, long = "format", default_value = "table")]
-    format: ListFormat,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum, Default)]
@@ -155,6 +216,18 @@ impl SqliteCommand {
                 let client = create_cloud_client(cmd.common.deployment_env_id.as_deref()).await?;
                 cmd.run(client).await
             }
+            Self::Dump(cmd) => {
+                let client = create_cloud_client(cmd.common.deployment_env_id.as_deref()).await?;
+                cmd.run(client).await
+            }
+            Self::Restore(cmd) => {
+                let client = create_cloud_client(cmd.common.deployment_env_id.as_deref()).await?;
+                cmd.run(client).await
+            }
+            Self::Shell(cmd) => {
+                let client = create_cloud_client(cmd.common.deployment_env_id.as_deref()).await?;
+                cmd.run(client).await
+            }
             Self::List(cmd) => cmd.run().await,
             Self::Rename(cmd) => cmd.run().await,
         }
@@ -215,23 +288,163 @@ impl ExecuteCommand {
         let database = target
             .find_in(to_resource_links(list), ResourceType::Database)?
             .name;
-        let statement = if let Some(path) = self.statement.strip_prefix('@') {
-            std::fs::read_to_string(path)
-                .with_context(|| format!("could not read sql file at '{path}'"))?
-        } else {
-            self.statement
+        let statement = match (&self.file, self.statement) {
+            (Some(path), _) => std::fs::read_to_string(path)
+                .with_context(|| format!("could not read sql file at '{}'", path.display()))?,
+            (None, Some(statement)) => match statement.strip_prefix('@') {
+                Some(path) => std::fs::read_to_string(path)
+                    .with_context(|| format!("could not read sql file at '{path}'"))?,
+                None => statement,
+            },
+            (None, None) => bail!("No statement to execute"), // Should be prevented by clap
         };
-        client
+        let results = client
             .execute_sql(database, statement)
             .await
             .context("Problem executing SQL")?;
+        print_results(&results, output::format())
+    }
+}
+
+impl ShellCommand {
+    pub async fn run(self, client: impl CloudClientInterface) -> Result<()> {
+        ensure_database_exists(&client, &self.name).await?;
+        sqlite_shell::run(&client, &self.name).await
+    }
+}
+
+impl DumpCommand {
+    pub async fn run(self, client: impl CloudClientInterface) -> Result<()> {
+        ensure_database_exists(&client, &self.name).await?;
+        let rows_per_request = usize::try_from(self.rows_per_request).unwrap_or(usize::MAX);
+        let Some(path) = &self.output else {
+            let mut stdout = std::io::BufWriter::new(std::io::stdout());
+            let summary =
+                sqlite_dump::dump(&client, &self.name, rows_per_request, &mut stdout).await?;
+            warn_of_virtual_tables(&summary);
+            return Ok(());
+        };
+        // The dump is written beside the output file and only moved into
+        // place once complete, so a failed dump leaves any earlier one intact
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => std::path::Path::new("."),
+        };
+        let temp = tempfile::NamedTempFile::new_in(dir)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut out = std::io::BufWriter::new(temp);
+        let summary = sqlite_dump::dump(&client, &self.name, rows_per_request, &mut out).await?;
+        out.into_inner()
+            .map_err(|e| e.into_error())
+            .and_then(|temp| temp.persist(path).map_err(|e| e.error))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        warn_of_virtual_tables(&summary);
+        if output::is_json() {
+            return output::print_json(&serde_json::json!({
+                "database": self.name,
+                "path": path,
+                "tables": summary.tables,
+                "rows": summary.rows,
+                "virtualTables": summary.virtual_tables,
+            }));
+        }
+        println!(
+            "Database \"{}\" dumped to {} ({} tables, {} rows)",
+            self.name,
+            path.display(),
+            summary.tables,
+            summary.rows
+        );
         Ok(())
     }
 }
 
+fn warn_of_virtual_tables(summary: &sqlite_dump::DumpSummary) {
+    for table in &summary.virtual_tables {
+        eprintln!(
+            "Warning: the rows of virtual table \"{table}\" were not dumped; it is created empty on restore"
+        );
+    }
+}
+
+impl RestoreCommand {
+    pub async fn run(self, client: impl CloudClientInterface) -> Result<()> {
+        ensure_database_exists(&client, &self.name).await?;
+        let file = std::fs::File::open(&self.file)
+            .with_context(|| format!("could not read sql file at '{}'", self.file.display()))?;
+        let statements_per_request =
+            usize::try_from(self.statements_per_request).unwrap_or(usize::MAX);
+        let summary = sqlite_dump::restore(
+            &client,
+            &self.name,
+            statements_per_request,
+            std::io::BufReader::new(file),
+        )
+        .await?;
+        if output::is_json() {
+            return output::print_json(&serde_json::json!({
+                "database": self.name,
+                "statements": summary.statements,
+            }));
+        }
+        println!(
+            "Database \"{}\" restored from {} ({} statements)",
+            self.name,
+            self.file.display(),
+            summary.statements
+        );
+        Ok(())
+    }
+}
+
+async fn ensure_database_exists(client: &impl CloudClientInterface, name: &str) -> Result<()> {
+    let list = client
+        .get_databases(None)
+        .await
+        .context("Problem fetching databases")?;
+    if !list.iter().any(|d| d.name == name) {
+        bail!(CliError::not_found(format!(
+            "No database found with name \"{name}\""
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn print_results(results: &[SqlStatementResult], format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            output::print_json(results)?;
+        }
+        OutputFormat::Table => {
+            // Statements such as CREATE or INSERT produce no columns and are not printed
+            for result in results.iter().filter(|r| !r.columns.is_empty()) {
+                let mut table = comfy_table::Table::new();
+                table.load_preset(ASCII_BORDERS_ONLY_CONDENSED);
+                table.set_header(&result.columns);
+                table.add_rows(
+                    result
+                        .rows
+                        .iter()
+                        .map(|row| row.iter().map(format_cell).collect::<Vec<_>>()),
+                );
+                println!("{table}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn format_cell(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => "NULL".to_owned(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
 impl ListCommand {
     pub async fn run(self) -> Result<()> {
-        if let (ListFormat::Json, Some(_)) = (&self.format, self.group_by) {
+        if let (OutputFormat::Json, Some(_)) = (output::format(), self.group_by) {
             bail!("Grouping is not supported with JSON format output")
         }
 
@@ -241,24 +454,24 @@ impl ListCommand {
             .await
             .context("Problem listing databases")?;
 
-        if databases.is_empty() {
+        if databases.is_empty() && !output::is_json() {
             println!("No databases");
             return Ok(());
         }
         if let Some(name) = &self.database {
             databases.retain(|db| db.name == *name);
-            if databases.is_empty() {
+            if databases.is_empty() && !output::is_json() {
                 println!("No database with name '{name}'");
                 return Ok(());
             }
         }
 
         let resource_links = to_resource_links(databases);
-        match self.format {
-            ListFormat::Json => {
+        match output::format() {
+            OutputFormat::Json => {
                 print_json(resource_links, self.app.as_deref(), ResourceType::Database)
             }
-            ListFormat::Table => print_table(
+            OutputFormat::Table => print_table(
                 resource_links,
                 self.app.as_deref(),
                 self.group_by.map(Into::into),
@@ -387,7 +600,8 @@ mod sqlite_tests {
             label: None,
             app: None,
             common: Default::default(),
-            statement: sql.to_owned(),
+            statement: Some(sql.to_owned()),
+            file: None,
         };
 
         let mut mock = MockCloudClientInterface::new();
@@ -395,7 +609,7 @@ mod sqlite_tests {
             .returning(move |_| Ok(vec![Database::new(db.to_string(), vec![])]));
         mock.expect_execute_sql()
             .withf(move |dbarg, sqlarg| dbarg == db && sqlarg == sql)
-            .returning(|_, _| Ok(()));
+            .returning(|_, _| Ok(vec![]));
 
         command.run(mock).await
     }
@@ -411,7 +625,8 @@ mod sqlite_tests {
             label: None,
             app: None,
             common: Default::default(),
-            statement: sql.to_owned(),
+            statement: Some(sql.to_owned()),
+            file: None,
         };
 
         let mut mock = MockCloudClientInterface::new();
@@ -440,7 +655,8 @@ mod sqlite_tests {
             label: Some(label.to_string()),
             app: Some(app.to_string()),
             common: Default::default(),
-            statement: sql.to_owned(),
+            statement: Some(sql.to_owned()),
+            file: None,
         };
 
         let mut mock = MockCloudClientInterface::new();
@@ -448,7 +664,7 @@ mod sqlite_tests {
             .returning(move |_| Ok(fake_dbs()));
         mock.expect_execute_sql()
             .withf(move |dbarg, sqlarg| dbarg == "db2" && sqlarg == sql)
-            .returning(|_, _| Ok(()));
+            .returning(|_, _| Ok(vec![]));
 
         command.run(mock).await
     }
@@ -464,7 +680,8 @@ mod sqlite_tests {
             label: Some(label.to_string()),
             app: Some(app.to_string()),
             common: Default::default(),
-            statement: sql.to_owned(),
+            statement: Some(sql.to_owned()),
+            file: None,
         };
 
         let mut mock = MockCloudClientInterface::new();
@@ -482,6 +699,13 @@ mod sqlite_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_format_cell() {
+        assert_eq!(format_cell(&serde_json::Value::Null), "NULL");
+        assert_eq!(format_cell(&serde_json::json!("text")), "text");
+        assert_eq!(format_cell(&serde_json::json!(42)), "42");
+    }
+
     fn fake_dbs() -> Vec<Database> {
         vec![
             Database::new(
@@ -0,0 +1,562 @@
+/// This module converts SQLite databases to and from SQL text, a chunk at a time
+use std::io::{BufRead, Write};
+
+use anyhow::{bail, Context, Result};
+use cloud::CloudClientInterface;
+
+// Tables are created before their rows are inserted, and indexes, triggers
+// and views afterwards so they are not maintained row by row during a restore
+const SCHEMA_QUERY: &str = "SELECT type, name, sql FROM sqlite_master \
+    WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%' \
+    ORDER BY CASE type WHEN 'table' THEN 0 ELSE 1 END, name";
+
+/// How much of a database `dump` wrote
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DumpSummary {
+    pub tables: usize,
+    pub rows: usize,
+    /// Virtual tables, such as full-text indexes, which are created by the
+    /// dump but whose rows are not written
+    pub virtual_tables: Vec<String>,
+}
+
+/// Writes the schema and rows of `database` as SQL statements, fetching at
+/// most `rows_per_request` rows at a time.
+pub async fn dump(
+    client: &impl CloudClientInterface,
+    database: &str,
+    rows_per_request: usize,
+    out: &mut impl Write,
+) -> Result<DumpSummary> {
+    let schema = query_rows(client, database, SCHEMA_QUERY.to_owned())
+        .await
+        .context("Problem reading the database schema")?;
+    let mut entries = vec![];
+    for row in &schema {
+        let [kind, name, sql] = row.as_slice() else {
+            bail!("Unexpected schema row {row:?}");
+        };
+        let (Some(kind), Some(name), Some(sql)) = (kind.as_str(), name.as_str(), sql.as_str())
+        else {
+            bail!("Unexpected schema row {row:?}");
+        };
+        entries.push((kind, name, sql));
+    }
+    // The tables that hold a virtual table's data are created along with it,
+    // and are named after it
+    let virtual_tables = entries
+        .iter()
+        .filter(|(kind, _, sql)| *kind == "table" && is_virtual_table(sql))
+        .map(|(_, name, _)| format!("{name}_"))
+        .collect::<Vec<_>>();
+    let is_shadow_table = |name: &str| virtual_tables.iter().any(|v| name.starts_with(v.as_str()));
+
+    let mut summary = DumpSummary::default();
+    let mut deferred = vec![];
+    for (kind, name, sql) in entries {
+        if kind != "table" {
+            deferred.push(sql);
+            continue;
+        }
+        if is_shadow_table(name) {
+            continue;
+        }
+        writeln!(out, "{sql};")?;
+        if is_virtual_table(sql) {
+            summary.virtual_tables.push(name.to_owned());
+            continue;
+        }
+        let order = match is_without_rowid(sql) {
+            true => Some(primary_key(client, database, name).await?),
+            false => None,
+        };
+        summary.rows += dump_rows(client, database, name, order, rows_per_request, out)
+            .await
+            .with_context(|| format!("Problem reading the rows of table {name:?}"))?;
+        summary.tables += 1;
+    }
+    for sql in deferred {
+        writeln!(out, "{sql};")?;
+    }
+    out.flush()?;
+    Ok(summary)
+}
+
+// Rows of a WITHOUT ROWID table are paged in the order of `order`, its
+// primary key, and other tables' rows by rowid
+async fn dump_rows(
+    client: &impl CloudClientInterface,
+    database: &str,
+    table: &str,
+    order: Option<String>,
+    rows_per_request: usize,
+    out: &mut impl Write,
+) -> Result<usize> {
+    let table = quote_identifier(table);
+    let mut count = 0;
+    // Rowid tables are paged by rowid, which unlike an offset stays cheap deep
+    // into a large table. The rowid is selected first and not written out.
+    let mut last_rowid = None::<i64>;
+    loop {
+        let query = match (&order, last_rowid) {
+            (Some(order), _) => format!(
+                "SELECT * FROM {table} ORDER BY {order} LIMIT {rows_per_request} OFFSET {count}"
+            ),
+            (None, None) => {
+                format!("SELECT rowid, * FROM {table} ORDER BY rowid LIMIT {rows_per_request}")
+            }
+            (None, Some(rowid)) => format!(
+                "SELECT rowid, * FROM {table} WHERE rowid > {rowid} ORDER BY rowid LIMIT {rows_per_request}"
+            ),
+        };
+        let rows = query_rows(client, database, query).await?;
+        for row in &rows {
+            let values = match order {
+                Some(_) => row.as_slice(),
+                None => {
+                    last_rowid = row.first().and_then(|rowid| rowid.as_i64());
+                    row.get(1..).unwrap_or_default()
+                }
+            };
+            let values = values.iter().map(sql_literal).collect::<Vec<_>>();
+            writeln!(out, "INSERT INTO {table} VALUES({});", values.join(","))?;
+        }
+        count += rows.len();
+        if rows.len() < rows_per_request {
+            return Ok(count);
+        }
+    }
+}
+
+// The columns of a table's primary key, in key order, as an ORDER BY list
+async fn primary_key(
+    client: &impl CloudClientInterface,
+    database: &str,
+    table: &str,
+) -> Result<String> {
+    let query = format!(
+        "SELECT name FROM pragma_table_info({}) WHERE pk > 0 ORDER BY pk",
+        sql_literal(&serde_json::Value::String(table.to_owned()))
+    );
+    let rows = query_rows(client, database, query)
+        .await
+        .with_context(|| format!("Problem reading the primary key of table {table:?}"))?;
+    let columns = rows
+        .iter()
+        .filter_map(|row| row.first().and_then(|name| name.as_str()))
+        .map(quote_identifier)
+        .collect::<Vec<_>>();
+    if columns.is_empty() {
+        bail!("Table {table:?} has no primary key");
+    }
+    Ok(columns.join(", "))
+}
+
+async fn query_rows(
+    client: &impl CloudClientInterface,
+    database: &str,
+    query: String,
+) -> Result<Vec<Vec<serde_json::Value>>> {
+    let results = client.execute_sql(database.to_owned(), query).await?;
+    Ok(results
+        .into_iter()
+        .next()
+        .map(|result| result.rows)
+        .unwrap_or_default())
+}
+
+/// How much of a dump `restore` executed
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RestoreSummary {
+    pub statements: usize,
+    pub requests: usize,
+}
+
+/// Executes the SQL statements read from `input` against `database`, sending
+/// at most `statements_per_request` statements in each request.
+pub async fn restore(
+    client: &impl CloudClientInterface,
+    database: &str,
+    statements_per_request: usize,
+    input: impl BufRead,
+) -> Result<RestoreSummary> {
+    let mut summary = RestoreSummary::default();
+    let mut splitter = StatementSplitter::default();
+    let mut batch = vec![];
+    for line in input.lines() {
+        batch.extend(splitter.push_line(&line?));
+        if batch.len() >= statements_per_request {
+            execute_batch(client, database, &mut batch, &mut summary).await?;
+        }
+    }
+    batch.extend(splitter.finish());
+    if !batch.is_empty() {
+        execute_batch(client, database, &mut batch, &mut summary).await?;
+    }
+    Ok(summary)
+}
+
+async fn execute_batch(
+    client: &impl CloudClientInterface,
+    database: &str,
+    batch: &mut Vec<String>,
+    summary: &mut RestoreSummary,
+) -> Result<()> {
+    let first = summary.statements + 1;
+    let last = summary.statements + batch.len();
+    client
+        .execute_sql(database.to_owned(), batch.join("\n"))
+        .await
+        .with_context(|| {
+            format!(
+                "Problem executing statements {first} to {last}. The {} statements before them were restored.",
+                summary.statements
+            )
+        })?;
+    summary.statements = last;
+    summary.requests += 1;
+    batch.clear();
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SplitState {
+    Normal,
+    Quoted(char),
+    BlockComment,
+}
+
+/// Splits SQL text, fed a line at a time, into complete statements. Comments
+/// are dropped, and semicolons inside quotes and trigger bodies do not end a
+/// statement.
+#[derive(Debug)]
+pub(crate) struct StatementSplitter {
+    current: String,
+    state: SplitState,
+}
+
+impl Default for StatementSplitter {
+    fn default() -> Self {
+        Self {
+            current: String::new(),
+            state: SplitState::Normal,
+        }
+    }
+}
+
+impl StatementSplitter {
+    pub(crate) fn push_line(&mut self, line: &str) -> Vec<String> {
+        let mut statements = vec![];
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match self.state {
+                SplitState::Quoted(close) => {
+                    self.current.push(c);
+                    if c == close {
+                        self.state = SplitState::Normal;
+                    }
+                }
+                SplitState::BlockComment => {
+                    if c == '*' && chars.peek() == Some(&'/') {
+                        chars.next();
+                        self.state = SplitState::Normal;
+                    }
+                }
+                SplitState::Normal => match c {
+                    '-' if chars.peek() == Some(&'-') => break,
+                    '/' if chars.peek() == Some(&'*') => {
+                        chars.next();
+                        self.state = SplitState::BlockComment;
+                    }
+                    '\'' | '"' | '`' => {
+                        self.current.push(c);
+                        self.state = SplitState::Quoted(c);
+                    }
+                    '[' => {
+                        self.current.push(c);
+                        self.state = SplitState::Quoted(']');
+                    }
+                    ';' => {
+                        self.current.push(c);
+                        if !is_unfinished_trigger(&self.current) {
+                            statements.extend(self.take());
+                        }
+                    }
+                    c => self.current.push(c),
+                },
+            }
+        }
+        if !self.current.is_empty() {
+            self.current.push('\n');
+        }
+        statements
+    }
+
+    /// Returns any trailing statement that was not terminated by a semicolon
+    pub(crate) fn finish(mut self) -> Option<String> {
+        self.take()
+    }
+
+    /// Whether a statement has been started but not yet finished
+    pub(crate) fn is_pending(&self) -> bool {
+        self.state != SplitState::Normal || !self.current.trim().is_empty()
+    }
+
+    fn take(&mut self) -> Option<String> {
+        let statement = std::mem::take(&mut self.current);
+        let statement = statement.trim();
+        match statement.trim_end_matches(';').trim().is_empty() {
+            true => None,
+            false => Some(statement.to_owned()),
+        }
+    }
+}
+
+// A trigger body holds statements of its own, so a trigger only ends at the
+// semicolon after its END
+fn is_unfinished_trigger(statement: &str) -> bool {
+    let upper = statement.to_ascii_uppercase();
+    let words = upper.split_whitespace().take(3).collect::<Vec<_>>();
+    let is_trigger = match words.as_slice() {
+        ["CREATE", "TRIGGER", ..] => true,
+        ["CREATE", "TEMP" | "TEMPORARY", "TRIGGER"] => true,
+        _ => false,
+    };
+    let body = upper.trim_end_matches(';').trim_end();
+    let ends_with_end = body.ends_with("END")
+        && !body[..body.len() - 3].ends_with(|c: char| c.is_ascii_alphanumeric() || c == '_');
+    is_trigger && !ends_with_end
+}
+
+fn is_virtual_table(sql: &str) -> bool {
+    let upper = sql.to_ascii_uppercase();
+    let words = upper.split_whitespace().take(3).collect::<Vec<_>>();
+    words == ["CREATE", "VIRTUAL", "TABLE"]
+}
+
+// Table options, such as WITHOUT ROWID and STRICT, follow the parenthesis
+// that closes the column definitions, and are separated by commas
+fn is_without_rowid(sql: &str) -> bool {
+    let Some(close) = sql.rfind(')') else {
+        return false;
+    };
+    let options = sql[close + 1..].trim_end_matches(';').to_ascii_uppercase();
+    options
+        .split(',')
+        .any(|option| option.split_whitespace().eq(["WITHOUT", "ROWID"]))
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// Blobs come back as arrays of bytes, and are written as blob literals
+fn sql_literal(value: &serde_json::Value) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+    match value {
+        serde_json::Value::Null => "NULL".to_owned(),
+        serde_json::Value::Bool(b) => u8::from(*b).to_string(),
+        serde_json::Value::Number(n) => n.to_string(),
+        serde_json::Value::String(s) => quote(s),
+        serde_json::Value::Array(items) => {
+            let bytes = items
+                .iter()
+                .map(|item| item.as_u64().and_then(|b| u8::try_from(b).ok()))
+                .collect::<Option<Vec<_>>>();
+            match bytes {
+                Some(bytes) => {
+                    let hex = bytes.iter().map(|b| format!("{b:02X}")).collect::<String>();
+                    format!("X'{hex}'")
+                }
+                None => quote(&value.to_string()),
+            }
+        }
+        serde_json::Value::Object(_) => quote(&value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod sqlite_dump_tests {
+    use super::*;
+    use cloud::{models::SqlStatementResult, MockCloudClientInterface};
+    use serde_json::json;
+
+    fn result(rows: Vec<Vec<serde_json::Value>>) -> Vec<SqlStatementResult> {
+        vec![SqlStatementResult {
+            columns: vec![],
+            rows,
+        }]
+    }
+
+    #[tokio::test]
+    async fn test_dump_pages_rows_by_rowid() -> Result<()> {
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_execute_sql().returning(|_, sql| {
+            Ok(match sql.as_str() {
+                SCHEMA_QUERY => result(vec![
+                    vec![
+                        json!("table"),
+                        json!("notes"),
+                        json!("CREATE TABLE notes (body TEXT)"),
+                    ],
+                    vec![
+                        json!("index"),
+                        json!("notes_body"),
+                        json!("CREATE INDEX notes_body ON notes (body)"),
+                    ],
+                ]),
+                r#"SELECT rowid, * FROM "notes" ORDER BY rowid LIMIT 2"# => result(vec![
+                    vec![json!(1), json!("it's")],
+                    vec![json!(4), json!(null)],
+                ]),
+                r#"SELECT rowid, * FROM "notes" WHERE rowid > 4 ORDER BY rowid LIMIT 2"# => {
+                    result(vec![vec![json!(7), json!([1, 255])]])
+                }
+                other => panic!("unexpected query {other}"),
+            })
+        });
+
+        let mut out = vec![];
+        let summary = dump(&mock, "db1", 2, &mut out).await?;
+        assert_eq!(
+            summary,
+            DumpSummary {
+                tables: 1,
+                rows: 3,
+                virtual_tables: vec![]
+            }
+        );
+        assert_eq!(
+            String::from_utf8(out)?,
+            "CREATE TABLE notes (body TEXT);\n\
+             INSERT INTO \"notes\" VALUES('it''s');\n\
+             INSERT INTO \"notes\" VALUES(NULL);\n\
+             INSERT INTO \"notes\" VALUES(X'01FF');\n\
+             CREATE INDEX notes_body ON notes (body);\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dump_orders_without_rowid_tables_and_skips_virtual_tables() -> Result<()> {
+        let mut mock = MockCloudClientInterface::new();
+        mock.expect_execute_sql().returning(|_, sql| {
+            Ok(match sql.as_str() {
+                SCHEMA_QUERY => result(vec![
+                    vec![
+                        json!("table"),
+                        json!("docs"),
+                        json!("CREATE VIRTUAL TABLE docs USING fts5(body)"),
+                    ],
+                    vec![
+                        json!("table"),
+                        json!("docs_content"),
+                        json!("CREATE TABLE 'docs_content'(id INTEGER PRIMARY KEY, c0)"),
+                    ],
+                    vec![
+                        json!("table"),
+                        json!("pairs"),
+                        json!("CREATE TABLE pairs (a, b, PRIMARY KEY (b, a))\n  without\trowid"),
+                    ],
+                ]),
+                "SELECT name FROM pragma_table_info('pairs') WHERE pk > 0 ORDER BY pk" => {
+                    result(vec![vec![json!("b")], vec![json!("a")]])
+                }
+                r#"SELECT * FROM "pairs" ORDER BY "b", "a" LIMIT 2 OFFSET 0"# => {
+                    result(vec![vec![json!(1), json!(2)]])
+                }
+                other => panic!("unexpected query {other}"),
+            })
+        });
+
+        let mut out = vec![];
+        let summary = dump(&mock, "db1", 2, &mut out).await?;
+        assert_eq!(
+            summary,
+            DumpSummary {
+                tables: 1,
+                rows: 1,
+                virtual_tables: vec!["docs".to_owned()]
+            }
+        );
+        assert_eq!(
+            String::from_utf8(out)?,
+            "CREATE VIRTUAL TABLE docs USING fts5(body);\n\
+             CREATE TABLE pairs (a, b, PRIMARY KEY (b, a))\n  without\trowid;\n\
+             INSERT INTO \"pairs\" VALUES(1,2);\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_without_rowid_is_a_table_option() {
+        assert!(is_without_rowid(
+            "CREATE TABLE t (a PRIMARY KEY) WITHOUT ROWID"
+        ));
+        assert!(is_without_rowid(
+            "CREATE TABLE t (a PRIMARY KEY) STRICT,without  rowid"
+        ));
+        assert!(!is_without_rowid(
+            "CREATE TABLE t (note DEFAULT 'WITHOUT ROWID')"
+        ));
+        assert!(!is_without_rowid("CREATE TABLE t (a)"));
+    }
+
+    #[tokio::test]
+    async fn test_restore_sends_statements_in_batches() -> Result<()> {
+        let dump = "CREATE TABLE t (a TEXT); -- a comment\n\
+                    INSERT INTO t VALUES('x;y');\n\
+                    INSERT INTO t VALUES('multi\nline');\n\
+                    INSERT INTO t VALUES(3)";
+        let mut mock = MockCloudClientInterface::new();
+        let mut seq = mockall::Sequence::new();
+        mock.expect_execute_sql()
+            .withf(|db, sql| {
+                db == "db1" && sql == "CREATE TABLE t (a TEXT);\nINSERT INTO t VALUES('x;y');"
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok(vec![]));
+        mock.expect_execute_sql()
+            .withf(|_, sql| sql == "INSERT INTO t VALUES('multi\nline');\nINSERT INTO t VALUES(3)")
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok(vec![]));
+
+        let summary = restore(&mock, "db1", 2, dump.as_bytes()).await?;
+        assert_eq!(
+            summary,
+            RestoreSummary {
+                statements: 4,
+                requests: 2
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_splitter_keeps_trigger_bodies_whole() {
+        let mut splitter = StatementSplitter::default();
+        let mut statements = splitter.push_line("CREATE TRIGGER t AFTER INSERT ON a BEGIN");
+        statements.extend(splitter.push_line("  INSERT INTO b VALUES(1);"));
+        assert!(statements.is_empty());
+        statements.extend(splitter.push_line("END; /* done */ SELECT 1;"));
+        assert_eq!(
+            statements,
+            vec![
+                "CREATE TRIGGER t AFTER INSERT ON a BEGIN\n  INSERT INTO b VALUES(1);\nEND;",
+                "SELECT 1;"
+            ]
+        );
+        assert_eq!(splitter.finish(), None);
+    }
+
+    #[test]
+    fn test_sql_literal() {
+        assert_eq!(sql_literal(&json!(null)), "NULL");
+        assert_eq!(sql_literal(&json!(1.5)), "1.5");
+        assert_eq!(sql_literal(&json!("a'b")), "'a''b'");
+        assert_eq!(sql_literal(&json!([0, 16])), "X'0010'");
+        assert_eq!(sql_literal(&json!(["x"])), r#"'["x"]'"#);
+    }
+}
@@ -0,0 +1,304 @@
+//! An interactive SQL shell on a cloud SQLite database. Each complete
+//! statement is sent as its own execute request.
+use std::collections::VecDeque;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use cloud::{models::SqlStatementResult, CloudClientInterface};
+
+use crate::commands::login::config_root_dir;
+use crate::commands::sqlite::print_results;
+use crate::commands::sqlite_dump::StatementSplitter;
+use crate::output;
+
+const HISTORY_FILE: &str = "sqlite_history";
+const MAX_HISTORY: usize = 500;
+
+const HELP: &str = "\
+Statements end with a semicolon and may span several lines.
+.tables          List the tables and views
+.schema [TABLE]  Show the CREATE statements, optionally of one table
+.help            Show this message
+.quit            Leave the shell";
+
+/// What a line starting with `.` asks for
+#[derive(Debug, PartialEq, Eq)]
+enum MetaCommand {
+    /// Run a query and print each value of its rows on a line of its own
+    Query(String),
+    Help,
+    Quit,
+}
+
+fn parse_meta_command(line: &str) -> Result<MetaCommand> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let argument = words.next();
+    match command {
+        ".tables" => Ok(MetaCommand::Query(
+            "SELECT name FROM sqlite_master WHERE type IN ('table', 'view') \
+             AND name NOT LIKE 'sqlite_%' ORDER BY name"
+                .to_owned(),
+        )),
+        ".schema" => {
+            let filter = match argument {
+                Some(table) => format!(" AND tbl_name = '{}'", table.replace('\'', "''")),
+                None => String::new(),
+            };
+            Ok(MetaCommand::Query(format!(
+                "SELECT sql || ';' FROM sqlite_master WHERE sql IS NOT NULL \
+                 AND name NOT LIKE 'sqlite_%'{filter} ORDER BY tbl_name, type DESC, name"
+            )))
+        }
+        ".help" => Ok(MetaCommand::Help),
+        ".quit" | ".exit" => Ok(MetaCommand::Quit),
+        other => bail!("Unknown command {other}. Enter .help for the commands available."),
+    }
+}
+
+pub(crate) async fn run(client: &impl CloudClientInterface, database: &str) -> Result<()> {
+    if !std::io::stdin().is_terminal() {
+        return run_script(client, database, std::io::stdin().lock()).await;
+    }
+    eprintln!("Connected to \"{database}\". Enter .help for help.");
+    let mut history = ShellHistory::load();
+    let mut splitter = StatementSplitter::default();
+    loop {
+        let prompt = if splitter.is_pending() {
+            "   ..."
+        } else {
+            "sqlite"
+        };
+        // Ctrl-D, or the terminal going away, ends the shell
+        let Ok(line) = dialoguer::Input::<String>::new()
+            .with_prompt(prompt)
+            .allow_empty(true)
+            .history_with(&mut history)
+            .interact_text()
+        else {
+            break;
+        };
+        if !splitter.is_pending() && line.trim_start().starts_with('.') {
+            match parse_meta_command(line.trim()) {
+                Ok(MetaCommand::Quit) => break,
+                Ok(command) => {
+                    run_meta_command(client, database, command).await;
+                }
+                Err(e) => eprintln!("{e}"),
+            }
+            continue;
+        }
+        for statement in splitter.push_line(&line) {
+            execute(client, database, statement).await;
+        }
+    }
+    history.save();
+    Ok(())
+}
+
+// Statements piped in are run the same way, without prompts or history. As
+// with sqlite3, a failing statement does not stop the script, but the shell
+// exits with an error once it ends.
+async fn run_script(
+    client: &impl CloudClientInterface,
+    database: &str,
+    input: impl BufRead,
+) -> Result<()> {
+    let mut splitter = StatementSplitter::default();
+    let mut failed = 0;
+    for line in input.lines() {
+        let line = line?;
+        if !splitter.is_pending() && line.trim_start().starts_with('.') {
+            let succeeded = match parse_meta_command(line.trim())? {
+                MetaCommand::Quit => break,
+                command => run_meta_command(client, database, command).await,
+            };
+            failed += usize::from(!succeeded);
+            continue;
+        }
+        for statement in splitter.push_line(&line) {
+            failed += usize::from(!execute(client, database, statement).await);
+        }
+    }
+    if let Some(statement) = splitter.finish() {
+        failed += usize::from(!execute(client, database, statement).await);
+    }
+    if failed > 0 {
+        bail!("{failed} statement(s) failed");
+    }
+    Ok(())
+}
+
+// A failing statement is reported and the shell carries on, as sqlite3 does.
+// Returns whether the statement succeeded.
+async fn execute(client: &impl CloudClientInterface, database: &str, statement: String) -> bool {
+    let printed = match client.execute_sql(database.to_owned(), statement).await {
+        Ok(results) => print_results(&results, output::format()),
+        Err(e) => Err(e),
+    };
+    match printed {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            false
+        }
+    }
+}
+
+async fn run_meta_command(
+    client: &impl CloudClientInterface,
+    database: &str,
+    command: MetaCommand,
+) -> bool {
+    match command {
+        MetaCommand::Query(query) => match client.execute_sql(database.to_owned(), query).await {
+            Ok(results) => {
+                for line in result_lines(&results) {
+                    println!("{line}");
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: {e:#}");
+                return false;
+            }
+        },
+        MetaCommand::Help => println!("{HELP}"),
+        MetaCommand::Quit => {}
+    }
+    true
+}
+
+fn result_lines(results: &[SqlStatementResult]) -> Vec<String> {
+    results
+        .iter()
+        .flat_map(|result| &result.rows)
+        .flatten()
+        .map(|value| match value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .collect()
+}
+
+/// Lines entered in earlier shells, most recent first, so the up arrow
+/// reaches them
+struct ShellHistory {
+    path: Option<PathBuf>,
+    entries: VecDeque<String>,
+}
+
+impl ShellHistory {
+    fn load() -> Self {
+        let path = config_root_dir().ok().map(|dir| dir.join(HISTORY_FILE));
+        let entries = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|content| content.lines().rev().map(str::to_owned).collect())
+            .unwrap_or_default();
+        Self { path, entries }
+    }
+
+    // History is a convenience, so failing to save it is not an error. It can
+    // hold values typed into statements, so only the user may read it.
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut content = self
+            .entries
+            .iter()
+            .rev()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+        content.push('\n');
+        _ = write_private(path, &content);
+    }
+}
+
+fn write_private(path: &std::path::Path, content: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    // A file saved before permissions were restricted keeps its old mode
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(content.as_bytes())
+}
+
+impl<T: ToString> dialoguer::History<T> for ShellHistory {
+    fn read(&self, pos: usize) -> Option<String> {
+        self.entries.get(pos).cloned()
+    }
+
+    fn write(&mut self, val: &T) {
+        let line = val.to_string();
+        if line.trim().is_empty() || self.entries.front() == Some(&line) {
+            return;
+        }
+        self.entries.push_front(line);
+        self.entries.truncate(MAX_HISTORY);
+    }
+}
+
+#[cfg(test)]
+mod sqlite_shell_tests {
+    use super::*;
+    use cloud::testing::RecordedClient;
+
+    #[test]
+    fn test_parse_meta_command() {
+        assert_eq!(parse_meta_command(".quit").unwrap(), MetaCommand::Quit);
+        assert_eq!(parse_meta_command(".help").unwrap(), MetaCommand::Help);
+        let MetaCommand::Query(query) = parse_meta_command(".schema o'brien").unwrap() else {
+            panic!("expected a query");
+        };
+        assert!(query.contains("tbl_name = 'o''brien'"));
+        assert!(parse_meta_command(".dump").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_script_sends_each_statement_on_its_own() -> Result<()> {
+        let client = RecordedClient::new().respond("execute_sql", Vec::<SqlStatementResult>::new());
+        let script = "CREATE TABLE t (\n  note TEXT\n);\nINSERT INTO t VALUES ('a;b'); -- seed\n\
+                      .quit\nSELECT 2;\n";
+        run_script(&client, "db", script.as_bytes()).await?;
+        let statements = client
+            .calls_to("execute_sql")
+            .into_iter()
+            .map(|args| args["statement"].as_str().unwrap_or_default().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statements,
+            [
+                "CREATE TABLE t (\n  note TEXT\n);",
+                "INSERT INTO t VALUES ('a;b');",
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_script_fails_once_it_ends_if_a_statement_failed() {
+        let client = RecordedClient::new().fail("execute_sql", "no such table: t");
+        let result = run_script(&client, "db", "SELECT * FROM t;\nSELECT 1;\n".as_bytes()).await;
+        assert_eq!(result.unwrap_err().to_string(), "2 statement(s) failed");
+        assert_eq!(client.calls_to("execute_sql").len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_history_is_only_readable_by_the_user() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(HISTORY_FILE);
+        std::fs::write(&path, "SELECT 1;\n")?;
+        write_private(&path, "SELECT 2;\n")?;
+        assert_eq!(std::fs::read_to_string(&path)?, "SELECT 2;\n");
+        assert_eq!(path.metadata()?.permissions().mode() & 0o777, 0o600);
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+//! Helps diagnose problems with Fermyon Cloud. The global `--record` flag
+//! keeps a copy of every API call a command makes, and `support bundle`
+//! packs those recordings up with details of the environment, ready to
+//! attach to a bug report.
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use clap::{Args, Parser};
+use cloud::recording::{redact, Recorder};
+use serde::Serialize;
+
+use crate::commands::{
+    deploy::config_file_path, env::resolve_environment, login::LoginConnection, CommonArgs,
+};
+use crate::output;
+
+static RECORDER: OnceLock<Option<Arc<Recorder>>> = OnceLock::new();
+
+// Proxies often explain network problems, so which of these are set is
+// reported, though not their values
+const PROXY_VARIABLES: [&str; 6] = [
+    "https_proxy",
+    "HTTPS_PROXY",
+    "http_proxy",
+    "HTTP_PROXY",
+    "no_proxy",
+    "NO_PROXY",
+];
+
+#[derive(Debug, Args)]
+pub(crate) struct RecordArgs {
+    /// Write a copy of every API call and its response, with tokens removed,
+    /// to this directory. Pack the recordings up with `spin cloud support bundle`.
+    #[clap(
+        long = "record",
+        global = true,
+        env = "SPIN_CLOUD_RECORD",
+        value_name = "DIR"
+    )]
+    pub record: Option<PathBuf>,
+}
+
+/// Starts recording API calls made by clients created from here on, if
+/// `--record` was given. Only the first call has any effect.
+pub(crate) fn set_recording(args: &RecordArgs) -> Result<()> {
+    let recorder = match &args.record {
+        Some(dir) => Some(Arc::new(Recorder::new(dir)?)),
+        None => None,
+    };
+    _ = RECORDER.set(recorder);
+    Ok(())
+}
+
+/// Where API calls are being recorded, if anywhere
+pub(crate) fn recorder() -> Option<Arc<Recorder>> {
+    RECORDER.get().cloned().flatten()
+}
+
+/// Collect information for Fermyon support
+#[derive(Parser, Debug)]
+#[clap(about = "Collect information for Fermyon support")]
+pub enum SupportCommand {
+    /// Pack recordings made with `--record`, and details of your environment,
+    /// into an archive to attach to a bug report
+    Bundle(BundleCommand),
+}
+
+#[derive(Parser, Debug)]
+pub struct BundleCommand {
+    /// Directory of recordings made with `--record`
+    recordings: PathBuf,
+
+    /// File to write the bundle to. Defaults to a timestamped
+    /// `spin-cloud-support-*.tar.gz` in the current directory.
+    #[clap(short = 'o', long = "output")]
+    output: Option<PathBuf>,
+
+    #[clap(flatten)]
+    common: CommonArgs,
+}
+
+impl SupportCommand {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Bundle(cmd) => cmd.run(),
+        }
+    }
+}
+
+/// What is known about where the plugin runs, without any credentials
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EnvironmentInfo {
+    plugin_version: &'static str,
+    spin_version: Option<String>,
+    os: &'static str,
+    arch: &'static str,
+    environment: Option<String>,
+    cloud_url: Option<String>,
+    token_expiration: Option<String>,
+    proxy_variables: Vec<&'static str>,
+    created_at: String,
+}
+
+impl BundleCommand {
+    fn run(self) -> Result<()> {
+        let recordings = recording_files(&self.recordings)?;
+        let output = self.output.clone().unwrap_or_else(|| {
+            let time = Utc::now().format("%Y%m%dT%H%M%SZ");
+            PathBuf::from(format!("spin-cloud-support-{time}.tar.gz"))
+        });
+        let environment = self.environment_info()?;
+
+        let file = std::fs::File::create(&output)
+            .with_context(|| format!("Failed to create {}", output.display()))?;
+        let gzip = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut archive = tar::Builder::new(gzip);
+        append(
+            &mut archive,
+            Path::new("environment.json"),
+            &serde_json::to_vec_pretty(&environment)?,
+        )?;
+        for path in &recordings {
+            // Recordings are written without tokens, but may have been
+            // edited since, so they are checked again
+            let mut recording: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)
+                .with_context(|| format!("{} is not a recording", path.display()))?;
+            redact(&mut recording);
+            let name = Path::new("recordings").join(path.file_name().unwrap_or_default());
+            append(&mut archive, &name, &serde_json::to_vec_pretty(&recording)?)?;
+        }
+        archive
+            .into_inner()?
+            .finish()
+            .with_context(|| format!("Failed to write {}", output.display()))?;
+
+        output::success(
+            &format!(
+                "Wrote {} recording(s) and environment details to {}",
+                recordings.len(),
+                output.display()
+            ),
+            serde_json::json!({ "path": output, "recordings": recordings.len() }),
+        )
+    }
+
+    fn environment_info(&self) -> Result<EnvironmentInfo> {
+        let environment = resolve_environment(self.common.deployment_env_id.as_deref())?;
+        // Only the parts of the login that are not secret are read
+        let login = config_file_path(environment.as_deref())
+            .ok()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|data| serde_json::from_str::<LoginConnection>(&data).ok());
+        Ok(EnvironmentInfo {
+            plugin_version: crate::VERSION,
+            spin_version: std::env::var("SPIN_VERSION").ok(),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            environment,
+            cloud_url: login.as_ref().map(|l| l.url.to_string()),
+            token_expiration: login.and_then(|l| l.expiration),
+            proxy_variables: PROXY_VARIABLES
+                .into_iter()
+                .filter(|name| std::env::var_os(name).is_some())
+                .collect(),
+            created_at: Utc::now().to_rfc3339(),
+        })
+    }
+}
+
+// The recordings in `dir`, oldest first, which their names sort by
+fn recording_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read recordings from {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+fn append(archive: &mut tar::Builder<impl std::io::Write>, name: &Path, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().try_into().unwrap_or_default());
+    header.set_cksum();
+    archive
+        .append_data(&mut header, name, data)
+        .with_context(|| format!("Failed to add {} to the bundle", name.display()))
+}
+
+#[cfg(test)]
+mod support_tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_holds_environment_and_redacted_recordings() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let recordings = dir.path().join("recordings");
+        std::fs::create_dir(&recordings)?;
+        std::fs::write(
+            recordings.join("run-0002-list_apps.json"),
+            r#"{ "method": "list_apps", "args": {}, "response": { "items": [] } }"#,
+        )?;
+        std::fs::write(
+            recordings.join("run-0001-login.json"),
+            r#"{ "method": "login", "args": { "token": "secret" } }"#,
+        )?;
+        std::fs::write(recordings.join("notes.txt"), "not a recording")?;
+        let output = dir.path().join("bundle.tar.gz");
+        BundleCommand {
+            recordings,
+            output: Some(output.clone()),
+            common: CommonArgs::default(),
+        }
+        .run()?;
+
+        let mut archive =
+            tar::Archive::new(flate2::read::GzDecoder::new(std::fs::File::open(&output)?));
+        let mut names = vec![];
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut entry, &mut content)?;
+            assert!(!content.contains("secret"));
+            names.push(entry.path()?.display().to_string());
+        }
+        assert_eq!(
+            names,
+            [
+                "environment.json",
+                "recordings/run-0001-login.json",
+                "recordings/run-0002-list_apps.json",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! The global `--timings` flag, which prints where a command spent its time
+//! once it finishes: logging in, each kind of API call, and the steps of a
+//! deploy. Nothing is sent anywhere; the breakdown only goes to stderr.
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use clap::Args;
+
+static TIMINGS: OnceLock<Timings> = OnceLock::new();
+
+// What is left over is mostly printing results and reading local files
+const OTHER_PHASE: &str = "rendering and other local work";
+
+#[derive(Debug, Args)]
+pub(crate) struct TimingsArgs {
+    /// Print a breakdown of where the command spent its time when it finishes
+    #[clap(long = "timings", global = true, env = "CLOUD_PLUGIN_TIMINGS")]
+    pub timings: bool,
+}
+
+struct Timings {
+    start: Instant,
+    phases: Mutex<Vec<Phase>>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Phase {
+    name: String,
+    count: usize,
+    elapsed: Duration,
+}
+
+/// Starts timing the command if `--timings` was given. Only the first call
+/// has any effect.
+pub(crate) fn set_timings(args: &TimingsArgs) {
+    if args.timings {
+        _ = TIMINGS.set(Timings {
+            start: Instant::now(),
+            phases: Mutex::default(),
+        });
+    }
+}
+
+/// Adds `elapsed` to the time spent in the phase called `name`
+pub(crate) fn record(name: &str, elapsed: Duration) {
+    let Some(timings) = TIMINGS.get() else {
+        return;
+    };
+    if let Ok(mut phases) = timings.phases.lock() {
+        add(&mut phases, name, elapsed);
+    }
+}
+
+/// Runs `future`, counting the time it takes towards the phase called `name`
+pub(crate) async fn time<T>(name: &str, future: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let output = future.await;
+    record(name, start.elapsed());
+    output
+}
+
+/// Records an API call made by a client, once it has finished
+pub(crate) fn record_api_call(operation: &str, elapsed: Duration) {
+    record(&format!("API {operation}"), elapsed);
+}
+
+/// Prints the breakdown to stderr, if `--timings` was given
+pub(crate) fn print_timings() {
+    let Some(timings) = TIMINGS.get() else {
+        return;
+    };
+    let Ok(phases) = timings.phases.lock() else {
+        return;
+    };
+    eprintln!();
+    for line in summary(&phases, timings.start.elapsed()) {
+        eprintln!("{line}");
+    }
+}
+
+fn add(phases: &mut Vec<Phase>, name: &str, elapsed: Duration) {
+    match phases.iter_mut().find(|p| p.name == name) {
+        Some(phase) => {
+            phase.count += 1;
+            phase.elapsed += elapsed;
+        }
+        None => phases.push(Phase {
+            name: name.to_owned(),
+            count: 1,
+            elapsed,
+        }),
+    }
+}
+
+// The phases in the order they first happened. Calls made concurrently, or
+// while logging in, overlap other phases, so the phases can add up to more
+// than the total.
+fn summary(phases: &[Phase], total: Duration) -> Vec<String> {
+    let accounted = phases.iter().map(|p| p.elapsed).sum::<Duration>();
+    let mut rows = phases
+        .iter()
+        .map(|p| match p.count {
+            1 => (p.name.clone(), p.elapsed),
+            count => (format!("{} (x{count})", p.name), p.elapsed),
+        })
+        .collect::<Vec<_>>();
+    rows.push((OTHER_PHASE.to_owned(), total.saturating_sub(accounted)));
+    rows.push(("total".to_owned(), total));
+
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let mut lines = vec!["Timings:".to_owned()];
+    lines.extend(
+        rows.into_iter()
+            .map(|(name, elapsed)| format!("  {name:<width$}  {:>8.2}s", elapsed.as_secs_f64())),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod timings_tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_groups_repeated_phases() {
+        let mut phases = vec![];
+        add(&mut phases, "auth", Duration::from_millis(400));
+        add(&mut phases, "API list_apps", Duration::from_millis(250));
+        add(&mut phases, "API list_apps", Duration::from_millis(250));
+        add(&mut phases, "upload", Duration::from_secs(3));
+        assert_eq!(
+            summary(&phases, Duration::from_secs(5)),
+            vec![
+                "Timings:",
+                "  auth                                0.40s",
+                "  API list_apps (x2)                  0.50s",
+                "  upload                              3.00s",
+                "  rendering and other local work      1.10s",
+                "  total                               5.00s",
+            ]
+        );
+    }
+}
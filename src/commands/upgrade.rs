@@ -0,0 +1,355 @@
+//! Upgrades the plugin to its latest release. Each release publishes a Spin
+//! plugin manifest naming a package, and its checksum, for every platform.
+//! The package is downloaded and checked here, then installed with `spin
+//! plugins install` so that Spin keeps track of it like any other plugin.
+use std::io::IsTerminal;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::commands::{config, http_config, login::config_root_dir};
+use crate::errors::CliError;
+use crate::{output, spin};
+
+const LATEST_MANIFEST_URL: &str =
+    "https://github.com/fermyon/cloud-plugin/releases/latest/download/cloud.json";
+
+// Remembers when other commands last looked for a new version
+const UPDATE_CHECK_FILE: &str = "cloud-plugin-update-check.json";
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+// The hint must never hold up a command for long
+const UPDATE_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+const NO_UPDATE_CHECK_ENV: &str = "SPIN_CLOUD_NO_UPDATE_CHECK";
+
+/// Upgrade the cloud plugin to its latest release
+#[derive(Parser, Debug)]
+pub struct UpgradeCommand {
+    /// Only report whether a newer version is available
+    #[clap(long = "check")]
+    check: bool,
+
+    /// Install without asking for confirmation
+    #[clap(short = 'y', long = "yes")]
+    yes: bool,
+}
+
+impl UpgradeCommand {
+    pub async fn run(self) -> Result<()> {
+        let manifest = fetch_latest_manifest(None).await?;
+        remember_update_check();
+        let current = current_version();
+        let latest = manifest.version()?;
+        let versions = serde_json::json!({
+            "version": current.to_string(),
+            "latest": latest.to_string(),
+        });
+        if latest <= current {
+            return output::success(
+                &format!("The cloud plugin is up to date (version {current})"),
+                versions,
+            );
+        }
+        if self.check {
+            let message = format!(
+                "Version {latest} of the cloud plugin is available (installed: {current}). \
+                 Run `spin cloud upgrade` to install it."
+            );
+            return output::success(&message, versions);
+        }
+        if !self.yes
+            && !confirm(&format!(
+                "Upgrade the cloud plugin from {current} to {latest}?"
+            ))?
+        {
+            return Ok(());
+        }
+
+        let (os, arch) = (spin_os(), spin_arch());
+        let package = manifest.package_for(os, arch).with_context(|| {
+            CliError::not_found(format!("Version {latest} has no package for {os}/{arch}"))
+        })?;
+        let archive = download(&package.url).await?;
+        verify_checksum(&archive, &package.sha256)
+            .with_context(|| format!("The package downloaded from {} is corrupt", package.url))?;
+
+        let dir = tempfile::tempdir()?;
+        let archive_path = dir.path().join(archive_name(&package.url));
+        std::fs::write(&archive_path, &archive)?;
+        let manifest_path = dir.path().join("cloud.json");
+        std::fs::write(
+            &manifest_path,
+            serde_json::to_string_pretty(&manifest.local(package, &archive_path)?)?,
+        )?;
+        install(&manifest_path)?;
+        output::success(
+            &format!("Upgraded the cloud plugin from {current} to {latest}"),
+            serde_json::json!({
+                "previousVersion": current.to_string(),
+                "version": latest.to_string(),
+            }),
+        )
+    }
+}
+
+/// The parts of a Spin plugin manifest needed to pick a package. The rest
+/// of the manifest is kept as it was, to be handed on to Spin.
+#[derive(Debug)]
+struct PluginManifest {
+    raw: serde_json::Value,
+    version: String,
+    packages: Vec<PluginPackage>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+struct PluginPackage {
+    os: String,
+    arch: String,
+    url: String,
+    sha256: String,
+}
+
+impl PluginManifest {
+    fn parse(data: &[u8]) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Fields {
+            version: String,
+            packages: Vec<PluginPackage>,
+        }
+        let raw: serde_json::Value = serde_json::from_slice(data)?;
+        let fields = Fields::deserialize(&raw)?;
+        Ok(Self {
+            raw,
+            version: fields.version,
+            packages: fields.packages,
+        })
+    }
+
+    fn version(&self) -> Result<Version> {
+        Version::parse(&self.version).with_context(|| {
+            format!(
+                "The latest release has an invalid version '{}'",
+                self.version
+            )
+        })
+    }
+
+    fn package_for(&self, os: &str, arch: &str) -> Option<&PluginPackage> {
+        self.packages.iter().find(|p| p.os == os && p.arch == arch)
+    }
+
+    // The manifest with only `package`, pointing at the copy downloaded to
+    // `archive`, so that Spin installs exactly what was checked
+    fn local(&self, package: &PluginPackage, archive: &Path) -> Result<serde_json::Value> {
+        let url = url::Url::from_file_path(archive)
+            .map_err(|_| anyhow::anyhow!("Cannot refer to {} by URL", archive.display()))?;
+        let package = PluginPackage {
+            url: url.to_string(),
+            ..package.clone()
+        };
+        let mut manifest = self.raw.clone();
+        manifest["packages"] = serde_json::json!([package]);
+        Ok(manifest)
+    }
+}
+
+fn current_version() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).expect("package version is valid semver")
+}
+
+// The platform names used in Spin plugin manifests
+fn spin_os() -> &'static str {
+    std::env::consts::OS
+}
+
+fn spin_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        arch => arch,
+    }
+}
+
+async fn fetch_latest_manifest(timeout: Option<Duration>) -> Result<PluginManifest> {
+    let mut builder = http_config().apply(reqwest::Client::builder());
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+    let http = builder.build().context("Failed to create HTTP client")?;
+    let response = http
+        .get(LATEST_MANIFEST_URL)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .context("Problem finding the latest release of the cloud plugin")?;
+    PluginManifest::parse(&response.bytes().await?)
+        .context("Problem reading the manifest of the latest release")
+}
+
+async fn download(url: &str) -> Result<Vec<u8>> {
+    let http = http_config()
+        .apply(reqwest::Client::builder())
+        .build()
+        .context("Failed to create HTTP client")?;
+    let response = http
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("Problem downloading {url}"))?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+fn verify_checksum(data: &[u8], expected: &str) -> Result<()> {
+    let actual = format!("{:x}", Sha256::digest(data));
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!(CliError::validation(format!(
+            "Checksum mismatch: expected {expected}, got {actual}"
+        )));
+    }
+    Ok(())
+}
+
+fn archive_name(url: &str) -> &str {
+    match url.rsplit('/').next() {
+        Some(name) if !name.is_empty() => name,
+        _ => "cloud.tar.gz",
+    }
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        bail!(CliError::usage("Confirmation is required to upgrade")
+            .with_hint("Pass `--yes` to upgrade without asking"));
+    }
+    Ok(dialoguer::Confirm::new()
+        .with_prompt(prompt)
+        .default(true)
+        .interact_opt()?
+        .unwrap_or_default())
+}
+
+fn install(manifest_path: &Path) -> Result<()> {
+    let spin = spin::bin_path().context("Upgrading must be run through `spin cloud upgrade`")?;
+    let status = std::process::Command::new(spin)
+        .args(["plugins", "install", "--yes", "--file"])
+        .arg(manifest_path)
+        .status()
+        .context("Failed to run `spin plugins install`")?;
+    if !status.success() {
+        bail!("`spin plugins install` failed ({status})");
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateCheck {
+    checked_at: DateTime<Utc>,
+}
+
+fn update_check_path() -> Result<std::path::PathBuf> {
+    Ok(config_root_dir()?.join(UPDATE_CHECK_FILE))
+}
+
+fn update_check_due(now: DateTime<Utc>) -> bool {
+    let last = update_check_path()
+        .and_then(|path| Ok(std::fs::read_to_string(path)?))
+        .and_then(|data| Ok(serde_json::from_str::<UpdateCheck>(&data)?));
+    match last {
+        Ok(last) => (now - last.checked_at).to_std().unwrap_or_default() >= UPDATE_CHECK_INTERVAL,
+        Err(_) => true,
+    }
+}
+
+// Failing to record the check only means checking again next time
+fn remember_update_check() {
+    let check = UpdateCheck {
+        checked_at: Utc::now(),
+    };
+    if let (Ok(path), Ok(data)) = (update_check_path(), serde_json::to_string(&check)) {
+        _ = config::write_atomic(&path, data);
+    }
+}
+
+/// Tells a person at a terminal, at most once a day, that a newer version of
+/// the plugin has been released. Scripts, JSON output and `--quiet` never see
+/// it, nor does anyone who sets `SPIN_CLOUD_NO_UPDATE_CHECK`. Any failure is
+/// ignored, as the hint is not what the command was run for.
+pub(crate) async fn print_update_hint() {
+    if output::is_json()
+        || output::is_quiet()
+        || !std::io::stderr().is_terminal()
+        || std::env::var_os(NO_UPDATE_CHECK_ENV).is_some()
+        || !update_check_due(Utc::now())
+    {
+        return;
+    }
+    remember_update_check();
+    let Ok(manifest) = fetch_latest_manifest(Some(UPDATE_CHECK_TIMEOUT)).await else {
+        return;
+    };
+    if let Ok(latest) = manifest.version() {
+        if latest > current_version() {
+            eprintln!(
+                "\nVersion {latest} of the cloud plugin is available. Run `spin cloud upgrade` to install it."
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod upgrade_tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+        "name": "cloud",
+        "description": "Commands for publishing applications to the Fermyon Cloud.",
+        "version": "0.10.0",
+        "spinCompatibility": ">=1.3",
+        "license": "Apache-2.0",
+        "packages": [
+            {
+                "os": "linux",
+                "arch": "amd64",
+                "url": "https://github.com/fermyon/cloud-plugin/releases/download/v0.10.0/cloud-v0.10.0-linux-amd64.tar.gz",
+                "sha256": "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+            },
+            {
+                "os": "macos",
+                "arch": "aarch64",
+                "url": "https://github.com/fermyon/cloud-plugin/releases/download/v0.10.0/cloud-v0.10.0-macos-aarch64.tar.gz",
+                "sha256": "0000"
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_manifest_is_narrowed_to_the_checked_package() -> Result<()> {
+        let manifest = PluginManifest::parse(MANIFEST.as_bytes())?;
+        assert_eq!(manifest.version()?, Version::new(0, 10, 0));
+        assert!(manifest.package_for("windows", "amd64").is_none());
+        let package = manifest.package_for("linux", "amd64").unwrap();
+        assert_eq!(
+            archive_name(&package.url),
+            "cloud-v0.10.0-linux-amd64.tar.gz"
+        );
+
+        verify_checksum(b"hello", &package.sha256)?;
+        assert!(verify_checksum(b"hello!", &package.sha256).is_err());
+
+        let dir = tempfile::tempdir()?;
+        let archive = dir.path().join("cloud.tar.gz");
+        let local = manifest.local(package, &archive)?;
+        assert_eq!(local["spinCompatibility"], ">=1.3");
+        let packages = local["packages"].as_array().unwrap();
+        assert_eq!(packages.len(), 1);
+        assert!(packages[0]["url"].as_str().unwrap().starts_with("file://"));
+        assert_eq!(packages[0]["sha256"], package.sha256.as_str());
+        Ok(())
+    }
+}
@@ -1,12 +1,21 @@
-use anyhow::{Context, Result};
-use clap::Parser;
-use cloud::{client::Client as CloudClient, CloudClientInterface};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, ValueEnum};
+use cloud::{CloudClientExt, CloudClientInterface};
+use comfy_table::presets::ASCII_BORDERS_ONLY_CONDENSED;
 use serde::Deserialize;
 use serde_json::from_str;
-use spin_common::arg_parser::parse_kv;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use crate::commands::{client_and_app_id, CommonArgs};
+use crate::commands::{
+    app_picker::app_or_pick, cache::ResponseCache, client_and_app_id, CommonArgs,
+};
+use crate::ops::Cloud;
+use crate::output;
 
 #[derive(Deserialize)]
 pub(crate) struct Variable {
@@ -23,100 +32,508 @@ pub enum VariablesCommand {
     Delete(DeleteCommand),
     /// List all variables of an application
     List(ListCommand),
+    /// Set the variables of a dotenv or JSON file whose values changed
+    Import(ImportCommand),
+    /// Write an application's variable names to a dotenv or JSON template
+    Export(ExportCommand),
 }
 
 #[derive(Parser, Debug)]
 pub struct SetCommand {
-    /// Variable pair to set
-    #[clap(parse(try_from_str = parse_kv))]
-    pub variables_to_set: Vec<(String, String)>,
+    /// Variable to set, either as a pair (variable=value) or as a bare variable
+    /// name whose value is taken from the environment, standard input or an
+    /// interactive prompt. Can be used multiple times.
+    #[clap(value_parser = clap::builder::ValueParser::new(parse_variable_arg))]
+    pub variables_to_set: Vec<(String, Option<String>)>,
+    /// Take values for bare variable names from environment variables of the same name
+    #[clap(long = "from-env", takes_value = false, conflicts_with = "stdin")]
+    pub from_env: bool,
+    /// Read the value for a single bare variable name from standard input
+    #[clap(name = "stdin", long = "stdin", takes_value = false)]
+    pub stdin: bool,
     #[clap(flatten)]
     common: CommonArgs,
-    /// Name of Spin app
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
     #[clap(name = "app", long = "app")]
-    pub app: String,
+    pub app: Option<String>,
 }
 
 #[derive(Parser, Debug)]
 pub struct DeleteCommand {
-    /// Variable pair to set
+    /// Variables to delete
     pub variables_to_delete: Vec<String>,
     #[clap(flatten)]
     common: CommonArgs,
-    /// Name of Spin app
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
     #[clap(name = "app", long = "app")]
-    pub app: String,
+    pub app: Option<String>,
 }
 
 #[derive(Parser, Debug)]
 pub struct ListCommand {
+    /// A manifest whose declared variables are listed too, including any
+    /// that have no value in Fermyon Cloud
+    #[clap(short = 'f', long = "from-file")]
+    pub manifest: Option<PathBuf>,
+    #[clap(flatten)]
+    common: CommonArgs,
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    #[clap(name = "app", long = "app")]
+    pub app: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct ImportCommand {
+    /// Path to the file of variables to import
+    #[clap(short = 'f', long = "file")]
+    pub file: PathBuf,
+    /// Format of the file. If omitted, files ending in `.json` are read as
+    /// JSON and anything else as dotenv.
+    #[clap(value_enum, long = "file-format")]
+    pub format: Option<VariablesFormat>,
+    /// Set every variable in the file. Fermyon Cloud never returns values, so
+    /// by default a variable is left alone if this machine last set it to the
+    /// same value, which misses changes made elsewhere.
+    #[clap(long = "all", takes_value = false)]
+    pub all: bool,
     #[clap(flatten)]
     common: CommonArgs,
-    /// Name of Spin app
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
     #[clap(name = "app", long = "app")]
-    pub app: String,
+    pub app: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct ExportCommand {
+    /// Format of the template. The global `--format` sets the output of
+    /// every command and cannot also name dotenv, so this has a flag of its
+    /// own; `--format json` alone also writes a JSON template.
+    #[clap(value_enum, long = "file-format")]
+    pub format: Option<VariablesFormat>,
+    /// File to write the template to. If omitted, it is written to standard output.
+    #[clap(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+    #[clap(flatten)]
+    common: CommonArgs,
+    /// Name of Spin app. If omitted in a terminal, you are asked to pick one.
+    #[clap(name = "app", long = "app")]
+    pub app: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum VariablesFormat {
+    Dotenv,
+    Json,
 }
 
 impl VariablesCommand {
     pub async fn run(self) -> Result<()> {
         match self {
             Self::Set(cmd) => {
-                let (client, app_id) =
-                    client_and_app_id(cmd.common.deployment_env_id.as_deref(), &cmd.app).await?;
-                set_variables(&client, app_id, &cmd.variables_to_set).await?;
+                let variables = cmd.resolve_values(|name| std::env::var(name).ok())?;
+                let deployment_env_id = cmd.common.deployment_env_id.as_deref();
+                let app = app_or_pick(deployment_env_id, cmd.app.clone()).await?;
+                let cloud = Cloud::connect(deployment_env_id).await?;
+                cloud.set_variables(&app, &variables).await?;
             }
             Self::Delete(cmd) => {
-                let (client, app_id) =
-                    client_and_app_id(cmd.common.deployment_env_id.as_deref(), &cmd.app).await?;
-                delete_variables(&client, app_id, &cmd.variables_to_delete).await?;
+                let deployment_env_id = cmd.common.deployment_env_id.as_deref();
+                let app = app_or_pick(deployment_env_id, cmd.app.clone()).await?;
+                let cloud = Cloud::connect(deployment_env_id).await?;
+                cloud
+                    .delete_variables(&app, &cmd.variables_to_delete)
+                    .await?;
             }
-            Self::List(cmd) => {
-                let (client, app_id) =
-                    client_and_app_id(cmd.common.deployment_env_id.as_deref(), &cmd.app).await?;
-                let var_names = get_variables(&client, app_id).await?;
-                for v in var_names {
-                    println!("{}", v.key);
-                }
+            Self::List(cmd) => cmd.run().await?,
+            Self::Import(cmd) => cmd.run().await?,
+            Self::Export(cmd) => cmd.run().await?,
+        }
+        Ok(())
+    }
+}
+
+impl ImportCommand {
+    async fn run(self) -> Result<()> {
+        let format = self
+            .format
+            .unwrap_or_else(|| VariablesFormat::from_path(&self.file));
+        let content = std::fs::read_to_string(&self.file)
+            .with_context(|| format!("Could not read variables file {}", self.file.display()))?;
+        let imported = parse_variables_file(&content, format)
+            .with_context(|| format!("Could not parse variables file {}", self.file.display()))?;
+        if imported.is_empty() {
+            println!("No variables to import");
+            return Ok(());
+        }
+
+        let deployment_env_id = self.common.deployment_env_id.as_deref();
+        let app = app_or_pick(deployment_env_id, self.app.clone()).await?;
+        let (client, app_id) = client_and_app_id(deployment_env_id, &app).await?;
+        let mut cache = ResponseCache::open(deployment_env_id)?;
+        let existing = get_variables(&client, app_id).await?;
+        let existing = existing.iter().map(|v| v.key.as_str()).collect();
+        let diff = if self.all {
+            ImportDiff::everything(&imported, &existing)
+        } else {
+            ImportDiff::new(&imported, &existing, |name| {
+                cache
+                    .get_stale(&value_digest_key(app_id, name))
+                    .map(|(digest, _)| digest)
+            })
+        };
+
+        let changed = imported
+            .iter()
+            .filter(|(name, _)| !diff.unchanged.contains(&name.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !changed.is_empty() {
+            set_variables(&client, &mut cache, app_id, &changed).await?;
+        }
+
+        if output::is_json() {
+            return output::print_json(&serde_json::json!({
+                "created": diff.created,
+                "updated": diff.updated,
+                "unchanged": diff.unchanged,
+            }));
+        }
+        for name in &diff.created {
+            println!("+ {name}");
+        }
+        for name in &diff.updated {
+            println!("~ {name}");
+        }
+        println!(
+            "Imported {} variable(s): {} created, {} updated, {} unchanged",
+            imported.len(),
+            diff.created.len(),
+            diff.updated.len(),
+            diff.unchanged.len()
+        );
+        Ok(())
+    }
+}
+
+impl ListCommand {
+    async fn run(self) -> Result<()> {
+        let declared = match &self.manifest {
+            Some(path) => {
+                let manifest = std::fs::read_to_string(path)
+                    .with_context(|| format!("Could not read manifest {}", path.display()))?;
+                declared_variables(&manifest)
+                    .with_context(|| format!("Could not parse manifest {}", path.display()))?
             }
+            None => BTreeMap::new(),
+        };
+        let deployment_env_id = self.common.deployment_env_id.as_deref();
+        let app = app_or_pick(deployment_env_id, self.app.clone()).await?;
+        let names = Cloud::connect(deployment_env_id)
+            .await?
+            .variables(&app)
+            .await?;
+        let statuses = variable_statuses(&names, &declared);
+        if output::is_json() {
+            let statuses = statuses
+                .iter()
+                .map(|(name, status)| {
+                    serde_json::json!({
+                        "name": name,
+                        "status": status.as_str(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            return output::print_json(&statuses);
+        }
+        if statuses.is_empty() {
+            println!("App '{app}' has no variables");
+            return Ok(());
+        }
+        // Values are never shown, only whether there is one
+        let mut table = comfy_table::Table::new();
+        table.load_preset(ASCII_BORDERS_ONLY_CONDENSED);
+        table.set_header(["Variable", "Value"]);
+        table.add_rows(
+            statuses
+                .iter()
+                .map(|(name, status)| [name.as_str(), status.as_str()]),
+        );
+        println!("{table}");
+        Ok(())
+    }
+}
+
+impl ExportCommand {
+    async fn run(self) -> Result<()> {
+        let deployment_env_id = self.common.deployment_env_id.as_deref();
+        let app = app_or_pick(deployment_env_id, self.app.clone()).await?;
+        let (client, app_id) = client_and_app_id(deployment_env_id, &app).await?;
+        let names = get_variables(&client, app_id)
+            .await?
+            .into_iter()
+            .map(|v| v.key)
+            .collect::<Vec<_>>();
+        let format = match self.format {
+            Some(format) => format,
+            None if output::is_json() => VariablesFormat::Json,
+            None => VariablesFormat::Dotenv,
+        };
+        let template = variables_template(&names, format)?;
+        match &self.output {
+            Some(path) => std::fs::write(path, template)
+                .with_context(|| format!("Could not write {}", path.display()))?,
+            None => print!("{template}"),
         }
+        eprintln!(
+            "Variable values cannot be read back from Fermyon Cloud, so only names were exported."
+        );
         Ok(())
     }
 }
 
+impl VariablesFormat {
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Dotenv,
+        }
+    }
+}
+
+// Entries without a value (commented-out dotenv lines or JSON nulls, as
+// written by `variables export`) are skipped.
+fn parse_variables_file(content: &str, format: VariablesFormat) -> Result<Vec<(String, String)>> {
+    match format {
+        VariablesFormat::Dotenv => dotenvy::from_read_iter(content.as_bytes())
+            .map(|item| item.map_err(anyhow::Error::from))
+            .collect(),
+        VariablesFormat::Json => {
+            let map: BTreeMap<String, serde_json::Value> = serde_json::from_str(content)?;
+            map.into_iter()
+                .filter_map(|(name, value)| match value {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(s) => Some(Ok((name, s))),
+                    serde_json::Value::Bool(_) | serde_json::Value::Number(_) => {
+                        Some(Ok((name, value.to_string())))
+                    }
+                    _ => Some(Err(anyhow::anyhow!(
+                        "Value of variable {name} must be a string, number or boolean"
+                    ))),
+                })
+                .collect()
+        }
+    }
+}
+
+// How the variables of an imported file compare with those the app has
+#[derive(Debug, Default)]
+struct ImportDiff<'a> {
+    created: Vec<&'a str>,
+    updated: Vec<&'a str>,
+    unchanged: Vec<&'a str>,
+}
+
+impl<'a> ImportDiff<'a> {
+    // An existing variable is unchanged if the digest of the value last set
+    // for it from this machine, given by `last_set`, matches the imported one
+    fn new(
+        variables: &'a [(String, String)],
+        existing: &HashSet<&str>,
+        last_set: impl Fn(&str) -> Option<String>,
+    ) -> Self {
+        let mut diff = Self::default();
+        for (name, value) in variables {
+            let name = name.as_str();
+            if !existing.contains(name) {
+                diff.created.push(name);
+            } else if last_set(name) == Some(value_digest(value)) {
+                diff.unchanged.push(name);
+            } else {
+                diff.updated.push(name);
+            }
+        }
+        diff
+    }
+
+    fn everything(variables: &'a [(String, String)], existing: &HashSet<&str>) -> Self {
+        Self::new(variables, existing, |_| None)
+    }
+}
+
+// Fermyon Cloud never returns variable values, so a digest of each value set
+// from this machine is cached for `variables import` to compare against
+fn value_digest_key(app_id: Uuid, name: &str) -> String {
+    format!("variable-digest/{app_id}/{name}")
+}
+
+fn value_digest(value: &str) -> String {
+    format!("{:x}", Sha256::digest(value.as_bytes()))
+}
+
+fn variables_template(names: &[String], format: VariablesFormat) -> Result<String> {
+    match format {
+        VariablesFormat::Dotenv => Ok(names.iter().map(|n| format!("# {n}=\n")).collect()),
+        VariablesFormat::Json => {
+            let map = names
+                .iter()
+                .map(|n| (n.as_str(), serde_json::Value::Null))
+                .collect::<BTreeMap<_, _>>();
+            Ok(serde_json::to_string_pretty(&map)? + "\n")
+        }
+    }
+}
+
+impl SetCommand {
+    fn resolve_values(
+        &self,
+        env_lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<Vec<(String, String)>> {
+        let bare_names = self
+            .variables_to_set
+            .iter()
+            .filter(|(_, value)| value.is_none())
+            .count();
+        if self.stdin && bare_names != 1 {
+            bail!("--stdin requires exactly one variable name without a value");
+        }
+
+        self.variables_to_set
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Some(v) => v.clone(),
+                    None if self.from_env => env_lookup(name)
+                        .with_context(|| format!("Environment variable {name} is not set"))?,
+                    None if self.stdin => read_stdin_value()?,
+                    None => prompt_value(name)?,
+                };
+                Ok((name.clone(), value))
+            })
+            .collect()
+    }
+}
+
+fn parse_variable_arg(arg: &str) -> Result<(String, Option<String>)> {
+    let (name, value) = match arg.split_once('=') {
+        Some((name, value)) => (name, Some(value.to_owned())),
+        None => (arg, None),
+    };
+    if name.is_empty() {
+        bail!("variable names cannot be empty");
+    }
+    Ok((name.to_owned(), value))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum VariableStatus {
+    /// Fermyon Cloud has a value for the variable
+    Set,
+    /// Only the manifest's default applies
+    Default,
+    NotSet,
+}
+
+impl VariableStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Set => "set",
+            Self::Default => "default",
+            Self::NotSet => "not set",
+        }
+    }
+}
+
+// The variables a manifest declares, and whether each has a default
+fn declared_variables(manifest: &str) -> Result<BTreeMap<String, bool>> {
+    let manifest: toml::Table = toml::from_str(manifest)?;
+    let Some(variables) = manifest.get("variables") else {
+        return Ok(BTreeMap::new());
+    };
+    let variables = variables
+        .as_table()
+        .context("`variables` must be a table")?;
+    Ok(variables
+        .iter()
+        .map(|(name, declaration)| (name.clone(), declaration.get("default").is_some()))
+        .collect())
+}
+
+fn variable_statuses(
+    set: &[String],
+    declared: &BTreeMap<String, bool>,
+) -> Vec<(String, VariableStatus)> {
+    let mut statuses = set
+        .iter()
+        .map(|name| (name.clone(), VariableStatus::Set))
+        .collect::<BTreeMap<_, _>>();
+    for (name, has_default) in declared {
+        statuses.entry(name.clone()).or_insert(match has_default {
+            true => VariableStatus::Default,
+            false => VariableStatus::NotSet,
+        });
+    }
+    statuses.into_iter().collect()
+}
+
+fn read_stdin_value() -> Result<String> {
+    let mut value = String::new();
+    std::io::stdin()
+        .read_to_string(&mut value)
+        .context("Could not read variable value from standard input")?;
+    Ok(value.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+fn prompt_value(name: &str) -> Result<String> {
+    if !std::io::stdin().is_terminal() {
+        bail!("No value provided for variable {name}. Use {name}=value, --from-env or --stdin.");
+    }
+    rpassword::prompt_password(format!("Value for {name}: "))
+        .with_context(|| format!("Could not read value for variable {name}"))
+}
+
+/// Sets variables on an app, remembering a digest of each value for
+/// `variables import` to tell which values it would change
 pub(crate) async fn set_variables(
-    client: &CloudClient,
+    client: &impl CloudClientInterface,
+    cache: &mut ResponseCache,
     app_id: Uuid,
     variables: &[(String, String)],
 ) -> Result<()> {
-    for var in variables {
-        CloudClient::add_variable_pair(client, app_id, var.0.to_owned(), var.1.to_owned())
-            .await
-            .with_context(|| format!("Problem creating variable {}", var.0))?;
+    client.add_variable_pairs(app_id, variables).await?;
+    for (name, value) in variables {
+        cache.put(&value_digest_key(app_id, name), &value_digest(value));
     }
     Ok(())
 }
 
 pub(crate) async fn delete_variables(
-    client: &CloudClient,
+    client: &impl CloudClientInterface,
+    cache: &mut ResponseCache,
     app_id: Uuid,
     variables: &[String],
 ) -> Result<()> {
-    for var in variables {
-        CloudClient::delete_variable_pair(client, app_id, var.to_owned())
-            .await
-            .with_context(|| format!("Problem deleting variable {var}"))?;
+    client.delete_variable_pairs(app_id, variables).await?;
+    for name in variables {
+        cache.remove(&value_digest_key(app_id, name));
     }
     Ok(())
 }
 
-async fn get_variables_json(client: &CloudClient, app_id: Uuid) -> Result<Vec<String>> {
-    let vars = CloudClient::get_variable_pairs(client, app_id)
+async fn get_variables_json(
+    client: &impl CloudClientInterface,
+    app_id: Uuid,
+) -> Result<Vec<String>> {
+    let vars = client
+        .get_variable_pairs(app_id)
         .await
         .context("Problem listing variables")?;
     Ok(vars)
 }
 
-pub(crate) async fn get_variables(client: &CloudClient, app_id: Uuid) -> Result<Vec<Variable>> {
+pub(crate) async fn get_variables(
+    client: &impl CloudClientInterface,
+    app_id: Uuid,
+) -> Result<Vec<Variable>> {
     let vars = get_variables_json(client, app_id).await?;
     let var_names = vars
         .iter()
@@ -125,3 +542,150 @@ pub(crate) async fn get_variables(client: &CloudClient, app_id: Uuid) -> Result<
         .context("could not parse variable")?;
     Ok(var_names)
 }
+
+#[cfg(test)]
+mod variables_tests {
+    use super::*;
+
+    fn set_command(args: &[&str], from_env: bool) -> SetCommand {
+        SetCommand {
+            variables_to_set: args
+                .iter()
+                .map(|a| parse_variable_arg(a).unwrap())
+                .collect(),
+            from_env,
+            stdin: false,
+            common: Default::default(),
+            app: Some("app".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_parse_variable_arg() {
+        assert_eq!(
+            parse_variable_arg("key=value=more").unwrap(),
+            ("key".to_owned(), Some("value=more".to_owned()))
+        );
+        assert_eq!(parse_variable_arg("key").unwrap(), ("key".to_owned(), None));
+        assert!(parse_variable_arg("=value").is_err());
+        assert!(parse_variable_arg("").is_err());
+    }
+
+    #[test]
+    fn test_variable_statuses_include_declared_variables() {
+        let manifest = r#"
+            spin_manifest_version = 2
+            [variables]
+            api_key = { required = true, secret = true }
+            region = { default = "eu" }
+            greeting = { default = "hello" }
+        "#;
+        let declared = declared_variables(manifest).unwrap();
+        let set = vec!["greeting".to_owned(), "extra".to_owned()];
+        assert_eq!(
+            variable_statuses(&set, &declared),
+            vec![
+                ("api_key".to_owned(), VariableStatus::NotSet),
+                ("extra".to_owned(), VariableStatus::Set),
+                ("greeting".to_owned(), VariableStatus::Set),
+                ("region".to_owned(), VariableStatus::Default),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_variables_file() {
+        let dotenv = "# api_key=\nregion=eu\ngreeting=\"hello world\"\n";
+        assert_eq!(
+            parse_variables_file(dotenv, VariablesFormat::Dotenv).unwrap(),
+            vec![
+                ("region".to_owned(), "eu".to_owned()),
+                ("greeting".to_owned(), "hello world".to_owned())
+            ]
+        );
+
+        let json = r#"{"api_key": null, "region": "eu", "retries": 3}"#;
+        assert_eq!(
+            parse_variables_file(json, VariablesFormat::Json).unwrap(),
+            vec![
+                ("region".to_owned(), "eu".to_owned()),
+                ("retries".to_owned(), "3".to_owned())
+            ]
+        );
+        assert!(parse_variables_file(r#"{"a": [1]}"#, VariablesFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_exported_template_imports_nothing() {
+        let names = vec!["api_key".to_owned(), "region".to_owned()];
+        for format in [VariablesFormat::Dotenv, VariablesFormat::Json] {
+            let template = variables_template(&names, format).unwrap();
+            assert!(parse_variables_file(&template, format).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_import_diff() {
+        let variables = vec![
+            ("region".to_owned(), "eu".to_owned()),
+            ("api_key".to_owned(), "s3cret".to_owned()),
+            ("greeting".to_owned(), "hello".to_owned()),
+        ];
+        let existing = HashSet::from(["api_key", "greeting"]);
+        let last_set = |name: &str| match name {
+            "api_key" => Some(value_digest("old")),
+            "greeting" => Some(value_digest("hello")),
+            _ => None,
+        };
+        let diff = ImportDiff::new(&variables, &existing, last_set);
+        assert_eq!(diff.created, vec!["region"]);
+        assert_eq!(diff.updated, vec!["api_key"]);
+        assert_eq!(diff.unchanged, vec!["greeting"]);
+
+        let diff = ImportDiff::everything(&variables, &existing);
+        assert_eq!(diff.updated, vec!["api_key", "greeting"]);
+        assert!(diff.unchanged.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_and_delete_remember_value_digests() -> Result<()> {
+        let client = cloud::testing::RecordedClient::new();
+        let mut cache = ResponseCache::disabled();
+        let app_id = Uuid::new_v4();
+        let key = value_digest_key(app_id, "region");
+
+        set_variables(
+            &client,
+            &mut cache,
+            app_id,
+            &[("region".to_owned(), "eu".to_owned())],
+        )
+        .await?;
+        assert_eq!(
+            cache.get_stale::<String>(&key).map(|(digest, _)| digest),
+            Some(value_digest("eu"))
+        );
+
+        delete_variables(&client, &mut cache, app_id, &["region".to_owned()]).await?;
+        assert!(cache.get_stale::<String>(&key).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bare_names_are_resolved_from_env() {
+        let command = set_command(&["token", "region=eu"], true);
+        let resolved = command
+            .resolve_values(|name| (name == "token").then(|| "s3cret".to_owned()))
+            .unwrap();
+        assert_eq!(
+            resolved,
+            vec![
+                ("token".to_owned(), "s3cret".to_owned()),
+                ("region".to_owned(), "eu".to_owned())
+            ]
+        );
+
+        let command = set_command(&["missing"], true);
+        assert!(command.resolve_values(|_| None).is_err());
+    }
+}
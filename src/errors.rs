@@ -0,0 +1,250 @@
+//! The kinds of failure that scripts can tell apart by exit code, or by the
+//! `code` of a `--format json` error, without parsing messages. Commands
+//! return a `CliError` where they know what went wrong; errors from the
+//! cloud service and the network are classified by their status or cause.
+use cloud::client::ResponseError;
+
+/// Exit code for any failure not covered by an `ErrorKind`
+pub const GENERAL_EXIT_CODE: i32 = 1;
+
+/// Describes the exit codes for `--help`
+pub const EXIT_CODES_HELP: &str = "EXIT CODES:
+    0  Success
+    1  Any other failure
+    2  Invalid command line
+    3  Not logged in, or the login has expired or was rejected
+    4  The app or resource does not exist
+    5  An account quota or limit was exceeded
+    6  The service could not be reached
+    7  The request or application was invalid
+    8  Timed out waiting, such as for new lines with `logs --idle-timeout`";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Usage,
+    Auth,
+    NotFound,
+    QuotaExceeded,
+    Network,
+    Validation,
+    TimedOut,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Usage => 2,
+            Self::Auth => 3,
+            Self::NotFound => 4,
+            Self::QuotaExceeded => 5,
+            Self::Network => 6,
+            Self::Validation => 7,
+            Self::TimedOut => 8,
+        }
+    }
+
+    /// The identifier reported as the `code` of JSON errors
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Usage => "usage",
+            Self::Auth => "auth",
+            Self::NotFound => "not_found",
+            Self::QuotaExceeded => "quota_exceeded",
+            Self::Network => "network",
+            Self::Validation => "validation",
+            Self::TimedOut => "timed_out",
+        }
+    }
+
+    fn default_hint(self) -> Option<&'static str> {
+        match self {
+            Self::Auth => Some("Run `spin cloud login` to log in again"),
+            Self::QuotaExceeded => Some(
+                "Delete apps or resources you no longer need, or check the limits of your plan",
+            ),
+            Self::Network => {
+                Some("Check your network connection and proxy settings, or run `spin cloud doctor`")
+            }
+            Self::Usage | Self::NotFound | Self::Validation | Self::TimedOut => None,
+        }
+    }
+}
+
+/// A failure whose kind is known where it happens. It displays as its
+/// message, so it reads like any other error.
+#[derive(Debug)]
+pub struct CliError {
+    pub kind: ErrorKind,
+    message: String,
+    hint: Option<String>,
+}
+
+impl CliError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    pub fn usage(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Usage, message)
+    }
+
+    pub fn auth(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Auth, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::NotFound, message)
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Validation, message)
+    }
+
+    pub fn timed_out(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::TimedOut, message)
+    }
+
+    /// Replaces the hint that errors of this kind give by default
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// The kind of an error and a suggested next step, if it is known. The
+/// outermost classifiable cause decides, so a command's own `CliError` takes
+/// precedence over the response that led to it.
+pub fn classify(error: &anyhow::Error) -> Option<(ErrorKind, Option<String>)> {
+    let from_cli_error = |e: &CliError| {
+        let hint = e
+            .hint
+            .clone()
+            .or_else(|| e.kind.default_hint().map(str::to_owned));
+        Some((e.kind, hint))
+    };
+    // A `CliError` given as context is only found by downcasting the error itself
+    if let Some(e) = error.downcast_ref::<CliError>() {
+        return from_cli_error(e);
+    }
+    error.chain().find_map(|cause| {
+        if let Some(e) = cause.downcast_ref::<CliError>() {
+            return from_cli_error(e);
+        }
+        let kind = if let Some(e) = cause.downcast_ref::<ResponseError>() {
+            response_kind(e)?
+        } else if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            (e.is_connect() || e.is_timeout()).then_some(ErrorKind::Network)?
+        } else {
+            return None;
+        };
+        Some((kind, kind.default_hint().map(str::to_owned)))
+    })
+}
+
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    classify(error).map_or(GENERAL_EXIT_CODE, |(kind, _)| kind.exit_code())
+}
+
+fn response_kind(error: &ResponseError) -> Option<ErrorKind> {
+    match error.status.as_u16() {
+        402 | 429 => Some(ErrorKind::QuotaExceeded),
+        401 => Some(ErrorKind::Auth),
+        404 => Some(ErrorKind::NotFound),
+        // The service also rejects requests that would exceed a limit with
+        // these statuses, but then always says so
+        403 | 400 | 409 | 422 if reports_exceeded_limit(&error.to_string()) => {
+            Some(ErrorKind::QuotaExceeded)
+        }
+        403 => Some(ErrorKind::Auth),
+        400 | 409 | 422 => Some(ErrorKind::Validation),
+        _ => None,
+    }
+}
+
+fn reports_exceeded_limit(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    message.contains("quota")
+        || [
+            "limit of",
+            "limit reached",
+            "limit exceeded",
+            "exceeds the limit",
+        ]
+        .iter()
+        .any(|phrase| message.contains(phrase))
+}
+
+#[cfg(test)]
+mod errors_tests {
+    use super::*;
+    use anyhow::Context;
+
+    fn response(status: u16, message: &str) -> anyhow::Error {
+        anyhow::Error::new(ResponseError::new(
+            reqwest::StatusCode::from_u16(status).unwrap(),
+            message.to_owned(),
+        ))
+    }
+
+    #[test]
+    fn test_responses_are_classified_by_status_and_message() {
+        let kind = |error| classify(&error).map(|(kind, _)| kind);
+        assert_eq!(kind(response(401, "Unauthorized")), Some(ErrorKind::Auth));
+        assert_eq!(
+            kind(response(404, "Not found").context("Problem fetching app")),
+            Some(ErrorKind::NotFound)
+        );
+        assert_eq!(
+            kind(response(400, "App limit of 5 reached")),
+            Some(ErrorKind::QuotaExceeded)
+        );
+        assert_eq!(
+            kind(response(422, "Invalid name")),
+            Some(ErrorKind::Validation)
+        );
+        // Only a status that can report a limit is checked for one
+        assert_eq!(
+            kind(response(404, "No app named rate-limiter")),
+            Some(ErrorKind::NotFound)
+        );
+        assert_eq!(
+            kind(response(422, "Set a limit between 1 and 10")),
+            Some(ErrorKind::Validation)
+        );
+        assert_eq!(
+            kind(response(403, "Quota exceeded for databases")),
+            Some(ErrorKind::QuotaExceeded)
+        );
+        assert_eq!(kind(response(500, "Oops")), None);
+        assert_eq!(exit_code(&anyhow::anyhow!("Oops")), GENERAL_EXIT_CODE);
+    }
+
+    #[test]
+    fn test_command_errors_take_precedence() {
+        let error = Err::<(), _>(response(404, "Not found"))
+            .context(CliError::auth("Your login has expired").with_hint("Log in again"))
+            .unwrap_err();
+        assert_eq!(
+            classify(&error),
+            Some((ErrorKind::Auth, Some("Log in again".to_owned())))
+        );
+        assert_eq!(exit_code(&error), 3);
+
+        let error = Err::<(), _>(anyhow::Error::new(CliError::not_found("No app 'x'")))
+            .context("Problem deploying")
+            .unwrap_err();
+        assert_eq!(classify(&error), Some((ErrorKind::NotFound, None)));
+    }
+}
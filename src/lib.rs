@@ -0,0 +1,215 @@
+//! The `spin cloud` plugin. Besides the command line, the [`ops`] module
+//! offers the plugin's main operations to programs that want to deploy to
+//! Fermyon Cloud without running `spin cloud`.
+mod commands;
+pub mod errors;
+pub mod ops;
+mod opts;
+mod output;
+mod random_name;
+mod spin;
+
+use anyhow::{Error, Result};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use commands::{
+    api::ApiCommand,
+    apps::AppsCommand,
+    cache::CacheCommand,
+    canary::CanaryCommand,
+    channels::ChannelsCommand,
+    ci::CiCommand,
+    completion::{CompleteAppsCommand, CompletionCommand},
+    config::ConfigCommand,
+    deploy::DeployCommand,
+    doctor::DoctorCommand,
+    domains::DomainsCommand,
+    env::EnvCommand,
+    key_value::KeyValueCommand,
+    link::{LinkCommand, UnlinkCommand},
+    login::{LoginCommand, LogoutCommand},
+    logs::LogsCommand,
+    rollback::RollbackCommand,
+    sqlite::SqliteCommand,
+    support::SupportCommand,
+    upgrade::UpgradeCommand,
+    variables::VariablesCommand,
+};
+
+/// Returns build information, similar to: 0.1.0 (2be4034 2022-03-31).
+const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("VERGEN_GIT_SHA"),
+    " ",
+    env!("VERGEN_GIT_COMMIT_DATE"),
+    ")"
+);
+
+#[derive(Parser)]
+#[clap(author, version = VERSION, about, long_about = None)]
+#[clap(propagate_version = true)]
+#[clap(after_long_help = errors::EXIT_CODES_HELP)]
+struct Cli {
+    #[clap(flatten)]
+    output: output::OutputArgs,
+    #[clap(flatten)]
+    retry: commands::RetryArgs,
+    #[clap(flatten)]
+    http: commands::HttpArgs,
+    #[clap(flatten)]
+    cache: commands::cache::CacheArgs,
+    #[clap(flatten)]
+    record: commands::support::RecordArgs,
+    #[clap(flatten)]
+    timings: commands::timings::TimingsArgs,
+    #[clap(subcommand)]
+    command: CloudCli,
+}
+
+#[derive(Subcommand)]
+enum CloudCli {
+    /// Send an authenticated request to the Fermyon Cloud API
+    Api(ApiCommand),
+    /// Manage applications deployed to Fermyon Cloud
+    #[clap(subcommand, alias = "app")]
+    Apps(AppsCommand),
+    /// Manage the cache of app and channel lookups
+    #[clap(subcommand)]
+    Cache(CacheCommand),
+    /// Package and upload an application to the Fermyon Cloud.
+    Deploy(DeployCommand),
+    /// Check for common problems with your login, network and application
+    Doctor(DoctorCommand),
+    /// Manage custom domains for apps deployed to Fermyon Cloud
+    #[clap(subcommand, alias = "domain")]
+    Domains(DomainsCommand),
+    /// Manage canary rollouts started with `deploy --canary`
+    #[clap(subcommand)]
+    Canary(CanaryCommand),
+    /// Inspect the channels an app is served on
+    #[clap(subcommand, alias = "channel")]
+    Channels(ChannelsCommand),
+    /// Generate CI configuration that deploys your app to Fermyon Cloud
+    #[clap(subcommand)]
+    Ci(CiCommand),
+    /// Print a shell completion script
+    Completion(CompletionCommand),
+    /// Inspect the plugin's configuration
+    #[clap(subcommand)]
+    Config(ConfigCommand),
+    /// Print app names for shell completion
+    #[clap(name = "__complete-apps", hide = true)]
+    CompleteApps(CompleteAppsCommand),
+    /// Manage the Fermyon instances you are logged into
+    #[clap(subcommand)]
+    Env(EnvCommand),
+    /// Log into Fermyon Cloud
+    Login(LoginCommand),
+    /// Log out of Fermyon Cloud
+    Logout(LogoutCommand),
+    /// Fetch logs for an app from Fermyon Cloud
+    Logs(LogsCommand),
+    /// Roll back an app to a previously deployed revision
+    Rollback(RollbackCommand),
+    /// Manage Spin application variables
+    #[clap(subcommand, alias = "vars")]
+    Variables(VariablesCommand),
+    /// Manage Fermyon Cloud SQLite databases
+    #[clap(subcommand)]
+    Sqlite(SqliteCommand),
+    /// Link apps to resources
+    #[clap(subcommand)]
+    Link(LinkCommand),
+    /// Unlink apps from resources
+    #[clap(subcommand)]
+    Unlink(UnlinkCommand),
+    /// Manage Fermyon Cloud key value stores
+    #[clap(subcommand, alias = "kv")]
+    KeyValue(KeyValueCommand),
+    /// Collect information for Fermyon support
+    #[clap(subcommand)]
+    Support(SupportCommand),
+    /// Upgrade the cloud plugin to its latest release
+    Upgrade(UpgradeCommand),
+}
+
+/// Runs the `spin cloud` command line, exiting the process if it fails.
+/// This is the plugin's binary; programs embedding the plugin use [`ops`].
+#[doc(hidden)]
+pub async fn run_cli() {
+    if let Err(e) = run().await {
+        output::print_error(&e);
+        std::process::exit(errors::exit_code(&e));
+    }
+}
+
+async fn run() -> Result<(), Error> {
+    let mut app = Cli::clap();
+    // Plugin should always be invoked from Spin so set binary name accordingly
+    app.set_bin_name("spin cloud");
+    let matches = app.get_matches();
+    let cli = Cli::from_arg_matches(&matches)?;
+    commands::timings::set_timings(&cli.timings);
+    init_logging(cli.output.verbose);
+    output::set_format(cli.output.format);
+    output::set_quiet(cli.output.quiet);
+    commands::set_retry_policy(&cli.retry);
+    commands::set_http_config(&cli.http)?;
+    commands::cache::set_cache_ttl(&cli.cache);
+    commands::support::set_recording(&cli.record)?;
+
+    // Completion output is read by the shell, and an upgrade checks anyway
+    let update_hint = !matches!(
+        cli.command,
+        CloudCli::Upgrade(_) | CloudCli::Completion(_) | CloudCli::CompleteApps(_)
+    );
+    let result = match cli.command {
+        CloudCli::Api(cmd) => cmd.run().await,
+        CloudCli::Apps(cmd) => cmd.run().await,
+        CloudCli::Cache(cmd) => cmd.run().await,
+        CloudCli::Deploy(cmd) => cmd.run().await,
+        CloudCli::Doctor(cmd) => cmd.run().await,
+        CloudCli::Domains(cmd) => cmd.run().await,
+        CloudCli::Canary(cmd) => cmd.run().await,
+        CloudCli::Channels(cmd) => cmd.run().await,
+        CloudCli::Ci(cmd) => cmd.run().await,
+        CloudCli::Completion(cmd) => cmd.run(Cli::command()),
+        CloudCli::Config(cmd) => cmd.run().await,
+        CloudCli::CompleteApps(cmd) => cmd.run().await,
+        CloudCli::Env(cmd) => cmd.run().await,
+        CloudCli::Login(cmd) => cmd.run().await,
+        CloudCli::Logout(cmd) => cmd.run().await,
+        CloudCli::Logs(cmd) => cmd.run().await,
+        CloudCli::Rollback(cmd) => cmd.run().await,
+        CloudCli::Variables(cmd) => cmd.run().await,
+        CloudCli::Sqlite(cmd) => cmd.run().await,
+        CloudCli::Link(cmd) => cmd.run().await,
+        CloudCli::Unlink(cmd) => cmd.run().await,
+        CloudCli::KeyValue(cmd) => cmd.run().await,
+        CloudCli::Support(cmd) => cmd.run().await,
+        CloudCli::Upgrade(cmd) => cmd.run().await,
+    };
+    commands::timings::print_timings();
+    if result.is_ok() && update_hint {
+        commands::upgrade::print_update_hint().await;
+    }
+    result
+}
+
+// `RUST_LOG` still configures logging as before; `-v` and `-vv` additionally
+// turn on the request log, and `-vv` the plugin's own debug messages.
+fn init_logging(verbose: u8) {
+    let mut builder = env_logger::Builder::from_default_env();
+    match verbose {
+        0 => {}
+        1 => {
+            builder.filter_module(cloud::client::API_LOG_TARGET, log::LevelFilter::Info);
+        }
+        _ => {
+            builder
+                .filter_module(cloud::client::API_LOG_TARGET, log::LevelFilter::Debug)
+                .filter_module("cloud_plugin", log::LevelFilter::Debug);
+        }
+    }
+    builder.init();
+}
@@ -0,0 +1,329 @@
+//! The plugin's main operations as a library, for programs that want to
+//! deploy to Fermyon Cloud, or inspect what is deployed there, without
+//! running `spin cloud`. The commands of the plugin are built on the same
+//! operations.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use cloud_plugin::ops::{Cloud, DeployOptions, DeploySource};
+//!
+//! let cloud = Cloud::connect(None).await?;
+//! let deployment = cloud
+//!     .deploy(DeployOptions::new(DeploySource::Manifest("spin.toml".into())))
+//!     .await?;
+//! println!("{} is running at {}", deployment.name, deployment.url);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Errors that the command line reports with a particular exit code carry a
+//! [`CliError`](crate::errors::CliError), which [`classify`](crate::errors::classify)
+//! finds. Operations print nothing; a deploy reports its progress to a
+//! [`DeployProgress`] given to [`Cloud::deploy_with_progress`].
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use cloud::{CloudClientInterface, LogStream};
+use url::Url;
+use uuid::Uuid;
+
+use crate::commands::{
+    cache::{self, ResponseCache},
+    client_for_connection,
+    credentials::TokenStorage,
+    deploy::{self, login_connection},
+    env::resolve_environment,
+    login::LoginConnection,
+    logs::timed_lines,
+    variables, CloudClient,
+};
+use crate::errors::CliError;
+
+/// A connection to a Fermyon instance
+pub struct Cloud<C = CloudClient> {
+    login: LoginConnection,
+    client: C,
+    // Where app lookups are cached. Connections made from a token have no
+    // saved login, and so no cache.
+    cache_environment: Option<Option<String>>,
+}
+
+/// An app deployed to Fermyon Cloud
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct App {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// Which logs of an app to fetch
+#[derive(Clone, Debug, Default)]
+pub struct LogQuery {
+    /// The channel to fetch the logs of. Defaults to the app's deploy channel.
+    pub channel: Option<String>,
+    /// Only fetch lines logged since this time, as an RFC 3339 timestamp
+    pub since: Option<String>,
+    /// The most lines to fetch, counting back from the newest
+    pub max_lines: Option<i32>,
+}
+
+/// A line logged by an app
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogLine {
+    /// When the line was logged, as an RFC 3339 timestamp
+    pub time: String,
+    pub line: String,
+}
+
+/// Where the app to deploy comes from
+#[derive(Clone, Debug)]
+pub enum DeploySource {
+    /// A `spin.toml` file, or a directory containing one
+    Manifest(PathBuf),
+    /// An app already pushed to a registry with `spin registry push`
+    Registry(String),
+}
+
+/// What to deploy, and how. These match the flags of `spin cloud deploy`.
+#[derive(Clone, Debug)]
+pub struct DeployOptions {
+    pub source: DeploySource,
+    /// Run `spin build` before deploying an app from a manifest
+    pub build: bool,
+    /// Variables to set on the deployed app
+    pub variables: Vec<(String, String)>,
+    /// Key/value pairs to set in the app's default key/value store
+    pub key_values: Vec<(String, String)>,
+    /// Links for resources the app uses, such as "sqlite:label=database".
+    /// If any are given, they must cover every label not linked yet.
+    pub links: Vec<String>,
+    /// Ask in the terminal how to link labels that are not linked yet, when
+    /// no `links` are given. Without a terminal, or without this, such labels
+    /// fail the deploy.
+    pub interactive: bool,
+    /// Deploy the new revision as a canary that receives this percentage of
+    /// traffic, from 1 to 99. Only for apps that are already deployed.
+    pub canary: Option<u8>,
+    /// How long to wait for an HTTP app to become ready, or zero not to wait
+    pub readiness_timeout: Duration,
+    /// Fail if an HTTP app does not become ready within the readiness
+    /// timeout, which must then be above zero
+    pub wait: bool,
+}
+
+impl DeployOptions {
+    pub fn new(source: DeploySource) -> Self {
+        Self {
+            source,
+            build: false,
+            variables: vec![],
+            key_values: vec![],
+            links: vec![],
+            interactive: false,
+            canary: None,
+            readiness_timeout: Duration::from_secs(60),
+            wait: false,
+        }
+    }
+}
+
+/// Receives what a deploy reports as it goes. Closures taking the message
+/// receive both kinds of report.
+pub trait DeployProgress: Sync {
+    /// A stage of the deploy, such as "Uploading..."
+    fn step(&self, message: &str);
+    /// Something to know about the outcome, such as where the app is served,
+    /// or that it did not become ready
+    fn notice(&self, message: &str);
+}
+
+impl<F: Fn(&str) + Sync> DeployProgress for F {
+    fn step(&self, message: &str) {
+        self(message)
+    }
+
+    fn notice(&self, message: &str) {
+        self(message)
+    }
+}
+
+/// An app that has been deployed
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Deployment {
+    pub app_id: Uuid,
+    pub name: String,
+    pub version: String,
+    /// Where the app is served
+    pub url: Url,
+}
+
+impl Cloud {
+    /// Connects with the login saved by `spin cloud login` for the named
+    /// environment, or else for the environment in use. Expired tokens are
+    /// refreshed as they are for the command line.
+    pub async fn connect(environment: Option<&str>) -> Result<Self> {
+        let environment = resolve_environment(environment)?;
+        let login = login_connection(environment.as_deref()).await?;
+        Ok(Self {
+            client: client_for_connection(&login),
+            login,
+            cache_environment: Some(environment),
+        })
+    }
+
+    /// Connects to the Fermyon instance at `url` with a personal access token
+    pub fn with_token(url: &str, token: impl Into<String>) -> Result<Self> {
+        let login = LoginConnection {
+            url: Url::parse(url).with_context(|| format!("Invalid URL '{url}'"))?,
+            danger_accept_invalid_certs: false,
+            token: token.into(),
+            refresh_token: None,
+            expiration: None,
+            token_storage: TokenStorage::File,
+        };
+        Ok(Self {
+            client: client_for_connection(&login),
+            login,
+            cache_environment: None,
+        })
+    }
+}
+
+impl<C: CloudClientInterface> Cloud<C> {
+    /// Finds the app with the given name
+    pub async fn resolve_app(&self, name: &str) -> Result<App> {
+        let id = cache::app_id(&self.client, &mut self.cache()?, name)
+            .await
+            .with_context(|| format!("Error finding app_id for app '{name}'"))?
+            .with_context(|| CliError::not_found(format!("Could not find app '{name}'")))?;
+        Ok(App {
+            id,
+            name: name.to_owned(),
+        })
+    }
+
+    /// Fetches lines logged by the named app, oldest first
+    pub async fn logs(&self, app: &str, query: &LogQuery) -> Result<Vec<LogLine>> {
+        let channel = query.channel.as_deref();
+        let (_, stream) =
+            cache::resolve_app_channel(&self.client, &mut self.cache()?, app, channel).await?;
+        logs(&self.client, app, stream, query).await
+    }
+
+    /// The names of the variables set on the named app
+    pub async fn variables(&self, app: &str) -> Result<Vec<String>> {
+        let app = self.resolve_app(app).await?;
+        variable_names(&self.client, app.id).await
+    }
+
+    /// Sets variables on the named app, keeping any others it has
+    pub async fn set_variables(&self, app: &str, values: &[(String, String)]) -> Result<()> {
+        let app = self.resolve_app(app).await?;
+        variables::set_variables(&self.client, &mut self.cache()?, app.id, values).await
+    }
+
+    /// Deletes variables from the named app
+    pub async fn delete_variables(&self, app: &str, names: &[String]) -> Result<()> {
+        let app = self.resolve_app(app).await?;
+        variables::delete_variables(&self.client, &mut self.cache()?, app.id, names).await
+    }
+
+    /// Deploys an app, creating it if it is not deployed yet
+    pub async fn deploy(&self, options: DeployOptions) -> Result<Deployment> {
+        self.deploy_with_progress(options, &|_: &str| {}).await
+    }
+
+    /// Deploys an app like [`deploy`](Self::deploy), reporting its progress
+    pub async fn deploy_with_progress(
+        &self,
+        options: DeployOptions,
+        progress: &dyn DeployProgress,
+    ) -> Result<Deployment> {
+        let mut cache = self.cache()?;
+        match deploy::deploy(&self.login, &self.client, &mut cache, &options, progress).await? {
+            Some(deployment) => Ok(deployment),
+            None => bail!("The deploy was canceled"),
+        }
+    }
+
+    fn cache(&self) -> Result<ResponseCache> {
+        match &self.cache_environment {
+            Some(environment) => ResponseCache::open(environment.as_deref()),
+            None => Ok(ResponseCache::disabled()),
+        }
+    }
+}
+
+async fn logs(
+    client: &impl CloudClientInterface,
+    app: &str,
+    stream: LogStream,
+    query: &LogQuery,
+) -> Result<Vec<LogLine>> {
+    let entries = client
+        .get_logs_raw(stream, query.max_lines, query.since.clone())
+        .await
+        .with_context(|| format!("Problem fetching logs for app '{app}'"))?;
+    Ok(timed_lines(&entries)
+        .into_iter()
+        .map(|(time, line)| LogLine {
+            time: time.to_owned(),
+            line: line.to_owned(),
+        })
+        .collect())
+}
+
+async fn variable_names(client: &impl CloudClientInterface, app_id: Uuid) -> Result<Vec<String>> {
+    let variables = variables::get_variables(client, app_id).await?;
+    Ok(variables.into_iter().map(|v| v.key).collect())
+}
+
+#[cfg(test)]
+mod ops_tests {
+    use super::*;
+    use cloud::testing::RecordedClient;
+    use cloud_openapi::models::{AppItem, AppItemPage};
+
+    fn cloud(client: RecordedClient) -> Cloud<RecordedClient> {
+        Cloud {
+            login: LoginConnection {
+                url: Url::parse("https://cloud.fermyon.com").unwrap(),
+                danger_accept_invalid_certs: false,
+                token: "token".to_owned(),
+                refresh_token: None,
+                expiration: None,
+                token_storage: TokenStorage::File,
+            },
+            client,
+            cache_environment: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apps_resolve_by_name() {
+        let id = Uuid::new_v4();
+        let page = AppItemPage {
+            items: vec![AppItem {
+                id,
+                name: "hello".to_owned(),
+                ..Default::default()
+            }],
+            is_last_page: true,
+            ..Default::default()
+        };
+        let cloud = cloud(RecordedClient::new().respond("list_apps", page));
+
+        let app = cloud.resolve_app("hello").await.unwrap();
+        assert_eq!(
+            app,
+            App {
+                id,
+                name: "hello".to_owned()
+            }
+        );
+
+        let err = cloud.resolve_app("missing").await.unwrap_err();
+        let (kind, _) = crate::errors::classify(&err).unwrap();
+        assert_eq!(kind, crate::errors::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,221 @@
+//! Selects between human-oriented and machine-readable output. The format is
+//! chosen once with the global `--format` flag and read by every command, so
+//! that `--format json` behaves the same everywhere, including for errors.
+use std::io::{IsTerminal, Write};
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+
+use crate::errors;
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+static QUIET: OnceLock<bool> = OnceLock::new();
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    #[clap(alias = "text")]
+    Table,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct OutputArgs {
+    /// Format of command output
+    #[clap(value_enum, long = "format", global = true, default_value = "table")]
+    pub format: OutputFormat,
+    /// Log each API call to stderr. Repeat (-vv) to also log every HTTP request with its status and latency.
+    #[clap(
+        short = 'v',
+        long = "verbose",
+        global = true,
+        parse(from_occurrences),
+        conflicts_with = "quiet"
+    )]
+    pub verbose: u8,
+    /// Print only results and errors, without progress messages
+    #[clap(short = 'q', long = "quiet", global = true)]
+    pub quiet: bool,
+}
+
+/// Sets the output format for the rest of the process. Only the first call has any effect.
+pub fn set_format(format: OutputFormat) {
+    _ = FORMAT.set(format);
+}
+
+/// The output format selected on the command line, or `Table` if none was set
+pub fn format() -> OutputFormat {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+/// Suppresses progress messages for the rest of the process. Only the first call has any effect.
+pub fn set_quiet(quiet: bool) {
+    _ = QUIET.set(quiet);
+}
+
+/// Whether progress messages, as opposed to results, should be left out
+pub fn is_quiet() -> bool {
+    QUIET.get().copied().unwrap_or_default()
+}
+
+/// Prints a progress message, such as "Deploying...", unless `--quiet` was
+/// given. With JSON output it goes to stderr, so that stdout only holds JSON.
+pub fn progress(message: &str) {
+    if !is_quiet() {
+        notice(message);
+    }
+}
+
+/// Prints a progress message or warning to stderr unless `--quiet` was
+/// given, for commands such as `logs` whose stdout only holds the lines they
+/// stream
+pub fn stream_progress(message: &str) {
+    if !is_quiet() {
+        eprintln!("{message}");
+    }
+}
+
+/// Prints a message that is not part of a command's result, such as a
+/// warning, to stdout, or to stderr when JSON output is selected
+pub fn notice(message: &str) {
+    if is_json() {
+        eprintln!("{message}");
+    } else {
+        println!("{message}");
+    }
+}
+
+const SPINNER_FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+const SPINNER_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// A spinner on stderr, shown while it is in scope. Like other progress, it
+/// is left out with `--quiet` or JSON output, and when stderr is not a terminal.
+pub struct Spinner {
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+/// Starts a spinner showing `message`. Must be called within the runtime.
+pub fn spinner(message: &str) -> Spinner {
+    if is_quiet() || is_json() || !std::io::stderr().is_terminal() {
+        return Spinner { task: None };
+    }
+    let message = message.to_owned();
+    let task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SPINNER_INTERVAL);
+        for frame in SPINNER_FRAMES.iter().cycle() {
+            interval.tick().await;
+            eprint!("\r{frame} {message}");
+            _ = std::io::stderr().flush();
+        }
+    });
+    Spinner { task: Some(task) }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+            // Clears the line for whatever is printed next
+            eprint!("\r\x1b[2K");
+        }
+    }
+}
+
+pub fn is_json() -> bool {
+    format() == OutputFormat::Json
+}
+
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Reports a completed action: as the given text, or as `{"message": ...}`
+/// alongside `details` when JSON output is selected.
+pub fn success(message: &str, details: serde_json::Value) -> Result<()> {
+    if !is_json() {
+        println!("{message}");
+        return Ok(());
+    }
+    let mut output = serde_json::json!({ "message": message });
+    if let (Some(output), serde_json::Value::Object(details)) = (output.as_object_mut(), details) {
+        output.extend(details);
+    }
+    print_json(&output)
+}
+
+/// An error as reported with `--format json`
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ErrorOutput {
+    /// A stable identifier for the kind of failure
+    pub code: String,
+    /// The error and its causes, outermost first
+    pub message: String,
+    /// A suggested next step, when one is known
+    pub hint: Option<String>,
+}
+
+impl ErrorOutput {
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        let (code, hint) = match errors::classify(error) {
+            Some((kind, hint)) => (kind.code(), hint),
+            None => ("error", None),
+        };
+        Self {
+            code: code.to_owned(),
+            message: format!("{error:#}"),
+            hint,
+        }
+    }
+}
+
+/// Prints an error in the selected format. JSON errors go to stdout so that
+/// scripts only need to parse one stream.
+pub fn print_error(error: &anyhow::Error) {
+    if is_json() {
+        match serde_json::to_string_pretty(&ErrorOutput::from_error(error)) {
+            Ok(json) => println!("{json}"),
+            Err(_) => eprintln!("Error: {error:?}"),
+        }
+    } else {
+        eprintln!("Error: {error:?}");
+        if let Some((_, Some(hint))) = errors::classify(error) {
+            eprintln!("\n{hint}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod output_tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_error_output_includes_causes() {
+        let error = Err::<(), _>(anyhow::anyhow!("connection refused"))
+            .context("Problem fetching databases")
+            .unwrap_err();
+        assert_eq!(
+            ErrorOutput::from_error(&error),
+            ErrorOutput {
+                code: "error".to_owned(),
+                message: "Problem fetching databases: connection refused".to_owned(),
+                hint: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_error_output_reports_kind_and_hint() {
+        let error = anyhow::Error::new(errors::CliError::auth("Your login has expired"))
+            .context("Problem listing apps");
+        let output = ErrorOutput::from_error(&error);
+        assert_eq!(output.code, "auth");
+        assert_eq!(
+            output.hint.as_deref(),
+            Some("Run `spin cloud login` to log in again")
+        );
+    }
+}